@@ -0,0 +1,385 @@
+//! The axes that a graph sweep can vary: the benchmark flags and data generator variables that
+//! can be given to `--x-axis` and `--z-axis`, and the values they take.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use itertools::Itertools;
+use serde_json::json;
+
+use crate::graph::{ArgOverride, CommaSeparatedString};
+
+/// The type of the values of `--x-axis`, from `--x-type`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, clap::ValueEnum)]
+pub enum XType {
+    Integer,
+    Float,
+    /// A number followed by one of the units `ns`, `us`, `ms`, `s`, `m` or `h`, e.g. `500ms`.
+    /// Numbers without a unit are seconds.
+    Duration,
+    String,
+}
+
+impl Display for XType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            XType::Integer => "integer",
+            XType::Float => "float",
+            XType::Duration => "duration",
+            XType::String => "string",
+        };
+        write!(f, "{s}")
+    }
+}
+
+/// The type of the values of an axis described by [`AxisMetadata`].
+pub type AxisValueType = XType;
+
+/// A description of a parameter that can be varied by a sweep, so that tools reading the results
+/// can label and format its values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AxisMetadata {
+    /// A human-readable name for the axis, e.g. `target QPS`.
+    pub name: String,
+    /// The unit of the axis's values, if it has one, e.g. `ops/s`.
+    pub unit: Option<String>,
+    pub value_type: AxisValueType,
+}
+
+impl AxisMetadata {
+    pub fn new(name: impl Into<String>, unit: Option<&str>, value_type: AxisValueType) -> Self {
+        Self {
+            name: name.into(),
+            unit: unit.map(str::to_owned),
+            value_type,
+        }
+    }
+}
+
+/// The metadata of each axis that's been described, by the name it's given as to `--x-axis`.
+/// Starts with the flags shared by several benchmarks; others are added by
+/// [`register_axis_metadata`].
+pub(crate) static KNOWN_AXES: LazyLock<Mutex<HashMap<String, AxisMetadata>>> =
+    LazyLock::new(|| {
+        let known_axes = [
+            (
+                "target-qps",
+                AxisMetadata::new("target QPS", Some("ops/s"), XType::Integer),
+            ),
+            (
+                "workers",
+                AxisMetadata::new("workers", None, XType::Integer),
+            ),
+            (
+                "threads",
+                AxisMetadata::new("threads", None, XType::Integer),
+            ),
+            (
+                "run-for",
+                AxisMetadata::new("run duration", Some("s"), XType::Duration),
+            ),
+        ];
+        Mutex::new(
+            known_axes
+                .into_iter()
+                .map(|(axis, metadata)| (axis.to_owned(), metadata))
+                .collect(),
+        )
+    });
+
+/// Describes `axis`, the name of a benchmark flag or `@`-prefixed data generator variable as it's
+/// given to `--x-axis`, for [`GraphParams::x_axis_metadata`]. Replaces any existing description.
+pub fn register_axis_metadata(axis: &str, metadata: AxisMetadata) {
+    KNOWN_AXES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(axis.to_owned(), metadata);
+}
+
+/// A value of `--x-axis`, parsed as its [`XType`] so that numbers sort and plot as numbers.
+#[derive(Clone, Debug, PartialEq)]
+pub enum XValue {
+    Integer(i64),
+    Float(f64),
+    Duration(Duration),
+    String(String),
+}
+
+impl XValue {
+    /// Parses `value` as `x_type`.
+    pub fn parse(value: &str, x_type: XType) -> anyhow::Result<Self> {
+        let trimmed = value.trim();
+        Ok(match x_type {
+            XType::Integer => XValue::Integer(
+                trimmed
+                    .parse()
+                    .map_err(|e| anyhow!("'{value}' is not an integer: {e}"))?,
+            ),
+            XType::Float => XValue::Float(
+                trimmed
+                    .parse()
+                    .map_err(|e| anyhow!("'{value}' is not a float: {e}"))?,
+            ),
+            XType::Duration => XValue::Duration(parse_duration(trimmed).ok_or_else(|| {
+                anyhow!(
+                    "'{value}' is not a duration: expected a number of seconds, optionally \
+                     followed by one of the units ns, us, ms, s, m or h"
+                )
+            })?),
+            XType::String => XValue::String(value.to_owned()),
+        })
+    }
+
+    /// Parses `value` as the first of an integer, a finite float, or a duration with a unit that
+    /// it is, or as a string if it is none of them.
+    pub fn infer(value: &str) -> Self {
+        let trimmed = value.trim();
+        if let Ok(integer) = trimmed.parse() {
+            XValue::Integer(integer)
+        } else if let Some(float) = trimmed.parse::<f64>().ok().filter(|v| v.is_finite()) {
+            XValue::Float(float)
+        } else if let Some(duration) = parse_duration(trimmed) {
+            XValue::Duration(duration)
+        } else {
+            XValue::String(value.to_owned())
+        }
+    }
+
+    /// Parses every one of `values` as `x_type`, or infers their type if it is `None`. Inferred
+    /// values must all have the same type, except that integers are converted to floats if
+    /// there are both.
+    pub fn parse_all(values: &[String], x_type: Option<XType>) -> anyhow::Result<Vec<Self>> {
+        if let Some(x_type) = x_type {
+            return values
+                .iter()
+                .map(|value| XValue::parse(value, x_type))
+                .collect();
+        }
+        let typed = values
+            .iter()
+            .map(|value| XValue::infer(value))
+            .collect::<Vec<_>>();
+        let types = typed
+            .iter()
+            .map(XValue::x_type)
+            .unique()
+            .collect::<Vec<_>>();
+        match types.as_slice() {
+            [] | [_] => Ok(typed),
+            [XType::Integer, XType::Float] | [XType::Float, XType::Integer] => Ok(typed
+                .into_iter()
+                .map(|value| match value {
+                    XValue::Integer(integer) => XValue::Float(integer as f64),
+                    value => value,
+                })
+                .collect()),
+            [first, second, ..] => {
+                let example = |x_type| {
+                    values
+                        .iter()
+                        .zip(&typed)
+                        .find(|(_, typed)| typed.x_type() == x_type)
+                        .map(|(value, _)| value.as_str())
+                        .unwrap_or_default()
+                };
+                bail!(
+                    "values of different types, such as {first} '{}' and {second} '{}'; pass \
+                     --x-type string to treat them all as strings",
+                    example(*first),
+                    example(*second)
+                )
+            }
+        }
+    }
+
+    /// Returns the type of this value.
+    pub fn x_type(&self) -> XType {
+        match self {
+            XValue::Integer(_) => XType::Integer,
+            XValue::Float(_) => XType::Float,
+            XValue::Duration(_) => XType::Duration,
+            XValue::String(_) => XType::String,
+        }
+    }
+
+    /// Returns this value as a number, with durations in seconds, or `None` for strings.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            XValue::Integer(integer) => Some(*integer as f64),
+            XValue::Float(float) => Some(*float),
+            XValue::Duration(duration) => Some(duration.as_secs_f64()),
+            XValue::String(_) => None,
+        }
+    }
+
+    /// Returns this value as JSON: a number, with durations in seconds, or a string.
+    pub(crate) fn to_json(&self) -> serde_json::Value {
+        match self {
+            XValue::Integer(integer) => json!(integer),
+            XValue::String(value) => json!(value),
+            value => json!(value.as_f64()),
+        }
+    }
+}
+
+impl Display for XValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            XValue::Integer(integer) => write!(f, "{integer}"),
+            XValue::Float(float) => write!(f, "{float}"),
+            XValue::Duration(duration) => write!(f, "{duration:?}"),
+            XValue::String(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// Parses a non-negative number followed by an optional unit of `ns`, `us`, `ms`, `s`, `m` or
+/// `h`, defaulting to seconds.
+fn parse_duration(value: &str) -> Option<Duration> {
+    let split = value
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number = number.trim().parse::<f64>().ok()?;
+    let seconds_per_unit = match unit {
+        "ns" => 1e-9,
+        "us" => 1e-6,
+        "ms" => 1e-3,
+        "" | "s" => 1.0,
+        "m" => 60.0,
+        "h" => 3600.0,
+        _ => return None,
+    };
+    Duration::try_from_secs_f64(number * seconds_per_unit).ok()
+}
+
+/// Parses a duration flag such as `--run-timeout`, in the syntax of [`parse_duration`].
+pub(crate) fn parse_duration_arg(value: &str) -> Result<Duration, String> {
+    parse_duration(value).ok_or_else(|| {
+        format!("Invalid duration '{value}': expected a number of ns, us, ms, s, m or h")
+    })
+}
+
+/// Separates the elements of each value for an axis that names several parameters, e.g.
+/// `--x-axis target-qps,connections --x-values 100:8,500:16`.
+pub const TUPLE_SEPARATOR: char = ':';
+
+/// Returns the names of the parameters set by `axis`, which may list several comma-separated
+/// benchmark flags and data generator variables.
+fn split_axis(axis: &str) -> Vec<&str> {
+    axis.split(',').map(str::trim).collect()
+}
+
+/// Whether any of the parameters set by `axis` is a data generator variable.
+pub(crate) fn is_datagen_axis(axis: &str) -> bool {
+    split_axis(axis).iter().any(|axis| axis.starts_with('@'))
+}
+
+/// Checks that `axis`, passed as `flag`, names one or more distinct benchmark flags and data
+/// generator variables. Flags must be the name of a long flag without its leading dashes, made
+/// of letters, digits, dashes and underscores, and variables must not have empty path segments.
+pub(crate) fn validate_axis(flag: &str, axis: &str) -> anyhow::Result<()> {
+    let axes = split_axis(axis);
+    for (i, axis) in axes.iter().enumerate() {
+        if axis.trim_start_matches('@').is_empty() {
+            bail!("{flag} must name a benchmark flag or data generator variable");
+        }
+        if let Some(path) = axis.strip_prefix('@') {
+            if path.split('.').any(str::is_empty) {
+                bail!("Invalid data generator variable '{axis}' in {flag}: empty path segment");
+            }
+        } else if !axis.starts_with(|c: char| c.is_ascii_alphanumeric())
+            || !axis
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!(
+                "Invalid benchmark flag '{axis}' in {flag}: expected the name of a flag without \
+                 its leading dashes, such as 'target-qps'"
+            );
+        }
+        if axes[..i].contains(axis) {
+            bail!("{flag} names '{axis}' more than once");
+        }
+    }
+    Ok(())
+}
+
+/// Checks that each of `values`, passed as `flag`, has one element for each parameter named by
+/// `axis`, and that every value for a data generator variable is numeric. Values for benchmark
+/// flags are validated by the benchmark's own argument parser.
+pub(crate) fn validate_axis_values(
+    flag: &str,
+    axis: &str,
+    values: &CommaSeparatedString,
+) -> anyhow::Result<()> {
+    let axes = split_axis(axis);
+    if axes.len() == 1 {
+        if axis.starts_with('@') {
+            values
+                .validate_as_f64()
+                .map_err(|e| anyhow!("Invalid {flag} for {axis}: {e}"))?;
+        }
+        return Ok(());
+    }
+
+    let tuples = values
+        .iter()
+        .map(|value| {
+            value
+                .split(TUPLE_SEPARATOR)
+                .map(str::trim)
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    if let Some((i, (value, tuple))) = values
+        .iter()
+        .zip(&tuples)
+        .enumerate()
+        .find(|(_, (_, tuple))| tuple.len() != axes.len())
+    {
+        bail!(
+            "Invalid value '{value}' at position {i} in {flag}: expected {} '{TUPLE_SEPARATOR}'-\
+             separated elements, one for each of {}, but found {}",
+            axes.len(),
+            axes.join(", "),
+            tuple.len()
+        );
+    }
+    for (j, axis) in axes
+        .iter()
+        .enumerate()
+        .filter(|(_, axis)| axis.starts_with('@'))
+    {
+        CommaSeparatedString(tuples.iter().map(|tuple| tuple[j].to_owned()).collect())
+            .validate_as_f64()
+            .map_err(|e| anyhow!("Invalid {flag} for {axis}: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Returns the override that sets `axis` to `value`. If `axis` names several parameters, `value`
+/// holds the value of each, separated by [`TUPLE_SEPARATOR`], and the override sets all of them:
+/// the flags as command-line arguments and the variables as data generator overrides.
+pub(crate) fn axis_args(axis: &str, value: &str) -> ArgOverride {
+    let axes = split_axis(axis);
+    if axes.len() > 1 {
+        return axes
+            .into_iter()
+            .zip(value.split(TUPLE_SEPARATOR))
+            .map(|(axis, value)| axis_args(axis, value.trim()))
+            .reduce(ArgOverride::merge)
+            .unwrap_or(ArgOverride::Json(json!({})));
+    }
+    match axis.strip_prefix('@') {
+        // A dotted path like `tables.users.rows` sets a variable nested within objects.
+        Some(path) => ArgOverride::Json(
+            path.rsplit('.')
+                .fold(json!(value), |json, key| json!({ key: json })),
+        ),
+        None => ArgOverride::CliArgs(vec![format!("--{axis}"), value.to_owned()]),
+    }
+}
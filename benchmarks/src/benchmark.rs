@@ -10,22 +10,28 @@
 //!     - Create a type that implements `BenchmarkControl`,
 //!     - Add the type's name as a variant `Benchmark`.
 
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::ffi::OsString;
 use std::str::FromStr;
+use std::time::Duration;
 
-use anyhow::Result;
-use clap::Parser;
+use anyhow::{anyhow, bail, Result};
+use clap::{Command, FromArgMatches, Parser, Subcommand};
 use database_utils::{DatabaseConnection, DatabaseType, DatabaseURL};
 use enum_dispatch::enum_dispatch;
 use hdrhistogram::Histogram;
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::time::Instant;
 
+use crate::resource_usage::ResourceUsage;
 use crate::utils::generate::DataGenerator;
 use crate::workload_emulator::WorkloadEmulator;
 
 #[allow(clippy::large_enum_variant)]
 #[enum_dispatch(BenchmarkControl)]
-#[derive(clap::Subcommand, Serialize, Deserialize)]
+#[derive(clap::Subcommand, Clone, Serialize, Deserialize)]
 pub enum Benchmark {
     WorkloadEmulator,
 }
@@ -36,6 +42,36 @@ impl Benchmark {
             Self::WorkloadEmulator(_) => "workload_emulator",
         }
     }
+
+    /// The name of this benchmark's subcommand on the command line.
+    fn subcommand_name(&self) -> &'static str {
+        match self {
+            Self::WorkloadEmulator(_) => "workload-emulator",
+        }
+    }
+
+    /// Applies `args` on top of this benchmark's current arguments, as if they had been passed
+    /// on the command line after the benchmark's subcommand. Arguments that are not present in
+    /// `args` keep their current values.
+    pub fn update_from_args<I, S>(&mut self, args: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        let name = self.subcommand_name();
+        // Defaults are cleared so that they don't overwrite values that were set explicitly.
+        let cmd = Self::augment_subcommands_for_update(Command::new("benchmark"))
+            .mut_subcommand(name, |sub| {
+                sub.mut_args(|arg| arg.default_value(None::<&'static str>))
+            });
+        let matches = cmd.try_get_matches_from(
+            [OsString::from("benchmark"), OsString::from(name)]
+                .into_iter()
+                .chain(args.into_iter().map(Into::into)),
+        )?;
+        self.update_from_arg_matches(&matches)?;
+        Ok(())
+    }
 }
 
 #[derive(Parser, Clone, Deserialize, Serialize)]
@@ -80,17 +116,32 @@ impl DeploymentParameters {
 }
 
 /// Indicates whether increasing or decreasing is the more desirable property for a metric
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum MetricGoal {
     Increasing,
     Decreasing,
 }
 
-#[derive(Debug)]
+/// A single value recorded for a metric with [`BenchmarkResults::record`], along with when it was
+/// recorded, so that the first values of a run can be told apart from the rest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampedResult {
+    pub value: u64,
+    pub recorded_at: Instant,
+}
+
+#[derive(Debug, Clone)]
 pub struct BenchmarkData {
     pub unit: String,
     pub desired_action: MetricGoal,
+    /// Every value recorded for the metric. The histogram grows to fit whatever is recorded, so
+    /// values aren't limited to any range.
     pub values: Histogram<u64>,
+    /// The values recorded one at a time with [`BenchmarkResults::record`], with when each was
+    /// recorded, which are also in [`Self::values`]. Keeping a value this way takes 24 bytes, so
+    /// benchmarks only record the first values of a run like this, for `--warmup-samples`, and
+    /// push the rest as a whole histogram with [`BenchmarkResults::push`].
+    pub samples: Vec<TimestampedResult>,
 }
 
 impl BenchmarkData {
@@ -100,6 +151,7 @@ impl BenchmarkData {
             desired_action,
             // TODO(jeb) why 3?
             values: Histogram::<u64>::new(3).unwrap(),
+            samples: Vec::new(),
         }
     }
 
@@ -110,20 +162,220 @@ impl BenchmarkData {
     pub fn to_histogram(&self) -> Histogram<u64> {
         self.values.clone()
     }
+
+    /// Returns the unit the values were recorded in, or `None` if `unit` isn't the name of a
+    /// [`metrics::Unit`].
+    pub fn metric_unit(&self) -> Option<metrics::Unit> {
+        metrics::Unit::from_string(&snake_case(&self.unit))
+    }
+}
+
+/// Converts a name in upper camel case, such as a [`metrics::Unit`] name like `BitsPerSecond`, to
+/// snake case.
+pub(crate) fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() && !snake.is_empty() {
+            snake.push('_');
+        }
+        if c.is_ascii_alphanumeric() {
+            snake.push(c.to_ascii_lowercase());
+        }
+    }
+    snake
+}
+
+/// Returns the abbreviation that labels values recorded in `unit`, such as `us` or `MiB`, or
+/// `None` for counts, which have no unit.
+pub fn unit_label(unit: metrics::Unit) -> Option<&'static str> {
+    match TimeUnit::from_metric_unit(unit) {
+        Some(time) => Some(time.label()),
+        None => Some(unit.as_canonical_label()).filter(|label| !label.is_empty()),
+    }
 }
 
-#[derive(Default, Debug)]
+/// A unit of time, which the values of metrics recorded in another unit of time can be converted
+/// to when they're written, e.g. with `--convert-units ms`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum TimeUnit {
+    Ns,
+    Us,
+    Ms,
+    S,
+}
+
+impl TimeUnit {
+    /// Returns the unit of time equivalent to `unit`, or `None` if it isn't a unit of time.
+    pub fn from_metric_unit(unit: metrics::Unit) -> Option<Self> {
+        match unit {
+            metrics::Unit::Nanoseconds => Some(Self::Ns),
+            metrics::Unit::Microseconds => Some(Self::Us),
+            metrics::Unit::Milliseconds => Some(Self::Ms),
+            metrics::Unit::Seconds => Some(Self::S),
+            _ => None,
+        }
+    }
+
+    /// Returns the unit of time labelled `label`, as returned by [`Self::label`].
+    pub fn from_label(label: &str) -> Option<Self> {
+        <Self as clap::ValueEnum>::from_str(label, false).ok()
+    }
+
+    /// The abbreviation that labels values in this unit, e.g. `us`.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Ns => "ns",
+            Self::Us => "us",
+            Self::Ms => "ms",
+            Self::S => "s",
+        }
+    }
+
+    /// The factor to multiply a value in this unit by to convert it to `other`.
+    pub fn scale_to(self, other: Self) -> f64 {
+        self.nanoseconds() / other.nanoseconds()
+    }
+
+    fn nanoseconds(self) -> f64 {
+        match self {
+            Self::Ns => 1.0,
+            Self::Us => 1e3,
+            Self::Ms => 1e6,
+            Self::S => 1e9,
+        }
+    }
+}
+
+/// The prefix of the names of metrics that a benchmark running several distinct queries records
+/// for each query separately, such as `query/q3/latency`. See [`query_metric`].
+pub const QUERY_METRIC_PREFIX: &str = "query/";
+
+/// Returns the name of `metric` recorded for `query` alone, e.g. `query/q3/latency` for the
+/// `latency` of `q3`. Query names can't contain `/`, and `all` is reserved for the rollup of every
+/// query written with `--breakdown-by-query`.
+pub fn query_metric(query: &str, metric: &str) -> String {
+    format!("{QUERY_METRIC_PREFIX}{query}/{metric}")
+}
+
+/// Splits the name of a metric recorded for a single query into the query and the metric, or
+/// returns `None` if `name` isn't a [`query_metric`].
+pub fn split_query_metric(name: &str) -> Option<(&str, &str)> {
+    name.strip_prefix(QUERY_METRIC_PREFIX)?
+        .split_once('/')
+        .filter(|(query, metric)| !query.is_empty() && !metric.is_empty())
+}
+
+#[derive(Default, Debug, Clone)]
 pub struct BenchmarkResults {
     pub results: HashMap<String, BenchmarkData>,
+    /// How long the benchmark took to record `results`, if it was recorded.
+    pub elapsed: Option<Duration>,
+    /// The number of samples that were discarded because they were pushed during the warmup.
+    pub discarded_samples: u64,
+    /// Whether the run these results are from was cut short by a `--run-timeout`, so that they
+    /// only cover part of it.
+    pub timed_out: bool,
+    /// How many attempts the graph run these results are from took with `--retries`, or zero if
+    /// it wasn't recorded.
+    pub attempts: u32,
+    /// The CPU and memory usage sampled while the results were recorded, with
+    /// `--resource-usage`.
+    pub resource_usage: Option<ResourceUsage>,
+    /// Samples pushed before this time are discarded, so that connection setup and cold caches
+    /// at the start of a benchmark don't skew its results.
+    warmup_until: Option<Instant>,
 }
 
 impl BenchmarkResults {
     pub fn new() -> Self {
         Self {
             results: HashMap::new(),
+            elapsed: None,
+            discarded_samples: 0,
+            timed_out: false,
+            attempts: 0,
+            resource_usage: None,
+            warmup_until: None,
+        }
+    }
+
+    /// Returns empty results that discard every sample pushed within `warmup` of now.
+    pub fn with_warmup(warmup: Duration) -> Self {
+        Self {
+            warmup_until: Some(Instant::now() + warmup),
+            ..Self::new()
         }
     }
 
+    /// Whether samples pushed now would be discarded as part of the warmup.
+    pub fn is_warming_up(&self) -> bool {
+        self.warmup_until
+            .is_some_and(|warmup_until| Instant::now() < warmup_until)
+    }
+
+    /// Records how long the benchmark took to produce these results, replacing any previously
+    /// recorded time.
+    pub fn record_elapsed_time(&mut self, elapsed: Duration) {
+        self.elapsed = Some(elapsed);
+    }
+
+    /// The total number of samples recorded across every metric.
+    pub fn sample_count(&self) -> u64 {
+        self.results.values().map(|data| data.values.len()).sum()
+    }
+
+    /// The number of samples recorded per second over `elapsed`, across every metric, or `None`
+    /// if `elapsed` is zero.
+    pub fn throughput_per_sec(&self, elapsed: Duration) -> Option<f64> {
+        (!elapsed.is_zero()).then(|| self.sample_count() as f64 / elapsed.as_secs_f64())
+    }
+
+    /// The number of samples recorded per second over the recorded elapsed time, or `None` if
+    /// no elapsed time was recorded or it was zero. For benchmarks that record a sample per operation, this is
+    /// the throughput they achieved, which can fall short of the throughput they aimed for.
+    pub fn achieved_qps(&self) -> Option<f64> {
+        self.elapsed
+            .and_then(|elapsed| self.throughput_per_sec(elapsed))
+    }
+
+    /// The names of every metric with results, in sorted order.
+    pub fn metric_names(&self) -> Vec<&str> {
+        let mut names = self.results.keys().map(String::as_str).collect::<Vec<_>>();
+        names.sort_unstable();
+        names
+    }
+
+    /// The `(value, count)` of every non-empty bucket in the histogram of `metric`, in increasing
+    /// order of value. Each value is the highest value the histogram treats as equivalent to the
+    /// values recorded in its bucket.
+    pub fn histogram_buckets(&self, metric: &str) -> Result<Vec<(u64, u64)>> {
+        Ok(self
+            .histogram(metric)?
+            .iter_recorded()
+            .map(|bucket| (bucket.value_iterated_to(), bucket.count_at_value()))
+            .collect())
+    }
+
+    /// The value of `metric` at quantile `q`, which must be between 0 and 1.
+    pub fn value_at_quantile(&self, metric: &str, q: f64) -> Result<u64> {
+        if !(0.0..=1.0).contains(&q) {
+            bail!("Quantile {q} must be between 0 and 1");
+        }
+        Ok(self.histogram(metric)?.value_at_quantile(q))
+    }
+
+    fn histogram(&self, metric: &str) -> Result<&Histogram<u64>> {
+        self.results
+            .get(metric)
+            .map(|data| &data.values)
+            .ok_or_else(|| {
+                anyhow!(
+                    "No results for metric '{metric}'; available metrics are [{}]",
+                    self.metric_names().join(", ")
+                )
+            })
+    }
+
     pub fn push(
         &mut self,
         key: &str,
@@ -131,6 +383,10 @@ impl BenchmarkResults {
         desired_action: MetricGoal,
         hist: Histogram<u64>,
     ) {
+        if self.is_warming_up() {
+            self.discarded_samples += hist.len();
+            return;
+        }
         self.results
             .entry(key.to_string())
             .or_insert_with(|| BenchmarkData::new(unit, desired_action))
@@ -138,6 +394,149 @@ impl BenchmarkResults {
             .add(hist)
             .unwrap();
     }
+
+    /// Records a single value of the metric `key`, keeping when it was recorded so that it can
+    /// be filtered out by [`Self::filter_warmup`]. Like [`Self::push`], values recorded during the
+    /// warmup are discarded.
+    pub fn record(
+        &mut self,
+        key: &str,
+        unit: metrics::Unit,
+        desired_action: MetricGoal,
+        result: TimestampedResult,
+    ) {
+        if self
+            .warmup_until
+            .is_some_and(|warmup_until| result.recorded_at < warmup_until)
+        {
+            self.discarded_samples += 1;
+            return;
+        }
+        let data = self
+            .results
+            .entry(key.to_string())
+            .or_insert_with(|| BenchmarkData::new(unit, desired_action));
+        data.push(result.value);
+        data.samples.push(result);
+    }
+
+    /// Returns these results without the first `warmup_samples` values recorded for each metric,
+    /// such as those that still reflect cold caches after a `--warmup-duration` has passed. The
+    /// removed values are counted as discarded samples.
+    ///
+    /// Which values came first is only known for those recorded with [`Self::record`], so only
+    /// those are removed: any values of a metric pushed as a whole histogram with [`Self::push`]
+    /// are taken to have been recorded after them. The first values of merged results are those
+    /// of whichever results recorded values first, so filter the results of each run before
+    /// merging them.
+    pub fn filter_warmup(&self, warmup_samples: usize) -> BenchmarkResults {
+        let mut filtered = BenchmarkResults {
+            results: HashMap::new(),
+            elapsed: self.elapsed,
+            discarded_samples: self.discarded_samples,
+            timed_out: self.timed_out,
+            attempts: self.attempts,
+            resource_usage: self.resource_usage.clone(),
+            warmup_until: self.warmup_until,
+        };
+        for (key, data) in &self.results {
+            let mut kept = BenchmarkData {
+                unit: data.unit.clone(),
+                desired_action: data.desired_action,
+                values: data.to_histogram(),
+                samples: data.samples.clone(),
+            };
+            kept.samples.sort_by_key(|sample| sample.recorded_at);
+            let removed = kept.samples.len().min(warmup_samples);
+            let mut warmup = Histogram::<u64>::new_from(&kept.values);
+            for sample in kept.samples.drain(..removed) {
+                warmup.record(sample.value).unwrap();
+            }
+            kept.values.subtract(&warmup).unwrap();
+            filtered.discarded_samples += removed as u64;
+            filtered.results.insert(key.clone(), kept);
+        }
+        filtered
+    }
+
+    /// Combines two sets of results, for example from parallel workers or repeated runs. The
+    /// histograms of metrics present in both are merged; metrics present in only one are kept
+    /// as-is. Elapsed times are added together, as for runs that happened one after the other, and
+    /// the merged results timed out if either did and took as many attempts as the one that took
+    /// the most. The resource usage samples of both are kept.
+    pub fn merge(mut self, other: BenchmarkResults) -> BenchmarkResults {
+        self.elapsed = match (self.elapsed, other.elapsed) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+        self.discarded_samples += other.discarded_samples;
+        self.timed_out |= other.timed_out;
+        self.attempts = self.attempts.max(other.attempts);
+        self.resource_usage = match (self.resource_usage, other.resource_usage) {
+            (Some(a), Some(b)) => Some(a.merge(b)),
+            (a, b) => a.or(b),
+        };
+        for (key, data) in other.results {
+            match self.results.entry(key) {
+                Entry::Occupied(mut entry) => {
+                    let existing = entry.get_mut();
+                    existing.values.add(data.values).unwrap();
+                    existing.samples.extend(data.samples);
+                }
+                Entry::Vacant(entry) => {
+                    entry.insert(data);
+                }
+            }
+        }
+        self
+    }
+
+    /// Merges all of the given results together, returning `None` if there are none.
+    pub fn merge_all(iter: impl Iterator<Item = BenchmarkResults>) -> Option<BenchmarkResults> {
+        iter.reduce(BenchmarkResults::merge)
+    }
+}
+
+/// The results a running benchmark recorded over one interval of a `--time-series` run.
+#[derive(Debug)]
+pub struct IntervalSnapshot {
+    /// How long the benchmark had been running at the end of the interval.
+    pub elapsed: Duration,
+    /// The results recorded during the interval only, with the length of the interval as their
+    /// elapsed time.
+    pub results: BenchmarkResults,
+}
+
+/// Where a benchmark sends an [`IntervalSnapshot`] of its results every `interval` while it runs,
+/// for `--time-series`, as well as returning the results of the whole run once it finishes. Set
+/// with [`BenchmarkControl::set_interval_results`].
+#[derive(Debug, Clone)]
+pub struct IntervalResults {
+    interval: Duration,
+    sender: UnboundedSender<IntervalSnapshot>,
+}
+
+impl IntervalResults {
+    /// Returns a handle that asks for a snapshot every `interval`, along with the receiver the
+    /// snapshots arrive on.
+    pub fn new(interval: Duration) -> (Self, UnboundedReceiver<IntervalSnapshot>) {
+        let (sender, receiver) = unbounded_channel();
+        (Self { interval, sender }, receiver)
+    }
+
+    /// How often the benchmark should send a snapshot of its results.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Sends the results recorded since the previous snapshot, which cover `interval_length`,
+    /// and ended `elapsed` into the run. Snapshots sent after the receiver was dropped are
+    /// discarded.
+    pub fn send(&self, elapsed: Duration, interval_length: Duration, results: &BenchmarkResults) {
+        let mut results = results.clone();
+        results.record_elapsed_time(interval_length);
+        let _ = self.sender.send(IntervalSnapshot { elapsed, results });
+    }
 }
 
 /// The formatted benchmark parameters and results for serialization
@@ -201,4 +600,306 @@ pub trait BenchmarkControl {
 
     /// The [`DataGenerator`] used by this benchmark, if any.
     fn data_generator(&mut self) -> Option<&mut DataGenerator>;
+
+    /// Discards the samples recorded during the first `warmup` of each later call to
+    /// [`BenchmarkControl::benchmark`]. Returns an error if the benchmark doesn't support it.
+    fn set_warmup(&mut self, _warmup: Duration) -> Result<()> {
+        anyhow::bail!("{} doesn't support a warmup period", self.name())
+    }
+
+    /// Records the first `samples` values of each metric in later calls to
+    /// [`BenchmarkControl::benchmark`] with [`BenchmarkResults::record`], after any warmup, so
+    /// that they can be removed with [`BenchmarkResults::filter_warmup`]. Returns an error if the
+    /// benchmark doesn't support it.
+    fn set_warmup_samples(&mut self, _samples: usize) -> Result<()> {
+        anyhow::bail!("{} doesn't support --warmup-samples", self.name())
+    }
+
+    /// Sends snapshots of the results of each later call to [`BenchmarkControl::benchmark`] to
+    /// `intervals` as it runs, for `--time-series`. Returns an error if the benchmark only
+    /// reports its results once it finishes.
+    fn set_interval_results(&mut self, _intervals: IntervalResults) -> Result<()> {
+        anyhow::bail!("{} doesn't support --time-series", self.name())
+    }
+
+    /// Tears down anything the benchmark started, such as connections or background tasks, after
+    /// a call to [`BenchmarkControl::benchmark`] was cancelled because it exceeded the
+    /// `--run-timeout`, or before a failed run is retried with `--retries`. Does nothing by
+    /// default.
+    async fn cleanup(&self, _deployment: &DeploymentParameters) -> Result<()> {
+        Ok(())
+    }
+
+    /// Whether the deployment has settled after a previous run, e.g. has no compactions or
+    /// replication still in progress. Polled between the runs of a graph sweep with
+    /// `--cooldown-until-idle`. Always reports idle by default.
+    async fn is_idle(&self, _deployment: &DeploymentParameters) -> Result<bool> {
+        Ok(true)
+    }
+
+    /// Whether separate runs of this benchmark, such as the runs of a graph sweep, can execute
+    /// concurrently against the same deployment. Benchmarks that modify state in the deployment
+    /// that other runs depend on must not allow this.
+    fn allows_concurrent_runs(&self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use metrics::Unit;
+
+    use super::*;
+    use crate::test_utils::results;
+
+    #[test]
+    fn merge_identical_results() {
+        let a = results(&[("latency", &[10, 20, 30, 40])]);
+        let b = results(&[("latency", &[10, 20, 30, 40])]);
+
+        let merged = a.merge(b);
+        let hist = merged.results["latency"].to_histogram();
+        assert_eq!(hist.len(), 8);
+        assert_eq!(
+            hist.mean(),
+            results(&[("latency", &[10, 20, 30, 40])]).results["latency"]
+                .to_histogram()
+                .mean()
+        );
+        assert_eq!(hist.min(), 10);
+        assert_eq!(hist.max(), 40);
+    }
+
+    #[test]
+    fn merge_takes_min_and_max_across_results() {
+        let merged = results(&[("latency", &[5, 50])]).merge(results(&[("latency", &[1, 20])]));
+        let hist = merged.results["latency"].to_histogram();
+        assert_eq!(hist.min(), 1);
+        assert_eq!(hist.max(), 50);
+    }
+
+    #[test]
+    fn merge_disjoint_metrics() {
+        let merged = results(&[("reads", &[1])]).merge(results(&[("writes", &[2, 3])]));
+        assert_eq!(merged.results["reads"].to_histogram().len(), 1);
+        assert_eq!(merged.results["writes"].to_histogram().len(), 2);
+    }
+
+    #[test]
+    fn merge_all() {
+        assert!(BenchmarkResults::merge_all(std::iter::empty()).is_none());
+
+        let merged =
+            BenchmarkResults::merge_all((0..3).map(|_| results(&[("latency", &[1, 2])]))).unwrap();
+        assert_eq!(merged.results["latency"].to_histogram().len(), 6);
+    }
+
+    #[test]
+    fn throughput_per_sec() {
+        let mut results = results(&[("latency", &[10; 1000])]);
+        assert_eq!(results.achieved_qps(), None);
+        results.record_elapsed_time(Duration::from_secs(2));
+        assert_eq!(results.achieved_qps(), Some(500.0));
+        assert_eq!(results.sample_count(), 1000);
+        assert_eq!(
+            results.throughput_per_sec(results.elapsed.unwrap()),
+            Some(500.0)
+        );
+
+        results.record_elapsed_time(Duration::ZERO);
+        assert_eq!(results.throughput_per_sec(Duration::ZERO), None);
+        assert_eq!(results.achieved_qps(), None);
+    }
+
+    #[test]
+    fn metric_units() {
+        let mut results = results(&[("latency", &[1])]);
+        assert_eq!(
+            results.results["latency"].metric_unit(),
+            Some(Unit::Microseconds)
+        );
+        results.push(
+            "throughput",
+            Unit::BitsPerSecond,
+            MetricGoal::Increasing,
+            Histogram::new(3).unwrap(),
+        );
+        assert_eq!(
+            results.results["throughput"].metric_unit(),
+            Some(Unit::BitsPerSecond)
+        );
+        results.results.get_mut("latency").unwrap().unit = "Furlongs".to_owned();
+        assert_eq!(results.results["latency"].metric_unit(), None);
+    }
+
+    #[test]
+    fn unit_labels() {
+        assert_eq!(unit_label(Unit::Microseconds), Some("us"));
+        assert_eq!(unit_label(Unit::Mebibytes), Some("MiB"));
+        assert_eq!(unit_label(Unit::Count), None);
+        assert_eq!(TimeUnit::from_label("ms"), Some(TimeUnit::Ms));
+        assert_eq!(TimeUnit::Us.scale_to(TimeUnit::Ms), 0.001);
+        assert_eq!(TimeUnit::S.scale_to(TimeUnit::Us), 1e6);
+    }
+
+    #[test]
+    fn metric_names() {
+        let results = results(&[("writes", &[1])]).merge(results(&[("reads", &[2])]));
+        assert_eq!(results.metric_names(), vec!["reads", "writes"]);
+        assert!(BenchmarkResults::new().metric_names().is_empty());
+    }
+
+    #[test]
+    fn histogram_buckets() {
+        let results = results(&[("latency", &[10, 10, 20, 30, 30, 30, 5000])]);
+        let hist = results.results["latency"].to_histogram();
+        assert_eq!(
+            results.histogram_buckets("latency").unwrap(),
+            vec![
+                (10, 2),
+                (20, 1),
+                (30, 3),
+                (hist.highest_equivalent(5000), 1)
+            ]
+        );
+
+        let err = results.histogram_buckets("errors").unwrap_err().to_string();
+        assert!(err.contains("'errors'"), "{err}");
+        assert!(err.contains("[latency]"), "{err}");
+    }
+
+    #[test]
+    fn value_at_quantile() {
+        let results = results(&[("latency", &(1..=100).collect::<Vec<_>>())]);
+        assert_eq!(results.value_at_quantile("latency", 0.0).unwrap(), 1);
+        assert_eq!(results.value_at_quantile("latency", 0.5).unwrap(), 50);
+        assert_eq!(results.value_at_quantile("latency", 0.99).unwrap(), 99);
+        assert_eq!(results.value_at_quantile("latency", 1.0).unwrap(), 100);
+
+        results.value_at_quantile("latency", 1.5).unwrap_err();
+        results.value_at_quantile("errors", 0.5).unwrap_err();
+    }
+
+    #[test]
+    fn discards_samples_during_warmup() {
+        let mut results = BenchmarkResults::with_warmup(Duration::from_secs(60));
+        assert!(results.is_warming_up());
+        let mut hist = Histogram::<u64>::new(3).unwrap();
+        hist.record_n(10, 5).unwrap();
+        results.push("latency", Unit::Microseconds, MetricGoal::Decreasing, hist);
+        assert!(results.results.is_empty());
+        assert_eq!(results.discarded_samples, 5);
+
+        let merged = results.merge(BenchmarkResults::with_warmup(Duration::ZERO));
+        assert_eq!(merged.discarded_samples, 5);
+
+        let mut results = BenchmarkResults::with_warmup(Duration::ZERO);
+        assert!(!results.is_warming_up());
+        results = results.merge(self::results(&[("latency", &[1, 2])]));
+        assert_eq!(results.sample_count(), 2);
+        assert_eq!(results.discarded_samples, 0);
+    }
+
+    #[test]
+    fn filter_warmup_samples() {
+        let start = Instant::now();
+        let sample = |value, secs| TimestampedResult {
+            value,
+            recorded_at: start + Duration::from_secs(secs),
+        };
+        let mut results = BenchmarkResults::new();
+        // The first samples are unusually fast, and are recorded out of order.
+        for (value, secs) in [(120, 5), (1, 1), (100, 3), (2, 0), (110, 4), (3, 2)] {
+            results.record(
+                "latency",
+                Unit::Microseconds,
+                MetricGoal::Decreasing,
+                sample(value, secs),
+            );
+        }
+        results.record("errors", Unit::Count, MetricGoal::Decreasing, sample(7, 0));
+        results.record_elapsed_time(Duration::from_secs(6));
+        let mut hist = Histogram::<u64>::new(3).unwrap();
+        hist.record_n(1, 4).unwrap();
+        results.push("queue", Unit::Count, MetricGoal::Decreasing, hist);
+
+        let filtered = results.filter_warmup(3);
+        let latency = filtered.results["latency"].to_histogram();
+        assert_eq!(latency.len(), 3);
+        assert_eq!(latency.min(), 100);
+        assert_eq!(latency.max(), 120);
+        assert_eq!(filtered.results["latency"].samples.len(), 3);
+        assert!(filtered.results["errors"].values.is_empty());
+        // Values pushed as a histogram have no order, so they're kept.
+        assert_eq!(filtered.results["queue"].values.len(), 4);
+        assert_eq!(filtered.discarded_samples, 4);
+        assert_eq!(filtered.elapsed, results.elapsed);
+        assert_eq!(results.sample_count(), 11);
+
+        assert_eq!(results.filter_warmup(0).sample_count(), 11);
+    }
+
+    #[test]
+    fn filter_warmup_before_pushed_values() {
+        let start = Instant::now();
+        let mut results = BenchmarkResults::new();
+        // Benchmarks only record their first values one at a time, and push the rest.
+        for (value, millis) in [(900, 0), (800, 1), (700, 2)] {
+            results.record(
+                "latency",
+                Unit::Microseconds,
+                MetricGoal::Decreasing,
+                TimestampedResult {
+                    value,
+                    recorded_at: start + Duration::from_millis(millis),
+                },
+            );
+        }
+        results = results.merge(self::results(&[("latency", &[10, 20, 30, 40])]));
+
+        let filtered = results.filter_warmup(2);
+        let latency = filtered.results["latency"].to_histogram();
+        assert_eq!(latency.len(), 5);
+        assert_eq!(latency.min(), 10);
+        assert_eq!(latency.max(), 700);
+        assert_eq!(filtered.discarded_samples, 2);
+
+        // Only values recorded one at a time can be removed.
+        let filtered = results.filter_warmup(10);
+        assert_eq!(filtered.results["latency"].to_histogram().max(), 40);
+        assert_eq!(filtered.sample_count(), 4);
+        assert_eq!(filtered.discarded_samples, 3);
+    }
+
+    #[test]
+    fn record_discards_samples_during_warmup() {
+        let mut results = BenchmarkResults::with_warmup(Duration::from_secs(60));
+        let result = TimestampedResult {
+            value: 5,
+            recorded_at: Instant::now(),
+        };
+        results.record(
+            "latency",
+            Unit::Microseconds,
+            MetricGoal::Decreasing,
+            result,
+        );
+        assert!(results.results.is_empty());
+        assert_eq!(results.discarded_samples, 1);
+    }
+
+    #[test]
+    fn merge_adds_elapsed_times() {
+        let mut a = results(&[("latency", &[1])]);
+        a.record_elapsed_time(Duration::from_secs(1));
+        let mut b = results(&[("latency", &[2])]);
+        b.record_elapsed_time(Duration::from_millis(500));
+
+        let merged = a.merge(b);
+        assert_eq!(merged.elapsed, Some(Duration::from_millis(1500)));
+        assert_eq!(
+            merged.merge(results(&[("latency", &[3])])).elapsed,
+            Some(Duration::from_millis(1500))
+        );
+    }
 }
@@ -0,0 +1,84 @@
+//! Inspects the data that the benchmarks' data generator would generate for an annotated schema,
+//! without connecting to a database.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use benchmarks::utils::path::benchmark_path;
+use benchmarks::utils::spec::{ColumnCardinality, DatabaseSchema};
+use clap::{Parser, Subcommand, ValueHint};
+use database_utils::DatabaseType;
+
+#[derive(Parser)]
+#[command(name = "data_generator")]
+struct DataGeneratorCli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print an estimate of the number of distinct values generated for every column of a
+    /// schema, from the row count of its table and its annotation. Estimates are rough upper
+    /// bounds, e.g. for planning index sizes and cache footprints.
+    PrintCardinalities {
+        /// Path to the annotated SQL schema.
+        #[arg(long, value_hint = ValueHint::AnyPath)]
+        schema: PathBuf,
+
+        /// The dialect the schema is written in.
+        #[arg(long, value_enum, default_value = "mysql")]
+        database_type: DatabaseType,
+    },
+}
+
+/// Writes `cardinalities` to `out` as a table with a row for each column.
+fn write_cardinalities(
+    out: &mut impl Write,
+    cardinalities: &[ColumnCardinality],
+) -> io::Result<()> {
+    let header = ["table", "column", "rows", "annotation", "cardinality"];
+    let rows = cardinalities
+        .iter()
+        .map(|c| {
+            [
+                c.table.clone(),
+                c.column.clone(),
+                c.rows.to_string(),
+                c.annotation.clone().unwrap_or_else(|| "-".to_owned()),
+                c.cardinality.to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    let header = header.map(str::to_owned);
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    match DataGeneratorCli::parse().command {
+        Command::PrintCardinalities {
+            schema,
+            database_type,
+        } => {
+            let ddl = std::fs::read_to_string(benchmark_path(&schema)?)?;
+            let schema = DatabaseSchema::new(&ddl, database_type.into())?;
+            write_cardinalities(&mut io::stdout().lock(), &schema.estimated_cardinalities())?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,804 @@
+//! Support for comparing two CSV files written by a `--graph` sweep, for example the results of
+//! the same sweep before and after a change.
+//!
+//! Rows are joined on their axis values (the x-value, and the z-value if the sweep had a z-axis)
+//! and the values of any `--tag` columns, and each metric column present in both files is
+//! compared. Rows present in only one file are
+//! reported separately rather than dropped.
+//!
+//! Columns are matched by name without the unit they're labelled with, such as ` (us)`, and
+//! values in units of time are converted to microseconds, so that results written with different
+//! `--convert-units` can be compared.
+//!
+//! A file may have several rows with the same key, such as the unaggregated results of repeated
+//! runs appended to the same file. Their mean is compared, and if both files have several runs of
+//! a row, each delta is annotated with whether it's significant, by [`WelchTest`].
+
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use clap::Parser;
+use itertools::Itertools;
+
+use crate::benchmark::TimeUnit;
+use crate::results_sink::{
+    ACHIEVED_QPS_COLUMN, ATTEMPTS_COLUMN, ERROR_COLUMN, METADATA_COLUMNS, TAG_COLUMN,
+    TIMED_OUT_COLUMN,
+};
+use crate::significance::{Summary, WelchTest, DEFAULT_SIGNIFICANCE_LEVEL};
+
+#[derive(Parser, Clone, Debug)]
+pub struct CompareParams {
+    /// A graph results CSV file to compare with. With `--candidate`, the two files are compared
+    /// instead of running a benchmark, with deltas computed relative to the baseline. With
+    /// `--graph`, percentage `--fail-if` conditions are evaluated relative to the baseline.
+    #[arg(long)]
+    pub baseline: Option<PathBuf>,
+
+    /// The graph results CSV file to compare against `--baseline`.
+    #[arg(long, requires = "baseline")]
+    pub candidate: Option<PathBuf>,
+
+    /// Where to write the comparison as a CSV, with a row for each metric column of every row
+    /// joined between the two files.
+    #[arg(long, requires = "candidate")]
+    pub compare_output: Option<PathBuf>,
+
+    /// The p-value below which a delta between rows that were each run several times is marked
+    /// as significant, rather than noise.
+    #[arg(
+        long,
+        requires = "candidate",
+        default_value_t = DEFAULT_SIGNIFICANCE_LEVEL,
+        value_parser = parse_significance_level
+    )]
+    pub significance_level: f64,
+}
+
+/// Parses a `--significance-level`, which must be between 0 and 1.
+fn parse_significance_level(value: &str) -> Result<f64, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|alpha| *alpha > 0.0 && *alpha < 1.0)
+        .ok_or_else(|| {
+            format!("Invalid significance level '{value}': expected a p-value in (0, 1)")
+        })
+}
+
+impl CompareParams {
+    /// Returns whether a comparison was requested, rather than a benchmark run.
+    pub fn is_enabled(&self) -> bool {
+        self.candidate.is_some()
+    }
+
+    /// Compares `--baseline` with `--candidate`, printing the comparison and writing it to
+    /// `--compare-output` if given.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let (Some(baseline), Some(candidate)) = (&self.baseline, &self.candidate) else {
+            bail!("--baseline and --candidate must both be set");
+        };
+        let comparison = Comparison::from_paths(baseline, candidate)?
+            .with_significance_level(self.significance_level);
+        print!("{comparison}");
+        if let Some(path) = &self.compare_output {
+            comparison.write_csv(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// The graph results read from a CSV file.
+pub struct GraphResults {
+    /// The names of the axis columns and `--tag` columns that identify each row.
+    key_columns: Vec<String>,
+    /// The names of the metric columns, following the key columns, without their units.
+    columns: Vec<String>,
+    /// The key and metric values of each row, in file order. Several rows may have the same key.
+    rows: Vec<(Vec<String>, Vec<f64>)>,
+}
+
+impl GraphResults {
+    /// Reads the graph results CSV file at `path`, converting values in units of time to
+    /// microseconds.
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let mut reader = csv::Reader::from_path(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let header = reader.headers()?.clone();
+        // The axis columns are followed by a `<metric> samples` column for the first metric.
+        let key_len = header
+            .iter()
+            .position(|column| column.ends_with(" samples"))
+            .unwrap_or(1)
+            .max(1);
+        // Tags, timeouts, errors and metadata written with `--with-metadata` aren't numeric, and
+        // the attempts of `--retries` say nothing about performance, so they aren't compared.
+        let is_text_column = |column: &str| {
+            [TIMED_OUT_COLUMN, ATTEMPTS_COLUMN, TAG_COLUMN, ERROR_COLUMN].contains(&column)
+                || METADATA_COLUMNS.contains(&column)
+        };
+        // The columns after the achieved QPS that the sweep didn't generate itself are `--tag`s,
+        // which identify rows along with the axes.
+        let achieved_qps = header
+            .iter()
+            .position(|column| column == ACHIEVED_QPS_COLUMN);
+        let is_tag_column = |i: usize, column: &str| {
+            achieved_qps.is_some_and(|achieved_qps| i > achieved_qps) && !is_text_column(column)
+        };
+        let (key_indices, other_indices): (Vec<_>, Vec<_>) = header
+            .iter()
+            .enumerate()
+            .partition(|(i, column)| *i < key_len || is_tag_column(*i, column));
+        let key_indices = key_indices.into_iter().map(|(i, _)| i).collect::<Vec<_>>();
+        let key_columns = key_indices.iter().map(|i| header[*i].to_owned()).collect();
+        let value_indices = other_indices
+            .into_iter()
+            .filter(|(_, column)| !is_text_column(column))
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        let (columns, scales): (Vec<_>, Vec<_>) = value_indices
+            .iter()
+            .map(|i| match split_unit(&header[*i]) {
+                (name, Some(unit)) => (name.to_owned(), unit.scale_to(TimeUnit::Us)),
+                (name, None) => (name.to_owned(), 1.0),
+            })
+            .unzip();
+
+        let rows = reader
+            .records()
+            .enumerate()
+            .map(|(row, record)| {
+                let record = record?;
+                let key = key_indices.iter().map(|i| record[*i].to_owned()).collect();
+                let values = value_indices
+                    .iter()
+                    .zip(&scales)
+                    .map(|(i, scale)| {
+                        let (value, column) = (&record[*i], &header[*i]);
+                        let value = value.trim().parse::<f64>().map_err(|e| {
+                            anyhow!(
+                                "Invalid value '{value}' for '{column}' in row {} of {}: {e}",
+                                row + 1,
+                                path.display()
+                            )
+                        })?;
+                        Ok(value * scale)
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                Ok((key, values))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            key_columns,
+            columns,
+            rows,
+        })
+    }
+
+    /// Returns the value of `column` in the row with the given axis values, or its mean if there
+    /// are several such rows, or `None` if there are none.
+    pub fn value(&self, key: &[String], column: &str) -> Option<f64> {
+        let i = self.columns.iter().position(|c| c == column)?;
+        let values = self.runs(key).map(|values| values[i]).collect::<Vec<_>>();
+        (!values.is_empty()).then(|| Summary::of(&values).mean)
+    }
+
+    /// Returns the metric values of every row with the given key.
+    fn runs<'a>(&'a self, key: &'a [String]) -> impl Iterator<Item = &'a Vec<f64>> + 'a {
+        self.rows
+            .iter()
+            .filter(move |(k, _)| k == key)
+            .map(|(_, values)| values)
+    }
+
+    /// Returns each distinct key, in the order it first appears.
+    fn keys(&self) -> impl Iterator<Item = &Vec<String>> {
+        self.rows.iter().map(|(key, _)| key).unique()
+    }
+}
+
+/// Splits the unit a column is labelled with, such as `(ms)` in `latency p99 (ms)`, from its name,
+/// returning the unit if it's a unit of time. Labels of other units are removed too.
+fn split_unit(column: &str) -> (&str, Option<TimeUnit>) {
+    match column.strip_suffix(')').and_then(|c| c.rsplit_once(" (")) {
+        Some((name, label)) => (name, TimeUnit::from_label(label)),
+        None => (column, None),
+    }
+}
+
+/// The change in a single value between the baseline and the candidate.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Delta {
+    pub baseline: f64,
+    pub candidate: f64,
+}
+
+impl Delta {
+    /// The candidate value minus the baseline value.
+    pub fn absolute(&self) -> f64 {
+        self.candidate - self.baseline
+    }
+
+    /// The change as a percentage of the baseline value. If the baseline is zero, this is zero
+    /// when the candidate is also zero, and positive or negative infinity otherwise.
+    pub fn percent(&self) -> f64 {
+        if self.baseline == 0.0 {
+            match self.candidate.partial_cmp(&0.0) {
+                Some(std::cmp::Ordering::Greater) => f64::INFINITY,
+                Some(std::cmp::Ordering::Less) => f64::NEG_INFINITY,
+                _ => 0.0,
+            }
+        } else {
+            self.absolute() / self.baseline.abs() * 100.0
+        }
+    }
+}
+
+/// The comparison of two graph results files.
+#[derive(Debug)]
+pub struct Comparison {
+    /// The names of the axis columns the files were joined on.
+    pub key_columns: Vec<String>,
+    /// The metric columns present in both files, in the order of the baseline.
+    pub columns: Vec<String>,
+    /// The key of each row present in both files, with a delta for each of `columns`. Rows that
+    /// were run several times are compared by their mean.
+    pub rows: Vec<(Vec<String>, Vec<Delta>)>,
+    /// The test of whether each delta in `rows` is significant, for rows run at least twice in
+    /// both files.
+    pub tests: Vec<Vec<Option<WelchTest>>>,
+    /// The p-value below which a delta is significant.
+    pub significance_level: f64,
+    /// The keys of rows present in only the baseline.
+    pub only_in_baseline: Vec<Vec<String>>,
+    /// The keys of rows present in only the candidate.
+    pub only_in_candidate: Vec<Vec<String>>,
+    /// Metric columns present in only one of the files, which aren't compared.
+    pub unmatched_columns: Vec<String>,
+}
+
+impl Comparison {
+    /// Reads and compares the graph results CSV files at `baseline` and `candidate`.
+    pub fn from_paths(baseline: &Path, candidate: &Path) -> anyhow::Result<Self> {
+        Self::new(
+            GraphResults::from_path(baseline)?,
+            GraphResults::from_path(candidate)?,
+        )
+    }
+
+    fn new(baseline: GraphResults, candidate: GraphResults) -> anyhow::Result<Self> {
+        if baseline.key_columns != candidate.key_columns {
+            bail!(
+                "Results were graphed over different axes: [{}] in the baseline, but [{}] in the \
+                 candidate",
+                baseline.key_columns.join(", "),
+                candidate.key_columns.join(", ")
+            );
+        }
+
+        let candidate_columns = candidate
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| (column.as_str(), i))
+            .collect::<HashMap<_, _>>();
+        let (matched, unmatched): (Vec<_>, Vec<_>) = baseline
+            .columns
+            .iter()
+            .enumerate()
+            .partition(|(_, column)| candidate_columns.contains_key(column.as_str()));
+        let unmatched_columns = unmatched
+            .into_iter()
+            .map(|(_, column)| column)
+            .chain(
+                candidate
+                    .columns
+                    .iter()
+                    .filter(|column| !baseline.columns.contains(column)),
+            )
+            .cloned()
+            .collect();
+
+        let mut rows = Vec::new();
+        let mut tests = Vec::new();
+        let mut only_in_baseline = Vec::new();
+        for key in baseline.keys() {
+            let baseline_runs = baseline.runs(key).collect::<Vec<_>>();
+            let candidate_runs = candidate.runs(key).collect::<Vec<_>>();
+            if candidate_runs.is_empty() {
+                only_in_baseline.push(key.clone());
+                continue;
+            }
+            let summarize = |runs: &[&Vec<f64>], i: usize| {
+                Summary::of(&runs.iter().map(|values| values[i]).collect::<Vec<_>>())
+            };
+            let (deltas, row_tests) = matched
+                .iter()
+                .map(|(i, column)| {
+                    let baseline = summarize(&baseline_runs, *i);
+                    let candidate = summarize(&candidate_runs, candidate_columns[column.as_str()]);
+                    let delta = Delta {
+                        baseline: baseline.mean,
+                        candidate: candidate.mean,
+                    };
+                    (delta, WelchTest::new(&baseline, &candidate))
+                })
+                .unzip();
+            rows.push((key.clone(), deltas));
+            tests.push(row_tests);
+        }
+        let baseline_keys = baseline.keys().collect::<Vec<_>>();
+        let only_in_candidate = candidate
+            .keys()
+            .filter(|key| !baseline_keys.contains(key))
+            .cloned()
+            .collect();
+
+        Ok(Self {
+            key_columns: baseline.key_columns,
+            columns: matched
+                .into_iter()
+                .map(|(_, column)| column.clone())
+                .collect(),
+            rows,
+            tests,
+            significance_level: DEFAULT_SIGNIFICANCE_LEVEL,
+            only_in_baseline,
+            only_in_candidate,
+            unmatched_columns,
+        })
+    }
+
+    /// Sets the p-value below which a delta is significant, rather than the default of
+    /// [`DEFAULT_SIGNIFICANCE_LEVEL`].
+    pub fn with_significance_level(mut self, alpha: f64) -> Self {
+        self.significance_level = alpha;
+        self
+    }
+
+    /// Returns whether any row was run several times in both files, and so has its deltas tested
+    /// for significance.
+    fn has_tests(&self) -> bool {
+        self.tests.iter().flatten().any(Option::is_some)
+    }
+
+    /// Returns the p-value of `test`, and whether it's significant, as table cells. Both are empty
+    /// if the delta wasn't tested.
+    fn significance_cells(&self, test: &Option<WelchTest>) -> [String; 2] {
+        match test {
+            Some(test) => [
+                format!("{:.3}", test.p_value),
+                if test.is_significant(self.significance_level) {
+                    "significant"
+                } else {
+                    "not significant"
+                }
+                .to_owned(),
+            ],
+            None => Default::default(),
+        }
+    }
+
+    /// Writes the comparison to `path` as a CSV, with the axis columns followed by the metric
+    /// column name, baseline value, candidate value, absolute delta and percentage delta. If any
+    /// row was run several times in both files, these are followed by the p-value of each delta
+    /// and whether it's significant, which are empty for rows that weren't. Rows present in only
+    /// one file are written once, with the metric column saying which file they are in and the
+    /// values left empty.
+    pub fn write_csv(&self, path: &Path) -> anyhow::Result<()> {
+        let mut writer = csv::Writer::from_path(path)?;
+        let significance_columns = if self.has_tests() {
+            &["p-value", "significant"][..]
+        } else {
+            &[]
+        };
+        let header = self
+            .key_columns
+            .iter()
+            .map(String::as_str)
+            .chain(["metric", "baseline", "candidate", "delta", "delta %"])
+            .chain(significance_columns.iter().copied());
+        writer.write_record(header)?;
+        for ((key, deltas), tests) in self.rows.iter().zip(&self.tests) {
+            for ((column, delta), test) in self.columns.iter().zip(deltas).zip(tests) {
+                let values = [
+                    column.clone(),
+                    delta.baseline.to_string(),
+                    delta.candidate.to_string(),
+                    delta.absolute().to_string(),
+                    delta.percent().to_string(),
+                ];
+                let significance = match test {
+                    Some(test) => vec![
+                        test.p_value.to_string(),
+                        test.is_significant(self.significance_level).to_string(),
+                    ],
+                    None => vec![String::new(); significance_columns.len()],
+                };
+                writer.write_record(key.iter().cloned().chain(values).chain(significance))?;
+            }
+        }
+        for (key, note) in self
+            .only_in_baseline
+            .iter()
+            .map(|key| (key, "only in baseline"))
+            .chain(
+                self.only_in_candidate
+                    .iter()
+                    .map(|key| (key, "only in candidate")),
+            )
+        {
+            let values = [note, "", "", "", ""];
+            let significance = significance_columns.iter().map(|_| "");
+            writer.write_record(
+                key.iter()
+                    .map(String::as_str)
+                    .chain(values)
+                    .chain(significance),
+            )?;
+        }
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn describe_key(&self, key: &[String]) -> String {
+        self.key_columns
+            .iter()
+            .zip(key)
+            .map(|(column, value)| format!("{column} = {value}"))
+            .join(", ")
+    }
+}
+
+impl Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let with_tests = self.has_tests();
+        let header = ["", "metric", "baseline", "candidate", "delta", "delta %"]
+            .into_iter()
+            .chain(with_tests.then_some(["p-value", ""]).into_iter().flatten())
+            .map(String::from)
+            .collect::<Vec<_>>();
+        let rows =
+            self.rows
+                .iter()
+                .zip(&self.tests)
+                .flat_map(|((key, deltas), tests)| {
+                    let key = self.describe_key(key);
+                    self.columns.iter().zip(deltas).zip(tests).map(
+                        move |((column, delta), test)| {
+                            [
+                                key.clone(),
+                                column.clone(),
+                                delta.baseline.to_string(),
+                                delta.candidate.to_string(),
+                                format!("{:+}", delta.absolute()),
+                                format!("{:+.1}%", delta.percent()),
+                            ]
+                            .into_iter()
+                            .chain(
+                                with_tests
+                                    .then(|| self.significance_cells(test))
+                                    .into_iter()
+                                    .flatten(),
+                            )
+                            .collect::<Vec<_>>()
+                        },
+                    )
+                })
+                .collect::<Vec<_>>();
+        let widths = (0..header.len())
+            .map(|i| {
+                std::iter::once(&header)
+                    .chain(&rows)
+                    .map(|row| row[i].len())
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        for row in std::iter::once(&header).chain(&rows) {
+            let line = row
+                .iter()
+                .zip(&widths)
+                .map(|(value, width)| format!("{value:width$}"))
+                .join("  ");
+            writeln!(f, "{}", line.trim_end())?;
+        }
+
+        for key in &self.only_in_baseline {
+            writeln!(f, "Only in baseline: {}", self.describe_key(key))?;
+        }
+        for key in &self.only_in_candidate {
+            writeln!(f, "Only in candidate: {}", self.describe_key(key))?;
+        }
+        if !self.unmatched_columns.is_empty() {
+            writeln!(
+                f,
+                "Not compared, present in only one file: {}",
+                self.unmatched_columns.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASELINE: &str = "\
+target-qps,latency samples,latency p50,errors samples
+100,10,5,0
+200,10,8,0
+300,10,20,1
+";
+
+    const CANDIDATE: &str = "\
+target-qps,latency samples,latency p50,latency p99
+100,10,4,9
+200,10,10,12
+400,10,30,40
+";
+
+    fn compare(baseline: &str, candidate: &str) -> anyhow::Result<Comparison> {
+        let dir = tempfile::tempdir().unwrap();
+        let baseline_path = dir.path().join("baseline.csv");
+        let candidate_path = dir.path().join("candidate.csv");
+        std::fs::write(&baseline_path, baseline).unwrap();
+        std::fs::write(&candidate_path, candidate).unwrap();
+        Comparison::from_paths(&baseline_path, &candidate_path)
+    }
+
+    #[test]
+    fn joins_on_x_value() {
+        let comparison = compare(BASELINE, CANDIDATE).unwrap();
+        assert_eq!(comparison.key_columns, ["target-qps"]);
+        assert_eq!(comparison.columns, ["latency samples", "latency p50"]);
+        assert_eq!(
+            comparison.unmatched_columns,
+            ["errors samples", "latency p99"]
+        );
+
+        assert_eq!(comparison.rows.len(), 2);
+        let (key, deltas) = &comparison.rows[1];
+        assert_eq!(key, &["200"]);
+        assert_eq!(deltas[1].absolute(), 2.0);
+        assert_eq!(deltas[1].percent(), 25.0);
+        assert_eq!(comparison.rows[0].1[1].percent(), -20.0);
+
+        assert_eq!(comparison.only_in_baseline, [["300"]]);
+        assert_eq!(comparison.only_in_candidate, [["400"]]);
+        let table = comparison.to_string();
+        assert!(
+            table.contains("Only in baseline: target-qps = 300"),
+            "{table}"
+        );
+        assert!(
+            table.contains("Only in candidate: target-qps = 400"),
+            "{table}"
+        );
+    }
+
+    #[test]
+    fn percent_with_zero_baseline() {
+        let delta = |baseline, candidate| Delta {
+            baseline,
+            candidate,
+        };
+        assert_eq!(delta(0.0, 0.0).percent(), 0.0);
+        assert_eq!(delta(0.0, 3.0).percent(), f64::INFINITY);
+        assert_eq!(delta(0.0, -3.0).percent(), f64::NEG_INFINITY);
+        assert_eq!(delta(-4.0, -2.0).percent(), 50.0);
+    }
+
+    #[test]
+    fn joins_on_z_value() {
+        let baseline = "target-qps,workers,latency samples\n100,1,5\n100,2,6\n";
+        let candidate = "target-qps,workers,latency samples\n100,2,9\n";
+        let comparison = compare(baseline, candidate).unwrap();
+        assert_eq!(comparison.key_columns, ["target-qps", "workers"]);
+        assert_eq!(comparison.rows.len(), 1);
+        assert_eq!(comparison.rows[0].1[0].absolute(), 3.0);
+        assert_eq!(comparison.only_in_baseline, [["100", "1"]]);
+
+        let err = compare(baseline, "target-qps,latency samples\n100,5\n").unwrap_err();
+        assert!(err.to_string().contains("different axes"), "{err}");
+    }
+
+    #[test]
+    fn joins_on_tags() {
+        let baseline = "\
+target-qps,latency samples,achieved_qps,tag,branch,region,timestamp
+100,5,99,main,main,us,2024-05-01T12:00:00Z
+100,6,98,main,feature,us,2024-05-01T12:00:00Z
+";
+        let candidate = "\
+target-qps,latency samples,achieved_qps,tag,branch,region,timestamp
+100,9,97,new,feature,us,2024-05-02T12:00:00Z
+";
+        let comparison = compare(baseline, candidate).unwrap();
+        assert_eq!(comparison.key_columns, ["target-qps", "branch", "region"]);
+        assert_eq!(comparison.columns, ["latency samples", "achieved_qps"]);
+        assert_eq!(comparison.rows.len(), 1);
+        assert_eq!(comparison.rows[0].0, ["100", "feature", "us"]);
+        assert_eq!(comparison.rows[0].1[0].absolute(), 3.0);
+        assert_eq!(comparison.only_in_baseline, [["100", "main", "us"]]);
+    }
+
+    #[test]
+    fn converts_units_of_time() {
+        let baseline = "target-qps,latency samples,latency p99 (ms),rows max (MiB)\n100,5,2.5,3\n";
+        let candidate = "target-qps,latency samples,latency p99 (us),rows max\n100,5,3000,4\n";
+        let comparison = compare(baseline, candidate).unwrap();
+        assert_eq!(
+            comparison.columns,
+            ["latency samples", "latency p99", "rows max"]
+        );
+        let deltas = &comparison.rows[0].1;
+        assert_eq!((deltas[1].baseline, deltas[1].candidate), (2500.0, 3000.0));
+        assert_eq!((deltas[2].baseline, deltas[2].candidate), (3.0, 4.0));
+    }
+
+    #[test]
+    fn write_diff_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("diff.csv");
+        compare(BASELINE, CANDIDATE)
+            .unwrap()
+            .write_csv(&path)
+            .unwrap();
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec![
+                "target-qps",
+                "metric",
+                "baseline",
+                "candidate",
+                "delta",
+                "delta %"
+            ]
+        );
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        // Two metrics for each of the two joined rows, then a row for each unmatched x-value.
+        assert_eq!(rows.len(), 6);
+        assert_eq!(
+            rows[3].iter().collect::<Vec<_>>(),
+            ["200", "latency p50", "8", "10", "2", "25"]
+        );
+        assert_eq!(
+            rows[4].iter().collect::<Vec<_>>(),
+            ["300", "only in baseline", "", "", "", ""]
+        );
+        assert_eq!(&rows[5][1], "only in candidate");
+    }
+
+    /// Per-run results of two x-values, run three times in each file, and a third x-value run
+    /// once in each.
+    const BASELINE_RUNS: &str = "\
+target-qps,latency p50,latency p99
+100,10,20
+100,11,21
+100,12,19
+200,20,40
+200,21,50
+200,19,45
+300,30,60
+";
+
+    const CANDIDATE_RUNS: &str = "\
+target-qps,latency p50,latency p99
+100,20,21
+100,21,19
+100,22,20
+200,20,45
+200,22,50
+200,21,40
+300,35,61
+";
+
+    #[test]
+    fn compares_repeated_runs() {
+        let comparison = compare(BASELINE_RUNS, CANDIDATE_RUNS).unwrap();
+        assert_eq!(comparison.rows.len(), 3);
+        assert!(comparison.only_in_baseline.is_empty());
+        assert!(comparison.only_in_candidate.is_empty());
+
+        let (key, deltas) = &comparison.rows[0];
+        assert_eq!(key, &["100"]);
+        assert_eq!((deltas[0].baseline, deltas[0].candidate), (11.0, 21.0));
+        let tests = &comparison.tests;
+        // A consistent shift in the p50 is significant, but the p99 doesn't change beyond the
+        // noise between runs.
+        let p50 = tests[0][0].unwrap();
+        assert!(p50.is_significant(0.001), "{p50:?}");
+        let p99 = tests[0][1].unwrap();
+        assert_eq!(p99.p_value, 1.0);
+        assert!(!tests[1][1].unwrap().is_significant(0.05));
+        // A row that was only run once in each file can't be tested.
+        assert_eq!(tests[2], [None, None]);
+
+        let table = comparison.to_string();
+        let cells = table
+            .lines()
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(cells[0].last(), Some(&"p-value"), "{table}");
+        assert_eq!(cells[1][9..], ["0.000", "significant"], "{table}");
+        assert_eq!(cells[2][9..], ["1.000", "not", "significant"], "{table}");
+        assert_eq!(cells[5][8..], ["+16.7%"], "{table}");
+    }
+
+    #[test]
+    fn configurable_significance_level() {
+        let baseline = "target-qps,latency p50\n100,10\n100,12\n100,11\n";
+        let candidate = "target-qps,latency p50\n100,12\n100,14\n100,12\n";
+        let comparison = compare(baseline, candidate).unwrap();
+        let test = comparison.tests[0][0].unwrap();
+        assert!(test.p_value > 0.05 && test.p_value < 0.2, "{test:?}");
+        assert!(comparison.to_string().contains("not significant"));
+        let comparison = comparison.with_significance_level(0.2);
+        assert!(!comparison.to_string().contains("not significant"));
+
+        assert!(parse_significance_level("0.01").is_ok());
+        for invalid in ["0", "1", "-0.5", "five"] {
+            assert!(parse_significance_level(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn write_diff_csv_with_significance() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("diff.csv");
+        compare(BASELINE_RUNS, &CANDIDATE_RUNS.replace("300,", "400,"))
+            .unwrap()
+            .write_csv(&path)
+            .unwrap();
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec![
+                "target-qps",
+                "metric",
+                "baseline",
+                "candidate",
+                "delta",
+                "delta %",
+                "p-value",
+                "significant"
+            ]
+        );
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(
+            rows[1].iter().collect::<Vec<_>>(),
+            ["100", "latency p99", "20", "20", "0", "0", "1", "false"]
+        );
+        assert_eq!(&rows[0][7], "true");
+        assert_eq!(
+            rows[4].iter().collect::<Vec<_>>(),
+            ["300", "only in baseline", "", "", "", "", "", ""]
+        );
+    }
+
+    #[test]
+    fn value_of_repeated_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("runs.csv");
+        std::fs::write(&path, BASELINE_RUNS).unwrap();
+        let results = GraphResults::from_path(&path).unwrap();
+        assert_eq!(
+            results.value(&["200".to_owned()], "latency p99"),
+            Some(45.0)
+        );
+        assert_eq!(
+            results.value(&["300".to_owned()], "latency p99"),
+            Some(60.0)
+        );
+        assert_eq!(results.value(&["400".to_owned()], "latency p99"), None);
+    }
+}
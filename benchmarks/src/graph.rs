@@ -0,0 +1,2475 @@
+//! Support for running a benchmark repeatedly while varying a single parameter, writing the
+//! results of every run to a file so they can be graphed.
+//!
+//! The parameter being varied (the "x-axis") is either one of the benchmark's own command-line
+//! flags, such as `target-qps`, or a data generator variable, which is named with a leading `@`
+//! (for example `@user_rows`) in the same way it is referenced from a schema file.
+
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::io::Write;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use chrono::Utc;
+use clap::error::ErrorKind;
+use clap::{Parser, ValueHint};
+use itertools::Itertools;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use serde::Serialize;
+use tracing::level_filters::LevelFilter;
+
+use crate::axis::{
+    axis_args, is_datagen_axis, parse_duration_arg, validate_axis, validate_axis_values,
+    AxisMetadata, XType, XValue, KNOWN_AXES,
+};
+use crate::benchmark::{BenchmarkResults, TimeUnit};
+use crate::histograms::HistogramFormat;
+use crate::path_template::{expand_path_template, PathVars};
+use crate::regression::FailCondition;
+use crate::report::Tag;
+use crate::resource_usage;
+use crate::results_sink::{
+    current_commit, ColumnTag, PlotFormat, ResourceUsageOutput, ResultsFormat, ACHIEVED_QPS_COLUMN,
+    ATTEMPTS_COLUMN, ERROR_COLUMN, METADATA_COLUMNS, RESULTS_EXTENSIONS, TAG_COLUMN,
+    TIMED_OUT_COLUMN,
+};
+use crate::sweep::{Cooldown, RetryPolicy, TIME_SERIES_X_AXIS};
+use crate::utils::generate::merge_json;
+use crate::Quantiles;
+
+/// A list of values passed on the command line as a single comma-separated string. A comma
+/// within a value is escaped as `\,`, and a backslash as `\\`, for example one that ends a value;
+/// any other backslash is kept as it is, so that values like regular expressions can be passed
+/// unchanged.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommaSeparatedString(pub(crate) Vec<String>);
+
+impl FromStr for CommaSeparatedString {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(Self(vec![]));
+        }
+        let mut values = vec![];
+        let mut value = String::new();
+        let mut chars = s.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some(escaped @ (',' | '\\')) => value.push(escaped),
+                    Some(other) => value.extend(['\\', other]),
+                    None => value.push('\\'),
+                },
+                ',' => values.push(std::mem::take(&mut value).trim().to_owned()),
+                c => value.push(c),
+            }
+        }
+        values.push(value.trim().to_owned());
+        Ok(Self(values))
+    }
+}
+
+impl Deref for CommaSeparatedString {
+    type Target = [String];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl CommaSeparatedString {
+    /// Reads a list of values from the file at `path`, with one value per line. Blank lines and
+    /// `#` comments are ignored, and it's an error for the file to contain no values.
+    pub fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read x-values from {}: {e}", path.display()))?;
+        let values = contents
+            .lines()
+            .map(|line| line.split('#').next().unwrap_or_default().trim())
+            .filter(|value| !value.is_empty())
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            bail!("{} doesn't contain any x-values", path.display());
+        }
+        Ok(Self(values))
+    }
+
+    /// Parses every value as an `f64`, returning an error naming the first value that could not
+    /// be parsed along with its position in the list.
+    pub fn validate_as_f64(&self) -> anyhow::Result<Vec<f64>> {
+        self.parse_all()
+    }
+
+    /// Parses every value as a `u64`, returning an error naming the first value that could not
+    /// be parsed along with its position in the list.
+    pub fn validate_as_u64(&self) -> anyhow::Result<Vec<u64>> {
+        self.parse_all()
+    }
+
+    fn parse_all<T>(&self) -> anyhow::Result<Vec<T>>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.0
+            .iter()
+            .enumerate()
+            .map(|(i, v)| {
+                v.parse().map_err(|e| {
+                    anyhow!(
+                        "Invalid value '{v}' at position {i}: expected {}: {e}",
+                        std::any::type_name::<T>()
+                    )
+                })
+            })
+            .collect()
+    }
+}
+
+#[derive(Parser, Clone, Debug)]
+pub struct GraphParams {
+    /// Run the benchmark once for each of `--x-values`, writing the results of every run to
+    /// `--graph-results-path` instead of printing them.
+    #[arg(long, requires = "x_axis")]
+    pub graph: bool,
+
+    /// Instead of sweeping `--x-axis`, run the benchmark once and write a row of results to
+    /// `--graph-results-path` for every `--time-series-interval` of the run, with how long the
+    /// benchmark had been running as the x-value, in seconds, e.g. to see how latency changes
+    /// while ReadySet snapshots. Intervals in which nothing was recorded are skipped. Only
+    /// supported by benchmarks that report their results as they run.
+    #[arg(long, conflicts_with_all = ["graph", "x_axis", "z_axis"])]
+    pub time_series: bool,
+
+    /// The length of each interval of a `--time-series` run, e.g. `30s`.
+    #[arg(
+        long,
+        requires = "time_series",
+        default_value = "10s",
+        value_parser = parse_duration_arg
+    )]
+    pub time_series_interval: Duration,
+
+    /// The parameter to vary between runs. Names prefixed with `@` refer to a data generator
+    /// variable; anything else is passed to the benchmark as a command-line flag, e.g.
+    /// `target-qps`. Dotted variable names, e.g. `@tables.users.rows`, set nested variables.
+    /// Several comma-separated flags and variables, e.g. `target-qps,connections`, can be set
+    /// together by giving each of `--x-values` as a `:`-separated tuple, e.g. `100:8`.
+    #[arg(long)]
+    pub x_axis: Option<String>,
+
+    /// Comma-separated values to run the benchmark with for `--x-axis`. A comma within a value
+    /// can be escaped as `\,`.
+    #[arg(long, conflicts_with = "x_axis_log_scale")]
+    pub x_values: Option<CommaSeparatedString>,
+
+    /// A file to read the values for `--x-axis` from instead of `--x-values`, with one value per
+    /// line. Blank lines and anything after a `#` are ignored. Also accepted as
+    /// `--x-values-from-file`.
+    #[arg(
+        long,
+        visible_alias = "x-values-from-file",
+        conflicts_with_all = ["x_values", "x_axis_log_scale"],
+        value_hint = ValueHint::FilePath
+    )]
+    pub x_values_file: Option<PathBuf>,
+
+    /// How to interpret the x-values: as integers, floats, durations (e.g. `500ms` or `10s`) or
+    /// strings. If not set, the type is inferred from the values, all of which must then have the
+    /// same type, except that integers and floats can be mixed. Numeric and duration values are
+    /// written to JSON and Parquet results as numbers, with durations in seconds.
+    #[arg(long, value_enum)]
+    pub x_type: Option<XType>,
+
+    /// Instead of listing `--x-values`, run the benchmark with `--x-axis-steps` values spaced
+    /// logarithmically from `--x-axis-min` to `--x-axis-max`. If both bounds are whole numbers,
+    /// so are the generated values.
+    #[arg(long, requires_all = ["x_axis_min", "x_axis_max", "x_axis_steps"])]
+    pub x_axis_log_scale: bool,
+
+    /// The smallest x-value generated by `--x-axis-log-scale`.
+    #[arg(long)]
+    pub x_axis_min: Option<f64>,
+
+    /// The largest x-value generated by `--x-axis-log-scale`.
+    #[arg(long)]
+    pub x_axis_max: Option<f64>,
+
+    /// The number of x-values generated by `--x-axis-log-scale`.
+    #[arg(long)]
+    pub x_axis_steps: Option<usize>,
+
+    /// An optional second parameter to vary, in the same form as `--x-axis`. The benchmark is
+    /// run for every combination of x- and z-values, and each z-value is graphed as a separate
+    /// series.
+    #[arg(long, requires = "z_values")]
+    pub z_axis: Option<String>,
+
+    /// Comma-separated values to run the benchmark with for `--z-axis`.
+    #[arg(long, requires = "z_axis")]
+    pub z_values: Option<CommaSeparatedString>,
+
+    /// Where to write the results of each run. The output format is chosen based on the file
+    /// extension. May be passed more than once to write the results to several files, for
+    /// example a CSV file for archival and a JSON file for dashboards; the sweep's checkpoint is
+    /// kept next to the first. Paths may contain the placeholders {benchmark}, {x_axis}, {date},
+    /// {time}, {git_sha} and {tag:<key>}, with {{ and }} for literal braces, e.g.
+    /// `results/{benchmark}/{x_axis}-{date}.csv`. Missing directories are created.
+    #[arg(
+        long = "graph-results-path",
+        default_value = "graph.csv",
+        value_hint = ValueHint::FilePath
+    )]
+    pub graph_results_paths: Vec<PathBuf>,
+
+    /// Another file to write the results of each run to, in addition to each
+    /// `--graph-results-path`. The output format is also chosen based on the file extension.
+    #[arg(
+        long,
+        requires = "graph",
+        conflicts_with = "push_gateway",
+        value_hint = ValueHint::FilePath
+    )]
+    pub graph_results_path2: Option<PathBuf>,
+
+    /// Plot each run at the throughput it achieved, in operations per second, instead of at its
+    /// x-value in `.png`, `.svg` and `.html` results. This shows the real relationship between
+    /// latency and throughput when a benchmark can't keep up with its `--target-qps`.
+    #[arg(long, requires = "graph")]
+    pub x_axis_achieved_qps: bool,
+
+    /// Push the results of each run to the Prometheus Pushgateway at this address, e.g.
+    /// `http://localhost:9091`, instead of writing them to `--graph-results-path`. Results are
+    /// grouped by the benchmark name, the `--instance-label` and the run's axis values.
+    #[arg(long, requires = "graph", conflicts_with_all = ["graph_results_paths", "append"])]
+    pub push_gateway: Option<String>,
+
+    /// How many times to retry pushing the results of a run to `--push-gateway` if it fails.
+    /// Results that still can't be pushed are logged and skipped without stopping the sweep.
+    #[arg(long, default_value = "3")]
+    pub push_gateway_retries: u32,
+
+    /// How many times to run the benchmark for each value. When greater than one, each statistic
+    /// is reported as the mean across runs along with its standard deviation.
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    pub runs_per_value: u32,
+
+    /// How many seconds of each run to discard before recording results, so that connection
+    /// setup and cold caches don't skew them. The benchmark still runs for its full duration
+    /// after the warmup.
+    #[arg(long = "warmup-duration", requires = "graph")]
+    pub warmup_secs: Option<u64>,
+
+    /// How many of the first values of each metric to discard from each iteration of a run,
+    /// after any `--warmup-duration`, as they can still reflect cold caches. Only the benchmark's
+    /// first values are kept in order to tell which they are, so this costs no memory beyond them.
+    #[arg(long, requires = "graph")]
+    pub warmup_samples: Option<usize>,
+
+    /// Exit with an error once every run has finished if this condition holds for any run, e.g.
+    /// `p99>10%` or `latency mean<5000`. Percentage thresholds are relative to the matching row
+    /// of `--baseline`. May be passed more than once.
+    #[arg(long = "fail-if")]
+    pub fail_if: Vec<FailCondition>,
+
+    /// Comma-separated names of the metrics to write and plot. If not set, every metric in the
+    /// results is written. A `*` matches any part of one level of a metric's name, so
+    /// `query/*/latency` selects the latency of every query with `--breakdown-by-query`.
+    #[arg(long)]
+    pub metrics: Option<CommaSeparatedString>,
+
+    /// Write the metrics that the benchmark recorded for each query separately, named
+    /// `query/<query>/<metric>`, in a group of columns per query, along with a `query/all/<metric>`
+    /// rollup of every query. Otherwise, the values of each per-query metric are merged into a
+    /// single `<metric>` for all of the queries.
+    #[arg(long)]
+    pub breakdown_by_query: bool,
+
+    /// Convert the values of metrics recorded in a unit of time to this unit when writing them,
+    /// e.g. `ms`. Metrics recorded in other units are written unchanged.
+    #[arg(long, value_enum, conflicts_with = "push_gateway")]
+    pub convert_units: Option<TimeUnit>,
+
+    /// Run each value of the sweep twice: once against ReadySet, and once against the upstream
+    /// database given by `--setup-conn-str`. Each metric is written once for each target,
+    /// prefixed with `readyset_` or `upstream_`, followed by the ratio of ReadySet's value of
+    /// each statistic to the upstream database's. If the run against one target fails, its
+    /// statistics are written with no samples and its ratios as NaN.
+    #[arg(long, requires = "graph", conflicts_with = "push_gateway")]
+    pub compare_upstream: bool,
+
+    /// Resume an interrupted sweep, skipping the values recorded as completed in its checkpoint
+    /// file and appending to the existing `--graph-results-path` instead of overwriting it.
+    #[arg(long, requires = "graph")]
+    pub resume: bool,
+
+    /// Add the results of this sweep to an existing `--graph-results-path` instead of
+    /// overwriting it, for example to accumulate sweeps run on different days in one file. The
+    /// header of an existing CSV file must match the columns being written.
+    #[arg(long)]
+    pub append: bool,
+
+    /// Don't flush each row of CSV and JSONL results to disk as soon as it's written, which is
+    /// faster for sweeps of many short runs. Rows that are still buffered when the process is
+    /// killed are lost, so runs aren't recorded in the checkpoint for `--resume`.
+    #[arg(long, conflicts_with = "resume")]
+    pub buffered: bool,
+
+    /// How many runs of the sweep to execute at once. Results are still written in the order of
+    /// the x-values. Only benchmarks that support concurrent runs can be run in parallel.
+    #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..))]
+    pub graph_parallelism: u32,
+
+    /// How many seconds to pause between consecutive runs of the sweep, for example to let
+    /// hardware that throttles under sustained load cool down. The pause isn't counted in the
+    /// elapsed time of either run.
+    #[arg(long, default_value = "0")]
+    pub cooldown_secs: f64,
+
+    /// The pause between consecutive runs of the sweep as a duration, e.g. `500ms` or `2m`. Takes
+    /// the place of `--cooldown-secs`.
+    #[arg(long, conflicts_with = "cooldown_secs", value_parser = parse_duration_arg)]
+    pub cooldown: Option<Duration>,
+
+    /// After each pause between runs, keep polling the benchmark until it reports that the
+    /// deployment is idle (e.g. that no compactions or replication are still in progress), for
+    /// at most this long, e.g. `30s`. The time spent cooling down before each run is logged and
+    /// written in a `cooldown_ms` column of its results.
+    #[arg(
+        long,
+        requires = "graph",
+        value_parser = parse_duration_arg,
+        value_name = "TIMEOUT"
+    )]
+    pub cooldown_until_idle: Option<Duration>,
+
+    /// The level to log each pause between runs at, or `off` to not log it.
+    #[arg(long, default_value = "info")]
+    pub cooldown_log_level: LevelFilter,
+
+    /// Keep going when a run of the sweep fails, instead of stopping the sweep. The error is
+    /// logged, and the run is written with NaN statistics and its error in an `error` column
+    /// (or an `"error"` field in JSON). The process still exits with an error once the sweep has
+    /// finished if any run failed.
+    #[arg(long, requires = "graph")]
+    pub keep_going: bool,
+
+    /// Give up on a run of the sweep that hasn't finished after this long, e.g. `90s` or `15m`,
+    /// cancelling the benchmark and tearing down anything it started. A run that times out stops
+    /// the sweep, unless `--keep-going` is passed, in which case the repetitions of the run that
+    /// had finished are written with a `timed_out` column set to true.
+    #[arg(long, requires = "graph", value_parser = parse_duration_arg)]
+    pub run_timeout: Option<Duration>,
+
+    /// Retry a run of the sweep that fails up to this many times before recording it as failed,
+    /// so that transient failures, such as a deployment that isn't accepting connections yet,
+    /// don't end the sweep. Each retry sets the run up from scratch, regenerating its data and
+    /// recreating its caches. Runs whose arguments are invalid are never retried. The number of
+    /// attempts each run took is written in an `attempts` column (or an `"attempts"` field in
+    /// JSON).
+    #[arg(long, requires = "graph", default_value = "0")]
+    pub retries: u32,
+
+    /// How long to wait before the first retry of a failed run, e.g. `500ms` or `10s`. The wait
+    /// doubles before each later retry of the same run.
+    #[arg(
+        long,
+        requires = "retries",
+        default_value = "1s",
+        value_parser = parse_duration_arg
+    )]
+    pub retry_backoff: Duration,
+
+    /// Record where and when each run happened alongside its results: a timestamp, the git
+    /// commit, the hostname, the benchmark's arguments and the `--warmup-duration`. These are
+    /// written as extra columns after the statistics in CSV files, and as a `"meta"` object in
+    /// JSON.
+    #[arg(long)]
+    pub with_metadata: bool,
+
+    /// A name for these results, such as the branch or configuration being benchmarked, made of
+    /// letters, digits and dashes. It's written as a `tag` column in CSV results and in the
+    /// `"meta"` object in JSON, and added to the file name of `--save-results` so that tagged
+    /// results can be shown side by side with `--compare-tags`.
+    #[arg(long)]
+    pub baseline_tag: Option<Tag>,
+
+    /// A constant column to add to every row of the results, given as `key=value`, e.g.
+    /// `--tag branch=main`, so that the rows of sweeps whose results are combined later can be
+    /// told apart. May be passed more than once. Tags are written after the `tag` column in CSV
+    /// and Parquet results, and as a `"tags"` object in JSON; `--compare` joins rows on them
+    /// along with the axis values.
+    #[arg(long = "tag", requires = "graph")]
+    pub tags: Vec<ColumnTag>,
+
+    /// A directory to save the full HDR histogram of every metric to for each value of the
+    /// sweep, in addition to the graph results, so that other statistics can be computed from
+    /// them later.
+    #[arg(long, requires = "graph", value_hint = ValueHint::DirPath)]
+    pub histogram_dir: Option<PathBuf>,
+
+    /// Comma-separated formats to save `--histogram-dir` histograms in: `hist`, the hdrhistogram
+    /// V2 serialization format, and `hgrm`, the percentile distribution text format read by
+    /// HdrHistogram plotters.
+    #[arg(
+        long,
+        requires = "histogram_dir",
+        value_enum,
+        value_delimiter = ',',
+        default_value = "hist"
+    )]
+    pub histogram_format: Vec<HistogramFormat>,
+
+    /// Sample the CPU utilization and resident memory of the benchmark process while each run of
+    /// the sweep executes, and write the mean and max CPU utilization, in percent of a core, and
+    /// the max resident memory of each run in `cpu_mean_pct`, `cpu_max_pct` and `rss_max_bytes`
+    /// columns (or a `"resource_usage"` object in JSON). Only supported on Linux.
+    #[arg(long, requires = "graph")]
+    pub resource_usage: bool,
+
+    /// How often to sample resource usage with `--resource-usage`, e.g. `250ms`.
+    #[arg(
+        long,
+        requires = "resource_usage",
+        default_value = "1s",
+        value_parser = parse_duration_arg
+    )]
+    pub resource_usage_interval: Duration,
+
+    /// The ID of another process to sample along with the benchmark with `--resource-usage`, such
+    /// as the ReadySet server being benchmarked. Its usage is written in columns prefixed with
+    /// `target_` (or a `"target"` object in JSON).
+    #[arg(long, requires = "resource_usage", value_name = "PID")]
+    pub resource_usage_pid: Option<u32>,
+
+    /// Also write every resource usage sample of each run: as a `"samples"` array in JSON, and
+    /// as a `{x_value}_resource_usage.csv` file in the `--histogram-dir`.
+    #[arg(long, requires = "resource_usage")]
+    pub resource_usage_samples: bool,
+
+    /// Comma-separated quantiles, each greater than 0 and at most 1 and in ascending order, to
+    /// report for every metric in the graph results and the printed results, e.g.
+    /// `0.5,0.9,0.99,0.9999`. Each quantile is labelled with its percentile, such as `p99.99`.
+    #[arg(long, default_value_t)]
+    pub quantiles: Quantiles,
+
+    /// The metric whose mean, p50 and p99 are printed to stderr as each run of the sweep
+    /// finishes. Defaults to the first metric in the results, by name.
+    #[arg(long, requires = "graph")]
+    pub progress_metric: Option<String>,
+
+    /// Don't print a summary of each run of the sweep to stderr as it finishes.
+    #[arg(long, requires = "graph")]
+    pub quiet: bool,
+
+    /// Don't print a table of the results of every run to stdout once the sweep has finished.
+    /// The table is colored unless stdout isn't a terminal or `NO_COLOR` is set.
+    #[arg(long, requires = "graph")]
+    pub no_summary: bool,
+
+    /// Print the runs the sweep would execute, with the arguments each one passes to the
+    /// benchmark, and where their results would be written, then exit without running anything.
+    #[arg(long, requires = "graph")]
+    pub dry_run: bool,
+
+    /// Execute the runs of the sweep in a random order, so that effects that build up over a long
+    /// sweep, such as a growing buffer pool or drifting neighbors, aren't confounded with the
+    /// x-values. Pass a seed to repeat an order; without one, a random seed is chosen and logged.
+    /// Results are still written in the planned order once the sweep finishes.
+    #[arg(long, requires = "graph", num_args = 0..=1, value_name = "SEED")]
+    pub shuffle_runs: Option<Option<u64>>,
+
+    /// The format to print the `--dry-run` plan in: `text`, a table of the runs followed by their
+    /// benchmark arguments and the results paths, or `json`, an array with a summary of each run.
+    #[arg(long, requires = "dry_run", value_enum, default_value = "text")]
+    pub dry_run_format: DryRunFormat,
+}
+
+/// The format of the plan printed by `--dry-run`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum DryRunFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// A description of a single [`GraphRun`] of a sweep, as printed by `--dry-run`.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct RunSummary {
+    pub x_axis: String,
+    pub x_value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub z_axis: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub z_value: Option<String>,
+    /// Whether the run varies a data generator variable, and so regenerates the data.
+    pub is_datagen_var: bool,
+    /// The flags the run passes to the benchmark.
+    pub flags: Vec<String>,
+    /// The data generator variables the run sets, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data_generator_variables: Option<serde_json::Value>,
+}
+
+impl From<&GraphRun> for RunSummary {
+    fn from(run: &GraphRun) -> Self {
+        let args = run.as_args();
+        Self {
+            x_axis: run.x_axis.clone(),
+            x_value: run.x_value.clone(),
+            z_axis: run.z.as_ref().map(|(z_axis, _)| z_axis.clone()),
+            z_value: run.z_value().map(str::to_owned),
+            is_datagen_var: run.is_datagen_var(),
+            flags: args.cli_args().to_vec(),
+            data_generator_variables: args.json().cloned(),
+        }
+    }
+}
+
+/// Shuffles `runs` into an order determined by `seed`.
+pub fn shuffle_runs(runs: &mut [GraphRun], seed: u64) {
+    runs.shuffle(&mut StdRng::seed_from_u64(seed));
+}
+
+/// Writes `summaries` as a table with a column for each axis and whether the run varies a data
+/// generator variable, with each run numbered out of the total.
+fn write_run_table(out: &mut impl Write, summaries: &[RunSummary]) -> anyhow::Result<()> {
+    let with_z = summaries.iter().any(|summary| summary.z_axis.is_some());
+    let header = ["run", "x-axis", "x-value"]
+        .into_iter()
+        .chain(
+            with_z
+                .then_some(["z-axis", "z-value"])
+                .into_iter()
+                .flatten(),
+        )
+        .chain(["datagen"])
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    let rows = summaries
+        .iter()
+        .enumerate()
+        .map(|(i, summary)| {
+            let z = [&summary.z_axis, &summary.z_value]
+                .map(|value| value.clone().unwrap_or_else(|| "-".to_owned()));
+            [
+                format!("{}/{}", i + 1, summaries.len()),
+                summary.x_axis.clone(),
+                summary.x_value.clone(),
+            ]
+            .into_iter()
+            .chain(with_z.then_some(z).into_iter().flatten())
+            .chain([if summary.is_datagen_var { "yes" } else { "no" }.to_owned()])
+            .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let widths = (0..header.len())
+        .map(|i| {
+            std::iter::once(&header)
+                .chain(&rows)
+                .map(|row| row[i].len())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{value:width$}"))
+            .join("  ");
+        writeln!(out, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+impl GraphParams {
+    /// Checks that the parameters describe a runnable sweep, so that bad values are reported
+    /// before any benchmark is run rather than part way through.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.graph && !self.time_series {
+            return Ok(());
+        }
+
+        let x_axis = self.x_axis_name();
+        let x_values = if self.time_series {
+            if self.time_series_interval.is_zero() {
+                bail!("--time-series-interval must be greater than zero");
+            }
+            // The x-values of a time series are only known once each interval has passed.
+            CommaSeparatedString::default()
+        } else {
+            validate_axis("--x-axis", x_axis)?;
+
+            if self.x_axis_log_scale {
+                let (min, max) = (
+                    self.x_axis_min.unwrap_or_default(),
+                    self.x_axis_max.unwrap_or_default(),
+                );
+                if !(min > 0.0 && min.is_finite() && max.is_finite()) {
+                    bail!("--x-axis-min and --x-axis-max must be positive for --x-axis-log-scale");
+                }
+                if min > max {
+                    bail!("--x-axis-min ({min}) must not be greater than --x-axis-max ({max})");
+                }
+                if self.x_axis_steps.unwrap_or_default() == 0 {
+                    bail!("--x-axis-steps must be at least 1");
+                }
+            }
+
+            let x_values = self.x_values()?;
+            if x_values.is_empty() {
+                bail!("--x-values must contain at least one value");
+            }
+            x_values
+        };
+
+        if self.push_gateway.is_none() {
+            for (flag, path) in self.results_paths() {
+                let extension = path.extension().and_then(OsStr::to_str);
+                if !extension.is_some_and(|extension| RESULTS_EXTENSIONS.contains(&extension)) {
+                    bail!(
+                        "Unsupported {flag} {}: expected one of [{}]",
+                        path.display(),
+                        RESULTS_EXTENSIONS
+                            .iter()
+                            .map(|ext| format!(".{ext}"))
+                            .join(", ")
+                    );
+                }
+            }
+        }
+        if let Some(path) = self
+            .results_paths()
+            .map(|(_, path)| path)
+            .duplicates()
+            .next()
+        {
+            bail!(
+                "Graph results can only be written to {} once",
+                path.display()
+            );
+        }
+
+        if self.runs_per_value == 0 {
+            bail!("--runs-per-value must be at least 1");
+        }
+        if self.graph_parallelism == 0 {
+            bail!("--graph-parallelism must be at least 1");
+        }
+        if let Err(e) = Duration::try_from_secs_f64(self.cooldown_secs) {
+            bail!("Invalid --cooldown-secs {}: {e}", self.cooldown_secs);
+        }
+
+        validate_axis_values("--x-values", x_axis, &x_values)?;
+        let typed_x_values = XValue::parse_all(&x_values, self.x_type)
+            .map_err(|e| anyhow!("Invalid --x-values: {e}"))?;
+
+        if let Some(z_axis) = &self.z_axis {
+            validate_axis("--z-axis", z_axis)?;
+            if z_axis == x_axis {
+                return Err(clap::Error::raw(
+                    ErrorKind::ArgumentConflict,
+                    format!("--z-axis must be different from --x-axis, but both are '{x_axis}'\n"),
+                )
+                .into());
+            }
+
+            let z_values = self.z_values.clone().unwrap_or_default();
+            if z_values.is_empty() {
+                bail!("--z-values must contain at least one value");
+            }
+            validate_axis_values("--z-values", z_axis, &z_values)?;
+        }
+
+        if self
+            .metrics
+            .as_ref()
+            .is_some_and(|metrics| metrics.is_empty())
+        {
+            bail!("--metrics must contain at least one metric name");
+        }
+
+        for condition in &self.fail_if {
+            condition.validate_stat(&self.quantiles)?;
+        }
+
+        if self.resource_usage && !resource_usage::SUPPORTED {
+            bail!("--resource-usage is only supported on Linux");
+        }
+        let resource_usage_columns = ResourceUsageOutput::from_params(self)
+            .map(|output| output.columns())
+            .unwrap_or_default();
+        let generated_columns = [
+            x_axis,
+            ACHIEVED_QPS_COLUMN,
+            TIMED_OUT_COLUMN,
+            ATTEMPTS_COLUMN,
+            TAG_COLUMN,
+        ]
+        .into_iter()
+        .chain(self.z_axis.as_deref())
+        .chain(resource_usage_columns.iter().map(String::as_str))
+        .chain(METADATA_COLUMNS.iter().copied())
+        .chain([ERROR_COLUMN]);
+        for column in generated_columns {
+            if self.tags.iter().any(|tag| tag.key == column) {
+                bail!("--tag {column} can't be used: it's the name of a column of the results");
+            }
+        }
+        if let Some(key) = self.tags.iter().map(|tag| &tag.key).duplicates().next() {
+            bail!("--tag {key} can only be given once");
+        }
+
+        for (_, path) in self.results_paths() {
+            let rewritten = match path.extension().and_then(OsStr::to_str) {
+                Some("parquet") => Some("Parquet results"),
+                Some("html") => Some("HTML reports"),
+                _ => None,
+            };
+            if let Some(rewritten) = rewritten {
+                if self.resume {
+                    bail!("--resume is not supported for {rewritten}");
+                }
+                if self.append {
+                    bail!("--append is not supported for {rewritten}");
+                }
+            }
+
+            // Plotted outputs place runs along a numeric x-axis, unless they're placed at the
+            // throughput they achieved.
+            if let Some(format) = PlotFormat::from_path(path) {
+                if let Some((value, _)) = x_values
+                    .iter()
+                    .zip(&typed_x_values)
+                    .find(|(_, typed)| typed.as_f64().is_none() && !self.x_axis_achieved_qps)
+                {
+                    bail!("Invalid --x-values for a {format} graph: '{value}' is not a number");
+                }
+                if self.resume {
+                    bail!("--resume is not supported for {format} graphs");
+                }
+                if self.append {
+                    bail!("--append is not supported for {format} graphs");
+                }
+            }
+        }
+
+        if self.x_axis_achieved_qps
+            && !self.results_paths().any(|(_, path)| {
+                PlotFormat::from_path(path).is_some()
+                    || path
+                        .extension()
+                        .is_some_and(|extension| extension == "html")
+            })
+        {
+            bail!("--x-axis-achieved-qps requires a .png, .svg or .html --graph-results-path");
+        }
+
+        Ok(())
+    }
+
+    /// Returns each file that results are written to, along with the flag that names it.
+    pub(crate) fn results_paths(&self) -> impl Iterator<Item = (&'static str, &Path)> {
+        self.graph_results_paths
+            .iter()
+            .map(|path| ("--graph-results-path", path.as_path()))
+            .chain(
+                self.graph_results_path2
+                    .as_deref()
+                    .map(|path| ("--graph-results-path2", path)),
+            )
+    }
+
+    /// Returns the description of the `--x-axis`, if it's one of the known axes or has been
+    /// [registered](register_axis_metadata). An explicit `--x-type` overrides its value type.
+    pub fn x_axis_metadata(&self) -> Option<AxisMetadata> {
+        let x_axis = self.x_axis.as_deref()?;
+        let mut metadata = KNOWN_AXES
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(x_axis)?
+            .clone();
+        if let Some(x_type) = self.x_type {
+            metadata.value_type = x_type;
+        }
+        Some(metadata)
+    }
+
+    /// The name of the x-axis that results are written with: the `--x-axis`, or
+    /// [`TIME_SERIES_X_AXIS`] for `--time-series`.
+    pub fn x_axis_name(&self) -> &str {
+        if self.time_series {
+            TIME_SERIES_X_AXIS
+        } else {
+            self.x_axis.as_deref().unwrap_or_default()
+        }
+    }
+
+    /// The first `--graph-results-path`, next to which the sweep's checkpoint is kept.
+    pub fn graph_results_path(&self) -> &Path {
+        self.graph_results_paths
+            .first()
+            .map_or(Path::new("graph.csv"), PathBuf::as_path)
+    }
+
+    /// Expands the [placeholders](PATH_PLACEHOLDERS) in each `--graph-results-path` and
+    /// `--graph-results-path2` for a sweep of `benchmark` starting now. Must be called before
+    /// anything is written to the paths.
+    pub fn expand_results_paths(&mut self, benchmark: &str) -> anyhow::Result<()> {
+        let vars = PathVars {
+            benchmark,
+            x_axis: self.x_axis_name(),
+            started_at: Utc::now(),
+            git_sha: current_commit(),
+            tags: &self.tags,
+        };
+        let expand = |path: &PathBuf| match path.to_str() {
+            Some(template) => expand_path_template(template, &vars).map(PathBuf::from),
+            None => Ok(path.clone()),
+        };
+        let paths = self
+            .graph_results_paths
+            .iter()
+            .map(expand)
+            .collect::<anyhow::Result<_>>()?;
+        let path2 = self.graph_results_path2.as_ref().map(expand).transpose()?;
+        self.graph_results_paths = paths;
+        self.graph_results_path2 = path2;
+        Ok(())
+    }
+
+    /// Creates the directories that the results files are written in, if they don't exist.
+    pub fn create_results_dirs(&self) -> anyhow::Result<()> {
+        for (_, path) in self.results_paths() {
+            if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+                std::fs::create_dir_all(dir)
+                    .map_err(|e| anyhow!("Failed to create {}: {e}", dir.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the sweep can be run with `--graph-parallelism`, given whether the benchmark
+    /// allows concurrent runs. Sweeps over a data generator variable regenerate the data for each
+    /// run, so they can never be run in parallel.
+    pub fn validate_parallelism(&self, allows_concurrent_runs: bool) -> anyhow::Result<()> {
+        if self.graph_parallelism <= 1 {
+            return Ok(());
+        }
+        if !allows_concurrent_runs {
+            bail!(
+                "--graph-parallelism is not supported by this benchmark, whose runs can't safely \
+                 run concurrently"
+            );
+        }
+        if let Some(run) = self.runs().iter().find(|run| run.is_datagen_var()) {
+            bail!(
+                "--graph-parallelism can't be used to vary a data generator variable, as in {run}"
+            );
+        }
+        Ok(())
+    }
+
+    /// The pause between consecutive runs of the sweep. Invalid values, which are rejected by
+    /// [`Self::validate`], are treated as no pause. Waiting until the deployment is idle requires
+    /// an idle check to be added with [`Cooldown::with_idle_check`].
+    pub fn cooldown(&self) -> Cooldown {
+        Cooldown {
+            duration: self.cooldown.unwrap_or_else(|| {
+                Duration::try_from_secs_f64(self.cooldown_secs).unwrap_or_default()
+            }),
+            log_level: self.cooldown_log_level.into_level(),
+            idle_timeout: self.cooldown_until_idle,
+            ..Cooldown::default()
+        }
+    }
+
+    /// How failed runs of the sweep are retried.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            retries: self.retries,
+            backoff: self.retry_backoff,
+        }
+    }
+
+    /// Returns a rough estimate of how long the rest of the sweep will take, given the results of
+    /// every run of the benchmark so far in the order of [`Self::runs`], with `--runs-per-value`
+    /// results for each value. Each remaining run is estimated from the most recent results (see
+    /// [`GraphRun::estimated_duration`]), so this is `None` before the first run has finished.
+    pub fn total_estimated_duration(
+        &self,
+        results_so_far: &[BenchmarkResults],
+    ) -> Option<Duration> {
+        let runs = self.runs();
+        let per_value = self.runs_per_value.max(1) as usize;
+        let next = runs.get(results_so_far.len() / per_value).or(runs.last())?;
+        let per_run = next.estimated_duration(results_so_far.last())?;
+        let remaining = (runs.len() * per_value).saturating_sub(results_so_far.len());
+        Some(self.remaining_duration(remaining, per_run))
+    }
+
+    /// Returns how long `remaining_runs` runs of the benchmark should take if each takes
+    /// `per_run`, spread across `--graph-parallelism` and with a `--cooldown-secs` pause before
+    /// each remaining value.
+    pub(crate) fn remaining_duration(&self, remaining_runs: usize, per_run: Duration) -> Duration {
+        let per_value = self.runs_per_value.max(1) as usize;
+        let pauses = self.cooldown().duration * remaining_runs.div_ceil(per_value) as u32;
+        (per_run * remaining_runs as u32 + pauses) / self.graph_parallelism.max(1)
+    }
+
+    /// Parses every x-value as an `f64`, for x-axes that are numeric.
+    pub fn x_values_as_f64(&self) -> anyhow::Result<Vec<f64>> {
+        self.x_values()?.validate_as_f64()
+    }
+
+    /// Returns the x-values parsed as `--x-type`, or as their inferred type if it isn't set.
+    pub fn typed_x_values(&self) -> anyhow::Result<Vec<XValue>> {
+        XValue::parse_all(&self.x_values()?, self.x_type)
+    }
+
+    /// Parses every x-value as a `usize`, for x-axes that are counts or sizes.
+    pub fn x_values_as_usize(&self) -> anyhow::Result<Vec<usize>> {
+        self.x_values()?.parse_all()
+    }
+
+    /// Returns `--x-axis-steps` values spaced logarithmically from `--x-axis-min` to
+    /// `--x-axis-max`, so that the ratio between consecutive values is constant. Returns an empty
+    /// list if any of them aren't set.
+    pub fn auto_x_values_log(&self) -> Vec<f64> {
+        let (Some(min), Some(max), Some(steps)) =
+            (self.x_axis_min, self.x_axis_max, self.x_axis_steps)
+        else {
+            return vec![];
+        };
+        if steps == 1 {
+            return vec![min];
+        }
+        let (log_min, log_max) = (min.ln(), max.ln());
+        (0..steps)
+            .map(|i| match i {
+                0 => min,
+                _ if i == steps - 1 => max,
+                _ => (log_min + (log_max - log_min) * i as f64 / (steps - 1) as f64).exp(),
+            })
+            .collect()
+    }
+
+    /// Returns the x-values to run with: either `--x-values`, those read from `--x-values-file`,
+    /// or the values generated by `--x-axis-log-scale`. Generated values are rounded to whole
+    /// numbers if both bounds are whole, skipping any that round to the same value.
+    fn x_values(&self) -> anyhow::Result<CommaSeparatedString> {
+        if self.x_axis_log_scale {
+            let whole = [self.x_axis_min, self.x_axis_max]
+                .iter()
+                .all(|bound| bound.is_some_and(|v| v.fract() == 0.0));
+            let values = self
+                .auto_x_values_log()
+                .into_iter()
+                .map(|v| if whole { v.round() } else { v })
+                .dedup()
+                .map(|v| v.to_string())
+                .collect();
+            return Ok(CommaSeparatedString(values));
+        }
+        if let Some(path) = &self.x_values_file {
+            return CommaSeparatedString::from_file(path);
+        }
+        self.x_values.clone().ok_or_else(|| {
+            anyhow!(
+                "One of --x-values, --x-values-file or --x-axis-log-scale must be set to run a \
+                 graph"
+            )
+        })
+    }
+
+    /// Returns a [`GraphRun`] for each of the x-values, in the order they were given (or in
+    /// increasing order, if generated by `--x-axis-log-scale`). If there is a z-axis, returns a
+    /// run for every combination of z- and x-values, running all of the x-values for each
+    /// z-value in turn.
+    pub fn runs(&self) -> Vec<GraphRun> {
+        let (Some(x_axis), Ok(x_values)) = (&self.x_axis, self.x_values()) else {
+            return vec![];
+        };
+        let Ok(typed_x_values) = XValue::parse_all(&x_values, self.x_type) else {
+            return vec![];
+        };
+        let z_values = match (&self.z_axis, &self.z_values) {
+            (Some(z_axis), Some(z_values)) => z_values
+                .iter()
+                .map(|z_value| Some((z_axis.clone(), z_value.clone())))
+                .collect(),
+            _ => vec![None],
+        };
+
+        z_values
+            .into_iter()
+            .cartesian_product(x_values.iter().zip(typed_x_values))
+            .map(|(z, (x_value, x))| GraphRun {
+                x_axis: x_axis.clone(),
+                x_value: x_value.clone(),
+                x,
+                z,
+            })
+            .collect()
+    }
+
+    /// Returns `runs` in the order to execute them: shuffled with `--shuffle-runs`, or as they
+    /// are otherwise. A random seed is chosen and logged if `--shuffle-runs` wasn't given one.
+    pub fn execution_order(&self, mut runs: Vec<GraphRun>) -> Vec<GraphRun> {
+        let Some(seed) = self.shuffle_runs else {
+            return runs;
+        };
+        let seed = seed.unwrap_or_else(|| {
+            let seed = rand::random();
+            tracing::info!("Shuffling the runs of the sweep with --shuffle-runs {seed}");
+            seed
+        });
+        shuffle_runs(&mut runs, seed);
+        runs
+    }
+
+    /// Returns a summary of each run of the sweep, in the order of [`Self::runs`].
+    pub fn summarize_runs(&self) -> Vec<RunSummary> {
+        self.runs().iter().map(RunSummary::from).collect()
+    }
+
+    /// Writes the plan of the sweep to `out` for `--dry-run`: how many runs there are, then a
+    /// table of the runs (see [`Self::summarize_runs`]), then the benchmark arguments of each
+    /// run, given by `benchmark_args`, and where the results will be written. With
+    /// `--dry-run-format json`, only the summaries of the runs are written, as a JSON array.
+    ///
+    /// Fails if `benchmark_args` fails for any run or any of the results paths couldn't be
+    /// written to, as the sweep itself would, in either format.
+    pub fn write_plan(
+        &self,
+        out: &mut impl Write,
+        benchmark_args: impl FnMut(&GraphRun) -> anyhow::Result<String>,
+    ) -> anyhow::Result<()> {
+        let runs = self.runs();
+        let benchmark_args = runs
+            .iter()
+            .map(benchmark_args)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let mut results = Vec::new();
+        if self.push_gateway.is_none() {
+            for (flag, path) in self.results_paths() {
+                let format = ResultsFormat::for_path(path, self.resume || self.append)?;
+                check_results_dir(flag, path)?;
+                results.push((path, format));
+            }
+        }
+        let summaries = runs.iter().map(RunSummary::from).collect::<Vec<_>>();
+        if self.dry_run_format == DryRunFormat::Json {
+            serde_json::to_writer_pretty(&mut *out, &summaries)?;
+            writeln!(out)?;
+            return Ok(());
+        }
+
+        let repetitions = self.runs_per_value * if self.compare_upstream { 2 } else { 1 };
+        write!(out, "{} runs planned", runs.len())?;
+        if self.runs_per_value > 1 {
+            write!(out, ", each repeated {} times", self.runs_per_value)?;
+        }
+        if self.compare_upstream {
+            write!(out, " against both ReadySet and the upstream database")?;
+        }
+        if repetitions > 1 {
+            write!(
+                out,
+                " ({} benchmark runs)",
+                runs.len() * repetitions as usize
+            )?;
+        }
+        writeln!(out)?;
+        write_run_table(out, &summaries)?;
+
+        writeln!(out, "Benchmark arguments:")?;
+        for (i, args) in benchmark_args.iter().enumerate() {
+            writeln!(out, "    {}/{}: {args}", i + 1, runs.len())?;
+        }
+
+        if let Some(address) = &self.push_gateway {
+            writeln!(
+                out,
+                "Results will be pushed to the Pushgateway at {address}"
+            )?;
+            return Ok(());
+        }
+        writeln!(out, "Results will be written to:")?;
+        for (path, format) in results {
+            writeln!(out, "    {} ({format})", path.display())?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks that the directory of the results file at `path` could be created by
+/// [`GraphParams::create_results_dirs`] and written to: the nearest of its ancestors that exists
+/// must be a writable directory.
+fn check_results_dir(flag: &str, path: &Path) -> anyhow::Result<()> {
+    let Some(dir) = path
+        .ancestors()
+        .skip(1)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .find(|dir| dir.exists())
+    else {
+        return Ok(());
+    };
+    let metadata = dir
+        .metadata()
+        .map_err(|e| anyhow!("Can't write {flag} {}: {e}", path.display()))?;
+    if !metadata.is_dir() {
+        bail!(
+            "Can't write {flag} {}: {} is not a directory",
+            path.display(),
+            dir.display()
+        );
+    }
+    if metadata.permissions().readonly() {
+        bail!(
+            "Can't write {flag} {}: {} is not writable",
+            path.display(),
+            dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// The arguments to apply to a benchmark for a single [`GraphRun`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ArgOverride {
+    /// Command-line flags, parsed as if they had been passed after the benchmark's subcommand.
+    CliArgs(Vec<String>),
+    /// Variable overrides for the benchmark's data generator.
+    Json(serde_json::Value),
+    /// Both command-line flags and data generator variable overrides.
+    Combined {
+        cli: Vec<String>,
+        json: serde_json::Value,
+    },
+}
+
+impl ArgOverride {
+    /// The command-line flags to apply, if any.
+    pub fn cli_args(&self) -> &[String] {
+        match self {
+            ArgOverride::CliArgs(cli) | ArgOverride::Combined { cli, .. } => cli,
+            ArgOverride::Json(_) => &[],
+        }
+    }
+
+    /// The data generator variable overrides to apply, if any.
+    pub fn json(&self) -> Option<&serde_json::Value> {
+        match self {
+            ArgOverride::Json(json) | ArgOverride::Combined { json, .. } => Some(json),
+            ArgOverride::CliArgs(_) => None,
+        }
+    }
+
+    /// Combines two overrides. If both set the same command-line flag or data generator
+    /// variable, the value from `other` takes precedence.
+    pub fn merge(self, other: ArgOverride) -> ArgOverride {
+        let (cli, json) = self.into_parts();
+        let (other_cli, other_json) = other.into_parts();
+
+        let overridden = other_cli
+            .iter()
+            .filter(|arg| arg.starts_with("--"))
+            .map(|arg| flag_name(arg))
+            .collect::<Vec<_>>();
+        let mut merged_cli = vec![];
+        let mut skipping = false;
+        for arg in cli {
+            if arg.starts_with("--") {
+                skipping = overridden.contains(&flag_name(&arg));
+            }
+            if !skipping {
+                merged_cli.push(arg);
+            }
+        }
+        merged_cli.extend(other_cli);
+
+        let merged_json = match (json, other_json) {
+            (Some(mut json), Some(other)) => {
+                merge_json(&mut json, other);
+                Some(json)
+            }
+            (json, other_json) => other_json.or(json),
+        };
+
+        match merged_json {
+            None => ArgOverride::CliArgs(merged_cli),
+            Some(json) if merged_cli.is_empty() => ArgOverride::Json(json),
+            Some(json) => ArgOverride::Combined {
+                cli: merged_cli,
+                json,
+            },
+        }
+    }
+
+    fn into_parts(self) -> (Vec<String>, Option<serde_json::Value>) {
+        match self {
+            ArgOverride::CliArgs(cli) => (cli, None),
+            ArgOverride::Json(json) => (vec![], Some(json)),
+            ArgOverride::Combined { cli, json } => (cli, Some(json)),
+        }
+    }
+}
+
+/// Returns the name of a `--flag` or `--flag=value` argument.
+fn flag_name(arg: &str) -> &str {
+    arg.split_once('=').map_or(arg, |(name, _)| name)
+}
+
+/// A single run of the benchmark within a sweep.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GraphRun {
+    pub x_axis: String,
+    /// The x-value as it was given, which is passed to the benchmark.
+    pub x_value: String,
+    /// The x-value parsed as the type of the sweep's x-values.
+    pub x: XValue,
+    /// The z-axis and its value for this run, if the sweep has a z-axis.
+    pub z: Option<(String, String)>,
+}
+
+impl GraphRun {
+    /// Returns a run without a z-axis, inferring the type of `x_value`.
+    pub fn new(x_axis: impl Into<String>, x_value: impl Into<String>) -> Self {
+        let x_value = x_value.into();
+        GraphRun {
+            x_axis: x_axis.into(),
+            x: XValue::infer(&x_value),
+            x_value,
+            z: None,
+        }
+    }
+
+    /// Returns the run that the results of a `--time-series` interval ending `elapsed` into the
+    /// benchmark are written as, with the elapsed time in seconds, to the millisecond, as its
+    /// x-value.
+    pub fn time_series(elapsed: Duration) -> Self {
+        let secs = elapsed.as_millis() as f64 / 1000.0;
+        GraphRun {
+            x_axis: TIME_SERIES_X_AXIS.to_owned(),
+            x_value: secs.to_string(),
+            x: XValue::Float(secs),
+            z: None,
+        }
+    }
+
+    /// Whether this run varies a data generator variable rather than only benchmark flags.
+    pub fn is_datagen_var(&self) -> bool {
+        is_datagen_axis(&self.x_axis) || self.z.as_ref().is_some_and(|(z, _)| is_datagen_axis(z))
+    }
+
+    /// Returns the override to apply to the benchmark for this run.
+    pub fn as_args(&self) -> ArgOverride {
+        let x = axis_args(&self.x_axis, &self.x_value);
+        match &self.z {
+            Some((z_axis, z_value)) => x.merge(axis_args(z_axis, z_value)),
+            None => x,
+        }
+    }
+
+    /// The z-value of this run, if there is a z-axis.
+    pub fn z_value(&self) -> Option<&str> {
+        self.z.as_ref().map(|(_, z_value)| z_value.as_str())
+    }
+
+    /// Returns a rough estimate of how long this run will take, assuming it takes as long as the
+    /// run that produced `previous_results`: their elapsed time if it was recorded, or otherwise
+    /// the mean of each of their time-valued metrics multiplied by its sample count. Returns
+    /// `None` without previous results, or if they have neither.
+    pub fn estimated_duration(
+        &self,
+        previous_results: Option<&BenchmarkResults>,
+    ) -> Option<Duration> {
+        let previous = previous_results?;
+        if let Some(elapsed) = previous.elapsed {
+            return Some(elapsed);
+        }
+        let secs = previous
+            .results
+            .values()
+            .filter_map(|data| {
+                let unit = TimeUnit::from_metric_unit(data.metric_unit()?)?;
+                let hist = &data.values;
+                Some(hist.mean() * hist.len() as f64 * unit.scale_to(TimeUnit::S))
+            })
+            .reduce(|a, b| a + b)?;
+        Duration::try_from_secs_f64(secs).ok()
+    }
+}
+
+impl Display for GraphRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} = {}", self.x_axis, self.x_value)?;
+        if let Some((z_axis, z_value)) = &self.z {
+            write!(f, ", {z_axis} = {z_value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::axis::register_axis_metadata;
+    use crate::axis::AxisValueType;
+    use crate::benchmark::MetricGoal;
+    use crate::sweep::Checkpoint;
+    use std::fs::File;
+
+    use hdrhistogram::Histogram;
+    use metrics::Unit;
+    use serde_json::json;
+
+    use super::*;
+    use crate::results_sink;
+    use crate::test_utils::{params, results, run, z_params};
+
+    #[test]
+    fn x_values_as_f64_round_trip() {
+        let values = [0.1, 2.5, 1e-3, 12345.678, 1e20];
+        let params = params("target-qps", &values.iter().map(f64::to_string).join(","));
+        assert_eq!(params.x_values_as_f64().unwrap(), values);
+    }
+
+    #[test]
+    fn typed_x_values() {
+        let typed = |values: &str| params("target-qps", values).typed_x_values();
+        assert_eq!(
+            typed("8,16,32,128").unwrap(),
+            [8, 16, 32, 128].map(XValue::Integer)
+        );
+        assert_eq!(typed("1, 2.5").unwrap(), [1.0, 2.5].map(XValue::Float));
+        assert_eq!(
+            typed("500ms,10s,1.5m").unwrap(),
+            [500, 10_000, 90_000].map(|ms| XValue::Duration(Duration::from_millis(ms)))
+        );
+        assert_eq!(
+            typed("read,write").unwrap(),
+            ["read", "write"].map(|v| XValue::String(v.to_owned()))
+        );
+
+        for mixed in ["8,10s", "8,fast", "1.5,fast"] {
+            let err = typed(mixed).unwrap_err().to_string();
+            assert!(err.contains("--x-type string"), "{err}");
+        }
+        let err = typed("100,10s").unwrap_err().to_string();
+        assert!(err.contains("integer '100' and duration '10s'"), "{err}");
+
+        let mut params = params("target-qps", "8,fast");
+        params.x_type = Some(XType::String);
+        params.validate().unwrap();
+        assert_eq!(params.runs()[0].x, XValue::String("8".to_owned()));
+        params.x_type = Some(XType::Integer);
+        let err = params.validate().unwrap_err().to_string();
+        assert!(err.contains("'fast' is not an integer"), "{err}");
+
+        let mut params = params_with_type("1,2.5", XType::Duration);
+        assert_eq!(
+            params.typed_x_values().unwrap(),
+            [1000, 2500].map(|ms| XValue::Duration(Duration::from_millis(ms)))
+        );
+        params.x_type = Some(XType::Float);
+        assert_eq!(params.runs()[0].x, XValue::Float(1.0));
+    }
+
+    fn params_with_type(x_values: &str, x_type: XType) -> GraphParams {
+        GraphParams {
+            x_type: Some(x_type),
+            ..params("target-qps", x_values)
+        }
+    }
+
+    #[test]
+    fn typed_x_values_in_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("run-for", "500ms,2s");
+        params.graph_results_paths = vec![dir.path().join("graph.json")];
+        let mut writer = results_sink::from_params(&params).unwrap();
+        for run in params.runs() {
+            writer
+                .write_results(&run, &[results(&[("latency", &[1])])], None)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_reader(File::open(params.graph_results_path()).unwrap()).unwrap();
+        assert_eq!(json[0]["x_value"], json!(0.5));
+        assert_eq!(json[1]["x_value"], json!(2.0));
+    }
+
+    #[test]
+    fn x_values_as_usize() {
+        assert_eq!(
+            params("threads", "1, 2,16").x_values_as_usize().unwrap(),
+            vec![1, 2, 16]
+        );
+        let err = params("threads", "1,-2").x_values_as_usize().unwrap_err();
+        assert!(err.to_string().contains("'-2' at position 1"), "{err}");
+    }
+
+    #[test]
+    fn x_axis_metadata() {
+        assert_eq!(
+            params("target-qps", "100").x_axis_metadata(),
+            Some(AxisMetadata::new(
+                "target QPS",
+                Some("ops/s"),
+                AxisValueType::Integer
+            ))
+        );
+        let mut float_qps = params("target-qps", "100.5");
+        float_qps.x_type = Some(XType::Float);
+        assert_eq!(
+            float_qps.x_axis_metadata().unwrap().value_type,
+            AxisValueType::Float
+        );
+        assert_eq!(params("@unregistered_rows", "10").x_axis_metadata(), None);
+
+        let rows = AxisMetadata::new("user rows", Some("rows"), AxisValueType::Integer);
+        register_axis_metadata("@registered_rows", rows.clone());
+        assert_eq!(
+            params("@registered_rows", "10").x_axis_metadata(),
+            Some(rows)
+        );
+    }
+
+    #[test]
+    fn escaped_commas() {
+        let parse = |s: &str| s.parse::<CommaSeparatedString>().unwrap().to_vec();
+        assert_eq!(parse(r"a\,b,c"), ["a,b", "c"]);
+        assert_eq!(parse(r"a\\,b"), [r"a\", "b"]);
+        assert_eq!(parse(r"\d{1\,3}, x\\\,y"), [r"\d{1,3}", r"x\,y"]);
+        assert_eq!(parse(r"a\"), [r"a\"]);
+        assert_eq!(parse("a,,b"), ["a", "", "b"]);
+    }
+
+    #[test]
+    fn validate_as_f64_valid() {
+        let values: CommaSeparatedString = "1, 2.5,1e3".parse().unwrap();
+        assert_eq!(values.validate_as_f64().unwrap(), vec![1.0, 2.5, 1000.0]);
+    }
+
+    #[test]
+    fn validate_as_f64_invalid() {
+        let values: CommaSeparatedString = "1.5,foo,bar".parse().unwrap();
+        let err = values.validate_as_f64().unwrap_err().to_string();
+        assert!(err.contains("'foo'"), "{err}");
+        assert!(err.contains("position 1"), "{err}");
+    }
+
+    #[test]
+    fn validate_as_u64_valid() {
+        let values: CommaSeparatedString = "100,500,1000".parse().unwrap();
+        assert_eq!(values.validate_as_u64().unwrap(), vec![100, 500, 1000]);
+    }
+
+    #[test]
+    fn validate_as_u64_invalid() {
+        let values: CommaSeparatedString = "100,200,-3".parse().unwrap();
+        let err = values.validate_as_u64().unwrap_err().to_string();
+        assert!(err.contains("'-3'"), "{err}");
+        assert!(err.contains("position 2"), "{err}");
+    }
+
+    #[test]
+    fn validate_datagen_var_requires_numbers() {
+        assert!(params("@user_rows", "100,1000").validate().is_ok());
+        assert!(params("@skew", "1.1,1.5").validate().is_ok());
+
+        let err = params("@user_rows", "100,lots").validate().unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("@user_rows"), "{err}");
+        assert!(err.contains("'lots'"), "{err}");
+    }
+
+    #[test]
+    fn validate_fail_if_quantiles() {
+        let mut params = params("target-qps", "100");
+        params.fail_if = vec!["p99.99>10%".parse().unwrap()];
+        let err = params.validate().unwrap_err();
+        assert!(
+            err.to_string().contains("unknown statistic 'p99.99'"),
+            "{err}"
+        );
+
+        params.quantiles = "0.5,0.9999".parse().unwrap();
+        params.validate().unwrap();
+    }
+
+    #[test]
+    fn validate_empty_x_values() {
+        let err = params("target-qps", "").validate().unwrap_err();
+        assert!(err.to_string().contains("--x-values"));
+    }
+
+    #[test]
+    fn x_values_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("x-values.txt");
+        std::fs::write(
+            &path,
+            "# Generated by sweep.py\n100\n\n  200  \n300 # the largest\n\t\n",
+        )
+        .unwrap();
+        let params = GraphParams {
+            x_values: None,
+            x_values_file: Some(path),
+            ..params("target-qps", "")
+        };
+        params.validate().unwrap();
+        assert_eq!(
+            params
+                .runs()
+                .iter()
+                .map(|run| run.x_value.as_str())
+                .collect::<Vec<_>>(),
+            ["100", "200", "300"]
+        );
+    }
+
+    #[test]
+    fn x_values_from_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("concurrency.txt");
+        std::fs::write(
+            &path,
+            "# Concurrency levels
+1
+
+# Doubling
+2
+4
+   
+8
+",
+        )
+        .unwrap();
+        let params = GraphParams::try_parse_from([
+            "graph".as_ref(),
+            "--graph".as_ref(),
+            "--x-axis".as_ref(),
+            "threads".as_ref(),
+            "--x-values-from-file".as_ref(),
+            path.as_os_str(),
+        ])
+        .unwrap();
+        assert_eq!(params.x_values_file.as_deref(), Some(path.as_path()));
+        params.validate().unwrap();
+        assert_eq!(
+            params
+                .runs()
+                .iter()
+                .map(|run| run.x_value.as_str())
+                .collect::<Vec<_>>(),
+            ["1", "2", "4", "8"]
+        );
+
+        let err = GraphParams::try_parse_from([
+            "graph".as_ref(),
+            "--graph".as_ref(),
+            "--x-axis".as_ref(),
+            "threads".as_ref(),
+            "--x-values".as_ref(),
+            "1,2".as_ref(),
+            "--x-values-from-file".as_ref(),
+            path.as_os_str(),
+        ])
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+    }
+
+    #[test]
+    fn invalid_x_values_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing.txt");
+        let empty = dir.path().join("empty.txt");
+        std::fs::write(&empty, "# Nothing to see here\n\n").unwrap();
+        for path in [missing, empty] {
+            let params = GraphParams {
+                x_values: None,
+                x_values_file: Some(path.clone()),
+                ..params("target-qps", "")
+            };
+            let err = params.validate().unwrap_err().to_string();
+            assert!(err.contains(&path.display().to_string()), "{err}");
+            assert!(params.runs().is_empty());
+        }
+    }
+
+    #[test]
+    fn x_values_file_conflicts_with_x_values() {
+        let err = GraphParams::try_parse_from([
+            "graph",
+            "--graph",
+            "--x-axis",
+            "target-qps",
+            "--x-values",
+            "100",
+            "--x-values-file",
+            "x-values.txt",
+        ])
+        .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::ArgumentConflict);
+        assert!(err.to_string().contains("--x-values-file"), "{err}");
+    }
+
+    #[test]
+    fn validate_x_axis() {
+        for x_axis in ["", "@"] {
+            let err = params(x_axis, "100").validate().unwrap_err();
+            assert!(err.to_string().contains("--x-axis"), "{err}");
+        }
+    }
+
+    #[test]
+    fn validate_graph_results_path() {
+        for path in ["graph.txt", "graph"] {
+            let mut params = params("target-qps", "100");
+            params.graph_results_paths = vec![path.into()];
+            let err = params.validate().unwrap_err().to_string();
+            assert!(err.contains("--graph-results-path"), "{err}");
+            assert!(err.contains(".csv, .html, .json, .jsonl"), "{err}");
+        }
+
+        // Results pushed to a Pushgateway aren't written to the results path.
+        let mut params = params("target-qps", "100");
+        params.graph_results_paths = vec!["graph.txt".into()];
+        params.push_gateway = Some("http://localhost:9091".to_owned());
+        params.validate().unwrap();
+    }
+
+    #[test]
+    fn validate_repetitions() {
+        let mut params = params("target-qps", "100");
+        params.runs_per_value = 0;
+        let err = params.validate().unwrap_err();
+        assert!(err.to_string().contains("--runs-per-value"), "{err}");
+
+        params.runs_per_value = 1;
+        params.graph_parallelism = 0;
+        let err = params.validate().unwrap_err();
+        assert!(err.to_string().contains("--graph-parallelism"), "{err}");
+    }
+
+    #[test]
+    fn runs_as_args() {
+        let runs = params("target-qps", "100,200").runs();
+        assert_eq!(runs.len(), 2);
+        assert!(!runs[0].is_datagen_var());
+        assert_eq!(
+            runs[1].as_args(),
+            ArgOverride::CliArgs(vec!["--target-qps".into(), "200".into()])
+        );
+
+        let runs = params("@user_rows", "10").runs();
+        assert!(runs[0].is_datagen_var());
+        assert_eq!(
+            runs[0].as_args(),
+            ArgOverride::Json(json!({ "user_rows": "10" }))
+        );
+    }
+
+    #[test]
+    fn runs_with_z_axis() {
+        let runs = z_params("100,200", "@user_rows", "10,20,30").runs();
+        assert_eq!(runs.len(), 6);
+        assert_eq!(
+            runs.iter()
+                .map(|run| (run.x_value.as_str(), run.z_value().unwrap()))
+                .collect::<Vec<_>>(),
+            vec![
+                ("100", "10"),
+                ("200", "10"),
+                ("100", "20"),
+                ("200", "20"),
+                ("100", "30"),
+                ("200", "30"),
+            ]
+        );
+        assert!(runs[0].is_datagen_var());
+        assert_eq!(
+            runs[1].as_args(),
+            ArgOverride::Combined {
+                cli: vec!["--target-qps".into(), "200".into()],
+                json: json!({ "user_rows": "10" }),
+            }
+        );
+        assert_eq!(runs[1].to_string(), "target-qps = 200, @user_rows = 10");
+    }
+
+    #[test]
+    fn validate_z_axis() {
+        z_params("100", "workers", "1,2").validate().unwrap();
+        z_params("100", "@user_rows", "10").validate().unwrap();
+
+        let err = z_params("100", "target-qps", "1").validate().unwrap_err();
+        assert!(
+            err.to_string().contains("--z-axis must be different"),
+            "{err}"
+        );
+        z_params("100", "@user_rows", "ten").validate().unwrap_err();
+        z_params("100", "workers", "").validate().unwrap_err();
+    }
+
+    #[test]
+    fn merge_cli_args() {
+        let qps = params("target-qps", "100").runs()[0].as_args();
+        let workers = params("workers", "4").runs()[0].as_args();
+
+        assert_eq!(
+            qps.merge(workers),
+            ArgOverride::CliArgs(vec![
+                "--target-qps".into(),
+                "100".into(),
+                "--workers".into(),
+                "4".into()
+            ])
+        );
+    }
+
+    #[test]
+    fn merge_cli_args_other_takes_precedence() {
+        let first = params("target-qps", "100").runs()[0].as_args();
+        let second = params("target-qps", "200").runs()[0].as_args();
+
+        let merged = first.merge(second);
+        assert_eq!(merged.cli_args(), ["--target-qps", "200"]);
+    }
+
+    #[test]
+    fn merge_json() {
+        let users = params("@user_rows", "10").runs()[0].as_args();
+        let articles = params("@article_rows", "20").runs()[0].as_args();
+        let users_again = params("@user_rows", "30").runs()[0].as_args();
+
+        assert_eq!(
+            users.merge(articles).merge(users_again),
+            ArgOverride::Json(json!({ "user_rows": "30", "article_rows": "20" }))
+        );
+    }
+
+    #[test]
+    fn merge_nested_json() {
+        let rows = params("@tables.users.rows", "10").runs()[0].as_args();
+        let skew = params("@tables.users.skew", "1.1").runs()[0].as_args();
+        let articles = params("@tables.articles.rows", "20").runs()[0].as_args();
+
+        assert_eq!(
+            rows.merge(skew).merge(articles),
+            ArgOverride::Json(json!({
+                "tables": {
+                    "users": { "rows": "10", "skew": "1.1" },
+                    "articles": { "rows": "20" },
+                }
+            }))
+        );
+    }
+
+    #[test]
+    fn runs_with_dotted_datagen_var() {
+        let runs = params("@tables.users.rows", "10").runs();
+        assert!(runs[0].is_datagen_var());
+        assert_eq!(
+            runs[0].as_args(),
+            ArgOverride::Json(json!({ "tables": { "users": { "rows": "10" } } }))
+        );
+    }
+
+    #[test]
+    fn runs_with_several_datagen_vars() {
+        let runs = params("@tables.users.rows, @skew", "1000:1.1,10000:1.5").runs();
+        assert_eq!(runs.len(), 2);
+        assert!(runs[0].is_datagen_var());
+        assert_eq!(
+            runs[1].as_args(),
+            ArgOverride::Json(json!({ "tables": { "users": { "rows": "10000" } }, "skew": "1.5" }))
+        );
+        assert_eq!(runs[1].to_string(), "@tables.users.rows, @skew = 10000:1.5");
+    }
+
+    #[test]
+    fn runs_with_several_flags() {
+        let runs = params("target-qps,connections", "100:8,500:16,1000:32").runs();
+        assert_eq!(runs.len(), 3);
+        assert!(!runs[0].is_datagen_var());
+        assert_eq!(
+            runs[1].as_args(),
+            ArgOverride::CliArgs(vec![
+                "--target-qps".into(),
+                "500".into(),
+                "--connections".into(),
+                "16".into()
+            ])
+        );
+        assert_eq!(runs[2].to_string(), "target-qps,connections = 1000:32");
+    }
+
+    #[test]
+    fn runs_with_flags_and_datagen_vars() {
+        let runs = params("target-qps, @tables.users.rows", "100:1000,500:5000").runs();
+        assert!(runs[0].is_datagen_var());
+        let args = runs[1].as_args();
+        assert_eq!(args.cli_args(), ["--target-qps", "500"]);
+        assert_eq!(
+            args.json(),
+            Some(&json!({ "tables": { "users": { "rows": "5000" } } }))
+        );
+
+        // The z-axis can name several parameters too.
+        let runs = z_params("100", "connections, @skew", "8:1.1").runs();
+        assert!(runs[0].is_datagen_var());
+        assert_eq!(
+            runs[0].as_args().cli_args(),
+            ["--target-qps", "100", "--connections", "8"]
+        );
+    }
+
+    #[test]
+    fn dry_run_plan() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = z_params("100,200", "@user_rows", "10");
+        params.runs_per_value = 2;
+        params.graph_results_paths =
+            vec![dir.path().join("graph.csv"), dir.path().join("graph.svg")];
+        let mut plan = Vec::new();
+        params
+            .write_plan(&mut plan, |run| Ok(format!("--target-qps={}", run.x_value)))
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(plan).unwrap(),
+            format!(
+                "2 runs planned, each repeated 2 times (4 benchmark runs)\n\
+                 run  x-axis      x-value  z-axis      z-value  datagen\n\
+                 1/2  target-qps  100      @user_rows  10       yes\n\
+                 2/2  target-qps  200      @user_rows  10       yes\n\
+                 Benchmark arguments:\n    \
+                 1/2: --target-qps=100\n    \
+                 2/2: --target-qps=200\n\
+                 Results will be written to:\n    \
+                 {dir}/graph.csv (CSV)\n    \
+                 {dir}/graph.svg (SVG graph)\n",
+                dir = dir.path().display()
+            )
+        );
+        // Nothing is written until the sweep is run.
+        assert!(!dir.path().join("graph.csv").exists());
+
+        // Missing directories are only created once the sweep is run.
+        params.graph_results_paths = vec![dir.path().join("missing").join("graph.csv")];
+        params
+            .write_plan(&mut Vec::new(), |_| Ok(String::new()))
+            .unwrap();
+        assert!(!dir.path().join("missing").exists());
+
+        params.graph_results_paths = vec![dir.path().join("graph.csv").join("graph.csv")];
+        std::fs::write(dir.path().join("graph.csv"), "").unwrap();
+        let err = params.write_plan(&mut Vec::new(), |_| Ok(String::new()));
+        assert!(err.unwrap_err().to_string().contains("is not a directory"));
+
+        params.graph_results_paths = vec![dir.path().join("graph.svg")];
+        params.append = true;
+        let err = params.write_plan(&mut Vec::new(), |_| Ok(String::new()));
+        assert!(err
+            .unwrap_err()
+            .to_string()
+            .contains("can't be appended to"));
+
+        params.append = false;
+        let err = params.write_plan(&mut Vec::new(), |_| bail!("Invalid --target-qps"));
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn summarize_runs() {
+        let summaries = params("target-qps", "100,200,300").summarize_runs();
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(
+            summaries[0],
+            RunSummary {
+                x_axis: "target-qps".to_owned(),
+                x_value: "100".to_owned(),
+                z_axis: None,
+                z_value: None,
+                is_datagen_var: false,
+                flags: vec!["--target-qps".to_owned(), "100".to_owned()],
+                data_generator_variables: None,
+            }
+        );
+
+        let summaries = z_params("100,200", "@user_rows", "10,20,30").summarize_runs();
+        assert_eq!(summaries.len(), 6);
+        assert!(summaries.iter().all(|summary| summary.is_datagen_var));
+        assert_eq!(
+            serde_json::to_value(&summaries[5]).unwrap(),
+            json!({
+                "x_axis": "target-qps",
+                "x_value": "200",
+                "z_axis": "@user_rows",
+                "z_value": "30",
+                "is_datagen_var": true,
+                "flags": ["--target-qps", "200"],
+                "data_generator_variables": { "user_rows": "30" },
+            })
+        );
+
+        let summaries = params("@user_rows", "10,20").summarize_runs();
+        assert_eq!(summaries.len(), 2);
+        assert!(summaries[1].flags.is_empty());
+        assert_eq!(
+            serde_json::to_value(&summaries[1]).unwrap(),
+            json!({
+                "x_axis": "@user_rows",
+                "x_value": "20",
+                "is_datagen_var": true,
+                "flags": [],
+                "data_generator_variables": { "user_rows": "20" },
+            })
+        );
+    }
+
+    #[test]
+    fn dry_run_plan_json() {
+        let mut params = params("target-qps", "100,200");
+        params.dry_run_format = DryRunFormat::Json;
+        let mut plan = Vec::new();
+        params
+            .write_plan(&mut plan, |run| Ok(format!("--target-qps={}", run.x_value)))
+            .unwrap();
+        let plan = serde_json::from_slice::<serde_json::Value>(&plan).unwrap();
+        assert_eq!(plan, serde_json::to_value(params.summarize_runs()).unwrap());
+        assert_eq!(plan.as_array().unwrap().len(), 2);
+
+        let err = params.write_plan(&mut Vec::new(), |_| bail!("Invalid --target-qps"));
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn validate_several_axes() {
+        params("@rows,@skew", "1000:1.1, 10000 : 1.5")
+            .validate()
+            .unwrap();
+        z_params("100", "@rows,@skew", "10:1,20:2")
+            .validate()
+            .unwrap();
+
+        let err = params("@rows,@skew", "1000:1.1,10000")
+            .validate()
+            .unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("'10000' at position 1"), "{err}");
+        assert!(err.contains("expected 2"), "{err}");
+        assert!(err.contains("found 1"), "{err}");
+
+        let err = params("@rows,@skew", "1000:1.1:3").validate().unwrap_err();
+        assert!(err.to_string().contains("found 3"), "{err}");
+
+        let err = params("@rows,@skew", "1000:high").validate().unwrap_err();
+        let err = err.to_string();
+        assert!(err.contains("@skew"), "{err}");
+        assert!(err.contains("'high'"), "{err}");
+
+        // Benchmark flags take any value, which the benchmark validates.
+        params("@rows,target-qps", "1:fast").validate().unwrap();
+        let err = params("@rows,target-qps", "high:fast")
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("@rows"), "{err}");
+
+        let err = params("target-qps,connections", "100:8,500")
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("'500' at position 1"), "{err}");
+
+        for axis in ["--target-qps", "target qps", "target-qps,=8"] {
+            let err = params(axis, "1").validate().unwrap_err();
+            assert!(
+                err.to_string().contains("Invalid benchmark flag"),
+                "{axis}: {err}"
+            );
+        }
+        let err = params("target-qps,target-qps", "1:2")
+            .validate()
+            .unwrap_err();
+        assert!(err.to_string().contains("more than once"), "{err}");
+
+        let err = params("@tables..rows", "1").validate().unwrap_err();
+        assert!(err.to_string().contains("empty path segment"), "{err}");
+        params("@rows,", "1:2").validate().unwrap_err();
+    }
+
+    #[test]
+    fn merge_cli_args_and_json() {
+        let qps = params("target-qps", "100").runs()[0].as_args();
+        let users = params("@user_rows", "10").runs()[0].as_args();
+
+        let merged = qps.clone().merge(users.clone());
+        assert_eq!(
+            merged,
+            ArgOverride::Combined {
+                cli: vec!["--target-qps".into(), "100".into()],
+                json: json!({ "user_rows": "10" }),
+            }
+        );
+        assert_eq!(users.merge(qps), merged);
+    }
+
+    #[test]
+    fn results_paths_must_differ() {
+        let mut params = params("target-qps", "100");
+        params.graph_results_paths = vec!["graph.csv".into(), "graph.json".into()];
+        params.validate().unwrap();
+        params.graph_results_path2 = Some("graph.csv".into());
+        let err = params.validate().unwrap_err();
+        assert!(err.to_string().contains("graph.csv once"), "{err}");
+        params.graph_results_path2 = Some("graph.txt".into());
+        let err = params.validate().unwrap_err();
+        assert!(err.to_string().contains("--graph-results-path2"), "{err}");
+        params.graph_results_paths.push("graph.json".into());
+        params.graph_results_path2 = None;
+        params.validate().unwrap_err();
+    }
+
+    #[test]
+    fn expand_and_create_results_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100");
+        params.tags = vec!["branch=main".parse().unwrap()];
+        params.graph_results_paths = vec![
+            dir.path().join("{benchmark}/{tag:branch}/{x_axis}.csv"),
+            dir.path().join("{x_axis}.json"),
+        ];
+        params.graph_results_path2 = Some(dir.path().join("{{literal}}.jsonl"));
+        params.expand_results_paths("workload_emulator").unwrap();
+        assert_eq!(
+            params.graph_results_paths,
+            [
+                dir.path().join("workload_emulator/main/target-qps.csv"),
+                dir.path().join("target-qps.json"),
+            ]
+        );
+        assert_eq!(
+            params.graph_results_path2,
+            Some(dir.path().join("{literal}.jsonl"))
+        );
+
+        params.create_results_dirs().unwrap();
+        assert!(dir.path().join("workload_emulator/main").is_dir());
+        let mut writer = results_sink::from_params(&params).unwrap();
+        writer
+            .write_result("100", &results(&[("latency", &[1])]))
+            .unwrap();
+        writer.finish().unwrap();
+        assert!(params.graph_results_path().exists());
+
+        params.graph_results_paths = vec![dir.path().join("{nope}.csv")];
+        params
+            .expand_results_paths("workload_emulator")
+            .unwrap_err();
+    }
+
+    #[test]
+    fn dry_run_templated_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100");
+        params.dry_run = true;
+        params.graph_results_paths = vec![dir.path().join("{benchmark}/{x_axis}.csv")];
+        params.expand_results_paths("workload_emulator").unwrap();
+        let mut plan = Vec::new();
+        params.write_plan(&mut plan, |_| Ok(String::new())).unwrap();
+        let plan = String::from_utf8(plan).unwrap();
+        assert!(
+            plan.contains(&format!(
+                "{}/workload_emulator/target-qps.csv (CSV)",
+                dir.path().display()
+            )),
+            "{plan}"
+        );
+        // A dry run doesn't create the directory.
+        assert!(!dir.path().join("workload_emulator").exists());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            let read_only = dir.path().join("read-only");
+            std::fs::create_dir(&read_only).unwrap();
+            std::fs::set_permissions(&read_only, std::fs::Permissions::from_mode(0o555)).unwrap();
+            params.graph_results_paths = vec![read_only.join("{benchmark}/graph.csv")];
+            params.expand_results_paths("workload_emulator").unwrap();
+            let err = params
+                .write_plan(&mut Vec::new(), |_| Ok(String::new()))
+                .unwrap_err();
+            assert!(err.to_string().contains("is not writable"), "{err}");
+        }
+    }
+
+    #[test]
+    fn invalid_tags() {
+        for tag in ["branch", "=main", "my branch=main"] {
+            assert!(tag.parse::<ColumnTag>().is_err(), "{tag}");
+        }
+
+        let mut params = params("target-qps", "100");
+        for column in [
+            "target-qps",
+            ACHIEVED_QPS_COLUMN,
+            TAG_COLUMN,
+            "commit",
+            ERROR_COLUMN,
+        ] {
+            params.tags = vec![format!("{column}=1").parse().unwrap()];
+            let err = params.validate().unwrap_err();
+            assert!(err.to_string().contains("name of a column"), "{err}");
+        }
+        params.tags = vec!["branch=a".parse().unwrap(), "branch=b".parse().unwrap()];
+        let err = params.validate().unwrap_err();
+        assert!(err.to_string().contains("only be given once"), "{err}");
+    }
+
+    fn log_params(x_axis: &str, min: f64, max: f64, steps: usize) -> GraphParams {
+        GraphParams {
+            x_values: None,
+            x_axis_log_scale: true,
+            x_axis_min: Some(min),
+            x_axis_max: Some(max),
+            x_axis_steps: Some(steps),
+            ..params(x_axis, "")
+        }
+    }
+
+    #[test]
+    fn auto_x_values_log_are_geometric() {
+        let values = log_params("target-qps", 100.0, 100_000.0, 7).auto_x_values_log();
+        assert_eq!(values.len(), 7);
+        assert_eq!(values[0], 100.0);
+        assert_eq!(values[6], 100_000.0);
+        let ratio = values[1] / values[0];
+        assert!((ratio - 10f64.sqrt()).abs() < 1e-9, "{ratio}");
+        for (a, b) in values.iter().tuple_windows() {
+            assert!((b / a - ratio).abs() < 1e-9, "{values:?}");
+        }
+
+        assert_eq!(
+            log_params("target-qps", 5.0, 500.0, 1).auto_x_values_log(),
+            vec![5.0]
+        );
+    }
+
+    #[test]
+    fn runs_with_x_axis_log_scale() {
+        let params = log_params("target-qps", 100.0, 10_000.0, 5);
+        params.validate().unwrap();
+        let x_values = params
+            .runs()
+            .into_iter()
+            .map(|run| run.x_value)
+            .collect::<Vec<_>>();
+        assert_eq!(x_values, ["100", "316", "1000", "3162", "10000"]);
+        assert_eq!(
+            params.x_values_as_f64().unwrap(),
+            vec![100.0, 316.0, 1000.0, 3162.0, 10_000.0]
+        );
+
+        // Values that round to the same whole number are only run once.
+        let params = log_params("@user_rows", 1.0, 4.0, 10);
+        params.validate().unwrap();
+        assert_eq!(params.x_values_as_usize().unwrap(), vec![1, 2, 3, 4]);
+
+        let values = log_params("scale", 0.5, 2.0, 3).x_values_as_f64().unwrap();
+        assert_eq!(values, vec![0.5, 1.0, 2.0]);
+    }
+
+    #[test]
+    fn validate_x_axis_log_scale() {
+        log_params("target-qps", 0.0, 100.0, 3)
+            .validate()
+            .unwrap_err();
+        log_params("target-qps", 100.0, 10.0, 3)
+            .validate()
+            .unwrap_err();
+        log_params("target-qps", 1.0, 100.0, 0)
+            .validate()
+            .unwrap_err();
+
+        let err = params("target-qps", "").validate().unwrap_err();
+        assert!(err.to_string().contains("--x-values"), "{err}");
+        let err = GraphParams {
+            x_values: None,
+            ..params("target-qps", "")
+        }
+        .validate()
+        .unwrap_err();
+        assert!(err.to_string().contains("--x-axis-log-scale"), "{err}");
+    }
+
+    #[test]
+    fn parse_x_axis_log_scale() {
+        let parse = |args: &str| GraphParams::try_parse_from(args.split_whitespace());
+        let params = parse(
+            "graph --graph --x-axis target-qps --x-axis-log-scale --x-axis-min 10
+             --x-axis-max 1000 --x-axis-steps 3",
+        )
+        .unwrap();
+        assert_eq!(params.x_values_as_f64().unwrap(), vec![10.0, 100.0, 1000.0]);
+
+        parse("graph --graph --x-axis target-qps --x-axis-log-scale --x-axis-min 10").unwrap_err();
+        parse(
+            "graph --graph --x-axis target-qps --x-values 1 --x-axis-log-scale --x-axis-min 1
+             --x-axis-max 2 --x-axis-steps 2",
+        )
+        .unwrap_err();
+    }
+
+    /// Runs the sweep described by `params` in the same way as the benchmark driver, returning
+    /// the x-values that were run. Fails before running the `fail_at`th value, if given.
+    fn sweep(params: &GraphParams, fail_at: Option<usize>) -> (Vec<String>, anyhow::Result<()>) {
+        let mut executed = Vec::new();
+        let result = (|| {
+            let mut checkpoint = Checkpoint::open(params.graph_results_path(), params.resume)?;
+            let mut writer = results_sink::from_params(params)?;
+            for run in params.runs() {
+                if checkpoint.is_completed(&run) {
+                    continue;
+                }
+                if fail_at == Some(executed.len()) {
+                    bail!("Benchmark failed running {run}");
+                }
+                executed.push(run.x_value.clone());
+                writer.write_results(&run, &[results(&[("latency", &[1, 2])])], None)?;
+                writer.flush()?;
+                checkpoint.record(&run)?;
+            }
+            writer.finish()?;
+            checkpoint.remove()
+        })();
+        (executed, result)
+    }
+
+    #[test]
+    fn resume_interrupted_sweep() {
+        let dir = tempfile::tempdir().unwrap();
+        for extension in ["csv", "json"] {
+            let mut params = params("target-qps", "100,200,300,400");
+            params.graph_results_paths = vec![dir.path().join(format!("graph.{extension}"))];
+            let checkpoint = Checkpoint::path_for(params.graph_results_path());
+
+            let (executed, result) = sweep(&params, Some(2));
+            assert!(result.is_err());
+            assert_eq!(executed, ["100", "200"]);
+            assert!(checkpoint.exists());
+
+            params.resume = true;
+            let (executed, result) = sweep(&params, None);
+            result.unwrap();
+            assert_eq!(executed, ["300", "400"]);
+            assert!(!checkpoint.exists());
+
+            let x_values = if extension == "csv" {
+                let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+                reader
+                    .records()
+                    .map(|row| row.unwrap()[0].to_owned())
+                    .collect::<Vec<_>>()
+            } else {
+                let json: Vec<serde_json::Value> =
+                    serde_json::from_reader(File::open(params.graph_results_path()).unwrap())
+                        .unwrap();
+                json.iter().map(|run| run["x_value"].to_string()).collect()
+            };
+            assert_eq!(x_values, ["100", "200", "300", "400"], "{extension}");
+        }
+    }
+
+    #[test]
+    fn estimated_duration() {
+        let run = run("100");
+        assert_eq!(run.estimated_duration(None), None);
+        // Without an elapsed time, each latency is assumed to take up the run.
+        let latency = results(&[("latency", &[100, 300])]);
+        assert_eq!(
+            run.estimated_duration(Some(&latency)),
+            Some(Duration::from_micros(400))
+        );
+        let mut timed = results(&[("latency", &[1000])]);
+        timed.record_elapsed_time(Duration::from_secs(10));
+        assert_eq!(
+            run.estimated_duration(Some(&timed)),
+            Some(Duration::from_secs(10))
+        );
+        let mut counts = BenchmarkResults::new();
+        counts.push(
+            "rows",
+            Unit::Count,
+            MetricGoal::Increasing,
+            Histogram::new(3).unwrap(),
+        );
+        assert_eq!(run.estimated_duration(Some(&counts)), None);
+    }
+
+    #[test]
+    fn shuffle_runs_is_reproducible() {
+        let runs = params("target-qps", "1,2,3,4,5,6,7,8,9,10").runs();
+        let shuffled = |seed| {
+            let mut runs = runs.clone();
+            shuffle_runs(&mut runs, seed);
+            runs
+        };
+        assert_eq!(shuffled(7), shuffled(7));
+        assert_ne!(shuffled(7), runs);
+        assert_ne!(shuffled(7), shuffled(8));
+        let mut sorted = shuffled(7);
+        sorted.sort_by_key(|run| run.x_value.parse::<u32>().unwrap());
+        assert_eq!(sorted, runs);
+
+        let mut params = params("target-qps", "1,2,3,4,5,6,7,8,9,10");
+        assert_eq!(params.execution_order(runs.clone()), runs);
+        params.shuffle_runs = Some(Some(7));
+        assert_eq!(params.execution_order(runs.clone()), shuffled(7));
+    }
+
+    #[test]
+    fn shuffled_runs_are_written_in_planned_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100,200,300,400,500,600");
+        params.keep_going = true;
+        params.shuffle_runs = Some(Some(3));
+        let runs = params.execution_order(params.runs());
+        assert_ne!(runs, params.runs());
+
+        for extension in ["csv", "jsonl", "json"] {
+            let path = dir.path().join(format!("graph.{extension}"));
+            let mut writer = results_sink::for_path(&params, &path).unwrap();
+            for run in &runs {
+                if run.x_value == "400" {
+                    writer
+                        .write_failure(run, &anyhow!("Connection refused"))
+                        .unwrap();
+                } else {
+                    let latency = run.x_value.parse::<u64>().unwrap();
+                    writer
+                        .write_results(run, &[results(&[("latency", &[latency])])], None)
+                        .unwrap();
+                }
+            }
+            writer.finish().unwrap();
+
+            let x_values = match extension {
+                "csv" => csv::Reader::from_path(&path)
+                    .unwrap()
+                    .records()
+                    .map(|row| row.unwrap()[0].to_owned())
+                    .collect::<Vec<_>>(),
+                "jsonl" => std::fs::read_to_string(&path)
+                    .unwrap()
+                    .lines()
+                    .map(|line| {
+                        serde_json::from_str::<serde_json::Value>(line).unwrap()["x_value"]
+                            .to_string()
+                    })
+                    .collect(),
+                "json" => {
+                    serde_json::from_reader::<_, Vec<serde_json::Value>>(File::open(&path).unwrap())
+                        .unwrap()
+                        .iter()
+                        .map(|run| run["x_value"].to_string())
+                        .collect()
+                }
+                _ => unreachable!(),
+            };
+            assert_eq!(
+                x_values,
+                ["100", "200", "300", "400", "500", "600"],
+                "{extension}"
+            );
+        }
+    }
+
+    #[test]
+    fn parse_cooldown() {
+        let parse = |args: &str| GraphParams::try_parse_from(args.split_whitespace());
+        let params = parse(
+            "graph --graph --x-axis target-qps --x-values 1 --cooldown 1500ms
+             --cooldown-until-idle 30s",
+        )
+        .unwrap();
+        assert_eq!(params.cooldown().duration, Duration::from_millis(1500));
+        assert_eq!(
+            params.cooldown().idle_timeout,
+            Some(Duration::from_secs(30))
+        );
+
+        parse("graph --graph --x-axis target-qps --x-values 1 --cooldown 1s --cooldown-secs 2")
+            .unwrap_err();
+        parse("graph --graph --x-axis target-qps --x-values 1 --cooldown-until-idle soon")
+            .unwrap_err();
+    }
+
+    #[test]
+    fn validate_cooldown() {
+        for cooldown_secs in [-1.0, f64::NAN, f64::INFINITY] {
+            let params = GraphParams {
+                cooldown_secs,
+                ..params("target-qps", "1")
+            };
+            let err = params.validate().unwrap_err().to_string();
+            assert!(err.contains("--cooldown-secs"), "{err}");
+        }
+    }
+
+    #[test]
+    fn validate_parallelism() {
+        let mut params = params("target-qps", "1,2");
+        params.validate_parallelism(false).unwrap();
+
+        params.graph_parallelism = 2;
+        params.validate_parallelism(true).unwrap();
+        let err = params.validate_parallelism(false).unwrap_err().to_string();
+        assert!(err.contains("can't safely run concurrently"), "{err}");
+
+        params.x_axis = Some("@rows".to_owned());
+        let err = params.validate_parallelism(true).unwrap_err().to_string();
+        assert!(err.contains("data generator variable"), "{err}");
+    }
+
+    #[test]
+    fn time_series_params() {
+        let parse = |args: &str| GraphParams::try_parse_from(args.split_whitespace());
+        let params = parse("graph --time-series --graph-results-path series.csv").unwrap();
+        params.validate().unwrap();
+        assert_eq!(params.x_axis_name(), TIME_SERIES_X_AXIS);
+        assert_eq!(params.time_series_interval, Duration::from_secs(10));
+        assert!(params.runs().is_empty());
+
+        let params = parse("graph --time-series --time-series-interval 0s").unwrap();
+        let err = params.validate().unwrap_err().to_string();
+        assert!(err.contains("--time-series-interval"), "{err}");
+
+        for args in [
+            "graph --time-series --graph --x-axis target-qps --x-values 1",
+            "graph --time-series --x-axis target-qps",
+            "graph --time-series-interval 5s",
+        ] {
+            assert!(parse(args).is_err(), "{args}");
+        }
+
+        assert_eq!(
+            GraphRun::time_series(Duration::from_millis(12345)).x,
+            XValue::Float(12.345)
+        );
+        assert_eq!(GraphRun::time_series(Duration::from_secs(30)).x_value, "30");
+    }
+}
@@ -0,0 +1,482 @@
+//! Saving the full HDR histogram of every metric recorded by each run of a `--graph` sweep with
+//! `--histogram-dir`, and loading them back.
+//!
+//! The statistics written to the graph results only describe a few points of each distribution.
+//! Keeping the histograms themselves makes it possible to compute other quantiles, or to merge
+//! the results of several runs, after a sweep has finished. Each histogram is written with the
+//! hdrhistogram V2 serialization format to a file named `{x_value}_{metric}.hist`, or
+//! `{x_value}_{z_value}_{metric}.hist` if the sweep has a z-axis.
+//!
+//! With `--histogram-format hgrm`, histograms are instead written as `.hgrm` files with the
+//! percentile distribution printed by HdrHistogram's `outputPercentileDistribution`, which
+//! HdrHistogram plotters and other latency tooling read. These can't be loaded back.
+//!
+//! With `--resource-usage-samples`, every resource usage sample of each run is also written to a
+//! CSV file named `{x_value}_resource_usage.csv`.
+
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context};
+use clap::ValueEnum;
+use hdrhistogram::serialization::{Deserializer, Serializer, V2Serializer};
+use hdrhistogram::Histogram;
+use itertools::Itertools;
+
+use crate::benchmark::BenchmarkResults;
+use crate::graph::GraphRun;
+
+/// The extension of histogram files.
+const EXTENSION: &str = "hist";
+
+/// The number of steps that the percentiles of `.hgrm` files are divided into each time the
+/// remaining distance to 100% halves, as in HdrHistogram's `outputPercentileDistribution`.
+const HGRM_TICKS_PER_HALF_DISTANCE: u32 = 5;
+
+/// The formats that `--histogram-dir` histograms can be written in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum HistogramFormat {
+    /// The hdrhistogram V2 serialization format, which [`read_histogram`] loads back.
+    #[default]
+    Hist,
+    /// The percentile distribution text format of HdrHistogram's `outputPercentileDistribution`.
+    Hgrm,
+}
+
+/// Returns the path in `dir` of the histogram of `metric` for `run`.
+///
+/// Characters other than ASCII letters, digits, `-` and `.` are replaced with `_` so that any
+/// metric name or axis value can be used in a file name.
+pub fn histogram_path(dir: &Path, run: &GraphRun, metric: &str) -> PathBuf {
+    let name = std::iter::once(run.x_value.as_str())
+        .chain(run.z_value())
+        .chain(std::iter::once(metric))
+        .map(file_name_component)
+        .collect::<Vec<_>>()
+        .join("_");
+    dir.join(format!("{name}.{EXTENSION}"))
+}
+
+/// Returns the path in `dir` of the `.hgrm` percentile distribution of `metric` for `run`, which
+/// is named like its [`histogram_path`].
+pub fn hgrm_path(dir: &Path, run: &GraphRun, metric: &str) -> PathBuf {
+    histogram_path(dir, run, metric).with_extension("hgrm")
+}
+
+fn file_name_component(s: &str) -> String {
+    s.trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Writes the histogram of every metric in `results` for `run` to `dir` in each of `formats`,
+/// creating it if needed. The histograms of repeated runs with the same parameters are merged
+/// into a single file.
+pub fn write_histograms(
+    dir: &Path,
+    run: &GraphRun,
+    results: &[BenchmarkResults],
+    formats: &[HistogramFormat],
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create histogram directory {}", dir.display()))?;
+    let mut merged = BTreeMap::<&str, Histogram<u64>>::new();
+    for result in results {
+        for (metric, data) in &result.results {
+            match merged.get_mut(metric.as_str()) {
+                Some(histogram) => histogram.add(&data.values)?,
+                None => {
+                    merged.insert(metric, data.to_histogram());
+                }
+            }
+        }
+    }
+
+    let mut serializer = V2Serializer::new();
+    for ((metric, histogram), format) in merged.iter().cartesian_product(formats) {
+        let path = match format {
+            HistogramFormat::Hist => histogram_path(dir, run, metric),
+            HistogramFormat::Hgrm => hgrm_path(dir, run, metric),
+        };
+        let mut writer = BufWriter::new(
+            File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?,
+        );
+        match format {
+            HistogramFormat::Hist => serializer
+                .serialize(histogram, &mut writer)
+                .map(|_| ())
+                .map_err(|e| anyhow!("{e}")),
+            HistogramFormat::Hgrm => write_hgrm(histogram, &mut writer).map_err(Into::into),
+        }
+        .and_then(|()| Ok(writer.flush()?))
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Returns the path in `dir` of the resource usage samples of `run`, which is named like a
+/// [`histogram_path`] for a `resource_usage` metric.
+pub fn resource_usage_path(dir: &Path, run: &GraphRun) -> PathBuf {
+    histogram_path(dir, run, "resource_usage").with_extension("csv")
+}
+
+/// Writes every resource usage sample of `results` for `run` to a CSV file in `dir`, creating it
+/// if needed. Each row has the index of the repeated run it's from, the process it's of
+/// (`benchmark`, or `target` for the `--resource-usage-pid`), and the sample's time, CPU
+/// utilization and resident memory. Nothing is written if no resource usage was sampled.
+pub fn write_resource_usage(
+    dir: &Path,
+    run: &GraphRun,
+    results: &[BenchmarkResults],
+) -> anyhow::Result<()> {
+    if results.iter().all(|result| result.resource_usage.is_none()) {
+        return Ok(());
+    }
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create histogram directory {}", dir.display()))?;
+    let path = resource_usage_path(dir, run);
+    let mut writer = csv::Writer::from_path(&path)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+    writer.write_record(["run", "process", "elapsed_ms", "cpu_percent", "rss_bytes"])?;
+    for (i, usage) in results.iter().enumerate() {
+        let Some(usage) = &usage.resource_usage else {
+            continue;
+        };
+        let processes = std::iter::once(("benchmark", &usage.benchmark))
+            .chain(usage.target.as_ref().map(|target| ("target", target)));
+        for (process, usage) in processes {
+            for sample in &usage.samples {
+                writer.write_record([
+                    i.to_string(),
+                    process.to_owned(),
+                    sample.elapsed_ms.to_string(),
+                    sample.cpu_percent.to_string(),
+                    sample.rss_bytes.to_string(),
+                ])?;
+            }
+        }
+    }
+    writer
+        .flush()
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Writes the percentile distribution of `histogram` in the `.hgrm` format of HdrHistogram's
+/// `outputPercentileDistribution`: a row for each step of the percentiles with the value, the
+/// percentile, the count of values up to it and `1/(1-percentile)`, followed by a summary.
+/// Values are printed with as many decimal places as the histogram has significant digits.
+pub fn write_hgrm(histogram: &Histogram<u64>, writer: &mut impl Write) -> io::Result<()> {
+    let precision = usize::from(histogram.sigfig());
+    writeln!(
+        writer,
+        "{:>12} {:>14} {:>10} {:>14}\n",
+        "Value", "Percentile", "TotalCount", "1/(1-Percentile)"
+    )?;
+    let mut total_count = 0;
+    for value in histogram.iter_quantiles(HGRM_TICKS_PER_HALF_DISTANCE) {
+        total_count += value.count_since_last_iteration();
+        let quantile = value.quantile_iterated_to();
+        write!(
+            writer,
+            "{:12.precision$} {quantile:2.12} {total_count:10}",
+            value.value_iterated_to() as f64,
+        )?;
+        // The last row is at 100%, for which the inverse is infinite.
+        if quantile < 1.0 {
+            write!(writer, " {:14.2}", 1.0 / (1.0 - quantile))?;
+        }
+        writeln!(writer)?;
+    }
+    // HdrHistogram's histograms have as many sub-buckets as it takes to tell apart every integer
+    // up to twice the largest number with the given significant digits.
+    let sub_buckets = (2 * 10u64.pow(histogram.sigfig().into())).next_power_of_two();
+    writeln!(
+        writer,
+        "#[Mean    = {:12.precision$}, StdDeviation   = {:12.precision$}]",
+        histogram.mean(),
+        histogram.stdev()
+    )?;
+    writeln!(
+        writer,
+        "#[Max     = {:12.precision$}, Total count    = {:12}]",
+        histogram.max() as f64,
+        histogram.len()
+    )?;
+    writeln!(
+        writer,
+        "#[Buckets = {:12}, SubBuckets     = {:12}]",
+        histogram.buckets(),
+        sub_buckets
+    )
+}
+
+/// Reads a histogram written by [`write_histograms`].
+pub fn read_histogram(path: &Path) -> anyhow::Result<Histogram<u64>> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?,
+    );
+    Deserializer::new()
+        .deserialize(&mut reader)
+        .map_err(|e| anyhow!("Failed to read histogram {}: {e}", path.display()))
+}
+
+/// Reads every histogram in `dir`, keyed by file name without the `.hist` extension. Other files
+/// in the directory are ignored.
+pub fn read_histograms(dir: &Path) -> anyhow::Result<BTreeMap<String, Histogram<u64>>> {
+    let mut histograms = BTreeMap::new();
+    for entry in std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read histogram directory {}", dir.display()))?
+    {
+        let path = entry?.path();
+        if path.extension().and_then(OsStr::to_str) != Some(EXTENSION) {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(OsStr::to_str) else {
+            continue;
+        };
+        histograms.insert(name.to_owned(), read_histogram(&path)?);
+    }
+    Ok(histograms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource_usage::{ProcessUsage, ResourceSample, ResourceUsage};
+    use crate::test_utils::{results, run};
+
+    /// Returns the run at `x_value` with a `threads` z-axis at `z_value`.
+    fn run_with_threads(x_value: &str, z_value: &str) -> GraphRun {
+        GraphRun {
+            z: Some(("threads".to_owned(), z_value.to_owned())),
+            ..run(x_value)
+        }
+    }
+
+    #[test]
+    fn histogram_paths() {
+        let dir = Path::new("hists");
+        assert_eq!(
+            histogram_path(dir, &run("100"), "latency"),
+            dir.join("100_latency.hist")
+        );
+        assert_eq!(
+            histogram_path(dir, &run_with_threads("0.5", "4"), "query latency/read"),
+            dir.join("0.5_4_query_latency_read.hist")
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let values = (1..=1000).collect::<Vec<u64>>();
+        let results = results(&[("latency", &values), ("rows", &[5, 10])]);
+        let run = run("100");
+        write_histograms(
+            dir.path(),
+            &run,
+            std::slice::from_ref(&results),
+            &[HistogramFormat::Hist],
+        )
+        .unwrap();
+
+        let latency = read_histogram(&histogram_path(dir.path(), &run, "latency")).unwrap();
+        let expected = &results.results["latency"].values;
+        assert_eq!(latency.len(), expected.len());
+        for quantile in [0.5, 0.9, 0.99, 0.999] {
+            assert_eq!(
+                latency.value_at_quantile(quantile),
+                expected.value_at_quantile(quantile),
+                "{quantile}"
+            );
+        }
+        assert_eq!(latency.max(), expected.max());
+
+        let rows = read_histogram(&histogram_path(dir.path(), &run, "rows")).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows.max(), 10);
+    }
+
+    #[test]
+    fn repeated_runs_are_merged() {
+        let dir = tempfile::tempdir().unwrap();
+        let run = run("100");
+        let repeated = [
+            results(&[("latency", &[1, 2])]),
+            results(&[("latency", &[3])]),
+        ];
+        write_histograms(dir.path(), &run, &repeated, &[HistogramFormat::Hist]).unwrap();
+
+        let latency = read_histogram(&histogram_path(dir.path(), &run, "latency")).unwrap();
+        assert_eq!(latency.len(), 3);
+        assert_eq!(latency.max(), 3);
+    }
+
+    #[test]
+    fn write_resource_usage_samples() {
+        let dir = tempfile::tempdir().unwrap();
+        let run = run("100");
+        let sample = |elapsed_ms, cpu_percent, rss_bytes| ResourceSample {
+            elapsed_ms,
+            cpu_percent,
+            rss_bytes,
+        };
+        let mut first = results(&[("latency", &[1])]);
+        first.resource_usage = Some(ResourceUsage {
+            benchmark: ProcessUsage {
+                samples: vec![sample(100, 50.0, 1024), sample(200, 75.5, 2048)],
+            },
+            target: Some(ProcessUsage {
+                samples: vec![sample(100, 200.0, 4096)],
+            }),
+        });
+        let mut second = results(&[("latency", &[2])]);
+        second.resource_usage = Some(ResourceUsage {
+            benchmark: ProcessUsage {
+                samples: vec![sample(100, 25.0, 1024)],
+            },
+            target: None,
+        });
+        write_resource_usage(dir.path(), &run, &[first, second]).unwrap();
+
+        let contents = std::fs::read_to_string(resource_usage_path(dir.path(), &run)).unwrap();
+        assert_eq!(
+            contents,
+            "run,process,elapsed_ms,cpu_percent,rss_bytes\n\
+             0,benchmark,100,50,1024\n\
+             0,benchmark,200,75.5,2048\n\
+             0,target,100,200,4096\n\
+             1,benchmark,100,25,1024\n"
+        );
+        // The samples aren't mistaken for a histogram.
+        assert!(read_histograms(dir.path()).unwrap().is_empty());
+
+        // Nothing is written for runs whose resource usage wasn't sampled.
+        let empty = tempfile::tempdir().unwrap();
+        write_resource_usage(empty.path(), &run, &[results(&[("latency", &[1])])]).unwrap();
+        assert!(!resource_usage_path(empty.path(), &run).exists());
+    }
+
+    #[test]
+    fn read_every_histogram() {
+        let dir = tempfile::tempdir().unwrap();
+        for x_value in ["100", "200"] {
+            let results = results(&[("latency", &[1, 2, 3])]);
+            write_histograms(
+                dir.path(),
+                &run(x_value),
+                &[results],
+                &[HistogramFormat::Hist],
+            )
+            .unwrap();
+        }
+        std::fs::write(dir.path().join("notes.txt"), "not a histogram").unwrap();
+
+        let histograms = read_histograms(dir.path()).unwrap();
+        assert_eq!(
+            histograms.keys().collect::<Vec<_>>(),
+            ["100_latency", "200_latency"]
+        );
+        assert!(histograms.values().all(|histogram| histogram.len() == 3));
+    }
+
+    #[test]
+    fn hgrm_distribution() {
+        let mut histogram = Histogram::<u64>::new(3).unwrap();
+        for value in 1..=100 {
+            histogram.record(value).unwrap();
+        }
+        let mut hgrm = vec![];
+        write_hgrm(&histogram, &mut hgrm).unwrap();
+        let hgrm = String::from_utf8(hgrm).unwrap();
+        let lines = hgrm.lines().collect::<Vec<_>>();
+
+        assert_eq!(
+            lines[..3],
+            [
+                "       Value     Percentile TotalCount 1/(1-Percentile)",
+                "",
+                "       1.000 0.000000000000          1           1.00",
+            ]
+        );
+        assert!(
+            lines.contains(&"      50.000 0.500000000000         50           2.00"),
+            "{hgrm}"
+        );
+        assert!(
+            lines.contains(&"      76.000 0.750000000000         76           4.00"),
+            "{hgrm}"
+        );
+        assert_eq!(
+            lines[lines.len() - 4..],
+            [
+                "     100.000 1.000000000000        100",
+                "#[Mean    =       50.500, StdDeviation   =       28.866]",
+                "#[Max     =      100.000, Total count    =          100]",
+                &format!(
+                    "#[Buckets = {:12}, SubBuckets     =         2048]",
+                    histogram.buckets()
+                ),
+            ]
+        );
+        // Total counts only ever increase towards the last row.
+        let counts = lines[2..lines.len() - 3]
+            .iter()
+            .map(|line| {
+                line.split_whitespace()
+                    .nth(2)
+                    .unwrap()
+                    .parse::<u64>()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(counts.is_sorted(), "{hgrm}");
+    }
+
+    #[test]
+    fn write_hgrm_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let run = run_with_threads("0.5", "4");
+        let results = results(&[("query latency/read", &[1, 2, 3])]);
+        write_histograms(
+            dir.path(),
+            &run,
+            &[results],
+            &[HistogramFormat::Hist, HistogramFormat::Hgrm],
+        )
+        .unwrap();
+
+        let path = hgrm_path(dir.path(), &run, "query latency/read");
+        assert_eq!(path, dir.path().join("0.5_4_query_latency_read.hgrm"));
+        let hgrm = std::fs::read_to_string(path).unwrap();
+        assert!(hgrm.contains("Total count    =            3"), "{hgrm}");
+        // `.hgrm` files are written alongside the histograms, and aren't read back as them.
+        let histograms = read_histograms(dir.path()).unwrap();
+        assert_eq!(
+            histograms.keys().collect::<Vec<_>>(),
+            ["0.5_4_query_latency_read"]
+        );
+    }
+
+    #[test]
+    fn read_invalid_histogram() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("100_latency.hist");
+        std::fs::write(&path, "not a histogram").unwrap();
+        let err = read_histogram(&path).unwrap_err();
+        assert!(
+            err.to_string().starts_with("Failed to read histogram"),
+            "{err}"
+        );
+    }
+}
@@ -0,0 +1,119 @@
+// Draws a line chart of the mean and quantiles of each metric in an HTML report of graph
+// results, and shows one metric at a time using the tabs. The results are embedded in the page
+// as JSON by `html_report.rs`.
+"use strict";
+
+const data = JSON.parse(document.getElementById("results").textContent);
+const SVG_NS = "http://www.w3.org/2000/svg";
+const COLORS = [
+  "#1f77b4", "#ff7f0e", "#2ca02c", "#d62728", "#9467bd",
+  "#8c564b", "#e377c2", "#7f7f7f", "#bcbd22", "#17becf",
+];
+const [WIDTH, HEIGHT, PAD, LEGEND_WIDTH] = [720, 360, 60, 200];
+
+function svgElement(name, attributes, text) {
+  const element = document.createElementNS(SVG_NS, name);
+  for (const [key, value] of Object.entries(attributes)) {
+    element.setAttribute(key, value);
+  }
+  if (text !== undefined) {
+    element.textContent = text;
+  }
+  return element;
+}
+
+// Returns the bounds of `values`, widened if they're all the same so the scale isn't empty.
+function bounds(values) {
+  const [min, max] = [Math.min(...values), Math.max(...values)];
+  return min === max ? [min - 1, max + 1] : [min, max];
+}
+
+// Returns where to place `run` on the x-axis: at its x-value, or at the throughput it achieved.
+function xOf(run) {
+  return data.x_achieved_qps ? run.achieved_qps : run.x_value;
+}
+
+function drawChart(container, metric) {
+  const runs = data.runs.filter((run) => run.metrics[metric] && xOf(run) != null);
+  const xValues = [...new Set(runs.map(xOf))];
+  // Non-numeric x-values are spaced evenly, in the order they were run.
+  const numeric = xValues.every((x) => typeof x === "number");
+  const position = (x) => (numeric ? x : xValues.indexOf(x));
+
+  const series = new Map();
+  for (const run of runs) {
+    const stats = run.metrics[metric];
+    const values = [["mean", stats.mean], ...data.quantiles.map((q) => [q, stats.quantiles[q]])];
+    for (const [stat, y] of values) {
+      if (typeof y !== "number") {
+        continue;
+      }
+      const name = run.z_value === undefined ? stat : `${stat}, ${data.z_axis} = ${run.z_value}`;
+      if (!series.has(name)) {
+        series.set(name, []);
+      }
+      series.get(name).push([position(xOf(run)), y]);
+    }
+  }
+  const points = [...series.values()].flat();
+  if (points.length === 0) {
+    return;
+  }
+
+  const [x0, x1] = bounds(points.map(([x]) => x));
+  const [y0, y1] = bounds([0, ...points.map(([, y]) => y)]);
+  const scaleX = (x) => PAD + ((x - x0) / (x1 - x0)) * (WIDTH - 2 * PAD);
+  const scaleY = (y) => HEIGHT - PAD - ((y - y0) / (y1 - y0)) * (HEIGHT - 2 * PAD);
+
+  const svg = svgElement("svg", { viewBox: `0 0 ${WIDTH + LEGEND_WIDTH} ${HEIGHT}` });
+  const axis = { stroke: "#333" };
+  const bottom = HEIGHT - PAD;
+  svg.append(svgElement("line", { x1: PAD, y1: bottom, x2: WIDTH - PAD, y2: bottom, ...axis }));
+  svg.append(svgElement("line", { x1: PAD, y1: PAD, x2: PAD, y2: bottom, ...axis }));
+  for (const x of xValues) {
+    const attributes = { x: scaleX(position(x)), y: bottom + 16, "text-anchor": "middle" };
+    // Achieved throughputs are rarely round numbers.
+    const label = typeof x === "number" && !Number.isInteger(x) ? +x.toPrecision(4) : x;
+    svg.append(svgElement("text", attributes, String(label)));
+  }
+  for (let i = 0; i <= 4; i++) {
+    const y = y0 + ((y1 - y0) * i) / 4;
+    const attributes = { x: PAD - 6, y: scaleY(y) + 4, "text-anchor": "end" };
+    svg.append(svgElement("text", attributes, String(+y.toPrecision(3))));
+  }
+  const xLabel = { x: WIDTH / 2, y: HEIGHT - 12, "text-anchor": "middle" };
+  svg.append(svgElement("text", xLabel, data.x_label));
+  const unit = runs[0].metrics[metric].unit;
+  if (unit) {
+    const transform = `rotate(-90 14 ${HEIGHT / 2})`;
+    const attributes = { x: 14, y: HEIGHT / 2, "text-anchor": "middle", transform };
+    svg.append(svgElement("text", attributes, unit));
+  }
+
+  [...series.entries()].forEach(([name, line], i) => {
+    const color = COLORS[i % COLORS.length];
+    line.sort(([a], [b]) => a - b);
+    const coordinates = line.map(([x, y]) => `${scaleX(x)},${scaleY(y)}`).join(" ");
+    const style = { fill: "none", stroke: color, "stroke-width": 2 };
+    svg.append(svgElement("polyline", { points: coordinates, ...style }));
+    for (const [x, y] of line) {
+      const point = svgElement("circle", { cx: scaleX(x), cy: scaleY(y), r: 3, fill: color });
+      point.append(svgElement("title", {}, `${name}: ${y}`));
+      svg.append(point);
+    }
+    svg.append(svgElement("text", { x: WIDTH - PAD + 20, y: PAD + 16 * i, fill: color }, name));
+  });
+  container.append(svg);
+}
+
+const sections = document.querySelectorAll("section.metric");
+const tabs = document.querySelectorAll("#tabs button");
+
+function showTab(index) {
+  sections.forEach((section, i) => (section.hidden = i !== index));
+  tabs.forEach((tab, i) => tab.classList.toggle("active", i === index));
+}
+
+sections.forEach((section) => drawChart(section.querySelector(".chart"), section.dataset.metric));
+tabs.forEach((tab, i) => tab.addEventListener("click", () => showTab(i)));
+showTab(0);
@@ -0,0 +1,214 @@
+//! Writing graph results as a single, self-contained HTML report, for sharing results with
+//! people who would rather not load a CSV file into a spreadsheet.
+//!
+//! The report has a tab for each metric with a line chart of its mean and quantiles against the
+//! x-axis (or the throughput each run achieved, with `--x-axis-achieved-qps`), and a table of its
+//! statistics for every run, followed by a table of any run metadata.
+//! The results are embedded in the page as JSON, in the same form as `.json` results, and the
+//! charts are drawn from them by an inline script, so the file can be opened without network
+//! access. Without scripts, the tables of every metric are shown one after another.
+
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+
+use serde_json::json;
+
+use crate::results_sink::{ACHIEVED_QPS_LABEL, STATS};
+use crate::Quantiles;
+
+/// Draws the charts and switches between tabs.
+const SCRIPT: &str = include_str!("html_report.js");
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; color: #222; }
+nav button { font: inherit; padding: 0.4em 1em; border: 1px solid #ccc; background: #f4f4f4; }
+nav button.active { background: #fff; border-bottom-color: #fff; font-weight: bold; }
+table { border-collapse: collapse; margin: 1em 0; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: right; }
+th { background: #f4f4f4; }
+svg { max-width: 100%; font-size: 12px; }";
+
+/// The results of a sweep to be written as an HTML report. The report is only written once every
+/// run has finished, by [`Self::write`].
+pub(crate) struct HtmlReport {
+    pub(crate) path: PathBuf,
+    /// The JSON object of each run, as written to `.json` results.
+    pub(crate) runs: Vec<serde_json::Value>,
+    /// Whether the charts place runs at the throughput they achieved, rather than their x-value.
+    pub(crate) x_achieved_qps: bool,
+}
+
+impl HtmlReport {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            runs: vec![],
+            x_achieved_qps: false,
+        }
+    }
+
+    /// Writes the report for every run so far to the output file, replacing its previous
+    /// contents.
+    pub(crate) fn write(
+        &self,
+        x_axis: &str,
+        z_axis: Option<&str>,
+        quantiles: &Quantiles,
+    ) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        writer.write_all(self.render(x_axis, z_axis, quantiles).as_bytes())?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn render(&self, x_axis: &str, z_axis: Option<&str>, quantiles: &Quantiles) -> String {
+        let metrics = self
+            .runs
+            .first()
+            .and_then(|run| run["metrics"].as_object())
+            .map(|metrics| metrics.keys().cloned().collect::<Vec<_>>())
+            .unwrap_or_default();
+        // Runs are identified by their axis values and the throughput they achieved, if recorded.
+        let with_qps = self
+            .runs
+            .iter()
+            .any(|run| run.get("achieved_qps").is_some());
+        let axes = std::iter::once(x_axis)
+            .chain(z_axis)
+            .chain(with_qps.then_some(ACHIEVED_QPS_LABEL))
+            .collect::<Vec<_>>();
+        let title = escape(&format!("Benchmark results by {x_axis}"));
+
+        let mut html = format!(
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>{title}</title>\n<style>\n{STYLE}\n</style>\n</head>\n<body>\n\
+             <h1>{title}</h1>\n<nav id=\"tabs\">"
+        );
+        for metric in &metrics {
+            let _ = write!(html, "<button type=\"button\">{}</button>", escape(metric));
+        }
+        html.push_str("</nav>\n");
+
+        for metric in &metrics {
+            let unit = self.runs[0]["metrics"][metric]["unit"]
+                .as_str()
+                .map(|unit| format!(" ({})", escape(unit)))
+                .unwrap_or_default();
+            let _ = write!(
+                html,
+                "<section class=\"metric\" data-metric=\"{0}\">\n<h2>{0}{unit}</h2>\n\
+                 <div class=\"chart\"></div>\n",
+                escape(metric)
+            );
+            let columns = STATS.iter().copied().chain(quantiles.labels());
+            table_header(&mut html, axes.iter().copied().chain(columns.clone()));
+            for run in &self.runs {
+                let stats = &run["metrics"][metric];
+                let values = columns.clone().map(|column| match stats.get(column) {
+                    Some(value) => value,
+                    None => &stats["quantiles"][column],
+                });
+                table_row(&mut html, axis_values(run, with_qps).chain(values));
+            }
+            html.push_str("</tbody></table>\n</section>\n");
+        }
+
+        let meta_columns = self
+            .runs
+            .iter()
+            .filter_map(|run| run["meta"].as_object())
+            .flat_map(|meta| meta.keys().map(String::as_str))
+            .collect::<BTreeSet<_>>();
+        if !meta_columns.is_empty() {
+            html.push_str("<section id=\"metadata\">\n<h2>Run metadata</h2>\n");
+            table_header(
+                &mut html,
+                axes.iter().copied().chain(meta_columns.iter().copied()),
+            );
+            for run in &self.runs {
+                let values = meta_columns.iter().map(|column| &run["meta"][*column]);
+                table_row(&mut html, axis_values(run, with_qps).chain(values));
+            }
+            html.push_str("</tbody></table>\n</section>\n");
+        }
+
+        let x_label = if self.x_achieved_qps {
+            ACHIEVED_QPS_LABEL
+        } else {
+            x_axis
+        };
+        let data = json!({
+            "x_axis": x_axis,
+            "z_axis": z_axis,
+            "x_label": x_label,
+            "x_achieved_qps": self.x_achieved_qps,
+            "quantiles": quantiles.labels().collect::<Vec<_>>(),
+            "runs": self.runs,
+        });
+        // `</` can't appear within a script element, and is equivalent to `<\/` in JSON strings.
+        let data = data.to_string().replace("</", "<\\/");
+        let _ = write!(
+            html,
+            "<script type=\"application/json\" id=\"results\">{data}</script>\n\
+             <script>\n{SCRIPT}</script>\n</body>\n</html>\n"
+        );
+        html
+    }
+}
+
+/// Returns the x-value of `run`, followed by its z-value if it has one and its achieved QPS if
+/// `with_qps` is set.
+fn axis_values(
+    run: &serde_json::Value,
+    with_qps: bool,
+) -> impl Iterator<Item = &serde_json::Value> {
+    std::iter::once(&run["x_value"])
+        .chain(run.get("z_value"))
+        .chain(with_qps.then(|| &run["achieved_qps"]))
+}
+
+/// Starts a table with a header row of `columns`, leaving its body open for [`table_row`].
+fn table_header<'a>(html: &mut String, columns: impl Iterator<Item = &'a str>) {
+    html.push_str("<table>\n<thead><tr>");
+    for column in columns {
+        let _ = write!(html, "<th>{}</th>", escape(column));
+    }
+    html.push_str("</tr></thead>\n<tbody>\n");
+}
+
+fn table_row<'a>(html: &mut String, values: impl Iterator<Item = &'a serde_json::Value>) {
+    html.push_str("<tr>");
+    for value in values {
+        let _ = write!(html, "<td>{}</td>", cell(value));
+    }
+    html.push_str("</tr>\n");
+}
+
+/// Formats a JSON value for a table cell, rounding fractional numbers to two decimal places.
+fn cell(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => String::new(),
+        serde_json::Value::String(value) => escape(value),
+        serde_json::Value::Number(number) if !number.is_f64() => number.to_string(),
+        serde_json::Value::Number(number) => format!("{:.2}", number.as_f64().unwrap_or_default()),
+        value => escape(&value.to_string()),
+    }
+}
+
+/// Escapes `text` for use in HTML text and attribute values.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -73,16 +73,43 @@
 //! }
 //! ```
 
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use itertools::Itertools;
+
+pub mod axis;
 pub mod benchmark;
+pub mod compare;
+pub mod graph;
+pub mod histograms;
+pub mod path_template;
+pub mod push_gateway;
+pub mod regression;
+pub mod render;
+pub mod report;
 pub mod reporting;
+pub mod resource_usage;
+pub mod results_sink;
+pub mod significance;
 pub mod spec;
+pub mod summary;
+pub mod sweep;
 pub mod utils;
 
+#[cfg(test)]
+pub(crate) mod test_utils;
+
+mod html_report;
+mod parquet_output;
+
 // Benchmarks
 mod workload_emulator;
 
 pub use workload_emulator::{QuerySet, WorkloadEmulator};
 
+/// The quantiles reported for each metric unless `--quantiles` is given.
 pub const QUANTILES: &[(&str, f64)] = &[
     ("p10", 0.1),
     ("p50", 0.5),
@@ -92,3 +119,126 @@ pub const QUANTILES: &[(&str, f64)] = &[
     ("p99", 0.99),
     ("p99.9", 0.999),
 ];
+
+/// The quantiles to report for each metric, each labelled with its percentile, e.g. `p99.9` for
+/// 0.999. Parsed from a comma-separated list of values in (0, 1] in ascending order, such as
+/// `0.5,0.9,0.99,0.9999`, where repeated values are only reported once. Defaults to
+/// [`QUANTILES`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantiles(Vec<(String, f64)>);
+
+impl Quantiles {
+    /// Returns the label and value of each quantile, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, f64)> + Clone {
+        self.0
+            .iter()
+            .map(|(label, quantile)| (label.as_str(), *quantile))
+    }
+
+    /// Returns the label of each quantile, in ascending order.
+    pub fn labels(&self) -> impl Iterator<Item = &str> + Clone {
+        self.iter().map(|(label, _)| label)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the label for `quantile`: the equivalent percentile prefixed with `p`, without
+    /// trailing zeros.
+    pub fn label(quantile: f64) -> String {
+        let percentile = format!("{:.6}", quantile * 100.0);
+        format!(
+            "p{}",
+            percentile.trim_end_matches('0').trim_end_matches('.')
+        )
+    }
+}
+
+impl Default for Quantiles {
+    fn default() -> Self {
+        Self(
+            QUANTILES
+                .iter()
+                .map(|(label, quantile)| ((*label).to_owned(), *quantile))
+                .collect(),
+        )
+    }
+}
+
+impl FromStr for Quantiles {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut quantiles: Vec<(String, f64)> = Vec::new();
+        for value in s.split(',').map(str::trim) {
+            let quantile = value
+                .parse::<f64>()
+                .map_err(|e| anyhow!("Invalid quantile '{value}': {e}"))?;
+            if !(quantile > 0.0 && quantile <= 1.0) {
+                bail!("Invalid quantile '{value}': quantiles must be greater than 0 and at most 1");
+            }
+            match quantiles.last() {
+                Some((_, last)) if quantile < *last => {
+                    bail!("Quantiles must be in ascending order, but {value} follows {last}")
+                }
+                Some((_, last)) if quantile == *last => {}
+                _ => quantiles.push((Self::label(quantile), quantile)),
+            }
+        }
+        Ok(Self(quantiles))
+    }
+}
+
+impl Display for Quantiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.iter().map(|(_, quantile)| quantile).join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_quantiles_round_trip() {
+        let quantiles = Quantiles::default();
+        assert_eq!(
+            quantiles.to_string().parse::<Quantiles>().unwrap(),
+            quantiles
+        );
+    }
+
+    #[test]
+    fn parse_quantiles() {
+        let quantiles = "0.5, 0.9,0.99,0.99,0.9999,1".parse::<Quantiles>().unwrap();
+        assert_eq!(
+            quantiles.iter().collect::<Vec<_>>(),
+            [
+                ("p50", 0.5),
+                ("p90", 0.9),
+                ("p99", 0.99),
+                ("p99.99", 0.9999),
+                ("p100", 1.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_invalid_quantiles() {
+        for (s, expected) in [
+            ("0.5,fast", "Invalid quantile 'fast'"),
+            ("0", "must be greater than 0 and at most 1"),
+            ("0.5,1.5", "must be greater than 0 and at most 1"),
+            ("0.99,0.5", "ascending order, but 0.5 follows 0.99"),
+            ("", "Invalid quantile ''"),
+        ] {
+            let err = s.parse::<Quantiles>().unwrap_err();
+            assert!(err.to_string().contains(expected), "{s}: {err}");
+        }
+    }
+}
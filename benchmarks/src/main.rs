@@ -1,11 +1,30 @@
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-use anyhow::bail;
-use benchmarks::benchmark::{Benchmark, BenchmarkControl, DeploymentParameters};
+use anyhow::{bail, Context};
+use benchmarks::benchmark::{
+    Benchmark, BenchmarkControl, BenchmarkResults, DeploymentParameters, IntervalResults,
+};
+use benchmarks::compare::{CompareParams, GraphResults};
+use benchmarks::graph::{GraphParams, GraphRun};
+use benchmarks::histograms::{write_histograms, write_resource_usage};
+use benchmarks::push_gateway::PushGateway;
+use benchmarks::regression::{self, RegressionError, REGRESSION_EXIT_CODE};
+use benchmarks::render::RenderParams;
+use benchmarks::report::{FormatReportParams, SavedResults};
 use benchmarks::reporting::ReportMode;
+use benchmarks::resource_usage::ResourceSampler;
+use benchmarks::results_sink::{
+    self, aggregate_stats, compare_targets, written_stats, PushGatewaySink, ResultsSink,
+    RunMetadata,
+};
+use benchmarks::summary::SummaryTable;
+use benchmarks::sweep::{
+    completed_results, run_in_order, run_time_series, with_run_timeout, Checkpoint, InvalidRunArgs,
+    Progress,
+};
 use benchmarks::utils::readyset_ready;
-use benchmarks::QUANTILES;
 use clap::builder::ArgPredicate;
 use clap::{Parser, ValueHint};
 use readyset_server::Handle;
@@ -25,16 +44,43 @@ struct BenchmarkRunner {
     #[arg(long)]
     skip_setup: bool,
 
-    /// The number of times we should run the benchmark.
+    /// The number of times we should run the benchmark. With `--graph`, the benchmark is run
+    /// this many times for each x-value and the results are merged.
     #[arg(long, default_value = "1")]
     iterations: u32,
 
+    /// Print the throughput of each run, in operations per second, once it finishes.
+    #[arg(long)]
+    print_throughput: bool,
+
     #[command(flatten)]
     tracing: readyset_tracing::Options,
 
     #[command(flatten)]
     deployment_params: DeploymentParameters,
 
+    #[command(flatten)]
+    graph_params: GraphParams,
+
+    #[command(flatten)]
+    compare_params: CompareParams,
+
+    #[command(flatten)]
+    format_report_params: FormatReportParams,
+
+    #[command(flatten)]
+    render_params: RenderParams,
+
+    /// Save the results of the benchmark, merged across every iteration, to a JSON file that can
+    /// be printed with `--format-report`. With a `--baseline-tag`, the tag is added to the file
+    /// name, e.g. `results-main.json` for `results.json`.
+    #[arg(
+        long,
+        value_hint = ValueHint::AnyPath,
+        conflicts_with_all = ["graph", "time_series"]
+    )]
+    save_results: Option<PathBuf>,
+
     /// Pass in the deployment parameters as a YAML formatted file. This overrides
     /// `--instance-label`, `--prometheus-push-gateway`, `--prometheus-endpoint`,
     /// `--target-conn-str`, and `--setup-conn-str`.
@@ -46,7 +92,11 @@ struct BenchmarkRunner {
 
     /// Pass in the benchmark_cmd parameters as a YAML formatted file. This overwrites
     /// any benchmark_cmd subcommand passed in.
-    #[arg(long, value_hint = ValueHint::AnyPath, required(true))]
+    #[arg(
+        long,
+        value_hint = ValueHint::AnyPath,
+        required_unless_present_any = ["candidate", "format_report", "render"]
+    )]
     benchmark: Option<PathBuf>,
 
     /// Location where benchmark reports are stored, either for validation or storage purposes
@@ -66,6 +116,167 @@ struct BenchmarkRunner {
     report_commit_id: Option<String>,
 }
 
+/// Everything needed to execute a single run of a graph sweep, shared between the tasks that
+/// execute the sweep.
+struct GraphRunner {
+    benchmark_cmd: Benchmark,
+    deployment_params: DeploymentParameters,
+    readyset_target: String,
+    iterations: u32,
+    runs_per_value: u32,
+    skip_setup: bool,
+    print_throughput: bool,
+    /// How many seconds at the start of each iteration to discard results from.
+    warmup_secs: Option<u64>,
+    /// How many of the first values of each metric to discard from each iteration.
+    warmup_samples: Option<usize>,
+    /// Whether to also run the benchmark against the upstream database, for `--compare-upstream`.
+    compare_upstream: bool,
+    /// How long each run against each target may take, from `--run-timeout`.
+    run_timeout: Option<Duration>,
+    /// Whether to keep the repetitions of a run that timed out that had finished, for
+    /// `--keep-going`.
+    keep_going: bool,
+    /// How often to sample the resource usage of each run, with `--resource-usage`.
+    resource_usage_interval: Option<Duration>,
+    /// Another process to sample the resource usage of, from `--resource-usage-pid`.
+    resource_usage_pid: Option<u32>,
+}
+
+impl GraphRunner {
+    /// Runs the benchmark `runs_per_value` times with the arguments for `run`, returning the
+    /// results of each along with the run's metadata. The results of every iteration of a run are
+    /// merged together. With `--compare-upstream`, the benchmark is then run against the upstream
+    /// database too, and the results of both are combined by [`compare_targets`].
+    ///
+    /// `attempt` counts the attempts at the run with `--retries`, from 1. Later attempts clean up
+    /// after the failed one and set the benchmark up again from scratch. Errors applying the run's
+    /// arguments to the benchmark are [`InvalidRunArgs`], so that they aren't retried.
+    async fn run(
+        &self,
+        run: &GraphRun,
+        attempt: u32,
+    ) -> anyhow::Result<(Vec<BenchmarkResults>, RunMetadata)> {
+        let mut benchmark_cmd = self.benchmark_cmd.clone();
+        if let Some(warmup_secs) = self.warmup_secs {
+            benchmark_cmd
+                .set_warmup(Duration::from_secs(warmup_secs))
+                .context(InvalidRunArgs)?;
+        }
+        if let Some(warmup_samples) = self.warmup_samples {
+            benchmark_cmd
+                .set_warmup_samples(warmup_samples)
+                .context(InvalidRunArgs)?;
+        }
+        let args = run.as_args();
+        if !args.cli_args().is_empty() {
+            benchmark_cmd
+                .update_from_args(args.cli_args())
+                .context(InvalidRunArgs)?;
+        }
+        if let Some(json) = args.json() {
+            benchmark_cmd
+                .update_data_generator_from(json.clone())
+                .context(InvalidRunArgs)?;
+        }
+        if attempt > 1 {
+            if let Err(e) = benchmark_cmd.cleanup(&self.deployment_params).await {
+                warn!("Failed to clean up after a failed attempt of {run}: {e:#}");
+            }
+        }
+        // Changing a data generator variable requires regenerating the data, and a retry must not
+        // depend on anything a failed attempt left half set up.
+        if (args.json().is_some() || attempt > 1) && !self.skip_setup {
+            benchmark_cmd.setup(&self.deployment_params).await?;
+            readyset_ready(&self.readyset_target).await?;
+        }
+
+        let metadata =
+            RunMetadata::collect(serde_json::to_string(&benchmark_cmd)?, self.warmup_secs);
+        let readyset = self
+            .execute(&benchmark_cmd, &self.deployment_params, run)
+            .await;
+        if !self.compare_upstream {
+            return Ok((readyset?, metadata));
+        }
+        let upstream = DeploymentParameters {
+            target_conn_str: self.deployment_params.setup_conn_str.clone(),
+            ..self.deployment_params.clone()
+        };
+        println!("Running with {run} against the upstream database");
+        let upstream = self.execute(&benchmark_cmd, &upstream, run).await;
+        Ok((compare_targets(readyset, upstream)?, metadata))
+    }
+
+    /// Runs `benchmark_cmd` against `deployment` `runs_per_value` times, merging the results of
+    /// the iterations of each run. If the runs take longer than the `--run-timeout`, the benchmark
+    /// is cancelled and [cleaned up](BenchmarkControl::cleanup), and with `--keep-going` the runs
+    /// that had finished are returned, marked as timed out.
+    async fn execute(
+        &self,
+        benchmark_cmd: &Benchmark,
+        deployment: &DeploymentParameters,
+        run: &GraphRun,
+    ) -> anyhow::Result<Vec<BenchmarkResults>> {
+        let mut runs = Vec::new();
+        let result = with_run_timeout(
+            self.run_timeout,
+            self.execute_runs(benchmark_cmd, deployment, run, &mut runs),
+            benchmark_cmd.cleanup(deployment),
+        )
+        .await;
+        completed_results(result, runs, self.keep_going)
+    }
+
+    /// Runs `benchmark_cmd` for [`Self::execute`], adding the results of each run to `runs` as it
+    /// finishes. With `--resource-usage`, the resource usage of each run is sampled while its
+    /// iterations execute; if the run is cancelled, sampling stops along with it.
+    async fn execute_runs(
+        &self,
+        benchmark_cmd: &Benchmark,
+        deployment: &DeploymentParameters,
+        run: &GraphRun,
+        runs: &mut Vec<BenchmarkResults>,
+    ) -> anyhow::Result<()> {
+        for r in 0..self.runs_per_value {
+            let sampler = self
+                .resource_usage_interval
+                .map(|interval| ResourceSampler::start(self.resource_usage_pid, interval))
+                .transpose()?;
+            let mut results = Vec::new();
+            for i in 0..self.iterations {
+                println!("Running with {run} (run {r}, iteration {i})");
+                let start_time = Instant::now();
+                let mut result = benchmark_cmd.benchmark(deployment).await?;
+                if let Some(warmup_samples) = self.warmup_samples {
+                    result = result.filter_warmup(warmup_samples);
+                }
+                result.record_elapsed_time(start_time.elapsed());
+                results.push(result);
+            }
+            let mut result = BenchmarkResults::merge_all(results.into_iter()).unwrap_or_default();
+            if let Some(sampler) = sampler {
+                result.resource_usage = Some(sampler.stop().await?);
+            }
+            if self.print_throughput {
+                print_throughput(&result);
+            }
+            runs.push(result);
+        }
+        Ok(())
+    }
+}
+
+/// Prints the throughput of a finished run, if its elapsed time was recorded and isn't zero.
+fn print_throughput(result: &BenchmarkResults) {
+    if let (Some(elapsed), Some(throughput)) = (result.elapsed, result.achieved_qps()) {
+        println!(
+            "Throughput: {throughput:.2} ops/sec ({} operations in {elapsed:.2?})",
+            result.sample_count()
+        );
+    }
+}
+
 fn make_prometheus_url(base: &str, benchmark_name_label: &str, instance_label: &str) -> String {
     format!("{base}/metrics/job/{benchmark_name_label}/instance/{instance_label}")
 }
@@ -165,11 +376,300 @@ impl BenchmarkRunner {
         Ok(handle)
     }
 
+    /// Check that every run in a graph sweep can be applied to the benchmark before anything is
+    /// run, so that a bad x-value doesn't fail a sweep part way through.
+    fn validate_graph_params(&self) -> anyhow::Result<()> {
+        self.graph_params.validate()?;
+        if let Some(condition) = self
+            .graph_params
+            .fail_if
+            .iter()
+            .find(|condition| condition.requires_baseline())
+        {
+            if self.compare_params.baseline.is_none() {
+                bail!("--fail-if '{condition}' requires a --baseline to compare with");
+            }
+        }
+        for run in self.graph_params.runs() {
+            let args = run.as_args();
+            if !args.cli_args().is_empty() {
+                let mut benchmark_cmd = self.benchmark_cmd.clone().unwrap();
+                benchmark_cmd.update_from_args(args.cli_args())?;
+            }
+        }
+        if self.graph_params.time_series {
+            let (intervals, _) = IntervalResults::new(self.graph_params.time_series_interval);
+            let mut benchmark_cmd = self.benchmark_cmd.clone().unwrap();
+            benchmark_cmd.set_interval_results(intervals)?;
+        }
+        self.graph_params.validate_parallelism(
+            self.benchmark_cmd
+                .as_ref()
+                .unwrap()
+                .allows_concurrent_runs(),
+        )?;
+        Ok(())
+    }
+
+    /// Prints the runs of the graph sweep and where their results will be written for
+    /// `--dry-run`, along with the arguments the benchmark will be run with for each run, without
+    /// running anything.
+    fn print_graph_plan(&self) -> anyhow::Result<()> {
+        let benchmark_cmd = self.benchmark_cmd.as_ref().unwrap();
+        self.graph_params
+            .write_plan(&mut std::io::stdout().lock(), |run| {
+                let mut benchmark_cmd = benchmark_cmd.clone();
+                if let Some(warmup_secs) = self.graph_params.warmup_secs {
+                    benchmark_cmd.set_warmup(Duration::from_secs(warmup_secs))?;
+                }
+                if let Some(warmup_samples) = self.graph_params.warmup_samples {
+                    benchmark_cmd.set_warmup_samples(warmup_samples)?;
+                }
+                let args = run.as_args();
+                if !args.cli_args().is_empty() {
+                    benchmark_cmd.update_from_args(args.cli_args())?;
+                }
+                if let Some(json) = args.json() {
+                    benchmark_cmd.update_data_generator_from(json.clone())?;
+                }
+                Ok(serde_json::to_string(&benchmark_cmd)?)
+            })
+    }
+
+    /// Runs the benchmark once for each of the graph x-values, writing the results of each run
+    /// to the graph results path and recording it in the sweep's [`Checkpoint`]. With `--resume`,
+    /// runs that completed before the sweep was interrupted are skipped, and with
+    /// `--graph-parallelism` several runs execute at once. Once every run has finished, returns a
+    /// [`RegressionError`] if any of the `--fail-if` conditions hold for the runs that were
+    /// executed.
+    ///
+    /// With `--keep-going`, runs that fail are logged and written with their error, and the sweep
+    /// carries on; an error is returned once every run has finished instead. Failed runs aren't
+    /// recorded in the checkpoint, so the checkpoint is kept for `--resume` to run them again.
+    /// Runs that time out but keep the results of some of their repetitions count as failed, but
+    /// are recorded in the checkpoint like any other written run.
+    ///
+    /// With `--retries`, a run that fails is retried before it counts as failed, and the number of
+    /// attempts each run took is written with its results. With `--shuffle-runs`, the runs execute
+    /// in a random order, but their results are still written in the planned order.
+    async fn run_graph(&self, readyset_target: &str) -> anyhow::Result<()> {
+        let mut checkpoint = Checkpoint::open(
+            self.graph_params.graph_results_path(),
+            self.graph_params.resume,
+        )?;
+        let mut writer = self.results_writer()?;
+        let all_runs = self.graph_params.runs();
+        let total = all_runs.len();
+        let runs = all_runs
+            .into_iter()
+            .filter(|run| {
+                let completed = checkpoint.is_completed(run);
+                if completed {
+                    println!("Skipping {run}, which completed before the sweep was resumed");
+                }
+                !completed
+            })
+            .collect::<Vec<_>>();
+        let runs = self.graph_params.execution_order(runs);
+        let mut progress = Progress::new(&self.graph_params, total - runs.len(), total);
+
+        let runner = Arc::new(GraphRunner {
+            benchmark_cmd: self.benchmark_cmd.clone().unwrap(),
+            deployment_params: self.deployment_params.clone(),
+            readyset_target: readyset_target.to_owned(),
+            iterations: self.iterations,
+            runs_per_value: self.graph_params.runs_per_value,
+            skip_setup: self.skip_setup,
+            print_throughput: self.print_throughput,
+            warmup_secs: self.graph_params.warmup_secs,
+            warmup_samples: self.graph_params.warmup_samples,
+            compare_upstream: self.graph_params.compare_upstream,
+            run_timeout: self.graph_params.run_timeout,
+            keep_going: self.graph_params.keep_going,
+            resource_usage_interval: self
+                .graph_params
+                .resource_usage
+                .then_some(self.graph_params.resource_usage_interval),
+            resource_usage_pid: self.graph_params.resource_usage_pid,
+        });
+        let retry_policy = self.graph_params.retry_policy();
+        let cooldown = self.graph_params.cooldown().with_idle_check({
+            let runner = Arc::clone(&runner);
+            move || {
+                let runner = Arc::clone(&runner);
+                Box::pin(async move {
+                    runner
+                        .benchmark_cmd
+                        .is_idle(&runner.deployment_params)
+                        .await
+                })
+            }
+        });
+        let mut stats = Vec::new();
+        let mut failed = 0;
+        let mut summary = (!self.graph_params.no_summary).then(SummaryTable::for_stdout);
+        run_in_order(
+            runs,
+            self.graph_params.graph_parallelism as usize,
+            cooldown,
+            self.graph_params.keep_going,
+            move |run, cooled_down| {
+                let runner = Arc::clone(&runner);
+                async move {
+                    let start_time = Instant::now();
+                    let ((mut results, mut metadata), attempts) = retry_policy
+                        .run(&run, |attempt| runner.run(&run, attempt))
+                        .await?;
+                    metadata.cooldown_ms = cooled_down.map(|d| d.as_millis() as u64);
+                    for results in &mut results {
+                        results.attempts = attempts;
+                    }
+                    Ok::<_, anyhow::Error>((results, metadata, start_time.elapsed()))
+                }
+            },
+            |run, result| {
+                let (results, metadata, elapsed) = match result {
+                    Ok(result) => result,
+                    Err(e) => {
+                        tracing::error!("Run with {run} failed: {e:#}");
+                        failed += 1;
+                        writer.write_failure(&run, &e)?;
+                        return writer.flush();
+                    }
+                };
+                if results.iter().any(|results| results.timed_out) {
+                    warn!("Run with {run} timed out; writing the results of its finished runs");
+                    failed += 1;
+                }
+                if let Some(progress) = &mut progress {
+                    progress.report(&run, elapsed, &results);
+                }
+                writer.write_results(&run, &results, Some(&metadata))?;
+                if let Some(summary) = &mut summary {
+                    summary.push(&run, &written_stats(&results, &self.graph_params)?);
+                }
+                if let Some(dir) = &self.graph_params.histogram_dir {
+                    write_histograms(dir, &run, &results, &self.graph_params.histogram_format)?;
+                    if self.graph_params.resource_usage_samples {
+                        write_resource_usage(dir, &run, &results)?;
+                    }
+                }
+                // The results must be on disk before the run is recorded as completed, so buffered
+                // runs aren't recorded at all.
+                if !self.graph_params.buffered {
+                    writer.flush()?;
+                    checkpoint.record(&run)?;
+                }
+                stats.push((run, self.aggregate_stats(&results)));
+                Ok(())
+            },
+        )
+        .await?;
+        writer.finish()?;
+        if let Some(summary) = summary.filter(|summary| !summary.is_empty()) {
+            print!("\n{summary}");
+        }
+        if failed > 0 {
+            bail!("{failed} of {total} runs of the sweep failed");
+        }
+        checkpoint.remove()?;
+        self.check_fail_if(&stats)
+    }
+
+    /// Runs the benchmark once for `--time-series`, writing a row of results to the graph results
+    /// path for each interval of the run as soon as it has passed. Once the run has finished,
+    /// returns a [`RegressionError`] if any of the `--fail-if` conditions hold for any interval.
+    async fn run_time_series(&self) -> anyhow::Result<()> {
+        let mut benchmark_cmd = self.benchmark_cmd.clone().unwrap();
+        let (intervals, snapshots) = IntervalResults::new(self.graph_params.time_series_interval);
+        benchmark_cmd.set_interval_results(intervals)?;
+        let metadata = RunMetadata::collect(serde_json::to_string(&benchmark_cmd)?, None);
+        let mut writer = self.results_writer()?;
+        let mut stats = Vec::new();
+
+        println!(
+            "Running a time series with intervals of {:?}",
+            self.graph_params.time_series_interval
+        );
+        let start_time = Instant::now();
+        let mut result = run_time_series(
+            benchmark_cmd.benchmark(&self.deployment_params),
+            snapshots,
+            |run, results| {
+                let results = [results];
+                writer.write_results(&run, &results, Some(&metadata))?;
+                if !self.graph_params.buffered {
+                    writer.flush()?;
+                }
+                stats.push((run, self.aggregate_stats(&results)));
+                Ok(())
+            },
+        )
+        .await?;
+        result.record_elapsed_time(start_time.elapsed());
+        if self.print_throughput {
+            print_throughput(&result);
+        }
+        writer.finish()?;
+        self.check_fail_if(&stats)
+    }
+
+    /// Returns where to write the results of a sweep or time series: the `--push-gateway` if there
+    /// is one, and otherwise the results file.
+    fn results_writer(&self) -> anyhow::Result<Box<dyn ResultsSink>> {
+        Ok(match &self.graph_params.push_gateway {
+            Some(address) => {
+                let gateway = PushGateway::new(
+                    address,
+                    self.benchmark_cmd.as_ref().unwrap().name_label(),
+                    &self.deployment_params.instance_label,
+                    self.graph_params.push_gateway_retries,
+                )?;
+                Box::new(PushGatewaySink::new(&self.graph_params, gateway))
+            }
+            None => results_sink::from_params(&self.graph_params)?,
+        })
+    }
+
+    /// Returns the statistics written for `results`, which `--fail-if` conditions are checked
+    /// against.
+    fn aggregate_stats(&self, results: &[BenchmarkResults]) -> Vec<(String, f64)> {
+        aggregate_stats(
+            results,
+            &self.graph_params.quantiles,
+            self.graph_params.breakdown_by_query,
+        )
+    }
+
+    /// Returns a [`RegressionError`] if any of the `--fail-if` conditions hold for `stats`, the
+    /// statistics of each run that was written, compared with the `--baseline` if there is one.
+    fn check_fail_if(&self, stats: &[(GraphRun, Vec<(String, f64)>)]) -> anyhow::Result<()> {
+        if self.graph_params.fail_if.is_empty() {
+            return Ok(());
+        }
+        let baseline = self
+            .compare_params
+            .baseline
+            .as_deref()
+            .map(GraphResults::from_path)
+            .transpose()?;
+        regression::check(&self.graph_params.fail_if, stats, baseline.as_ref())
+    }
+
     pub async fn run(&mut self) -> anyhow::Result<()> {
         // Initializes `DeploymentParameters` and `Benchmark` from the set of arguments passed by
         // the user. These arguments need not be passed by the arguments in the flattened structs
         // directly, and instead may be passed via YAML.
         let handle = self.initialize_from_args().await?;
+        let benchmark = self.benchmark_cmd.as_ref().unwrap().name_label();
+        self.graph_params.expand_results_paths(benchmark)?;
+        self.validate_graph_params()?;
+        if self.graph_params.dry_run {
+            return self.print_graph_plan();
+        }
+        if self.graph_params.graph || self.graph_params.time_series {
+            self.graph_params.create_results_dirs()?;
+        }
 
         let cmd_as_yaml = serde_yaml_ng::to_string(&self.benchmark_cmd.as_ref().unwrap())?;
         let deployment_as_yaml = serde_yaml_ng::to_string(&self.deployment_params)?;
@@ -191,6 +691,18 @@ impl BenchmarkRunner {
         );
         readyset_ready(&readyset_target).await?;
 
+        if self.graph_params.graph || self.graph_params.time_series {
+            if self.graph_params.time_series {
+                self.run_time_series().await?;
+            } else {
+                self.run_graph(&readyset_target).await?;
+            }
+            if let Some((_, shutdown_tx)) = handle {
+                shutdown_tx.shutdown().await;
+            }
+            return Ok(());
+        }
+
         let bench_start_time = std::time::SystemTime::now();
 
         let mut results = Vec::new();
@@ -201,8 +713,12 @@ impl BenchmarkRunner {
                 readyset_ready(&readyset_target).await?;
             }
             let start_time = Instant::now();
-            let result = benchmark_cmd.benchmark(&self.deployment_params).await?;
+            let mut result = benchmark_cmd.benchmark(&self.deployment_params).await?;
             let duration = start_time.elapsed();
+            result.record_elapsed_time(duration);
+            if self.print_throughput {
+                print_throughput(&result);
+            }
 
             if let Some(report_mode) = self.report_mode {
                 let session = benchmarks::reporting::BenchSession {
@@ -236,13 +752,27 @@ impl BenchmarkRunner {
                 let max = hist.max();
                 let mean = hist.mean();
                 print!("\t{metric} ({} - {:?} goal) - Average QPS: {qps} - Min: {min} - Max: {max} - Mean: {mean}", data.unit, data.desired_action);
-                for (label, quantile) in QUANTILES {
-                    print!(" - {label}: {}", hist.value_at_quantile(*quantile));
+                for (label, quantile) in self.graph_params.quantiles.iter() {
+                    print!(" - {label}: {}", hist.value_at_quantile(quantile));
                 }
                 println!();
             }
         }
 
+        if let Some(path) = &self.save_results {
+            let merged = BenchmarkResults::merge_all(results.into_iter().map(|(result, _)| result))
+                .unwrap_or_default();
+            let mut saved = SavedResults::from_results(&merged, &self.graph_params.quantiles);
+            let path = match &self.graph_params.baseline_tag {
+                Some(tag) => {
+                    saved.tag = Some(tag.clone());
+                    tag.tagged_path(path)
+                }
+                None => path.clone(),
+            };
+            saved.save(&path)?;
+        }
+
         // Push metrics recorded in the push gateway manually before exiting.
         if let (Some(addr), Some(prometheus_handle)) = (
             &self.deployment_params.prometheus_push_gateway,
@@ -273,7 +803,22 @@ async fn main() -> anyhow::Result<()> {
         .tracing
         .init("benchmarks", "benchmark-deployment")?;
 
-    benchmark_cmd_runner.run().await?;
+    if benchmark_cmd_runner.compare_params.is_enabled() {
+        return benchmark_cmd_runner.compare_params.run();
+    }
+    if benchmark_cmd_runner.format_report_params.is_enabled() {
+        return benchmark_cmd_runner.format_report_params.run();
+    }
+    if benchmark_cmd_runner.render_params.is_enabled() {
+        return benchmark_cmd_runner.render_params.run();
+    }
+    if let Err(e) = benchmark_cmd_runner.run().await {
+        if let Some(e) = e.downcast_ref::<RegressionError>() {
+            eprintln!("{e}");
+            std::process::exit(REGRESSION_EXIT_CODE);
+        }
+        return Err(e);
+    }
     Ok(())
 }
 
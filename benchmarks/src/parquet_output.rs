@@ -0,0 +1,140 @@
+//! Writing graph results to a Parquet file, for loading into tools like pandas or DuckDB without
+//! the type inference of CSV files mangling large integers.
+//!
+//! The file has the same columns as CSV results, with the same names: the axis values, the
+//! statistics of each metric, and any tag and metadata. Axis values are written as integers or
+//! floats if every value is one, with durations in seconds, and as strings otherwise. Sample
+//! counts, minimums, maximums and quantiles are written as unsigned integers, except when they are
+//! averaged across repeated runs; means and standard deviations are always floats.
+
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use arrow_array::{ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray, UInt64Array};
+use arrow_schema::{Field, Schema};
+use parquet::arrow::ArrowWriter;
+
+use crate::axis::XValue;
+
+/// The results of a sweep to be written to a Parquet file. Parquet files can't be appended to, so
+/// rows are buffered and the whole file is rewritten by [`Self::write`].
+pub(crate) struct ParquetResults {
+    path: PathBuf,
+    /// The name of each column, in the same order as the header of CSV results.
+    columns: Vec<String>,
+    /// How many of the first columns are axis values.
+    num_axes: usize,
+    /// Whether each of the statistic columns after the axes holds integers.
+    integer_values: Vec<bool>,
+    rows: Vec<ParquetRow>,
+}
+
+/// A row of results, split into the types of columns in [`ParquetResults`].
+pub(crate) struct ParquetRow {
+    pub axes: Vec<XValue>,
+    pub values: Vec<f64>,
+    /// The tag and metadata of the row, if any, which follow the statistics.
+    pub text: Vec<String>,
+}
+
+impl ParquetResults {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            columns: vec![],
+            num_axes: 0,
+            integer_values: vec![],
+            rows: vec![],
+        }
+    }
+
+    /// Sets the names of the columns, of which the first `num_axes` are axis values and the next
+    /// `integer_values.len()` are statistics, each of which is an integer if its flag is set.
+    pub(crate) fn set_columns(
+        &mut self,
+        columns: Vec<String>,
+        num_axes: usize,
+        integer_values: Vec<bool>,
+    ) {
+        self.columns = columns;
+        self.num_axes = num_axes;
+        self.integer_values = integer_values;
+    }
+
+    pub(crate) fn push(&mut self, row: ParquetRow) {
+        self.rows.push(row);
+    }
+
+    /// The rows pushed so far, in the order they'll be written.
+    pub(crate) fn rows_mut(&mut self) -> &mut Vec<ParquetRow> {
+        &mut self.rows
+    }
+
+    /// Writes every row pushed so far to the output file, replacing its previous contents. Does
+    /// nothing if no rows have been pushed, since the columns aren't known until then.
+    pub(crate) fn write(&self) -> anyhow::Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let mut arrays = (0..self.num_axes)
+            .map(|i| axis_array(self.rows.iter().map(|row| &row.axes[i])))
+            .collect::<Vec<_>>();
+        arrays.extend(self.integer_values.iter().enumerate().map(|(i, integer)| {
+            let values = self.rows.iter().map(|row| row.values[i]);
+            if *integer {
+                Arc::new(UInt64Array::from(
+                    values.map(|value| value as u64).collect::<Vec<_>>(),
+                )) as ArrayRef
+            } else {
+                Arc::new(Float64Array::from(values.collect::<Vec<_>>()))
+            }
+        }));
+        let num_text = self.columns.len() - arrays.len();
+        arrays.extend((0..num_text).map(|i| {
+            let values = self.rows.iter().map(|row| Some(row.text[i].as_str()));
+            Arc::new(values.collect::<StringArray>()) as ArrayRef
+        }));
+
+        let schema = Schema::new(
+            self.columns
+                .iter()
+                .zip(&arrays)
+                .map(|(name, array)| Field::new(name, array.data_type().clone(), false))
+                .collect::<Vec<_>>(),
+        );
+        let batch = RecordBatch::try_new(Arc::new(schema), arrays)?;
+        let file = File::create(&self.path)?;
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+        Ok(())
+    }
+}
+
+/// Returns `values` as integers if they all are, as floats if they all are numbers or durations,
+/// and as strings otherwise.
+fn axis_array<'a>(values: impl Iterator<Item = &'a XValue> + Clone) -> ArrayRef {
+    if let Some(integers) = values
+        .clone()
+        .map(|value| match value {
+            XValue::Integer(integer) => Some(*integer),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+    {
+        return Arc::new(Int64Array::from(integers));
+    }
+    if let Some(floats) = values
+        .clone()
+        .map(XValue::as_f64)
+        .collect::<Option<Vec<_>>>()
+    {
+        return Arc::new(Float64Array::from(floats));
+    }
+    Arc::new(
+        values
+            .map(|value| Some(value.to_string()))
+            .collect::<StringArray>(),
+    )
+}
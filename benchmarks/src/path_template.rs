@@ -0,0 +1,155 @@
+//! Expanding the placeholders of a templated `--graph-results-path`, such as `{git_sha}` or
+//! `{tag:<key>}`, into the path that the results of a sweep are written to.
+
+use anyhow::bail;
+use chrono::Utc;
+
+use crate::results_sink::ColumnTag;
+
+/// The placeholders that a `--graph-results-path` may contain; see [`expand_path_template`].
+pub const PATH_PLACEHOLDERS: &[&str] = &[
+    "{benchmark}",
+    "{x_axis}",
+    "{date}",
+    "{time}",
+    "{git_sha}",
+    "{tag:<key>}",
+];
+
+/// The values that the placeholders of a templated `--graph-results-path` expand to.
+#[derive(Clone, Debug)]
+pub struct PathVars<'a> {
+    /// The name of the benchmark, for `{benchmark}`.
+    pub benchmark: &'a str,
+    /// The `--x-axis`, for `{x_axis}`.
+    pub x_axis: &'a str,
+    /// When the sweep started, for `{date}` (e.g. `2024-05-01`) and `{time}` (e.g. `120000`),
+    /// both in UTC.
+    pub started_at: chrono::DateTime<Utc>,
+    /// The git commit being benchmarked, for `{git_sha}`, if it could be determined.
+    pub git_sha: Option<&'a str>,
+    /// The `--tag`s of the sweep, for `{tag:<key>}`.
+    pub tags: &'a [ColumnTag],
+}
+
+/// Replaces each of the [`PATH_PLACEHOLDERS`] in `template` with its value from `vars`, e.g.
+/// `results/{benchmark}/{x_axis}-{date}.csv`. `{{` and `}}` stand for literal braces. Returns an
+/// error for unknown placeholders, unmatched braces, and placeholders without a value, such as
+/// `{git_sha}` outside a git repository.
+pub fn expand_path_template(template: &str, vars: &PathVars) -> anyhow::Result<String> {
+    let mut expanded = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        expanded.push_str(&rest[..i]);
+        let brace = &rest[i..i + 1];
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix(brace) {
+            expanded.push_str(brace);
+            rest = after;
+            continue;
+        }
+        if brace == "}" {
+            bail!(
+                "Unmatched '}}' in --graph-results-path '{template}': use '}}}}' for a literal \
+                 '}}'"
+            );
+        }
+        let Some((name, after)) = rest.split_once('}') else {
+            bail!(
+                "Unclosed '{{' in --graph-results-path '{template}': use '{{{{' for a literal '{{'"
+            );
+        };
+        rest = after;
+        let value = match name.split_once(':') {
+            None if name == "benchmark" => vars.benchmark.to_owned(),
+            None if name == "x_axis" => vars.x_axis.to_owned(),
+            None if name == "date" => vars.started_at.format("%Y-%m-%d").to_string(),
+            None if name == "time" => vars.started_at.format("%H%M%S").to_string(),
+            None if name == "git_sha" => match vars.git_sha {
+                Some(sha) => sha.to_owned(),
+                None => bail!(
+                    "Can't expand {{git_sha}} in --graph-results-path '{template}': the git \
+                     commit couldn't be determined"
+                ),
+            },
+            Some(("tag", key)) => match vars.tags.iter().find(|tag| tag.key == key) {
+                Some(tag) => tag.value.clone(),
+                None => bail!(
+                    "Can't expand {{tag:{key}}} in --graph-results-path '{template}': no \
+                     --tag {key}=<value> was given"
+                ),
+            },
+            _ => bail!(
+                "Unknown placeholder {{{name}}} in --graph-results-path '{template}'; the \
+                 supported placeholders are {}",
+                PATH_PLACEHOLDERS.join(", ")
+            ),
+        };
+        expanded.push_str(&value);
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn path_vars(tags: &[ColumnTag]) -> PathVars<'_> {
+        PathVars {
+            benchmark: "workload_emulator",
+            x_axis: "target-qps",
+            started_at: chrono::DateTime::parse_from_rfc3339("2024-05-01T12:34:56Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            git_sha: Some("0123abc"),
+            tags,
+        }
+    }
+
+    #[test]
+    fn expand_path_template() {
+        let tags = ["branch=main".parse().unwrap()];
+        let vars = path_vars(&tags);
+        let expand = |template| super::expand_path_template(template, &vars).unwrap();
+        assert_eq!(expand("graph.csv"), "graph.csv");
+        assert_eq!(
+            expand("results/{benchmark}/{x_axis}-{date}.csv"),
+            "results/workload_emulator/target-qps-2024-05-01.csv"
+        );
+        assert_eq!(
+            expand("{date}T{time}-{git_sha}-{tag:branch}.json"),
+            "2024-05-01T123456-0123abc-main.json"
+        );
+        assert_eq!(
+            expand("{{x_axis}}-{x_axis}}}.csv"),
+            "{x_axis}-target-qps}.csv"
+        );
+        assert_eq!(expand("{{}}"), "{}");
+    }
+
+    #[test]
+    fn invalid_path_templates() {
+        let tags = ["branch=main".parse().unwrap()];
+        let vars = PathVars {
+            git_sha: None,
+            ..path_vars(&tags)
+        };
+        let expand = |template| {
+            super::expand_path_template(template, &vars)
+                .unwrap_err()
+                .to_string()
+        };
+        let err = expand("{host}.csv");
+        assert!(err.contains("Unknown placeholder {host}"), "{err}");
+        for placeholder in PATH_PLACEHOLDERS {
+            assert!(err.contains(placeholder), "{err}");
+        }
+        assert!(expand("{tag}.csv").contains("Unknown placeholder {tag}"));
+        assert!(expand("{tag:commit}.csv").contains("no --tag commit=<value>"));
+        assert!(expand("{git_sha}.csv").contains("git commit couldn't be determined"));
+        assert!(expand("{date.csv").contains("Unclosed '{'"));
+        assert!(expand("date}.csv").contains("Unmatched '}'"));
+    }
+}
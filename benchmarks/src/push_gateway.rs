@@ -0,0 +1,405 @@
+//! Pushing the results of each run of a `--graph` sweep to a Prometheus Pushgateway with
+//! `--push-gateway`, instead of writing them to a file.
+//!
+//! Each run is pushed to its own group, keyed by the benchmark name (`job`), the deployment
+//! (`instance`) and the run's x- and z-values, so that the results of earlier runs aren't
+//! replaced by later ones. Every statistic is pushed as a gauge: the quantiles of a metric as
+//! `benchmark_<unit>{metric="..", quantile="p99", x_axis="..", x_value=".."}`, where the unit is
+//! e.g. `latency_us` for metrics recorded in microseconds, and the sample count, min, max and mean
+//! as `benchmark_samples`, `benchmark_<unit>_min`, `benchmark_<unit>_max` and
+//! `benchmark_<unit>_mean` with the same labels.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use tokio::runtime::Handle;
+use tracing::warn;
+use url::Url;
+
+use crate::benchmark::{snake_case, BenchmarkResults};
+use crate::graph::GraphRun;
+use crate::report::Tag;
+use crate::results_sink::STATS;
+
+/// How long to wait before retrying a push that failed.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// A Pushgateway that the results of each run of a sweep are pushed to.
+pub struct PushGateway {
+    client: reqwest::Client,
+    /// The URL of the group shared by every run, which each run's grouping key is added to.
+    url: Url,
+    /// How many times to retry a push that failed before giving up on it.
+    retries: u32,
+    retry_interval: Duration,
+}
+
+impl PushGateway {
+    /// Creates a client for the Pushgateway at `address`, e.g. `http://localhost:9091`, that
+    /// groups results by the benchmark name `job` and the deployment `instance`.
+    pub fn new(address: &str, job: &str, instance: &str, retries: u32) -> anyhow::Result<Self> {
+        let mut url = Url::parse(address)
+            .map_err(|e| anyhow!("Invalid --push-gateway address '{address}': {e}"))?;
+        if url.cannot_be_a_base() {
+            bail!("Invalid --push-gateway address '{address}': expected an http(s) URL");
+        }
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("Invalid --push-gateway address '{address}'"))?
+            .pop_if_empty()
+            .extend(["metrics", "job", job, "instance", instance]);
+        Ok(Self {
+            client: reqwest::Client::new(),
+            url,
+            retries,
+            retry_interval: RETRY_INTERVAL,
+        })
+    }
+
+    /// Returns the URL that the results of `run` are pushed to.
+    pub fn url_for(&self, run: &GraphRun) -> Url {
+        let mut url = self.url.clone();
+        {
+            let mut segments = url
+                .path_segments_mut()
+                .expect("checked in PushGateway::new");
+            segments.extend(["x_value", run.x_value.as_str()]);
+            if let Some(z_value) = run.z_value() {
+                segments.extend(["z_value", z_value]);
+            }
+        }
+        url
+    }
+
+    /// Pushes `body`, the results of `run` in the Prometheus text format, replacing any results
+    /// previously pushed for the same run. Failed pushes are retried up to the configured number
+    /// of times, after which the failure is logged rather than returned, so that a Pushgateway
+    /// that is unavailable doesn't abort the sweep.
+    ///
+    /// This blocks the current thread until the push has finished, so it must be called from
+    /// within a multi-threaded tokio runtime.
+    pub(crate) fn push(&self, run: &GraphRun, body: String) {
+        let url = self.url_for(run);
+        let attempts = self.retries + 1;
+        let result = tokio::task::block_in_place(|| {
+            Handle::current().block_on(async {
+                let mut attempt = 1;
+                loop {
+                    match self.try_push(&url, body.clone()).await {
+                        Ok(()) => return Ok(()),
+                        Err(e) if attempt >= attempts => return Err(e),
+                        Err(e) => {
+                            warn!(%url, %e, attempt, "Failed to push results, retrying");
+                            attempt += 1;
+                            tokio::time::sleep(self.retry_interval).await;
+                        }
+                    }
+                }
+            })
+        });
+        if let Err(e) = result {
+            warn!(%url, %e, "Giving up on pushing the results of {run} after {attempts} attempts");
+        }
+    }
+
+    async fn try_push(&self, url: &Url, body: String) -> reqwest::Result<()> {
+        self.client
+            .put(url.clone())
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+}
+
+/// Returns the statistics of `run` in the Prometheus text format. `columns` names the metric and
+/// statistic of each of `values`, and the unit of each metric is taken from `results`. Every
+/// sample is labelled with the metric, the run's axes and values, and the `tag`, if any. The
+/// run's `achieved_qps` is pushed as `benchmark_achieved_qps`, unless it's NaN.
+pub(crate) fn render(
+    run: &GraphRun,
+    columns: &[(String, String)],
+    values: &[f64],
+    achieved_qps: f64,
+    results: &BenchmarkResults,
+    tag: Option<&Tag>,
+) -> String {
+    let mut run_labels = vec![
+        ("x_axis", run.x_axis.as_str()),
+        ("x_value", run.x_value.as_str()),
+    ];
+    if let Some((z_axis, z_value)) = &run.z {
+        run_labels.push(("z_axis", z_axis));
+        run_labels.push(("z_value", z_value));
+    }
+    if let Some(tag) = tag {
+        run_labels.push(("tag", tag.as_str()));
+    }
+
+    // Samples must be grouped by gauge, each group preceded by its type.
+    let mut gauges = BTreeMap::<String, Vec<String>>::new();
+    for ((metric, stat), value) in columns.iter().zip(values) {
+        let unit = results
+            .results
+            .get(metric)
+            .map_or_else(|| "value".to_owned(), |data| unit_name(&data.unit));
+        let (gauge, quantile) = match stat.as_str() {
+            "samples" => ("benchmark_samples".to_owned(), None),
+            stat if STATS.contains(&stat) => (format!("benchmark_{unit}_{stat}"), None),
+            quantile => (format!("benchmark_{unit}"), Some(quantile)),
+        };
+        let labels = std::iter::once(("metric", metric.as_str()))
+            .chain(quantile.map(|quantile| ("quantile", quantile)))
+            .chain(run_labels.iter().copied())
+            .map(|(name, value)| format!("{name}=\"{}\"", escape_label_value(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+        gauges
+            .entry(gauge.clone())
+            .or_default()
+            .push(format!("{gauge}{{{labels}}} {value}"));
+    }
+
+    if !achieved_qps.is_nan() {
+        let labels = run_labels
+            .iter()
+            .map(|(name, value)| format!("{name}=\"{}\"", escape_label_value(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+        gauges.insert(
+            "benchmark_achieved_qps".to_owned(),
+            vec![format!("benchmark_achieved_qps{{{labels}}} {achieved_qps}")],
+        );
+    }
+
+    let mut body = String::new();
+    for (gauge, samples) in gauges {
+        let _ = writeln!(body, "# TYPE {gauge} gauge");
+        for sample in samples {
+            let _ = writeln!(body, "{sample}");
+        }
+    }
+    body
+}
+
+/// Returns the name of the gauges for a metric recorded in `unit`, as written by
+/// [`BenchmarkData`](crate::benchmark::BenchmarkData): time units are treated as latencies, e.g.
+/// `latency_us` for `Microseconds`, and other units are converted to snake case.
+fn unit_name(unit: &str) -> String {
+    match unit {
+        "Nanoseconds" => "latency_ns".to_owned(),
+        "Microseconds" => "latency_us".to_owned(),
+        "Milliseconds" => "latency_ms".to_owned(),
+        "Seconds" => "latency_seconds".to_owned(),
+        _ => snake_case(unit),
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use clap::Parser;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server, StatusCode};
+
+    use super::*;
+    use crate::graph::GraphParams;
+    use crate::results_sink::{PushGatewaySink, ResultsSink};
+    use crate::test_utils::{results, run};
+
+    /// The path and body of each push received by a [`serve`]d Pushgateway.
+    type Pushes = Arc<Mutex<Vec<(String, String)>>>;
+
+    /// Starts a server on a local port that records the body of every request it receives, after
+    /// responding to the first `failures` requests with an error.
+    fn serve(failures: usize) -> (SocketAddr, Pushes) {
+        let pushes = Pushes::default();
+        let failures = Arc::new(AtomicUsize::new(failures));
+        let make_service = make_service_fn({
+            let pushes = Arc::clone(&pushes);
+            move |_| {
+                let pushes = Arc::clone(&pushes);
+                let failures = Arc::clone(&failures);
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                        let pushes = Arc::clone(&pushes);
+                        let failures = Arc::clone(&failures);
+                        async move {
+                            if failures
+                                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                                    n.checked_sub(1)
+                                })
+                                .is_ok()
+                            {
+                                let mut response = Response::new(Body::empty());
+                                *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+                                return Ok::<_, Infallible>(response);
+                            }
+                            let path = request.uri().path().to_owned();
+                            let body = hyper::body::to_bytes(request.into_body()).await.unwrap();
+                            pushes
+                                .lock()
+                                .unwrap()
+                                .push((path, String::from_utf8(body.to_vec()).unwrap()));
+                            Ok(Response::new(Body::empty()))
+                        }
+                    }))
+                }
+            }
+        });
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        (addr, pushes)
+    }
+
+    fn gateway(addr: SocketAddr, retries: u32) -> PushGateway {
+        let mut gateway =
+            PushGateway::new(&format!("http://{addr}"), "bench", "local", retries).unwrap();
+        gateway.retry_interval = Duration::from_millis(10);
+        gateway
+    }
+
+    fn read_latency() -> BenchmarkResults {
+        results(&[("read latency", &[100, 200, 300])])
+    }
+
+    fn writer(gateway: PushGateway) -> PushGatewaySink {
+        let params = GraphParams::try_parse_from([
+            "graph",
+            "--graph",
+            "--x-axis",
+            "target-qps",
+            "--x-values",
+            "100,200",
+            "--quantiles",
+            "0.5,0.99",
+        ])
+        .unwrap();
+        PushGatewaySink::new(&params, gateway)
+    }
+
+    #[test]
+    fn grouping_key() {
+        let gateway = PushGateway::new("http://localhost:9091/", "bench", "ci 1", 0).unwrap();
+        assert_eq!(
+            gateway.url_for(&run("500")).as_str(),
+            "http://localhost:9091/metrics/job/bench/instance/ci%201/x_value/500"
+        );
+        let run = GraphRun {
+            z: Some(("threads".to_owned(), "4".to_owned())),
+            ..run("500")
+        };
+        assert_eq!(
+            gateway.url_for(&run).as_str(),
+            "http://localhost:9091/metrics/job/bench/instance/ci%201/x_value/500/z_value/4"
+        );
+        assert!(PushGateway::new("localhost:9091", "bench", "local", 0).is_err());
+    }
+
+    #[test]
+    fn render_gauges() {
+        let columns =
+            ["samples", "min", "p99"].map(|stat| ("read latency".to_owned(), stat.to_owned()));
+        let tag = "main".parse::<Tag>().unwrap();
+        assert_eq!(
+            render(
+                &run("500"),
+                &columns,
+                &[3.0, 100.0, 299.5],
+                1.5,
+                &read_latency(),
+                Some(&tag)
+            ),
+            "# TYPE benchmark_achieved_qps gauge\n\
+             benchmark_achieved_qps{x_axis=\"target-qps\",x_value=\"500\",tag=\"main\"} 1.5\n\
+             # TYPE benchmark_latency_us gauge\n\
+             benchmark_latency_us{metric=\"read latency\",quantile=\"p99\",x_axis=\"target-qps\",\
+             x_value=\"500\",tag=\"main\"} 299.5\n\
+             # TYPE benchmark_latency_us_min gauge\n\
+             benchmark_latency_us_min{metric=\"read latency\",x_axis=\"target-qps\",\
+             x_value=\"500\",tag=\"main\"} 100\n\
+             # TYPE benchmark_samples gauge\n\
+             benchmark_samples{metric=\"read latency\",x_axis=\"target-qps\",x_value=\"500\",\
+             tag=\"main\"} 3\n"
+        );
+    }
+
+    #[test]
+    fn unit_names() {
+        assert_eq!(unit_name("Microseconds"), "latency_us");
+        assert_eq!(unit_name("Count"), "count");
+        assert_eq!(unit_name("BitsPerSecond"), "bits_per_second");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn push_each_run() {
+        let (addr, pushes) = serve(0);
+        let mut writer = writer(gateway(addr, 0));
+        writer.write_result("100", &read_latency()).unwrap();
+        writer.write_result("200", &read_latency()).unwrap();
+        writer.finish().unwrap();
+
+        let pushes = pushes.lock().unwrap();
+        assert_eq!(
+            pushes
+                .iter()
+                .map(|(path, _)| path.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "/metrics/job/bench/instance/local/x_value/100",
+                "/metrics/job/bench/instance/local/x_value/200"
+            ]
+        );
+        let body = &pushes[0].1;
+        for sample in [
+            "benchmark_latency_us{metric=\"read latency\",quantile=\"p50\",\
+             x_axis=\"target-qps\",x_value=\"100\"} 200",
+            "benchmark_latency_us_max{metric=\"read latency\",x_axis=\"target-qps\",\
+             x_value=\"100\"} 300",
+            "benchmark_latency_us_mean{metric=\"read latency\",x_axis=\"target-qps\",\
+             x_value=\"100\"} 200",
+            "benchmark_samples{metric=\"read latency\",x_axis=\"target-qps\",x_value=\"100\"} 3",
+        ] {
+            assert!(body.lines().any(|line| line == sample), "{sample}\n{body}");
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retry_failed_pushes() {
+        let (addr, pushes) = serve(2);
+        let mut writer = writer(gateway(addr, 2));
+        writer.write_result("100", &read_latency()).unwrap();
+        assert_eq!(pushes.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn failed_pushes_dont_abort_the_sweep() {
+        let (addr, pushes) = serve(3);
+        let mut writer = writer(gateway(addr, 1));
+        writer.write_result("100", &read_latency()).unwrap();
+        writer.write_result("200", &read_latency()).unwrap();
+        // The first run is given up on after two attempts, and the second succeeds on its second.
+        let pushes = pushes.lock().unwrap();
+        assert_eq!(
+            pushes
+                .iter()
+                .map(|(path, _)| path.as_str())
+                .collect::<Vec<_>>(),
+            ["/metrics/job/bench/instance/local/x_value/200"]
+        );
+    }
+}
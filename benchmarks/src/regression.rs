@@ -0,0 +1,434 @@
+//! Conditions that fail a `--graph` sweep once every run has finished, so that sweeps run in
+//! automation can exit with an error when a metric regresses.
+//!
+//! A condition has the form `[<metric>] <stat> (>|<) <threshold>[%]`, for example `p99>10%` or
+//! `latency mean<5000`. The statistic is one of the columns written for each metric (`samples`,
+//! `min`, `max`, `mean` or one of the `--quantiles`, such as `p99`), and if the metric is omitted
+//! the condition applies to every metric. Absolute thresholds are compared with the observed
+//! value, and percentage thresholds with the change from the value in a baseline results file.
+
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use itertools::Itertools;
+
+use crate::compare::{Delta, GraphResults};
+use crate::graph::GraphRun;
+use crate::results_sink::STATS;
+use crate::Quantiles;
+
+/// The process exit code used when a `--fail-if` condition fails, to distinguish regressions
+/// from other errors.
+pub const REGRESSION_EXIT_CODE: i32 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operator {
+    GreaterThan,
+    LessThan,
+}
+
+impl Operator {
+    fn holds(self, value: f64, threshold: f64) -> bool {
+        match self {
+            Operator::GreaterThan => value > threshold,
+            Operator::LessThan => value < threshold,
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::GreaterThan => write!(f, ">"),
+            Operator::LessThan => write!(f, "<"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Threshold {
+    /// Compared with the observed value.
+    Absolute(f64),
+    /// Compared with the percentage change from the baseline value.
+    Percent(f64),
+}
+
+/// A condition that fails the sweep if it holds for any run, parsed from `--fail-if`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FailCondition {
+    /// The metric the condition applies to, or `None` for every metric.
+    pub metric: Option<String>,
+    pub stat: String,
+    pub operator: Operator,
+    pub threshold: Threshold,
+}
+
+impl FromStr for FailCondition {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (i, operator) = match (s.find('>'), s.find('<')) {
+            (Some(i), None) => (i, Operator::GreaterThan),
+            (None, Some(i)) => (i, Operator::LessThan),
+            _ => bail!("Invalid condition '{s}': expected exactly one of '>' or '<'"),
+        };
+        let (selector, threshold) = (s[..i].trim(), s[i + 1..].trim());
+
+        let (metric, stat) = match selector.rsplit_once(char::is_whitespace) {
+            Some((metric, stat)) => (Some(metric.trim().to_owned()), stat),
+            None => (None, selector),
+        };
+        // Which quantiles are written depends on `--quantiles`, so any percentile is accepted
+        // here and checked by `validate_stat`.
+        let is_quantile = stat
+            .strip_prefix('p')
+            .and_then(|percentile| percentile.parse::<f64>().ok())
+            .is_some_and(|percentile| percentile > 0.0 && percentile <= 100.0);
+        if !STATS.contains(&stat) && !is_quantile {
+            bail!(
+                "Invalid condition '{s}': unknown statistic '{stat}', expected one of {} or a \
+                 quantile such as p99",
+                STATS.join(", ")
+            );
+        }
+
+        let parse = |value: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|e| anyhow!("Invalid condition '{s}': invalid threshold '{value}': {e}"))
+        };
+        let threshold = match threshold.strip_suffix('%') {
+            Some(percent) => Threshold::Percent(parse(percent.trim())?),
+            None => Threshold::Absolute(parse(threshold)?),
+        };
+
+        Ok(Self {
+            metric,
+            stat: stat.to_owned(),
+            operator,
+            threshold,
+        })
+    }
+}
+
+impl FailCondition {
+    /// Checks that the condition's statistic is written for each metric when reporting the given
+    /// `quantiles`.
+    pub fn validate_stat(&self, quantiles: &Quantiles) -> anyhow::Result<()> {
+        let mut stats = STATS.iter().copied().chain(quantiles.labels());
+        if !stats.clone().any(|name| name == self.stat) {
+            bail!(
+                "Invalid condition '{self}': unknown statistic '{}', expected one of {}",
+                self.stat,
+                stats.join(", ")
+            );
+        }
+        Ok(())
+    }
+}
+
+impl Display for FailCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(metric) = &self.metric {
+            write!(f, "{metric} ")?;
+        }
+        write!(f, "{}{}", self.stat, self.operator)?;
+        match self.threshold {
+            Threshold::Absolute(threshold) => write!(f, "{threshold}"),
+            Threshold::Percent(threshold) => write!(f, "{threshold}%"),
+        }
+    }
+}
+
+impl FailCondition {
+    /// Whether the condition compares against a baseline results file.
+    pub fn requires_baseline(&self) -> bool {
+        matches!(self.threshold, Threshold::Percent(_))
+    }
+
+    /// Whether the condition applies to the `"<metric> <stat>"` column named `column`.
+    fn applies_to(&self, column: &str) -> bool {
+        match &self.metric {
+            Some(metric) => {
+                column
+                    .strip_prefix(metric.as_str())
+                    .and_then(|rest| rest.strip_prefix(' '))
+                    == Some(&self.stat)
+            }
+            None => column
+                .rsplit_once(' ')
+                .is_some_and(|(_, stat)| stat == self.stat),
+        }
+    }
+}
+
+/// A condition that held for a run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Failure {
+    /// The run the condition held for.
+    pub run: String,
+    pub condition: FailCondition,
+    /// The `"<metric> <stat>"` column the condition was evaluated on.
+    pub column: String,
+    pub observed: f64,
+    /// The value in the baseline, for percentage conditions. If `None` for a percentage
+    /// condition, the baseline had no value to compare with.
+    pub baseline: Option<f64>,
+}
+
+impl Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} = {} failed `{}`",
+            self.run, self.column, self.observed, self.condition
+        )?;
+        match (self.condition.threshold, self.baseline) {
+            (Threshold::Absolute(_), _) => Ok(()),
+            (Threshold::Percent(_), Some(baseline)) => write!(
+                f,
+                " (baseline {baseline}, {:+.1}%)",
+                percent_change(baseline, self.observed)
+            ),
+            (Threshold::Percent(_), None) => write!(f, " (no baseline value to compare with)"),
+        }
+    }
+}
+
+/// The error returned when any `--fail-if` condition fails.
+#[derive(Debug)]
+pub struct RegressionError {
+    pub failures: Vec<Failure>,
+}
+
+impl Display for RegressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} --fail-if condition(s) failed:", self.failures.len())?;
+        for failure in &self.failures {
+            write!(f, "\n  {failure}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RegressionError {}
+
+/// Returns the percentage change from `baseline` to `value`, as in [`Delta::percent`].
+fn percent_change(baseline: f64, value: f64) -> f64 {
+    Delta {
+        baseline,
+        candidate: value,
+    }
+    .percent()
+}
+
+/// Evaluates `conditions` against the statistics of each run, as returned by
+/// [`crate::results_sink::aggregate_stats`], returning every condition that held. Percentage
+/// conditions are evaluated against the matching row of `baseline`; a run with no baseline value
+/// fails them.
+///
+/// Returns an error if a condition doesn't apply to any of the results, since that is most likely
+/// a mistyped metric name.
+pub fn evaluate(
+    conditions: &[FailCondition],
+    runs: &[(GraphRun, Vec<(String, f64)>)],
+    baseline: Option<&GraphResults>,
+) -> anyhow::Result<Vec<Failure>> {
+    let mut failures = Vec::new();
+    for condition in conditions {
+        if condition.requires_baseline() && baseline.is_none() {
+            bail!("--fail-if '{condition}' requires a --baseline to compare with");
+        }
+        let mut applied = false;
+        for (run, stats) in runs {
+            let key = std::iter::once(run.x_value.clone())
+                .chain(run.z_value().map(str::to_owned))
+                .collect::<Vec<_>>();
+            for (column, observed) in stats {
+                if !condition.applies_to(column) {
+                    continue;
+                }
+                applied = true;
+                let (held, baseline) = match condition.threshold {
+                    Threshold::Absolute(threshold) => {
+                        (condition.operator.holds(*observed, threshold), None)
+                    }
+                    Threshold::Percent(threshold) => {
+                        match baseline.and_then(|b| b.value(&key, column)) {
+                            Some(baseline) => (
+                                condition
+                                    .operator
+                                    .holds(percent_change(baseline, *observed), threshold),
+                                Some(baseline),
+                            ),
+                            None => (true, None),
+                        }
+                    }
+                };
+                if held {
+                    failures.push(Failure {
+                        run: run.to_string(),
+                        condition: condition.clone(),
+                        column: column.clone(),
+                        observed: *observed,
+                        baseline,
+                    });
+                }
+            }
+        }
+        if !applied && !runs.is_empty() {
+            bail!("--fail-if '{condition}' doesn't match any metric in the results");
+        }
+    }
+    Ok(failures)
+}
+
+/// Evaluates `conditions` as in [`evaluate`], returning a [`RegressionError`] if any held.
+pub fn check(
+    conditions: &[FailCondition],
+    runs: &[(GraphRun, Vec<(String, f64)>)],
+    baseline: Option<&GraphResults>,
+) -> anyhow::Result<()> {
+    let failures = evaluate(conditions, runs, baseline)?;
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(RegressionError { failures }.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::run;
+
+    fn condition(s: &str) -> FailCondition {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_conditions() {
+        assert_eq!(
+            condition("p99>10%"),
+            FailCondition {
+                metric: None,
+                stat: "p99".to_owned(),
+                operator: Operator::GreaterThan,
+                threshold: Threshold::Percent(10.0),
+            }
+        );
+        assert_eq!(
+            condition(" query latency mean < 5000 "),
+            FailCondition {
+                metric: Some("query latency".to_owned()),
+                stat: "mean".to_owned(),
+                operator: Operator::LessThan,
+                threshold: Threshold::Absolute(5000.0),
+            }
+        );
+        assert_eq!(condition("p99.9<-2.5%").threshold, Threshold::Percent(-2.5));
+        assert_eq!(condition("latency p99>10%").to_string(), "latency p99>10%");
+    }
+
+    #[test]
+    fn parse_invalid_conditions() {
+        for s in ["p99", "p99>>1", "p99<1>2", "p101>1", "p99>fast", "mean>%"] {
+            assert!(s.parse::<FailCondition>().is_err(), "{s}");
+        }
+        let err = "latency median>1".parse::<FailCondition>().unwrap_err();
+        assert!(
+            err.to_string().contains("unknown statistic 'median'"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn validate_condition_stats() {
+        let quantiles = Quantiles::default();
+        condition("p99.9<-2.5%").validate_stat(&quantiles).unwrap();
+        condition("latency mean>5")
+            .validate_stat(&quantiles)
+            .unwrap();
+        for s in ["p98>1", "latency p42>1"] {
+            let err = condition(s).validate_stat(&quantiles).unwrap_err();
+            assert!(err.to_string().contains("unknown statistic"), "{s}: {err}");
+        }
+
+        let quantiles = "0.5,0.98".parse().unwrap();
+        condition("p98>1").validate_stat(&quantiles).unwrap();
+        assert!(condition("p99>1").validate_stat(&quantiles).is_err());
+    }
+
+    fn stats(values: &[(&str, f64)]) -> Vec<(String, f64)> {
+        values.iter().map(|(c, v)| ((*c).to_owned(), *v)).collect()
+    }
+
+    fn baseline(csv: &str) -> GraphResults {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("baseline.csv");
+        std::fs::write(&path, csv).unwrap();
+        GraphResults::from_path(&path).unwrap()
+    }
+
+    #[test]
+    fn evaluate_absolute() {
+        let runs = [
+            (
+                run("100"),
+                stats(&[("latency mean", 4000.0), ("latency p99", 9000.0)]),
+            ),
+            (
+                run("200"),
+                stats(&[("latency mean", 6000.0), ("errors mean", 1.0)]),
+            ),
+        ];
+        let failures = evaluate(&[condition("mean<5000")], &runs, None).unwrap();
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].column, "latency mean");
+        assert_eq!(failures[0].observed, 4000.0);
+        assert_eq!(failures[1].column, "errors mean");
+
+        let failures = evaluate(&[condition("latency mean>5000")], &runs, None).unwrap();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].run, "target-qps = 200");
+
+        assert!(evaluate(&[condition("p99>9000")], &runs, None)
+            .unwrap()
+            .is_empty());
+        evaluate(&[condition("throughput mean>1")], &runs, None).unwrap_err();
+    }
+
+    #[test]
+    fn evaluate_percent() {
+        let baseline = baseline(
+            "target-qps,latency samples,latency p99\n\
+             100,10,100\n\
+             200,10,200\n",
+        );
+        let runs = [
+            (run("100"), stats(&[("latency p99", 105.0)])),
+            (run("200"), stats(&[("latency p99", 230.0)])),
+            (run("300"), stats(&[("latency p99", 1.0)])),
+        ];
+        evaluate(&[condition("p99>10%")], &runs, None).unwrap_err();
+
+        let failures = evaluate(&[condition("p99>10%")], &runs, Some(&baseline)).unwrap();
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].run, "target-qps = 200");
+        assert_eq!(failures[0].baseline, Some(200.0));
+        assert!(
+            failures[0].to_string().contains("+15.0%"),
+            "{}",
+            failures[0]
+        );
+        // There's no baseline for x = 300 to compare with.
+        assert_eq!(failures[1].baseline, None);
+
+        check(&[condition("latency p99<-1%")], &runs[..1], Some(&baseline)).unwrap();
+        let err = check(&[condition("latency p99>1%")], &runs[..1], Some(&baseline)).unwrap_err();
+        let err = err.downcast::<RegressionError>().unwrap();
+        assert_eq!(err.failures.len(), 1);
+        assert!(err.to_string().contains("latency p99 = 105"), "{err}");
+    }
+}
@@ -0,0 +1,543 @@
+//! Rendering the results of a previous graph sweep again, as a graph or an HTML report, without
+//! rerunning the benchmark: for example to plot different metrics, or to log-scale the y-axis.
+//!
+//! Results are read from a `.csv`, `.json`, `.jsonl` or `.ndjson` file written by a sweep, and
+//! converted to the JSON objects written to `.json` results, which the graph and HTML outputs are
+//! drawn from.
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use clap::{Parser, ValueHint};
+use itertools::Itertools;
+use serde_json::json;
+
+use crate::axis::XValue;
+use crate::graph::CommaSeparatedString;
+use crate::html_report::HtmlReport;
+use crate::results_sink::{
+    json_number, json_value, Plot, ResultsFormat, ACHIEVED_QPS_COLUMN, ATTEMPTS_COLUMN,
+    ERROR_COLUMN, METADATA_COLUMNS, STATS, TAG_COLUMN, TIMED_OUT_COLUMN,
+};
+use crate::Quantiles;
+
+#[derive(Parser, Clone, Debug)]
+pub struct RenderParams {
+    /// A graph results file written by a previous sweep, in CSV, JSON or JSON Lines format, to
+    /// render to `--render-output` instead of running a benchmark.
+    #[arg(long, value_hint = ValueHint::FilePath, requires = "render_output")]
+    pub render: Option<PathBuf>,
+
+    /// Where to render the results of `--render`: a `.png` or `.svg` graph, or an `.html` report.
+    #[arg(long, requires = "render", value_hint = ValueHint::FilePath)]
+    pub render_output: Option<PathBuf>,
+
+    /// Comma-separated metrics to render. By default, every metric in the results is rendered.
+    #[arg(long, requires = "render")]
+    pub render_metrics: Option<CommaSeparatedString>,
+
+    /// Log-scale the y-axis of the rendered graph, as long as every value is positive. Without
+    /// it, the y-axis is only log-scaled if the values span more than two orders of magnitude.
+    #[arg(long, requires = "render")]
+    pub log_y: bool,
+}
+
+impl RenderParams {
+    /// Returns whether rendering saved results was requested, rather than a benchmark run.
+    pub fn is_enabled(&self) -> bool {
+        self.render.is_some()
+    }
+
+    /// Renders the results of `--render` to `--render-output`.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let (Some(input), Some(output)) = (&self.render, &self.render_output) else {
+            bail!("--render and --render-output must both be set");
+        };
+        let mut results = SavedRuns::from_path(input)?;
+        if let Some(metrics) = &self.render_metrics {
+            results.retain_metrics(metrics)?;
+        }
+        results.render(output, self.log_y)
+    }
+}
+
+/// The runs of a sweep read from a results file, as the JSON objects written to `.json` results.
+#[derive(Debug)]
+pub struct SavedRuns {
+    /// The name of the x-axis. JSON results don't record it, so runs read from them are labelled
+    /// `x_value`.
+    pub x_axis: String,
+    /// The name of the z-axis, if the sweep had one.
+    pub z_axis: Option<String>,
+    pub quantiles: Quantiles,
+    pub runs: Vec<serde_json::Value>,
+}
+
+impl SavedRuns {
+    /// Reads the results at `path`, in the format given by its extension.
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let format = ResultsFormat::for_path(path, false)?;
+        let file =
+            || File::open(path).with_context(|| format!("Failed to open {}", path.display()));
+        let runs = match format {
+            ResultsFormat::Csv => return Self::from_csv(path),
+            ResultsFormat::Json => serde_json::from_reader(BufReader::new(file()?))
+                .with_context(|| format!("Invalid JSON results in {}", path.display()))?,
+            ResultsFormat::Jsonl => BufReader::new(file()?)
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.as_ref().is_ok_and(|line| line.trim().is_empty()))
+                .map(|(i, line)| {
+                    serde_json::from_str(&line?).with_context(|| {
+                        format!("Invalid JSON on line {} of {}", i + 1, path.display())
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?,
+            format => bail!(
+                "Can't render {}: {format} results can't be read back",
+                path.display()
+            ),
+        };
+        Self::from_json(runs)
+    }
+
+    /// Creates the results of `runs`, JSON objects of the form written to `.json` results.
+    fn from_json(runs: Vec<serde_json::Value>) -> anyhow::Result<Self> {
+        let z_axis = runs
+            .iter()
+            .any(|run| run.get("z_value").is_some())
+            .then(|| "z_value".to_owned());
+        // Quantiles are keyed by their labels, which are in alphabetical rather than numeric
+        // order once parsed.
+        let labels = runs
+            .iter()
+            .filter_map(|run| run["metrics"].as_object()?.values().next())
+            .filter_map(|stats| stats["quantiles"].as_object())
+            .next()
+            .map(|quantiles| quantiles.keys().map(String::as_str).collect::<Vec<_>>())
+            .unwrap_or_default();
+        Ok(Self {
+            x_axis: "x_value".to_owned(),
+            z_axis,
+            quantiles: quantiles_from_labels(labels)?,
+            runs,
+        })
+    }
+
+    /// Reads a results CSV file, whose header names the axes, the statistics of each metric
+    /// along with their units, and any of the columns written after them.
+    fn from_csv(path: &Path) -> anyhow::Result<Self> {
+        let mut reader = csv::Reader::from_path(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        let header = reader.headers()?.clone();
+        // The axis columns are followed by a `<metric> samples` column for the first metric.
+        let num_axes = header
+            .iter()
+            .position(|column| column.ends_with(" samples"))
+            .filter(|num_axes| (1..=2).contains(num_axes))
+            .ok_or_else(|| {
+                anyhow!(
+                    "Can't render {}: expected one or two axis columns followed by statistics",
+                    path.display()
+                )
+            })?;
+        let achieved_qps = header
+            .iter()
+            .position(|column| column == ACHIEVED_QPS_COLUMN)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Can't render {}: it has no {ACHIEVED_QPS_COLUMN} column",
+                    path.display()
+                )
+            })?;
+        let columns = (num_axes..achieved_qps)
+            .filter_map(|i| StatColumn::parse(i, &header[i]))
+            .collect::<Vec<_>>();
+        let metrics = columns
+            .iter()
+            .map(|column| column.metric.as_str())
+            .unique()
+            .collect::<Vec<_>>();
+        let quantiles = quantiles_from_labels(
+            columns
+                .iter()
+                .filter(|column| column.metric == metrics[0] && !column.stddev)
+                .map(|column| column.stat.as_str())
+                .filter(|stat| !STATS.contains(stat)),
+        )?;
+        // Everything after the achieved QPS that the sweep didn't write itself is a `--tag`.
+        let tag_columns = (achieved_qps + 1..header.len())
+            .filter(|i| {
+                let column = &header[*i];
+                ![TIMED_OUT_COLUMN, ATTEMPTS_COLUMN, TAG_COLUMN, ERROR_COLUMN].contains(&column)
+                    && !METADATA_COLUMNS.contains(&column)
+            })
+            .collect::<Vec<_>>();
+        let column = |name: &str| header.iter().position(|column| column == name);
+
+        let runs = reader
+            .records()
+            .enumerate()
+            .map(|(row, record)| {
+                let record = record?;
+                let mut run = json!({ "x_value": XValue::infer(&record[0]).to_json() });
+                if num_axes == 2 {
+                    run["z_value"] = json_value(&record[1]);
+                }
+                match column(ERROR_COLUMN).map(|i| &record[i]) {
+                    Some(error) if !error.is_empty() => run["error"] = json!(error),
+                    _ => {
+                        run["metrics"] = csv_metrics(&metrics, &columns, &record)
+                            .with_context(|| format!("In row {} of {}", row + 1, path.display()))?;
+                        let qps = record[achieved_qps].trim().parse::<f64>();
+                        if let Some(qps) = qps.ok().filter(|qps| qps.is_finite()) {
+                            run["achieved_qps"] = json_number(qps);
+                        }
+                    }
+                }
+                if column(TIMED_OUT_COLUMN).is_some_and(|i| &record[i] == "true") {
+                    run["timed_out"] = json!(true);
+                }
+                if let Some(i) = column(ATTEMPTS_COLUMN) {
+                    run["attempts"] = json_value(&record[i]);
+                }
+                let mut meta = serde_json::Map::new();
+                for name in METADATA_COLUMNS {
+                    if let Some(i) = column(name) {
+                        let value = match &record[i] {
+                            "" => serde_json::Value::Null,
+                            value if *name == "warmup_secs" || *name == "cooldown_ms" => {
+                                json_value(value)
+                            }
+                            value => json!(value),
+                        };
+                        meta.insert((*name).to_owned(), value);
+                    }
+                }
+                if let Some(i) = column(TAG_COLUMN) {
+                    meta.insert(TAG_COLUMN.to_owned(), json!(&record[i]));
+                }
+                if !meta.is_empty() {
+                    run["meta"] = meta.into();
+                }
+                if !tag_columns.is_empty() {
+                    run["tags"] = tag_columns
+                        .iter()
+                        .map(|i| (header[*i].to_owned(), json!(&record[*i])))
+                        .collect::<serde_json::Map<_, _>>()
+                        .into();
+                }
+                Ok(run)
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            x_axis: header[0].to_owned(),
+            z_axis: (num_axes == 2).then(|| header[1].to_owned()),
+            quantiles,
+            runs,
+        })
+    }
+
+    /// Returns the name of every metric in the results, in the order they're written.
+    pub fn metrics(&self) -> Vec<String> {
+        self.runs
+            .iter()
+            .filter_map(|run| run["metrics"].as_object())
+            .flat_map(|metrics| metrics.keys().cloned())
+            .unique()
+            .collect()
+    }
+
+    /// Removes every metric but `metrics` from the results. Returns an error if any of `metrics`
+    /// isn't in them.
+    pub fn retain_metrics(&mut self, metrics: &[String]) -> anyhow::Result<()> {
+        let existing = self.metrics();
+        if let Some(unknown) = metrics.iter().find(|metric| !existing.contains(metric)) {
+            bail!(
+                "Unknown metric '{unknown}': expected one of [{}]",
+                existing.join(", ")
+            );
+        }
+        for run in &mut self.runs {
+            if let Some(stats) = run.get_mut("metrics").and_then(|m| m.as_object_mut()) {
+                stats.retain(|metric, _| metrics.contains(metric));
+            }
+        }
+        Ok(())
+    }
+
+    /// Renders the results to `path`, as a graph or an HTML report depending on its extension.
+    /// With `log_y`, the y-axis of a graph is log-scaled if every value is positive.
+    pub fn render(&self, path: &Path, log_y: bool) -> anyhow::Result<()> {
+        match ResultsFormat::for_path(path, false)? {
+            ResultsFormat::Plot(_) => {
+                let mut plot = Plot::from_json(path, &self.runs, &self.metrics(), &self.quantiles)?;
+                plot.log_y = log_y;
+                plot.render(&self.x_axis, self.z_axis.as_deref())
+            }
+            ResultsFormat::Html if !log_y => {
+                let mut report = HtmlReport::new(path.to_owned());
+                report.runs = self.runs.clone();
+                report.write(&self.x_axis, self.z_axis.as_deref(), &self.quantiles)
+            }
+            ResultsFormat::Html => bail!("--log-y only applies to .png and .svg graphs"),
+            format => bail!(
+                "Can't render results as {format}: expected a .png, .svg or .html path, not {}",
+                path.display()
+            ),
+        }
+    }
+}
+
+/// A statistic column of a results CSV file, named `<metric> <stat>[ stddev][ (<unit>)]`.
+struct StatColumn {
+    index: usize,
+    metric: String,
+    stat: String,
+    /// Whether the column holds the standard deviation of the statistic, rather than its mean.
+    stddev: bool,
+    unit: Option<String>,
+}
+
+impl StatColumn {
+    /// Parses the column `name` at `index`, or returns `None` if it isn't a statistic, such as the
+    /// ratio columns of `--compare-upstream` sweeps.
+    fn parse(index: usize, name: &str) -> Option<Self> {
+        let (name, unit) = match name.strip_suffix(')').and_then(|n| n.rsplit_once(" (")) {
+            Some((name, unit)) => (name, Some(unit.to_owned())),
+            None => (name, None),
+        };
+        let (name, stddev) = match name.strip_suffix(" stddev") {
+            Some(name) => (name, true),
+            None => (name, false),
+        };
+        let (metric, stat) = name.rsplit_once(' ')?;
+        let is_quantile = stat
+            .strip_prefix('p')
+            .is_some_and(|percentile| percentile.parse::<f64>().is_ok());
+        (STATS.contains(&stat) || is_quantile).then(|| Self {
+            index,
+            metric: metric.to_owned(),
+            stat: stat.to_owned(),
+            stddev,
+            unit,
+        })
+    }
+}
+
+/// Returns the `"metrics"` object of a run from a row of a results CSV file, with the statistics
+/// of each of `metrics` in the same form as [`json_result`](crate::graph) writes them.
+fn csv_metrics(
+    metrics: &[&str],
+    columns: &[StatColumn],
+    record: &csv::StringRecord,
+) -> anyhow::Result<serde_json::Value> {
+    let mut json = serde_json::Map::new();
+    for metric in metrics {
+        let mut stats = json!({ "quantiles": {} });
+        for column in columns.iter().filter(|column| column.metric == *metric) {
+            let value = record[column.index].trim();
+            let value = value.parse::<f64>().map_err(|e| {
+                anyhow!("Invalid value '{value}' for {metric} {}: {e}", column.stat)
+            })?;
+            let stats = if column.stddev {
+                &mut stats["stddev"]
+            } else {
+                &mut stats
+            };
+            if STATS.contains(&column.stat.as_str()) {
+                stats[&column.stat] = json_number(value);
+            } else {
+                stats["quantiles"][&column.stat] = json_number(value);
+            }
+        }
+        // The first statistic is the sample count, which has no unit.
+        if let Some(unit) = columns
+            .iter()
+            .find(|column| column.metric == *metric && column.stat == "min")
+            .and_then(|column| column.unit.as_ref())
+        {
+            stats["unit"] = json!(unit);
+        }
+        json.insert((*metric).to_owned(), stats);
+    }
+    Ok(json.into())
+}
+
+/// Returns the quantiles with the given labels, such as `p99.9`, in any order.
+fn quantiles_from_labels<'a>(
+    labels: impl IntoIterator<Item = &'a str>,
+) -> anyhow::Result<Quantiles> {
+    let quantiles = labels
+        .into_iter()
+        .map(|label| {
+            label
+                .strip_prefix('p')
+                .and_then(|percentile| percentile.parse::<f64>().ok())
+                .map(|percentile| percentile / 100.0)
+                .ok_or_else(|| anyhow!("Invalid quantile '{label}'"))
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    if quantiles.is_empty() {
+        return Ok(Quantiles::default());
+    }
+    quantiles
+        .iter()
+        .sorted_by(|a, b| a.total_cmp(b))
+        .join(",")
+        .parse()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::results_sink;
+    use crate::test_utils::results;
+
+    /// Writes the same sweep to `path`, with latency and connect time for two x-values.
+    fn write_sweep(path: &Path) {
+        let mut writer = results_sink::open(path, "target-qps").unwrap();
+        for (x, latencies) in [("100", &[1, 2, 3]), ("200", &[40, 50, 600])] {
+            writer
+                .write_result(
+                    x,
+                    &results(&[("latency", latencies), ("connect", &[7, 8, 9])]),
+                )
+                .unwrap();
+        }
+        writer.finish().unwrap();
+    }
+
+    #[test]
+    fn render_matches_direct_output() {
+        let dir = tempfile::tempdir().unwrap();
+        for input in ["graph.csv", "graph.json", "graph.jsonl"] {
+            let input = dir.path().join(input);
+            write_sweep(&input);
+            let saved = SavedRuns::from_path(&input).unwrap();
+            assert_eq!(saved.metrics(), ["connect", "latency"]);
+            assert_eq!(saved.runs.len(), 2);
+
+            for output in ["graph.html", "graph.svg"] {
+                let direct = dir.path().join(format!("direct-{output}"));
+                write_sweep(&direct);
+                let rendered = dir.path().join(format!("rendered-{output}"));
+                saved.render(&rendered, false).unwrap();
+                let rendered = std::fs::read_to_string(rendered).unwrap();
+                // JSON results don't record the name of the x-axis.
+                let direct = match input.extension().unwrap().to_str() {
+                    Some("csv") => std::fs::read_to_string(direct).unwrap(),
+                    _ => std::fs::read_to_string(direct)
+                        .unwrap()
+                        .replace("target-qps", "x_value"),
+                };
+                assert_eq!(rendered, direct, "{} as {output}", input.display());
+            }
+        }
+    }
+
+    #[test]
+    fn csv_with_tags_and_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.csv");
+        std::fs::write(
+            &path,
+            "target-qps,workers,latency samples,latency min (us),latency max (us),\
+             latency mean (us),latency p50 (us),latency p99 (us),latency p50 ratio,\
+             achieved_qps,timed_out,tag,host,error\n\
+             100,1,3,1,3,2,2,3,0.5,98.5,false,main,a,\n\
+             200,1,3,4,6,5,5,6,0.5,,true,main,a,\n\
+             300,1,0,0,0,0,0,0,0,,false,main,a,connection refused\n",
+        )
+        .unwrap();
+        let saved = SavedRuns::from_path(&path).unwrap();
+        assert_eq!(saved.x_axis, "target-qps");
+        assert_eq!(saved.z_axis.as_deref(), Some("workers"));
+        assert_eq!(saved.quantiles.to_string(), "0.5,0.99");
+        assert_eq!(
+            saved.runs[0],
+            json!({
+                "x_value": 100,
+                "z_value": 1,
+                "metrics": {
+                    "latency": {
+                        "samples": 3,
+                        "min": 1,
+                        "max": 3,
+                        "mean": 2,
+                        "quantiles": { "p50": 2, "p99": 3 },
+                        "unit": "us",
+                    },
+                },
+                "achieved_qps": 98.5,
+                "meta": { "tag": "main" },
+                "tags": { "host": "a" },
+            })
+        );
+        assert_eq!(saved.runs[1]["timed_out"], json!(true));
+        assert!(saved.runs[1].get("achieved_qps").is_none());
+        assert_eq!(saved.runs[2]["error"], json!("connection refused"));
+        assert!(saved.runs[2].get("metrics").is_none());
+
+        // Failed runs aren't drawn.
+        let output = dir.path().join("graph.svg");
+        saved.render(&output, true).unwrap();
+        let svg = std::fs::read_to_string(output).unwrap();
+        assert!(svg.contains("latency p99 (workers = 1)"), "{svg}");
+    }
+
+    #[test]
+    fn render_some_metrics() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("graph.csv");
+        write_sweep(&input);
+        let output = dir.path().join("graph.svg");
+        RenderParams {
+            render: Some(input.clone()),
+            render_output: Some(output.clone()),
+            render_metrics: Some("latency".parse().unwrap()),
+            log_y: false,
+        }
+        .run()
+        .unwrap();
+        let svg = std::fs::read_to_string(output).unwrap();
+        assert!(svg.contains("latency mean"));
+        assert!(!svg.contains("connect mean"));
+
+        let mut saved = SavedRuns::from_path(&input).unwrap();
+        let err = saved
+            .retain_metrics(&["throughput".to_owned()])
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Unknown metric 'throughput': expected one of [connect, latency]"
+        );
+    }
+
+    #[test]
+    fn invalid_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("graph.csv");
+        write_sweep(&input);
+        let saved = SavedRuns::from_path(&input).unwrap();
+
+        let err = saved
+            .render(&dir.path().join("graph.json"), false)
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected a .png, .svg or .html path"));
+        let err = saved
+            .render(&dir.path().join("graph.html"), true)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "--log-y only applies to .png and .svg graphs"
+        );
+        let err = SavedRuns::from_path(&dir.path().join("graph.svg")).unwrap_err();
+        assert!(err.to_string().contains("can't be read back"), "{err}");
+    }
+}
@@ -0,0 +1,624 @@
+//! Saving the results of a benchmark run to a JSON file with `--save-results`, and printing a
+//! saved file as a human-readable report with `--format-report`.
+//!
+//! Results saved with a `--baseline-tag` get the tag added to their file name, so that the results
+//! of several branches or configurations can be shown side by side with `--compare-tags`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{bail, Context};
+use clap::{Parser, ValueHint};
+use console::{style, Term};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::benchmark::{BenchmarkData, BenchmarkResults, MetricGoal};
+use crate::compare::Delta;
+use crate::Quantiles;
+
+/// The statistics shown in a report, in column order.
+pub const REPORT_STATS: &[&str] = &[
+    "min", "p50", "p95", "p99", "p99.9", "max", "mean", "samples",
+];
+
+#[derive(Parser, Clone, Debug)]
+pub struct FormatReportParams {
+    /// Print a results file saved with `--save-results` as a table, instead of running a
+    /// benchmark.
+    #[arg(long, value_hint = ValueHint::FilePath)]
+    pub format_report: Option<PathBuf>,
+
+    /// The column to sort the report by: `metric`, which sorts by name, or one of the reported
+    /// statistics, which sorts from largest to smallest.
+    #[arg(long, requires = "format_report", default_value = "metric")]
+    pub sort_by: SortColumn,
+
+    /// A results file saved with `--save-results` to compare the report with. Each statistic is
+    /// shown alongside its baseline value and the absolute and percentage change from it.
+    #[arg(long = "compare", requires = "format_report", value_hint = ValueHint::FilePath)]
+    pub compare: Option<PathBuf>,
+
+    /// Comma-separated tags of results saved with `--baseline-tag` to show side by side, instead
+    /// of a single report. The results for each tag are read from the `--format-report` path
+    /// with the tag added to its file name, e.g. `results-main.json` for `main`.
+    #[arg(
+        long,
+        value_delimiter = ',',
+        requires = "format_report",
+        conflicts_with = "compare"
+    )]
+    pub compare_tags: Vec<Tag>,
+}
+
+impl FormatReportParams {
+    /// Returns whether a report was requested, rather than a benchmark run.
+    pub fn is_enabled(&self) -> bool {
+        self.format_report.is_some()
+    }
+
+    /// Prints the report for `--format-report`. Regressions and improvements relative to
+    /// `--compare` are colored if stdout is a terminal.
+    pub fn run(&self) -> anyhow::Result<()> {
+        let Some(path) = &self.format_report else {
+            bail!("--format-report must be set");
+        };
+        if !self.compare_tags.is_empty() {
+            if self.compare_tags.len() < 2 {
+                bail!("--compare-tags needs at least two tags to compare");
+            }
+            print!("{}", TagComparison::load(path, &self.compare_tags)?);
+            return Ok(());
+        }
+        let report = Report {
+            results: SavedResults::from_path(path)?,
+            baseline: self
+                .compare
+                .as_deref()
+                .map(SavedResults::from_path)
+                .transpose()?,
+            sort_by: self.sort_by.clone(),
+            colored: Term::stdout().is_term(),
+        };
+        print!("{report}");
+        Ok(())
+    }
+}
+
+/// A name for a set of results, such as the branch or configuration that was benchmarked, given
+/// by `--baseline-tag`. Tags are non-empty and contain only ASCII letters, digits and dashes, so
+/// they can be used in file names as-is.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Tag(String);
+
+impl Tag {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns `path` with `-<tag>` added to the end of its file name, before the extension, e.g.
+    /// `results-main.json` for `results.json`.
+    pub fn tagged_path(&self, path: &Path) -> PathBuf {
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(extension) => format!("{stem}-{}.{}", self.0, extension.to_string_lossy()),
+            None => format!("{stem}-{}", self.0),
+        };
+        path.with_file_name(name)
+    }
+}
+
+impl FromStr for Tag {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            bail!("Tags must not be empty");
+        }
+        if !s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+            bail!("Invalid tag '{s}': tags may only contain letters, digits and dashes");
+        }
+        Ok(Self(s.to_owned()))
+    }
+}
+
+impl TryFrom<String> for Tag {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Tag> for String {
+    fn from(tag: Tag) -> Self {
+        tag.0
+    }
+}
+
+impl Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The column a [`Report`] is sorted by.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SortColumn {
+    Metric,
+    Stat(&'static str),
+}
+
+impl FromStr for SortColumn {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s == "metric" {
+            return Ok(Self::Metric);
+        }
+        match REPORT_STATS.iter().find(|stat| **stat == s) {
+            Some(stat) => Ok(Self::Stat(stat)),
+            None => bail!(
+                "Unknown column '{s}'; expected one of metric, {}",
+                REPORT_STATS.join(", ")
+            ),
+        }
+    }
+}
+
+/// The summary statistics of a single metric in a [`SavedResults`] file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MetricSummary {
+    pub unit: String,
+    pub goal: MetricGoal,
+    pub samples: u64,
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    /// The value at each of the `--quantiles` the results were saved with, by name.
+    pub quantiles: BTreeMap<String, u64>,
+}
+
+impl MetricSummary {
+    fn from_data(data: &BenchmarkData, quantiles: &Quantiles) -> Self {
+        let hist = &data.values;
+        Self {
+            unit: data.unit.clone(),
+            goal: data.desired_action,
+            samples: hist.len(),
+            min: hist.min(),
+            max: hist.max(),
+            mean: hist.mean(),
+            quantiles: quantiles
+                .iter()
+                .map(|(name, quantile)| (name.to_owned(), hist.value_at_quantile(quantile)))
+                .collect(),
+        }
+    }
+
+    /// Returns the value of one of [`REPORT_STATS`], or `None` if it isn't a statistic of this
+    /// metric.
+    pub fn value(&self, stat: &str) -> Option<f64> {
+        match stat {
+            "samples" => Some(self.samples as f64),
+            "min" => Some(self.min as f64),
+            "max" => Some(self.max as f64),
+            "mean" => Some(self.mean),
+            quantile => self.quantiles.get(quantile).map(|v| *v as f64),
+        }
+    }
+}
+
+/// The results of a benchmark run, as saved with `--save-results`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SavedResults {
+    /// The `--baseline-tag` the results were saved with, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<Tag>,
+    /// How long the run took, if it was recorded.
+    pub elapsed_secs: Option<f64>,
+    pub metrics: BTreeMap<String, MetricSummary>,
+}
+
+impl SavedResults {
+    /// Summarizes each metric in `results`, including its value at each of `quantiles`.
+    pub fn from_results(results: &BenchmarkResults, quantiles: &Quantiles) -> Self {
+        Self {
+            tag: None,
+            elapsed_secs: results.elapsed.map(|elapsed| elapsed.as_secs_f64()),
+            metrics: results
+                .results
+                .iter()
+                .map(|(metric, data)| (metric.clone(), MetricSummary::from_data(data, quantiles)))
+                .collect(),
+        }
+    }
+
+    /// Reads results saved to `path` by [`Self::save`].
+    pub fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        serde_json::from_reader(BufReader::new(file))
+            .with_context(|| format!("Failed to read results from {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// A table of the [`REPORT_STATS`] of each metric in a [`SavedResults`], optionally compared with
+/// a baseline.
+pub struct Report {
+    pub results: SavedResults,
+    pub baseline: Option<SavedResults>,
+    pub sort_by: SortColumn,
+    /// Whether to color regressions red and improvements green.
+    pub colored: bool,
+}
+
+impl Report {
+    /// Returns the metrics of the results in the order they are reported.
+    fn sorted_metrics(&self) -> Vec<(&String, &MetricSummary)> {
+        let metrics = self.results.metrics.iter();
+        match &self.sort_by {
+            SortColumn::Metric => metrics.collect(),
+            SortColumn::Stat(stat) => metrics
+                .sorted_by(|(_, a), (_, b)| {
+                    let (a, b) = (a.value(stat), b.value(stat));
+                    b.partial_cmp(&a).unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .collect(),
+        }
+    }
+
+    /// Styles `value` by `change`: red if it is worse, green if it is better. The sample count
+    /// has no goal, so it is never styled.
+    fn style_change(&self, value: String, change: &Change) -> String {
+        if !self.colored || change.stat == "samples" || change.delta == 0.0 {
+            return value;
+        }
+        let worse = match change.goal {
+            MetricGoal::Increasing => change.delta < 0.0,
+            MetricGoal::Decreasing => change.delta > 0.0,
+        };
+        let value = style(value).force_styling(true);
+        if worse {
+            value.red().to_string()
+        } else {
+            value.green().to_string()
+        }
+    }
+}
+
+/// The change in a statistic of a metric compared with the baseline.
+struct Change {
+    stat: &'static str,
+    goal: MetricGoal,
+    delta: f64,
+}
+
+/// The index of the first of the delta columns of a comparison, which are styled by [`Change`].
+const DELTA_COLUMNS: usize = 4;
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rows: Vec<Vec<String>> = Vec::new();
+        // The change in each row compared with the baseline, which its delta columns are styled by.
+        let mut changes: Vec<Option<Change>> = Vec::new();
+        match &self.baseline {
+            None => {
+                rows.push(
+                    std::iter::once("metric")
+                        .chain(REPORT_STATS.iter().copied())
+                        .map(str::to_owned)
+                        .collect(),
+                );
+                for (metric, summary) in self.sorted_metrics() {
+                    rows.push(
+                        std::iter::once(metric.clone())
+                            .chain(REPORT_STATS.iter().map(|stat| {
+                                summary.value(stat).map(format_value).unwrap_or_default()
+                            }))
+                            .collect(),
+                    );
+                }
+            }
+            Some(baseline) => {
+                rows.push(
+                    ["metric", "stat", "baseline", "value", "delta", "delta %"]
+                        .map(str::to_owned)
+                        .to_vec(),
+                );
+                changes.push(None);
+                for (metric, summary) in self.sorted_metrics() {
+                    let Some(base) = baseline.metrics.get(metric) else {
+                        continue;
+                    };
+                    for stat in REPORT_STATS {
+                        let (Some(candidate), Some(baseline)) =
+                            (summary.value(stat), base.value(stat))
+                        else {
+                            continue;
+                        };
+                        let delta = Delta {
+                            baseline,
+                            candidate,
+                        };
+                        rows.push(vec![
+                            metric.clone(),
+                            (*stat).to_owned(),
+                            format_value(baseline),
+                            format_value(candidate),
+                            format_delta(delta.absolute()),
+                            format!("{:+.1}%", delta.percent()),
+                        ]);
+                        changes.push(Some(Change {
+                            stat,
+                            goal: summary.goal,
+                            delta: delta.absolute(),
+                        }));
+                    }
+                }
+            }
+        }
+
+        write_table(f, &rows, |row, column, value| match changes.get(row) {
+            Some(Some(change)) if column >= DELTA_COLUMNS => self.style_change(value, change),
+            _ => value,
+        })?;
+
+        if let Some(baseline) = &self.baseline {
+            for metric in baseline.metrics.keys() {
+                if !self.results.metrics.contains_key(metric) {
+                    writeln!(f, "Only in baseline: {metric}")?;
+                }
+            }
+            for metric in self.results.metrics.keys() {
+                if !baseline.metrics.contains_key(metric) {
+                    writeln!(f, "Only in results: {metric}")?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A side-by-side table of the [`REPORT_STATS`] of each metric in the results saved with several
+/// tags, with a column per tag in the order given.
+pub struct TagComparison {
+    pub results: Vec<(Tag, SavedResults)>,
+}
+
+impl TagComparison {
+    /// Reads the results saved for each of `tags`, at `path` with the tag added to its file name.
+    pub fn load(path: &Path, tags: &[Tag]) -> anyhow::Result<Self> {
+        let results = tags
+            .iter()
+            .map(|tag| {
+                Ok((
+                    tag.clone(),
+                    SavedResults::from_path(&tag.tagged_path(path))?,
+                ))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { results })
+    }
+}
+
+impl Display for TagComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rows = vec![["metric", "stat"]
+            .into_iter()
+            .map(str::to_owned)
+            .chain(self.results.iter().map(|(tag, _)| tag.to_string()))
+            .collect::<Vec<_>>()];
+        let metrics = self
+            .results
+            .iter()
+            .flat_map(|(_, results)| results.metrics.keys())
+            .collect::<BTreeSet<_>>();
+        for metric in metrics {
+            for stat in REPORT_STATS {
+                let values = self
+                    .results
+                    .iter()
+                    .map(|(_, results)| results.metrics.get(metric)?.value(stat))
+                    .collect::<Vec<_>>();
+                if values.iter().all(Option::is_none) {
+                    continue;
+                }
+                rows.push(
+                    [metric.clone(), (*stat).to_owned()]
+                        .into_iter()
+                        .chain(
+                            values
+                                .into_iter()
+                                .map(|value| value.map(format_value).unwrap_or_default()),
+                        )
+                        .collect(),
+                );
+            }
+        }
+        write_table(f, &rows, |_, _, value| value)
+    }
+}
+
+/// Writes `rows` as a table with aligned columns, passing each padded value through `style` along
+/// with its row and column index. Values are padded before styling so that escape codes don't
+/// affect column widths.
+pub(crate) fn write_table(
+    f: &mut fmt::Formatter<'_>,
+    rows: &[Vec<String>],
+    style: impl Fn(usize, usize, String) -> String,
+) -> fmt::Result {
+    let widths = (0..rows.first().map_or(0, Vec::len))
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].len())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect::<Vec<_>>();
+    for (i, row) in rows.iter().enumerate() {
+        let line = row
+            .iter()
+            .zip(&widths)
+            .enumerate()
+            .map(|(column, (value, width))| style(i, column, format!("{value:width$}")))
+            .join("  ");
+        writeln!(f, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// Formats whole numbers without a fractional part, and anything else to two decimal places.
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{value}")
+    } else {
+        format!("{value:.2}")
+    }
+}
+
+/// Formats a change in the same way as [`format_value`], with a leading sign.
+fn format_delta(delta: f64) -> String {
+    let sign = if delta >= 0.0 { "+" } else { "" };
+    format!("{sign}{}", format_value(delta))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::test_utils::results;
+
+    #[test]
+    fn save_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.json");
+        let mut results = results(&[("latency", &[1, 2, 3, 4])]);
+        results.record_elapsed_time(Duration::from_secs(2));
+
+        let saved = SavedResults::from_results(&results, &Quantiles::default());
+        saved.save(&path).unwrap();
+        let read = SavedResults::from_path(&path).unwrap();
+        assert_eq!(read, saved);
+        assert_eq!(read.elapsed_secs, Some(2.0));
+        let latency = &read.metrics["latency"];
+        assert_eq!(latency.samples, 4);
+        assert_eq!(latency.value("max"), Some(4.0));
+        assert_eq!(latency.value("mean"), Some(2.5));
+        assert_eq!(latency.value("p50"), Some(2.0));
+        assert_eq!(latency.value("p12"), None);
+    }
+
+    #[test]
+    fn parse_tags() {
+        assert_eq!("main".parse::<Tag>().unwrap().as_str(), "main");
+        assert_eq!(" pr-1234 ".parse::<Tag>().unwrap().as_str(), "pr-1234");
+        for (s, expected) in [
+            ("", "must not be empty"),
+            ("  ", "must not be empty"),
+            ("feature/x", "Invalid tag 'feature/x'"),
+            ("a b", "Invalid tag 'a b'"),
+            ("v1.2", "Invalid tag 'v1.2'"),
+        ] {
+            let err = s.parse::<Tag>().unwrap_err();
+            assert!(err.to_string().contains(expected), "{s}: {err}");
+        }
+    }
+
+    #[test]
+    fn tagged_paths() {
+        let tag = "main".parse::<Tag>().unwrap();
+        assert_eq!(
+            tag.tagged_path(Path::new("out/results.json")),
+            Path::new("out/results-main.json")
+        );
+        assert_eq!(
+            tag.tagged_path(Path::new("results")),
+            Path::new("results-main")
+        );
+    }
+
+    #[test]
+    fn save_and_read_tagged() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("results.json");
+        let mut saved =
+            SavedResults::from_results(&results(&[("latency", &[1, 2])]), &Quantiles::default());
+        saved.tag = Some("main".parse().unwrap());
+        saved.save(&path).unwrap();
+        assert_eq!(SavedResults::from_path(&path).unwrap(), saved);
+
+        std::fs::write(
+            &path,
+            r#"{"tag": "not/valid", "elapsed_secs": null, "metrics": {}}"#,
+        )
+        .unwrap();
+        let err = SavedResults::from_path(&path).unwrap_err();
+        assert!(format!("{err:#}").contains("Invalid tag"), "{err:#}");
+    }
+
+    #[test]
+    fn parse_sort_column() {
+        assert_eq!("metric".parse::<SortColumn>().unwrap(), SortColumn::Metric);
+        assert_eq!(
+            "p99.9".parse::<SortColumn>().unwrap(),
+            SortColumn::Stat("p99.9")
+        );
+        let err = "p12".parse::<SortColumn>().unwrap_err().to_string();
+        assert!(err.contains("'p12'"), "{err}");
+    }
+
+    #[test]
+    fn colors_regressions_and_improvements() {
+        // Lower latency is better, and higher throughput is.
+        let saved = |value| {
+            let mut results = results(&[("latency", &[value]), ("throughput", &[value])]);
+            results
+                .results
+                .get_mut("throughput")
+                .unwrap()
+                .desired_action = MetricGoal::Increasing;
+            SavedResults::from_results(&results, &Quantiles::default())
+        };
+        let baseline = saved(10);
+        let candidate = saved(20);
+        let report = Report {
+            results: candidate,
+            baseline: Some(baseline),
+            sort_by: SortColumn::Metric,
+            colored: true,
+        };
+        let output = report.to_string();
+        let line = |metric: &str, stat: &str| {
+            output
+                .lines()
+                .find(|line| {
+                    let line = console::strip_ansi_codes(line);
+                    let mut columns = line.split_whitespace();
+                    columns.next() == Some(metric) && columns.next() == Some(stat)
+                })
+                .unwrap()
+                .to_owned()
+        };
+        // Red for a latency increase, green for a throughput increase.
+        assert!(line("latency", "p50").contains("\u{1b}[31m"), "{output}");
+        assert!(line("throughput", "p50").contains("\u{1b}[32m"), "{output}");
+        // The sample count didn't change, and has no goal anyway.
+        assert!(!line("latency", "samples").contains('\u{1b}'), "{output}");
+    }
+}
@@ -0,0 +1,389 @@
+//! Sampling the CPU utilization and resident memory of the benchmark process, and optionally of
+//! another process such as the ReadySet server being benchmarked, while each run of a `--graph`
+//! sweep executes with `--resource-usage`.
+//!
+//! Processes are sampled by reading `/proc`, so sampling is only supported on Linux. Elsewhere,
+//! [`ResourceSampler::start`] returns an error, and [`SUPPORTED`] is false so that the sweep can
+//! reject `--resource-usage` before anything is run.
+
+use std::time::Duration;
+
+use anyhow::bail;
+use serde::Serialize;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::warn;
+
+/// Whether resource usage can be sampled on this platform.
+pub const SUPPORTED: bool = cfg!(target_os = "linux");
+
+/// The total CPU time used by a process and its resident memory at some point in time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct ProcessStats {
+    cpu_time: Duration,
+    rss_bytes: u64,
+}
+
+#[cfg(target_os = "linux")]
+mod procfs {
+    use std::time::Duration;
+
+    use anyhow::{anyhow, Context};
+
+    use super::ProcessStats;
+
+    /// The units of the CPU times in `/proc/<pid>/stat`. The kernel always reports them in
+    /// `USER_HZ`, which is 100 on every architecture Linux supports.
+    const CLOCK_TICKS_PER_SEC: u64 = 100;
+
+    /// Reads the CPU time and resident memory of the process `pid` from `/proc`.
+    pub(super) fn read(pid: u32) -> anyhow::Result<ProcessStats> {
+        let stat = std::fs::read_to_string(format!("/proc/{pid}/stat"))
+            .with_context(|| format!("Failed to read the CPU time of process {pid}"))?;
+        let status = std::fs::read_to_string(format!("/proc/{pid}/status"))
+            .with_context(|| format!("Failed to read the memory usage of process {pid}"))?;
+        Ok(ProcessStats {
+            cpu_time: parse_cpu_time(&stat)
+                .ok_or_else(|| anyhow!("Invalid /proc/{pid}/stat: {stat}"))?,
+            rss_bytes: parse_rss_bytes(&status)
+                .ok_or_else(|| anyhow!("/proc/{pid}/status has no valid VmRSS"))?,
+        })
+    }
+
+    /// Returns the user and system CPU time of a process from the contents of its
+    /// `/proc/<pid>/stat`. The fields are counted from after the parenthesized command name,
+    /// which may itself contain spaces and parentheses.
+    fn parse_cpu_time(stat: &str) -> Option<Duration> {
+        let (_, fields) = stat.rsplit_once(')')?;
+        let mut fields = fields.split_whitespace().skip(11);
+        let utime = fields.next()?.parse::<u64>().ok()?;
+        let stime = fields.next()?.parse::<u64>().ok()?;
+        Some(Duration::from_millis(
+            (utime + stime) * 1000 / CLOCK_TICKS_PER_SEC,
+        ))
+    }
+
+    /// Returns the resident memory of a process from the `VmRSS` line of its
+    /// `/proc/<pid>/status`, which is given in kilobytes.
+    fn parse_rss_bytes(status: &str) -> Option<u64> {
+        let line = status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))?;
+        let kb = line.trim().strip_suffix("kB")?.trim().parse::<u64>().ok()?;
+        Some(kb * 1024)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn parse_stat_and_status() {
+            let stat = "1234 (my (odd) bench) S 1 1234 1234 0 -1 4194560 2030 0 0 0 250 125 0 \
+                        0 20 0 8 0 12345 1000000 500 18446744073709551615";
+            assert_eq!(parse_cpu_time(stat), Some(Duration::from_millis(3750)));
+            assert_eq!(parse_cpu_time("1234 (bench) S 1"), None);
+
+            let status = "Name:\tbench\nVmPeak:\t  20480 kB\nVmRSS:\t   10240 kB\nThreads:\t8\n";
+            assert_eq!(parse_rss_bytes(status), Some(10240 * 1024));
+            assert_eq!(parse_rss_bytes("Name:\tkthreadd\n"), None);
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod procfs {
+    use super::ProcessStats;
+
+    pub(super) fn read(_pid: u32) -> anyhow::Result<ProcessStats> {
+        anyhow::bail!("Sampling resource usage is only supported on Linux")
+    }
+}
+
+/// The resource usage of a process over one sampling interval.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub struct ResourceSample {
+    /// When the sample was taken, in milliseconds since sampling started.
+    pub elapsed_ms: u64,
+    /// The CPU time the process used since the previous sample, as a percentage of the time
+    /// between them. A process using several cores at once can exceed 100%.
+    pub cpu_percent: f64,
+    /// The resident memory of the process when the sample was taken.
+    pub rss_bytes: u64,
+}
+
+/// The samples of the resource usage of a single process over a run.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ProcessUsage {
+    pub samples: Vec<ResourceSample>,
+}
+
+impl ProcessUsage {
+    /// The mean CPU utilization across the samples, in percent, or NaN if there are none.
+    pub fn mean_cpu_percent(&self) -> f64 {
+        let total = self
+            .samples
+            .iter()
+            .map(|sample| sample.cpu_percent)
+            .sum::<f64>();
+        total / self.samples.len() as f64
+    }
+
+    /// The highest CPU utilization of any sample, in percent, or NaN if there are none.
+    pub fn max_cpu_percent(&self) -> f64 {
+        self.samples
+            .iter()
+            .map(|sample| sample.cpu_percent)
+            .reduce(f64::max)
+            .unwrap_or(f64::NAN)
+    }
+
+    /// The highest resident memory of any sample, or `None` if there are none.
+    pub fn max_rss_bytes(&self) -> Option<u64> {
+        self.samples.iter().map(|sample| sample.rss_bytes).max()
+    }
+
+    /// Returns the mean and max CPU utilization and the max resident memory, in the order of
+    /// [`RESOURCE_USAGE_COLUMNS`](crate::results_sink::RESOURCE_USAGE_COLUMNS).
+    pub fn summary(&self) -> [f64; 3] {
+        [
+            self.mean_cpu_percent(),
+            self.max_cpu_percent(),
+            self.max_rss_bytes().map_or(f64::NAN, |rss| rss as f64),
+        ]
+    }
+}
+
+/// The resource usage sampled over a run.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ResourceUsage {
+    /// The usage of the benchmark process itself.
+    pub benchmark: ProcessUsage,
+    /// The usage of the process given by `--resource-usage-pid`, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<ProcessUsage>,
+}
+
+impl ResourceUsage {
+    /// Combines the usage sampled over two runs that happened one after the other, such as the
+    /// iterations of a single run of a sweep.
+    pub fn merge(mut self, other: ResourceUsage) -> ResourceUsage {
+        self.benchmark.samples.extend(other.benchmark.samples);
+        self.target = match (self.target, other.target) {
+            (Some(mut a), Some(b)) => {
+                a.samples.extend(b.samples);
+                Some(a)
+            }
+            (a, b) => a.or(b),
+        };
+        self
+    }
+}
+
+/// A process being sampled, along with its stats as of the previous sample.
+struct Sampled {
+    pid: u32,
+    previous: ProcessStats,
+    previous_at: Instant,
+    usage: ProcessUsage,
+    /// Whether the process could no longer be read, such as because it exited, in which case
+    /// it isn't sampled any more.
+    stopped: bool,
+}
+
+impl Sampled {
+    fn start(pid: u32, now: Instant) -> anyhow::Result<Self> {
+        Ok(Self {
+            pid,
+            previous: procfs::read(pid)?,
+            previous_at: now,
+            usage: ProcessUsage::default(),
+            stopped: false,
+        })
+    }
+
+    /// Records a sample of the process's usage since the previous sample, unless it can no
+    /// longer be read or no time has passed.
+    fn sample(&mut self, started_at: Instant) {
+        if self.stopped {
+            return;
+        }
+        let now = Instant::now();
+        let interval = now.duration_since(self.previous_at);
+        if interval.is_zero() {
+            return;
+        }
+        let stats = match procfs::read(self.pid) {
+            Ok(stats) => stats,
+            Err(e) => {
+                warn!(
+                    "Stopped sampling the resource usage of process {}: {e:#}",
+                    self.pid
+                );
+                self.stopped = true;
+                return;
+            }
+        };
+        let cpu_time = stats.cpu_time.saturating_sub(self.previous.cpu_time);
+        self.usage.samples.push(ResourceSample {
+            elapsed_ms: now.duration_since(started_at).as_millis() as u64,
+            cpu_percent: cpu_time.as_secs_f64() / interval.as_secs_f64() * 100.0,
+            rss_bytes: stats.rss_bytes,
+        });
+        self.previous = stats;
+        self.previous_at = now;
+    }
+}
+
+/// A background task sampling the resource usage of the benchmark process, and optionally of
+/// another process, at a fixed interval. A last sample is taken when it's [stopped](Self::stop),
+/// so that runs shorter than the interval are still sampled. Dropping the sampler, such as when
+/// a run times out and is cancelled, stops the task without waiting for it.
+pub struct ResourceSampler {
+    stop: Option<oneshot::Sender<()>>,
+    task: JoinHandle<ResourceUsage>,
+}
+
+impl ResourceSampler {
+    /// Starts sampling the current process, and the process `target_pid` if given, every
+    /// `interval`. Returns an error if either process can't be sampled, or if sampling isn't
+    /// [supported](SUPPORTED) on this platform.
+    pub fn start(target_pid: Option<u32>, interval: Duration) -> anyhow::Result<Self> {
+        if interval.is_zero() {
+            bail!("The resource usage sampling interval must be greater than zero");
+        }
+        let started_at = Instant::now();
+        let mut benchmark = Sampled::start(std::process::id(), started_at)?;
+        let mut target = target_pid
+            .map(|pid| Sampled::start(pid, started_at))
+            .transpose()?;
+        let (stop, mut stopped) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            let mut ticks = tokio::time::interval_at(started_at + interval, interval);
+            loop {
+                tokio::select! {
+                    _ = &mut stopped => break,
+                    _ = ticks.tick() => {}
+                }
+                benchmark.sample(started_at);
+                if let Some(target) = &mut target {
+                    target.sample(started_at);
+                }
+            }
+            benchmark.sample(started_at);
+            if let Some(target) = &mut target {
+                target.sample(started_at);
+            }
+            ResourceUsage {
+                benchmark: benchmark.usage,
+                target: target.map(|target| target.usage),
+            }
+        });
+        Ok(Self {
+            stop: Some(stop),
+            task,
+        })
+    }
+
+    /// Takes a last sample and returns every sample taken since the sampler started.
+    pub async fn stop(mut self) -> anyhow::Result<ResourceUsage> {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        Ok((&mut self.task).await?)
+    }
+}
+
+impl Drop for ResourceSampler {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    /// Keeps a core busy for `duration`.
+    fn spin(duration: Duration) -> u64 {
+        let start = std::time::Instant::now();
+        let mut n = 0u64;
+        while start.elapsed() < duration {
+            n = std::hint::black_box(n.wrapping_mul(31).wrapping_add(7));
+        }
+        n
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn samples_the_test_process() {
+        let sampler = ResourceSampler::start(None, Duration::from_millis(50)).unwrap();
+        tokio::task::spawn_blocking(|| spin(Duration::from_millis(300)))
+            .await
+            .unwrap();
+        let usage = sampler.stop().await.unwrap();
+
+        assert!(usage.target.is_none());
+        let samples = &usage.benchmark.samples;
+        assert!(samples.len() >= 3, "{samples:?}");
+        assert!(samples
+            .windows(2)
+            .all(|w| w[0].elapsed_ms <= w[1].elapsed_ms));
+        assert!(usage.benchmark.max_rss_bytes().unwrap() > 0);
+        let [mean_cpu, max_cpu, max_rss] = usage.benchmark.summary();
+        assert!(
+            mean_cpu > 0.0 && mean_cpu <= max_cpu,
+            "{mean_cpu} {max_cpu}"
+        );
+        assert!(max_rss > 0.0);
+    }
+
+    #[tokio::test]
+    async fn samples_a_target_process() {
+        let sampler =
+            ResourceSampler::start(Some(std::process::id()), Duration::from_secs(60)).unwrap();
+        spin(Duration::from_millis(20));
+        let usage = sampler.stop().await.unwrap();
+        // Runs shorter than the interval still get the sample taken when the sampler stops.
+        assert_eq!(usage.benchmark.samples.len(), 1);
+        let target = usage.target.unwrap();
+        assert_eq!(target.samples.len(), 1);
+        assert!(target.samples[0].rss_bytes > 0);
+    }
+
+    #[tokio::test]
+    async fn invalid_target() {
+        // Process IDs are never larger than 2^22 on Linux.
+        let err = ResourceSampler::start(Some(u32::MAX), Duration::from_secs(1))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("process 4294967295"), "{err}");
+        ResourceSampler::start(None, Duration::ZERO).err().unwrap();
+    }
+
+    #[test]
+    fn merge_and_summarize() {
+        let sample = |cpu_percent, rss_bytes| ResourceSample {
+            elapsed_ms: 0,
+            cpu_percent,
+            rss_bytes,
+        };
+        let a = ResourceUsage {
+            benchmark: ProcessUsage {
+                samples: vec![sample(50.0, 100), sample(150.0, 300)],
+            },
+            target: None,
+        };
+        let b = ResourceUsage {
+            benchmark: ProcessUsage {
+                samples: vec![sample(100.0, 200)],
+            },
+            target: Some(ProcessUsage::default()),
+        };
+        let merged = a.merge(b);
+        assert_eq!(merged.benchmark.summary(), [100.0, 150.0, 300.0]);
+        let target = merged.target.unwrap();
+        assert!(target.summary().iter().all(|value| value.is_nan()));
+        assert_eq!(target.max_rss_bytes(), None);
+    }
+}
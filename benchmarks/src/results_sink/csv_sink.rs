@@ -0,0 +1,518 @@
+//! Writing the results of a sweep to a CSV file, a row per run.
+
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+
+use anyhow::bail;
+
+use super::row_format::{header_diff, Failure, RowFormat, RowOrder, RunMetadata, SinkOptions};
+use super::ResultsSink;
+use crate::benchmark::BenchmarkResults;
+use crate::graph::GraphRun;
+
+/// Formats a CSV row for `run`: its x-value, its z-value if there is a z-axis, each of `values`,
+/// and then each of the `text` columns.
+fn csv_record(
+    run: &GraphRun,
+    values: impl IntoIterator<Item = f64>,
+    text: impl IntoIterator<Item = String>,
+) -> Vec<String> {
+    std::iter::once(run.x_value.clone())
+        .chain(run.z_value().map(str::to_owned))
+        .chain(values.into_iter().map(|value| value.to_string()))
+        .chain(text)
+        .collect()
+}
+
+/// Writes `record` to a CSV file, recording it in `reorder` first if the runs are shuffled, and
+/// flushes it to disk unless the writer is `buffered`.
+fn write_csv_record(
+    writer: &mut csv::Writer<File>,
+    record: Vec<String>,
+    run: &GraphRun,
+    reorder: Option<&mut RowOrder>,
+    buffered: bool,
+) -> anyhow::Result<()> {
+    if let Some(reorder) = reorder {
+        writer.flush()?;
+        let mut bytes = csv::Writer::from_writer(Vec::new());
+        bytes.write_record(&record)?;
+        let bytes = bytes.into_inner().map_err(|e| e.into_error())?;
+        reorder.push_bytes(run, writer.get_ref(), bytes)?;
+    }
+    writer.write_record(record)?;
+    if !buffered {
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+impl GraphRun {
+    /// Returns the CSV row written for the repeated runs `results` of this run, laid out by
+    /// `format`: the x-value (and z-value, if any), the sample count, min, max, mean and each of
+    /// the quantiles of every metric, ordered as in [`metric_order`] and converted to the units
+    /// of their columns, each followed by its standard deviation if `format` has them, then any
+    /// ratios and resource usage, the achieved QPS, and finally the text columns, such as the
+    /// run's `metadata`. If `format` has been used to write results before, `results` must have
+    /// the same metrics as them.
+    pub fn to_csv_row(
+        &self,
+        results: &[BenchmarkResults],
+        format: &RowFormat,
+        metadata: Option<&RunMetadata>,
+    ) -> anyhow::Result<Vec<String>> {
+        let metadata = format.metadata(self, metadata)?;
+        let (results, columns) = format.columns(self, results)?;
+        let row = format.row(&columns, &results);
+        Ok(csv_record(
+            self,
+            format.values(&row),
+            format.text_columns(row.timed_out, row.attempts, metadata, ""),
+        ))
+    }
+}
+
+/// Writes each row of results to a CSV file as soon as it's written, after a header naming each
+/// column.
+pub(crate) struct CsvSink {
+    format: RowFormat,
+    writer: csv::Writer<File>,
+    /// The header of the existing CSV file being appended to, if any, which is written instead
+    /// of a new header.
+    existing_header: Option<Vec<String>>,
+    /// With `--shuffle-runs`, the rows written so far, to put back in the planned order of the
+    /// runs once the sink is finished.
+    reorder: Option<RowOrder>,
+    buffered: bool,
+}
+
+impl CsvSink {
+    /// Creates a sink that writes to `path`. When appending, the existing header of the file is
+    /// kept, and must match the header that would otherwise be written; a file that doesn't exist
+    /// yet or is empty is written from scratch.
+    pub(crate) fn new(
+        path: &Path,
+        format: RowFormat,
+        options: SinkOptions,
+    ) -> anyhow::Result<Self> {
+        let mut existing_header = None;
+        let writer = if options.append {
+            let file = OpenOptions::new().create(true).append(true).open(path)?;
+            if file.metadata()?.len() > 0 {
+                let mut reader = csv::Reader::from_path(path)?;
+                existing_header = Some(reader.headers()?.iter().map(str::to_owned).collect());
+            }
+            csv::Writer::from_writer(file)
+        } else {
+            csv::Writer::from_path(path)?
+        };
+        Ok(Self {
+            format,
+            writer,
+            existing_header,
+            reorder: options.planned_runs.map(RowOrder::new),
+            buffered: options.buffered,
+        })
+    }
+
+    /// Writes the header, followed by the rows of the runs that failed before it was known.
+    fn start(&mut self, failures: Vec<(GraphRun, Failure)>) -> anyhow::Result<()> {
+        let header = self.format.header();
+        match &self.existing_header {
+            Some(existing) if *existing == header => {}
+            Some(existing) => bail!(
+                "Can't append to a CSV file with a different header: {}",
+                header_diff(&header, existing)
+            ),
+            None => self.writer.write_record(header)?,
+        }
+        for (run, failure) in failures {
+            self.write_failed_row(&run, &failure)?;
+        }
+        Ok(())
+    }
+
+    fn write_failed_row(&mut self, run: &GraphRun, failure: &Failure) -> anyhow::Result<()> {
+        let record = csv_record(
+            run,
+            self.format.failed_values(),
+            self.format
+                .text_columns(failure.timed_out, failure.attempts, None, &failure.error),
+        );
+        write_csv_record(
+            &mut self.writer,
+            record,
+            run,
+            self.reorder.as_mut(),
+            self.buffered,
+        )
+    }
+}
+
+impl ResultsSink for CsvSink {
+    fn write_result(&mut self, x_value: &str, results: &BenchmarkResults) -> anyhow::Result<()> {
+        let run = self.format.run(x_value);
+        self.write_results(&run, std::slice::from_ref(results), None)
+    }
+
+    fn write_results(
+        &mut self,
+        run: &GraphRun,
+        results: &[BenchmarkResults],
+        metadata: Option<&RunMetadata>,
+    ) -> anyhow::Result<()> {
+        let record = run.to_csv_row(results, &self.format, metadata)?;
+        let (_, failures) = self.format.start_row(run, results)?;
+        if let Some(failures) = failures {
+            self.start(failures)?;
+        }
+        write_csv_record(
+            &mut self.writer,
+            record,
+            run,
+            self.reorder.as_mut(),
+            self.buffered,
+        )
+    }
+
+    fn write_failure(&mut self, run: &GraphRun, error: &anyhow::Error) -> anyhow::Result<()> {
+        match self.format.failure(run, error)? {
+            Some(failure) => self.write_failed_row(run, &failure),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        if let Some(failures) = self.format.finish_rows() {
+            self.start(failures)?;
+        }
+        if let Some(reorder) = &self.reorder {
+            self.writer.flush()?;
+            reorder.rewrite(self.writer.get_ref())?;
+        }
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::results_sink::row_format::stat_columns;
+    use crate::results_sink::ACHIEVED_QPS_COLUMN;
+    use crate::results_sink::METADATA_COLUMNS;
+    use crate::results_sink::RESOURCE_USAGE_COLUMNS;
+    use crate::results_sink::STATS;
+    use crate::Quantiles;
+    use itertools::Itertools;
+
+    use anyhow::anyhow;
+
+    use super::*;
+    use crate::results_sink;
+    use crate::test_utils::{metadata, params, results, results_with_usage, run, z_params};
+    use crate::QUANTILES;
+
+    #[test]
+    fn csv_with_z_axis() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = z_params("100,200", "workers", "1,2");
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        let mut writer = results_sink::from_params(&params).unwrap();
+        for run in params.runs() {
+            writer
+                .write_results(&run, &[results(&[("latency", &[1])])], None)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+        let header = reader.headers().unwrap().clone();
+        assert_eq!(&header[0], "target-qps");
+        assert_eq!(&header[1], "workers");
+        assert_eq!(&header[2], "latency samples");
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(rows.len(), 4);
+        assert_eq!((&rows[2][0], &rows[2][1]), ("100", "2"));
+        assert!(rows.iter().all(|row| row.len() == header.len()));
+    }
+
+    #[test]
+    fn csv_with_runs_per_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100,200");
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        params.runs_per_value = 3;
+        let mut writer = results_sink::from_params(&params).unwrap();
+        for run in params.runs() {
+            let repeated = [
+                results(&[("latency", &[10])]),
+                results(&[("latency", &[20])]),
+                results(&[("latency", &[30])]),
+            ];
+            writer.write_results(&run, &repeated, None).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+        let header = reader.headers().unwrap().clone();
+        assert_eq!(header.len(), 2 + 2 * (STATS.len() + QUANTILES.len()));
+        assert_eq!(&header[1], "latency samples");
+        assert_eq!(&header[2], "latency samples stddev");
+        let column = |name: &str| header.iter().position(|h| h == name).unwrap();
+
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        for row in &rows {
+            assert_eq!(&row[column("latency samples")], "1");
+            assert_eq!(&row[column("latency samples stddev")], "0");
+            assert_eq!(&row[column("latency p50 (us)")], "20");
+            assert_eq!(&row[column("latency p50 stddev (us)")], "10");
+        }
+    }
+
+    #[test]
+    fn csv_with_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100");
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        params.with_metadata = true;
+        let mut writer = results_sink::from_params(&params).unwrap();
+        writer
+            .write_results(
+                &run("100"),
+                &[results(&[("latency", &[1, 2])])],
+                Some(&metadata()),
+            )
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+        let header = reader.headers().unwrap().clone();
+        let row = reader.records().next().unwrap().unwrap();
+        let metadata_start = header.len() - METADATA_COLUMNS.len();
+        assert_eq!(
+            header.iter().skip(metadata_start).collect::<Vec<_>>(),
+            METADATA_COLUMNS
+        );
+        assert_eq!(
+            row.iter().skip(metadata_start).collect::<Vec<_>>(),
+            [
+                "2024-05-01T12:00:00Z",
+                "0123abc",
+                "",
+                r#"{"target-qps":100}"#,
+                "5",
+                "250"
+            ]
+        );
+
+        // The metadata columns aren't numeric, so they're skipped when reading results back.
+        let graph_results =
+            crate::compare::GraphResults::from_path(params.graph_results_path()).unwrap();
+        assert!(graph_results
+            .value(&["100".to_owned()], "latency max")
+            .is_some());
+    }
+
+    #[test]
+    fn csv_with_resource_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100,200");
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        params.resource_usage = true;
+        params.resource_usage_pid = Some(1);
+        params.keep_going = true;
+        let mut writer = results_sink::from_params(&params).unwrap();
+        writer
+            .write_results(
+                &run("100"),
+                &[
+                    results_with_usage(&[(50.0, 1000), (150.0, 3000)], Some(&[(10.0, 500)])),
+                    results_with_usage(&[(100.0, 2000)], None),
+                ],
+                None,
+            )
+            .unwrap();
+        writer
+            .write_failure(&run("200"), &anyhow!("connection refused"))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+        let header = reader.headers().unwrap().clone();
+        let start = header
+            .iter()
+            .position(|column| column == RESOURCE_USAGE_COLUMNS[0])
+            .unwrap();
+        assert_eq!(
+            header.iter().skip(start).take(7).collect::<Vec<_>>(),
+            [
+                "cpu_mean_pct",
+                "cpu_max_pct",
+                "rss_max_bytes",
+                "target_cpu_mean_pct",
+                "target_cpu_max_pct",
+                "target_rss_max_bytes",
+                ACHIEVED_QPS_COLUMN
+            ]
+        );
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(
+            rows[0].iter().skip(start).take(6).collect::<Vec<_>>(),
+            ["100", "150", "3000", "10", "10", "500"]
+        );
+        assert!(rows[1]
+            .iter()
+            .skip(start)
+            .take(6)
+            .all(|value| value == "NaN"));
+
+        // The resource usage columns are compared like any other statistic.
+        let graph_results =
+            crate::compare::GraphResults::from_path(params.graph_results_path()).unwrap();
+        assert_eq!(
+            graph_results.value(&["100".to_owned()], "target_rss_max_bytes"),
+            Some(500.0)
+        );
+
+        params.tags = vec!["cpu_max_pct=high".parse().unwrap()];
+        let err = params.validate().unwrap_err();
+        assert!(err.to_string().contains("--tag cpu_max_pct"), "{err}");
+    }
+
+    #[test]
+    fn buffered_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100,200");
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        params.buffered = true;
+        let mut writer = results_sink::from_params(&params).unwrap();
+        for run in params.runs() {
+            writer
+                .write_results(&run, &[results(&[("latency", &[1])])], None)
+                .unwrap();
+        }
+        // The rows are still buffered in memory.
+        assert_eq!(
+            std::fs::metadata(params.graph_results_path())
+                .unwrap()
+                .len(),
+            0
+        );
+        writer.finish().unwrap();
+        let reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+        assert_eq!(reader.into_records().count(), 2);
+    }
+
+    #[test]
+    fn append_with_different_header() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.csv");
+        let mut writer = results_sink::open(&path, "target-qps").unwrap();
+        writer
+            .write_result("100", &results(&[("latency", &[1])]))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut writer = results_sink::append(&path, "target-qps").unwrap();
+        let err = writer
+            .write_result("200", &results(&[("errors", &[1])]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("different header"), "{err}");
+        assert!(err.contains("missing columns [errors samples,"), "{err}");
+        assert!(
+            err.contains("unexpected columns [latency samples,"),
+            "{err}"
+        );
+    }
+
+    /// A CSV file for a `target-qps` sweep of a single `latency` metric with one row, as written
+    /// by an earlier sweep.
+    fn latency_fixture() -> String {
+        let header = std::iter::once("target-qps".to_owned())
+            .chain(
+                stat_columns(&["latency".to_owned()], &Quantiles::default())
+                    .into_iter()
+                    .map(|(m, s)| match s.as_str() {
+                        "samples" => format!("{m} {s}"),
+                        _ => format!("{m} {s} (us)"),
+                    }),
+            )
+            .chain(std::iter::once(ACHIEVED_QPS_COLUMN.to_owned()))
+            .join(",");
+        let row = std::iter::once("50")
+            .chain(std::iter::repeat_n("7", STATS.len() + QUANTILES.len()))
+            .chain(std::iter::once("NaN"))
+            .join(",");
+        format!("{header}\n{row}\n")
+    }
+
+    #[test]
+    fn append_to_existing_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100,200");
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        params.append = true;
+        std::fs::write(params.graph_results_path(), latency_fixture()).unwrap();
+
+        let mut writer = results_sink::from_params(&params).unwrap();
+        for run in params.runs() {
+            writer
+                .write_results(&run, &[results(&[("latency", &[1, 2])])], None)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let contents = std::fs::read_to_string(params.graph_results_path()).unwrap();
+        assert_eq!(contents.matches("target-qps").count(), 1, "{contents}");
+        let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(
+            rows.iter().map(|row| &row[0]).collect::<Vec<_>>(),
+            ["50", "100", "200"]
+        );
+        assert_eq!(&rows[0][1], "7");
+        assert_eq!(&rows[1][1], "2");
+    }
+
+    #[test]
+    fn append_to_empty_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.csv");
+        File::create(&path).unwrap();
+
+        let mut writer = results_sink::append(&path, "target-qps").unwrap();
+        writer
+            .write_result("100", &results(&[("latency", &[1])]))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        assert_eq!(&reader.headers().unwrap()[0], "target-qps");
+        assert_eq!(reader.records().count(), 1);
+    }
+
+    #[test]
+    fn append_with_reordered_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.csv");
+        let fixture = latency_fixture().replacen(
+            "latency min (us),latency max (us)",
+            "latency max (us),latency min (us)",
+            1,
+        );
+        std::fs::write(&path, fixture).unwrap();
+
+        let mut writer = results_sink::append(&path, "target-qps").unwrap();
+        let err = writer
+            .write_result("100", &results(&[("latency", &[1])]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("different order"), "{err}");
+    }
+}
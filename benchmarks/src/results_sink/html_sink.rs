@@ -0,0 +1,162 @@
+//! Writing the results of a sweep as a self-contained HTML report.
+
+use std::path::Path;
+
+use super::row_format::{Failure, RowFormat, RowOrder, RunMetadata, SinkOptions};
+use super::sidecar::{sidecar_path, Sidecar};
+use super::ResultsSink;
+use crate::benchmark::BenchmarkResults;
+use crate::graph::GraphRun;
+use crate::html_report::HtmlReport;
+
+/// Writes the rows of results as a self-contained HTML report, which is only written once the
+/// sink is finished or dropped. Failed runs are left out.
+pub(crate) struct HtmlSink {
+    format: RowFormat,
+    report: HtmlReport,
+    reorder: Option<RowOrder>,
+    sidecar: Option<Sidecar>,
+    finished: bool,
+}
+
+impl HtmlSink {
+    pub(crate) fn new(
+        path: &Path,
+        format: RowFormat,
+        options: SinkOptions,
+    ) -> anyhow::Result<Self> {
+        let mut report = HtmlReport::new(path.to_owned());
+        report.x_achieved_qps = options.x_achieved_qps;
+        Ok(Self {
+            format,
+            report,
+            reorder: options.planned_runs.map(RowOrder::new),
+            sidecar: Some(Sidecar::create(sidecar_path(path))?),
+            finished: false,
+        })
+    }
+
+    fn write_failed_row(&mut self, run: &GraphRun, failure: &Failure) -> anyhow::Result<()> {
+        if let Some(sidecar) = &mut self.sidecar {
+            sidecar.write(&self.format.failed_json(run, failure))?;
+        }
+        Ok(())
+    }
+
+    fn write_report(&self) -> anyhow::Result<()> {
+        self.report.write(
+            &self.format.x_axis,
+            self.format.z_axis.as_deref(),
+            &self.format.quantiles,
+        )
+    }
+}
+
+impl ResultsSink for HtmlSink {
+    fn write_result(&mut self, x_value: &str, results: &BenchmarkResults) -> anyhow::Result<()> {
+        let run = self.format.run(x_value);
+        self.write_results(&run, std::slice::from_ref(results), None)
+    }
+
+    fn write_results(
+        &mut self,
+        run: &GraphRun,
+        results: &[BenchmarkResults],
+        metadata: Option<&RunMetadata>,
+    ) -> anyhow::Result<()> {
+        let metadata = self.format.metadata(run, metadata)?;
+        let (results, failures) = self.format.start_row(run, results)?;
+        for (run, failure) in failures.into_iter().flatten() {
+            self.write_failed_row(&run, &failure)?;
+        }
+        let row = self.format.row(self.format.fixed_columns(), &results);
+        let result = self.format.json(run, &row, &results, metadata);
+        if let Some(sidecar) = &mut self.sidecar {
+            sidecar.write(&result)?;
+        }
+        self.report.runs.push(result);
+        if let Some(reorder) = &mut self.reorder {
+            reorder.push(run);
+        }
+        Ok(())
+    }
+
+    fn write_failure(&mut self, run: &GraphRun, error: &anyhow::Error) -> anyhow::Result<()> {
+        match self.format.failure(run, error)? {
+            Some(failure) => self.write_failed_row(run, &failure),
+            None => Ok(()),
+        }
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.finished = true;
+        for (run, failure) in self.format.finish_rows().into_iter().flatten() {
+            self.write_failed_row(&run, &failure)?;
+        }
+        if let Some(reorder) = &self.reorder {
+            reorder.sort_tail(&mut self.report.runs);
+        }
+        self.write_report()?;
+        self.sidecar.take().map_or(Ok(()), Sidecar::remove)
+    }
+}
+
+impl Drop for HtmlSink {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(e) = self.write_report() {
+                eprintln!("Failed to write graph results: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use serde_json::json;
+
+    use crate::results_sink;
+    use crate::test_utils::results;
+
+    #[test]
+    fn html_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.html");
+        let mut writer = results_sink::open(&path, "target-qps").unwrap();
+        let first = results(&[("latency", &[1, 2, 3, 4]), ("</script>", &[7])]);
+        writer.write_result("100", &first).unwrap();
+        writer
+            .write_result("200", &results(&[("latency", &[5]), ("</script>", &[8])]))
+            .unwrap();
+
+        // The report is only written once every run has finished.
+        writer.flush().unwrap();
+        assert!(!path.exists());
+        writer.finish().unwrap();
+
+        let html = std::fs::read_to_string(&path).unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<button type=\"button\">latency</button>"));
+        let hist = first.results["latency"].to_histogram();
+        let mean = hist.mean();
+        assert!(html.contains(&format!(
+            "<td>100</td><td>4</td><td>1</td><td>4</td><td>{mean:.2}</td>"
+        )));
+        // The results are embedded as JSON for the charts.
+        let data = html
+            .split("<script type=\"application/json\" id=\"results\">")
+            .nth(1)
+            .and_then(|data| data.split("</script>").next())
+            .unwrap();
+        let data: serde_json::Value = serde_json::from_str(data).unwrap();
+        assert_eq!(data["x_axis"], json!("target-qps"));
+        assert_eq!(data["runs"][0]["x_value"], json!(100));
+        assert_eq!(data["runs"][0]["metrics"]["latency"]["mean"], json!(mean));
+        assert_eq!(data["runs"][1]["metrics"]["latency"]["max"], json!(5));
+        assert_eq!(data["runs"][1]["metrics"]["</script>"]["max"], json!(8));
+        assert!(html.contains("<section class=\"metric\" data-metric=\"&lt;/script&gt;\">"));
+
+        assert!(results_sink::append(&path, "target-qps").is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! Writing the results of a sweep to a JSON file.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+
+use super::row_format::{RowFormat, RowOrder, RunMetadata, SinkOptions};
+use super::sidecar::{sidecar_path, Sidecar};
+use super::ResultsSink;
+use crate::benchmark::BenchmarkResults;
+use crate::graph::GraphRun;
+
+/// Writes the rows of results to a JSON file as an array of JSON objects (see [`json_result`]).
+/// The whole file is rewritten each time the sink is flushed.
+pub(crate) struct JsonSink {
+    format: RowFormat,
+    path: PathBuf,
+    runs: Vec<serde_json::Value>,
+    reorder: Option<RowOrder>,
+    sidecar: Option<Sidecar>,
+    finished: bool,
+}
+
+impl JsonSink {
+    /// Creates a sink that writes to `path`. When appending, the runs already in the file are
+    /// kept before those written.
+    pub(crate) fn new(
+        path: &Path,
+        format: RowFormat,
+        options: SinkOptions,
+    ) -> anyhow::Result<Self> {
+        let runs = if options.append && path.metadata().is_ok_and(|m| m.len() > 0) {
+            serde_json::from_reader(BufReader::new(File::open(path)?))
+                .map_err(|e| anyhow!("Failed to read {}: {e}", path.display()))?
+        } else {
+            Vec::new()
+        };
+        Ok(Self {
+            format,
+            path: path.to_owned(),
+            runs,
+            reorder: options.planned_runs.map(RowOrder::new),
+            sidecar: Some(Sidecar::create(sidecar_path(path))?),
+            finished: false,
+        })
+    }
+
+    fn push(&mut self, run: &GraphRun, result: serde_json::Value) -> anyhow::Result<()> {
+        if let Some(sidecar) = &mut self.sidecar {
+            sidecar.write(&result)?;
+        }
+        self.runs.push(result);
+        if let Some(reorder) = &mut self.reorder {
+            reorder.push(run);
+        }
+        Ok(())
+    }
+}
+
+impl ResultsSink for JsonSink {
+    fn write_result(&mut self, x_value: &str, results: &BenchmarkResults) -> anyhow::Result<()> {
+        let run = self.format.run(x_value);
+        self.write_results(&run, std::slice::from_ref(results), None)
+    }
+
+    fn write_results(
+        &mut self,
+        run: &GraphRun,
+        results: &[BenchmarkResults],
+        metadata: Option<&RunMetadata>,
+    ) -> anyhow::Result<()> {
+        let metadata = self.format.metadata(run, metadata)?;
+        let (results, failures) = self.format.start_row(run, results)?;
+        for (run, failure) in failures.into_iter().flatten() {
+            self.push(&run, self.format.failed_json(&run, &failure))?;
+        }
+        let row = self.format.row(self.format.fixed_columns(), &results);
+        self.push(run, self.format.json(run, &row, &results, metadata))
+    }
+
+    fn write_failure(&mut self, run: &GraphRun, error: &anyhow::Error) -> anyhow::Result<()> {
+        match self.format.failure(run, error)? {
+            Some(failure) => self.push(run, self.format.failed_json(run, &failure)),
+            None => Ok(()),
+        }
+    }
+
+    /// Rewrites the file with the rows written so far, so a sweep that is interrupted part way
+    /// through still leaves a usable file.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        let mut writer = BufWriter::new(File::create(&self.path)?);
+        serde_json::to_writer_pretty(&mut writer, &self.runs)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.finished = true;
+        for (run, failure) in self.format.finish_rows().into_iter().flatten() {
+            self.push(&run, self.format.failed_json(&run, &failure))?;
+        }
+        if let Some(reorder) = &self.reorder {
+            reorder.sort_tail(&mut self.runs);
+        }
+        self.flush()?;
+        self.sidecar.take().map_or(Ok(()), Sidecar::remove)
+    }
+}
+
+impl Drop for JsonSink {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(e) = self.flush() {
+                eprintln!("Failed to write graph results: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::results_sink;
+    use crate::test_utils::{metadata, params, results, results_with_usage, run};
+
+    #[test]
+    fn json_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.json");
+        let mut writer = results_sink::open(&path, "@rows").unwrap();
+        let first = results(&[("latency", &[1, 2, 3, 4]), ("errors", &[0])]);
+        writer.write_result("100", &first).unwrap();
+        writer
+            .write_result("200", &results(&[("latency", &[5]), ("errors", &[1])]))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let json: serde_json::Value = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        let runs = json.as_array().unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0]["x_value"], json!(100));
+        assert_eq!(runs[1]["x_value"], json!(200));
+
+        let hist = first.results["latency"].to_histogram();
+        let latency = &runs[0]["metrics"]["latency"];
+        assert_eq!(latency["samples"], json!(4));
+        assert_eq!(latency["max"], json!(hist.max()));
+        assert_eq!(latency["mean"], json!(hist.mean()));
+        assert_eq!(
+            latency["quantiles"]["p50"],
+            json!(hist.value_at_quantile(0.5))
+        );
+        assert_eq!(runs[1]["metrics"]["errors"]["min"], json!(1));
+    }
+
+    #[test]
+    fn json_with_runs_per_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100");
+        params.graph_results_paths = vec![dir.path().join("graph.json")];
+        params.runs_per_value = 2;
+        let mut writer = results_sink::from_params(&params).unwrap();
+        let repeated = [
+            results(&[("latency", &[1, 3])]),
+            results(&[("latency", &[5, 7])]),
+        ];
+        writer
+            .write_results(&params.runs()[0], &repeated, None)
+            .unwrap();
+        writer.finish().unwrap();
+
+        let json: serde_json::Value =
+            serde_json::from_reader(File::open(params.graph_results_path()).unwrap()).unwrap();
+        let latency = &json[0]["metrics"]["latency"];
+        assert_eq!(latency["mean"], json!(4));
+        assert_eq!(latency["stddev"]["samples"], json!(0));
+        assert_eq!(latency["stddev"]["mean"], json!(8f64.sqrt()));
+        assert!(json[0].get("elapsed_secs").is_none());
+    }
+
+    #[test]
+    fn json_with_elapsed_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.json");
+        let mut writer = results_sink::open(&path, "target-qps").unwrap();
+        let repeated = [1, 2].map(|secs| {
+            let mut results = results(&[("latency", &[1, 2])]);
+            results.record_elapsed_time(Duration::from_secs(secs));
+            results
+        });
+        writer.write_results(&run("100"), &repeated, None).unwrap();
+        writer.finish().unwrap();
+
+        let json: serde_json::Value = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        assert_eq!(json[0]["elapsed_secs"], json!(1.5));
+    }
+
+    #[test]
+    fn json_with_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        for extension in ["json", "jsonl"] {
+            let mut params = params("target-qps", "100");
+            params.graph_results_paths = vec![dir.path().join(format!("graph.{extension}"))];
+            params.with_metadata = true;
+            let mut writer = results_sink::from_params(&params).unwrap();
+            writer
+                .write_results(
+                    &run("100"),
+                    &[results(&[("latency", &[1, 2])])],
+                    Some(&metadata()),
+                )
+                .unwrap();
+            writer.finish().unwrap();
+
+            let contents = std::fs::read_to_string(params.graph_results_path()).unwrap();
+            let row = match extension {
+                "json" => serde_json::from_str::<serde_json::Value>(&contents).unwrap()[0].take(),
+                _ => serde_json::from_str(contents.lines().next().unwrap()).unwrap(),
+            };
+            assert_eq!(
+                row["meta"],
+                json!({
+                    "timestamp": "2024-05-01T12:00:00Z",
+                    "commit": "0123abc",
+                    "hostname": null,
+                    "arguments": r#"{"target-qps":100}"#,
+                    "warmup_secs": 5,
+                    "cooldown_ms": 250,
+                }),
+                "{extension}"
+            );
+        }
+    }
+
+    #[test]
+    fn json_with_resource_usage() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100");
+        params.graph_results_paths = vec![dir.path().join("graph.json")];
+        params.resource_usage = true;
+        params.resource_usage_samples = true;
+        let mut writer = results_sink::from_params(&params).unwrap();
+        writer
+            .write_results(
+                &run("100"),
+                &[results_with_usage(&[(50.0, 1000), (150.0, 3000)], None)],
+                None,
+            )
+            .unwrap();
+        writer.finish().unwrap();
+
+        let contents = std::fs::read_to_string(params.graph_results_path()).unwrap();
+        let runs = serde_json::from_str::<serde_json::Value>(&contents).unwrap();
+        assert_eq!(
+            runs[0]["resource_usage"],
+            json!({
+                "cpu_mean_pct": 100,
+                "cpu_max_pct": 150,
+                "rss_max_bytes": 3000,
+                "samples": [[
+                    { "elapsed_ms": 0, "cpu_percent": 50.0, "rss_bytes": 1000 },
+                    { "elapsed_ms": 100, "cpu_percent": 150.0, "rss_bytes": 3000 },
+                ]],
+            })
+        );
+    }
+}
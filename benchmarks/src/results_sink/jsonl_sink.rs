@@ -0,0 +1,217 @@
+//! Writing the results of a sweep to a JSONL file, a row per line.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::row_format::{Failure, RowFormat, RowOrder, RunMetadata, SinkOptions};
+use super::ResultsSink;
+use crate::benchmark::BenchmarkResults;
+use crate::graph::GraphRun;
+
+/// Writes `result` as a line of a JSONL file, recording it in `reorder` first if the runs are
+/// shuffled, and flushes it to disk unless the writer is `buffered`.
+fn write_jsonl_line(
+    writer: &mut BufWriter<File>,
+    result: &serde_json::Value,
+    run: &GraphRun,
+    reorder: Option<&mut RowOrder>,
+    buffered: bool,
+) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(result)?;
+    line.push(b'\n');
+    if let Some(reorder) = reorder {
+        writer.flush()?;
+        reorder.push_bytes(run, writer.get_ref(), line.clone())?;
+    }
+    writer.write_all(&line)?;
+    if !buffered {
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// Writes each row of results to a JSONL file as a JSON object (see [`json_result`]) as soon as
+/// it's written.
+pub(crate) struct JsonlSink {
+    format: RowFormat,
+    writer: BufWriter<File>,
+    reorder: Option<RowOrder>,
+    buffered: bool,
+}
+
+impl JsonlSink {
+    pub(crate) fn new(
+        path: &Path,
+        format: RowFormat,
+        options: SinkOptions,
+    ) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(options.append)
+            .truncate(!options.append)
+            .open(path)?;
+        Ok(Self {
+            format,
+            writer: BufWriter::new(file),
+            reorder: options.planned_runs.map(RowOrder::new),
+            buffered: options.buffered,
+        })
+    }
+
+    fn write_failed_row(&mut self, run: &GraphRun, failure: &Failure) -> anyhow::Result<()> {
+        let result = self.format.failed_json(run, failure);
+        write_jsonl_line(
+            &mut self.writer,
+            &result,
+            run,
+            self.reorder.as_mut(),
+            self.buffered,
+        )
+    }
+}
+
+impl ResultsSink for JsonlSink {
+    fn write_result(&mut self, x_value: &str, results: &BenchmarkResults) -> anyhow::Result<()> {
+        let run = self.format.run(x_value);
+        self.write_results(&run, std::slice::from_ref(results), None)
+    }
+
+    fn write_results(
+        &mut self,
+        run: &GraphRun,
+        results: &[BenchmarkResults],
+        metadata: Option<&RunMetadata>,
+    ) -> anyhow::Result<()> {
+        let metadata = self.format.metadata(run, metadata)?;
+        let (results, failures) = self.format.start_row(run, results)?;
+        for (run, failure) in failures.into_iter().flatten() {
+            self.write_failed_row(&run, &failure)?;
+        }
+        let row = self.format.row(self.format.fixed_columns(), &results);
+        let result = self.format.json(run, &row, &results, metadata);
+        write_jsonl_line(
+            &mut self.writer,
+            &result,
+            run,
+            self.reorder.as_mut(),
+            self.buffered,
+        )
+    }
+
+    fn write_failure(&mut self, run: &GraphRun, error: &anyhow::Error) -> anyhow::Result<()> {
+        match self.format.failure(run, error)? {
+            Some(failure) => self.write_failed_row(run, &failure),
+            None => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        for (run, failure) in self.format.finish_rows().into_iter().flatten() {
+            self.write_failed_row(&run, &failure)?;
+        }
+        if let Some(reorder) = &self.reorder {
+            self.writer.flush()?;
+            reorder.rewrite(self.writer.get_ref())?;
+        }
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::BufRead;
+    use std::io::BufReader;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::results_sink;
+    use crate::test_utils::results;
+
+    #[test]
+    fn jsonl_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.jsonl");
+        let mut writer = results_sink::open(&path, "target-qps").unwrap();
+        writer
+            .write_result("100", &results(&[("latency", &[1, 2, 3])]))
+            .unwrap();
+
+        // Each row is written as soon as it's available.
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 1);
+        assert!(contents.ends_with('\n'));
+
+        writer
+            .write_result("200", &results(&[("latency", &[4])]))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let rows = BufReader::new(File::open(&path).unwrap())
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(&line.unwrap()).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["x_value"], json!(100));
+        assert_eq!(rows[0]["metrics"]["latency"]["samples"], json!(3));
+        assert_eq!(rows[1]["x_value"], json!(200));
+        assert_eq!(rows[1]["metrics"]["latency"]["max"], json!(4));
+    }
+
+    #[test]
+    fn jsonl_rows_survive_without_finish() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.ndjson");
+        let mut writer = results_sink::open(&path, "target-qps").unwrap();
+        for x_value in ["100", "200", "300"] {
+            writer
+                .write_result(x_value, &results(&[("latency", &[1, 2])]))
+                .unwrap();
+        }
+        // As if the sweep were killed: the writer is never finished.
+        std::mem::forget(writer);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let rows = contents
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 3);
+        for (row, x_value) in rows.iter().zip([100, 200, 300]) {
+            assert_eq!(row["x_value"], json!(x_value));
+            assert_eq!(row["metrics"]["latency"]["samples"], json!(2));
+            assert!(row["metrics"]["latency"]["quantiles"].is_object());
+        }
+    }
+
+    #[test]
+    fn jsonl_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.jsonl");
+        for (x_value, append) in [("100", false), ("200", true)] {
+            let mut writer = if append {
+                results_sink::append(&path, "target-qps").unwrap()
+            } else {
+                results_sink::open(&path, "target-qps").unwrap()
+            };
+            writer
+                .write_result(x_value, &results(&[("latency", &[1])]))
+                .unwrap();
+            writer.finish().unwrap();
+        }
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let x_values = contents
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap()["x_value"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(x_values, [json!(100), json!(200)]);
+    }
+}
@@ -0,0 +1,461 @@
+//! Where the results of a graph sweep are written.
+//!
+//! Every run of a sweep is written to a [`ResultsSink`]. Each of the built-in formats, chosen by
+//! the extension of each `--graph-results-path`, is written by a sink of its own, as is the
+//! Pushgateway ([`PushGatewaySink`]); other destinations, such as a database of results, can be
+//! supported by implementing the trait. [`MultiSink`] writes every run to several sinks at once.
+
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::path::Path;
+
+use anyhow::bail;
+use itertools::Itertools;
+
+use crate::axis::XValue;
+use crate::benchmark::BenchmarkResults;
+use crate::graph::{GraphParams, GraphRun};
+
+mod csv_sink;
+mod html_sink;
+mod json_sink;
+mod jsonl_sink;
+mod parquet_sink;
+mod plot_sink;
+mod push_gateway_sink;
+mod row_format;
+mod sidecar;
+
+pub(crate) use self::csv_sink::CsvSink;
+pub(crate) use self::html_sink::HtmlSink;
+pub(crate) use self::json_sink::JsonSink;
+pub(crate) use self::jsonl_sink::JsonlSink;
+pub(crate) use self::parquet_sink::ParquetSink;
+pub use self::plot_sink::PlotFormat;
+pub(crate) use self::plot_sink::{Plot, PlotSink, ACHIEVED_QPS_LABEL};
+pub use self::push_gateway_sink::PushGatewaySink;
+pub use self::row_format::{
+    aggregate_stats, compare_targets, group_by_query, written_stats, ColumnTag, RowFormat,
+    RunMetadata, WrittenStat, ACHIEVED_QPS_COLUMN, ALL_QUERIES, ATTEMPTS_COLUMN, ERROR_COLUMN,
+    MAX_ERROR_LEN, METADATA_COLUMNS, READYSET_PREFIX, RESOURCE_USAGE_COLUMNS, TAG_COLUMN,
+    TARGET_COLUMN_PREFIX, TIMED_OUT_COLUMN, UPSTREAM_PREFIX,
+};
+pub(crate) use self::row_format::{
+    current_commit, json_number, json_value, ResourceUsageOutput, SinkOptions, STATS,
+};
+pub use self::sidecar::sidecar_path;
+
+/// A destination for the results of each run of a graph sweep.
+pub trait ResultsSink: Send {
+    /// Writes the results of a single run of the benchmark with the x-value `x_value`.
+    fn write_result(&mut self, x_value: &str, results: &BenchmarkResults) -> anyhow::Result<()>;
+
+    /// Writes the results of every repetition of the benchmark for `run`, one for each of the
+    /// `--runs-per-value`, along with the run's `metadata` if it was recorded. By default, the
+    /// repetitions are merged and written with [`Self::write_result`], without the run's z-value
+    /// or metadata.
+    fn write_results(
+        &mut self,
+        run: &GraphRun,
+        results: &[BenchmarkResults],
+        _metadata: Option<&RunMetadata>,
+    ) -> anyhow::Result<()> {
+        let Some(merged) = BenchmarkResults::merge_all(results.iter().cloned()) else {
+            bail!("No results to write for {run}");
+        };
+        self.write_result(&run.x_value, &merged)
+    }
+
+    /// Records that `run` failed with `error`, for sweeps run with `--keep-going`. Does nothing
+    /// by default.
+    fn write_failure(&mut self, _run: &GraphRun, _error: &anyhow::Error) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Makes sure everything written so far has been saved, before the run is recorded as
+    /// completed in the sweep's checkpoint. Does nothing by default.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Finishes writing once every run of the sweep has been written.
+    fn finish(&mut self) -> anyhow::Result<()>;
+}
+
+/// A sink that writes every run to each of several sinks, for example to keep the results in
+/// more than one format. Writing only fails if it fails for every sink; if only some fail, their
+/// errors are logged and the others keep their results.
+pub struct MultiSink {
+    sinks: Vec<Box<dyn ResultsSink>>,
+}
+
+impl MultiSink {
+    pub fn new(sinks: Vec<Box<dyn ResultsSink>>) -> Self {
+        Self { sinks }
+    }
+
+    /// Returns a sink that writes every run to both `a` and `b`.
+    pub fn tee(a: Box<dyn ResultsSink>, b: Box<dyn ResultsSink>) -> Self {
+        Self::new(vec![a, b])
+    }
+
+    /// Calls `f` with each of the sinks, only failing if it fails for all of them.
+    fn for_each(
+        &mut self,
+        mut f: impl FnMut(&mut dyn ResultsSink) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let errors = self
+            .sinks
+            .iter_mut()
+            .filter_map(|sink| f(sink.as_mut()).err())
+            .collect::<Vec<_>>();
+        if !errors.is_empty() && errors.len() == self.sinks.len() {
+            bail!(
+                "Failed to write graph results to any output: {}",
+                errors.iter().map(|e| format!("{e:#}")).join("; ")
+            );
+        }
+        for e in errors {
+            tracing::warn!(
+                "Failed to write graph results to one of {} outputs: {e:#}",
+                self.sinks.len()
+            );
+        }
+        Ok(())
+    }
+}
+
+impl ResultsSink for MultiSink {
+    fn write_result(&mut self, x_value: &str, results: &BenchmarkResults) -> anyhow::Result<()> {
+        self.for_each(|sink| sink.write_result(x_value, results))
+    }
+
+    fn write_results(
+        &mut self,
+        run: &GraphRun,
+        results: &[BenchmarkResults],
+        metadata: Option<&RunMetadata>,
+    ) -> anyhow::Result<()> {
+        self.for_each(|sink| sink.write_results(run, results, metadata))
+    }
+
+    fn write_failure(&mut self, run: &GraphRun, error: &anyhow::Error) -> anyhow::Result<()> {
+        self.for_each(|sink| sink.write_failure(run, error))
+    }
+
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.for_each(|sink| sink.flush())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.for_each(|sink| sink.finish())
+    }
+}
+
+/// Returns a sink that writes the results of the sweep described by `params` to each of its
+/// `--graph-results-path`s (and `--graph-results-path2`), in the format given by each one's
+/// extension. See [`for_path`].
+pub fn from_params(params: &GraphParams) -> anyhow::Result<Box<dyn ResultsSink>> {
+    let mut sinks = params
+        .results_paths()
+        .map(|(_, path)| for_path(params, path))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    match sinks.len() {
+        1 => Ok(sinks.remove(0)),
+        _ => Ok(Box::new(MultiSink::new(sinks))),
+    }
+}
+
+/// Returns a sink that writes the results of the sweep described by `params` to `path`, in the
+/// format given by its extension, with the rows laid out by [`RowFormat::from_params`]. With
+/// `--resume` or `--append`, the rows are added to those already in the file. Plotted graphs
+/// place an x-axis tick at each of the x-values, unless runs are placed at their achieved QPS.
+pub fn for_path(params: &GraphParams, path: &Path) -> anyhow::Result<Box<dyn ResultsSink>> {
+    let mut options = SinkOptions::from_params(params);
+    let format = ResultsFormat::for_path(path, options.append)?;
+    if matches!(format, ResultsFormat::Plot(_)) && !params.x_axis_achieved_qps {
+        options.x_ticks = params
+            .typed_x_values()?
+            .iter()
+            .filter_map(XValue::as_f64)
+            .collect();
+    }
+    open_as(format, path, RowFormat::from_params(params), options)
+}
+
+/// Returns a sink that writes results along `x_axis` to `path`, in the format given by its
+/// extension, with the default [`RowFormat`].
+pub fn open(path: &Path, x_axis: &str) -> anyhow::Result<Box<dyn ResultsSink>> {
+    let format = ResultsFormat::for_path(path, false)?;
+    open_as(format, path, RowFormat::new(x_axis), SinkOptions::default())
+}
+
+/// Like [`open`], but adds results to those already in `path`, as written by an earlier sink
+/// with the same columns. A CSV file's existing header is kept, and must match the header that
+/// would otherwise be written; a file that doesn't exist yet or is empty is written from
+/// scratch. Parquet files, graphs and HTML reports can't be appended to.
+pub fn append(path: &Path, x_axis: &str) -> anyhow::Result<Box<dyn ResultsSink>> {
+    let options = SinkOptions {
+        append: true,
+        ..SinkOptions::default()
+    };
+    let format = ResultsFormat::for_path(path, true)?;
+    open_as(format, path, RowFormat::new(x_axis), options)
+}
+
+/// Returns the sink that writes `format` to `path`.
+fn open_as(
+    format: ResultsFormat,
+    path: &Path,
+    rows: RowFormat,
+    options: SinkOptions,
+) -> anyhow::Result<Box<dyn ResultsSink>> {
+    Ok(match format {
+        ResultsFormat::Csv => Box::new(CsvSink::new(path, rows, options)?),
+        ResultsFormat::Json => Box::new(JsonSink::new(path, rows, options)?),
+        ResultsFormat::Jsonl => Box::new(JsonlSink::new(path, rows, options)?),
+        ResultsFormat::Parquet => Box::new(ParquetSink::new(path, rows, options)?),
+        ResultsFormat::Plot(_) => Box::new(PlotSink::new(path, rows, options)?),
+        ResultsFormat::Html => Box::new(HtmlSink::new(path, rows, options)?),
+    })
+}
+
+/// The extensions of the files that graph results can be written to.
+pub(crate) const RESULTS_EXTENSIONS: &[&str] = &[
+    "csv", "html", "json", "jsonl", "ndjson", "parquet", "png", "svg",
+];
+
+/// The format that graph results are written to a file in, chosen by its extension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResultsFormat {
+    Csv,
+    Json,
+    Jsonl,
+    Parquet,
+    Plot(PlotFormat),
+    Html,
+}
+
+impl ResultsFormat {
+    /// Returns the format to write `path` in, or an error if its extension isn't one of
+    /// [`RESULTS_EXTENSIONS`] or, when `append`ing, the format can't be appended to.
+    pub fn for_path(path: &Path, append: bool) -> anyhow::Result<Self> {
+        let format = match path.extension().and_then(OsStr::to_str) {
+            Some("csv") => Self::Csv,
+            Some("json") => Self::Json,
+            Some("jsonl" | "ndjson") => Self::Jsonl,
+            Some("parquet") => Self::Parquet,
+            Some("png") => Self::Plot(PlotFormat::Png),
+            Some("svg") => Self::Plot(PlotFormat::Svg),
+            Some("html") => Self::Html,
+            _ => bail!(
+                "Unsupported graph results path {}: expected one of [{}]",
+                path.display(),
+                RESULTS_EXTENSIONS
+                    .iter()
+                    .map(|ext| format!(".{ext}"))
+                    .join(", ")
+            ),
+        };
+        let unappendable = match format {
+            Self::Parquet => Some("Parquet files"),
+            Self::Plot(_) => Some("graphs"),
+            Self::Html => Some("HTML reports"),
+            Self::Csv | Self::Json | Self::Jsonl => None,
+        };
+        if let Some(unappendable) = unappendable.filter(|_| append) {
+            bail!(
+                "Can't append to {}: {unappendable} can't be appended to",
+                path.display()
+            );
+        }
+        Ok(format)
+    }
+}
+
+impl Display for ResultsFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Csv => write!(f, "CSV"),
+            Self::Json => write!(f, "JSON"),
+            Self::Jsonl => write!(f, "JSONL"),
+            Self::Parquet => write!(f, "Parquet"),
+            Self::Plot(format) => write!(f, "{format} graph"),
+            Self::Html => write!(f, "HTML report"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::results_sink;
+    use std::fs::File;
+    use std::sync::{Arc, Mutex};
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::test_utils::{results, run};
+
+    /// A sink that records the x-value of each run it's given, followed by `finish`.
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl ResultsSink for Recorder {
+        fn write_result(&mut self, x_value: &str, _: &BenchmarkResults) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push(x_value.to_owned());
+            Ok(())
+        }
+
+        fn finish(&mut self) -> anyhow::Result<()> {
+            self.0.lock().unwrap().push("finish".to_owned());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writes_to_every_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("graph.csv");
+        let json_path = dir.path().join("graph.json");
+        let params = GraphParams::try_parse_from([
+            "graph".as_ref(),
+            "--graph".as_ref(),
+            "--x-axis=target-qps".as_ref(),
+            "--x-values=100,200".as_ref(),
+            "--graph-results-path".as_ref(),
+            csv_path.as_os_str(),
+            "--graph-results-path".as_ref(),
+            json_path.as_os_str(),
+        ])
+        .unwrap();
+        params.validate().unwrap();
+        let mut sink = from_params(&params).unwrap();
+        sink.write_result("100", &results(&[("latency", &[1, 2, 3])]))
+            .unwrap();
+        sink.write_result("200", &results(&[("latency", &[4])]))
+            .unwrap();
+        sink.finish().unwrap();
+
+        let mut reader = csv::Reader::from_path(&csv_path).unwrap();
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        let json: serde_json::Value =
+            serde_json::from_reader(File::open(&json_path).unwrap()).unwrap();
+        let runs = json.as_array().unwrap();
+        assert_eq!((rows.len(), runs.len()), (2, 2));
+        for (row, run) in rows.iter().zip(runs) {
+            assert_eq!(run["x_value"].to_string(), row[0]);
+            assert_eq!(run["metrics"]["latency"]["samples"].to_string(), row[1]);
+        }
+    }
+
+    #[test]
+    fn custom_sinks() {
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.jsonl");
+        let mut sink = MultiSink::new(vec![
+            Box::new(Recorder(Arc::clone(&recorded))),
+            open(&path, "target-qps").unwrap(),
+        ]);
+        sink.write_result("100", &results(&[("latency", &[1])]))
+            .unwrap();
+        sink.write_results(&run("200"), &[results(&[("latency", &[2])])], None)
+            .unwrap();
+        sink.finish().unwrap();
+
+        assert_eq!(*recorded.lock().unwrap(), ["100", "200", "finish"]);
+        assert_eq!(std::fs::read_to_string(&path).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn tee() {
+        let dir = tempfile::tempdir().unwrap();
+        let [a, b, c] = ["a.csv", "b.csv", "c.csv"].map(|name| dir.path().join(name));
+        let mut sink = MultiSink::tee(
+            open(&a, "target-qps").unwrap(),
+            open(&b, "target-qps").unwrap(),
+        );
+        sink.write_result("100", &results(&[("latency", &[1, 2, 3])]))
+            .unwrap();
+        sink.write_result("200", &results(&[("latency", &[4])]))
+            .unwrap();
+        sink.finish().unwrap();
+        let contents = std::fs::read_to_string(&a).unwrap();
+        assert_eq!(contents.lines().count(), 3);
+        assert_eq!(contents, std::fs::read_to_string(&b).unwrap());
+
+        // A single failing output doesn't fail the write.
+        std::fs::write(&a, "target-qps,other\n").unwrap();
+        let mut sink = MultiSink::tee(
+            append(&a, "target-qps").unwrap(),
+            open(&c, "target-qps").unwrap(),
+        );
+        sink.write_result("100", &results(&[("latency", &[1, 2, 3])]))
+            .unwrap();
+        sink.write_result("200", &results(&[("latency", &[4])]))
+            .unwrap();
+        sink.finish().unwrap();
+        assert_eq!(contents, std::fs::read_to_string(&c).unwrap());
+    }
+
+    #[test]
+    fn multi_sink_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let csv_path = dir.path().join("graph.csv");
+        std::fs::write(&csv_path, "target-qps,other\n").unwrap();
+        let jsonl_path = dir.path().join("graph.jsonl");
+        let mismatched = || append(&csv_path, "target-qps").unwrap();
+
+        // A failure to write to only some of the outputs is ignored.
+        let mut sink = MultiSink::new(vec![mismatched(), open(&jsonl_path, "target-qps").unwrap()]);
+        sink.write_result("100", &results(&[("latency", &[1])]))
+            .unwrap();
+        sink.finish().unwrap();
+        let contents = std::fs::read_to_string(&jsonl_path).unwrap();
+        assert_eq!(contents.lines().count(), 1);
+
+        let mut sink = MultiSink::new(vec![mismatched(), mismatched()]);
+        let err = sink
+            .write_result("100", &results(&[("latency", &[1])]))
+            .unwrap_err();
+        assert!(err.to_string().contains("any output"), "{err}");
+    }
+
+    #[test]
+    fn dropped_writer_flushes() {
+        let dir = tempfile::tempdir().unwrap();
+        for extension in ["csv", "json"] {
+            let path = dir.path().join(format!("graph.{extension}"));
+            let mut writer = results_sink::open(&path, "target-qps").unwrap();
+            writer
+                .write_result("100", &results(&[("latency", &[1, 2, 3])]))
+                .unwrap();
+            drop(writer);
+
+            let contents = std::fs::read_to_string(&path).unwrap();
+            assert!(contents.contains("100"), "{extension}: {contents}");
+            assert!(contents.contains("latency"), "{extension}: {contents}");
+        }
+    }
+
+    #[test]
+    fn flush_writes_results_so_far() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.json");
+        let mut writer = results_sink::open(&path, "target-qps").unwrap();
+        writer
+            .write_result("100", &results(&[("latency", &[1])]))
+            .unwrap();
+        writer.flush().unwrap();
+
+        let json: serde_json::Value = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 1);
+        writer
+            .write_result("200", &results(&[("latency", &[1])]))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let json: serde_json::Value = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        assert_eq!(json.as_array().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,298 @@
+//! Writing the results of a sweep to a Parquet file.
+
+use std::path::Path;
+
+use super::row_format::{Failure, RowFormat, RowOrder, RunMetadata, SinkOptions};
+use super::sidecar::{sidecar_path, Sidecar};
+use super::ResultsSink;
+use crate::axis::XValue;
+use crate::benchmark::BenchmarkResults;
+use crate::graph::GraphRun;
+use crate::parquet_output::{ParquetResults, ParquetRow};
+
+/// Writes the rows of results to a Parquet file, with a column of the same name as each column of
+/// CSV results. Parquet files can't be appended to, so the whole file is rewritten each time the
+/// sink is flushed.
+pub(crate) struct ParquetSink {
+    format: RowFormat,
+    parquet: ParquetResults,
+    reorder: Option<RowOrder>,
+    sidecar: Option<Sidecar>,
+    finished: bool,
+}
+
+impl ParquetSink {
+    pub(crate) fn new(
+        path: &Path,
+        format: RowFormat,
+        options: SinkOptions,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            format,
+            parquet: ParquetResults::new(path.to_owned()),
+            reorder: options.planned_runs.map(RowOrder::new),
+            sidecar: Some(Sidecar::create(sidecar_path(path))?),
+            finished: false,
+        })
+    }
+
+    /// Sets the columns of the file, followed by the rows of the runs that failed before they
+    /// were known.
+    fn start(&mut self, failures: Vec<(GraphRun, Failure)>) -> anyhow::Result<()> {
+        let format = &self.format;
+        let with_stddev = format.with_stddev;
+        let columns = format.fixed_columns();
+        let num_resource_usage = format
+            .resource_usage
+            .map_or(0, |output| output.columns().len());
+        // Statistics are only whole numbers if they aren't averaged across runs, and if no run can
+        // fail and leave them NaN.
+        let integer_values = format
+            .stat_columns()
+            .iter()
+            .zip(&columns.units)
+            .flat_map(|((_, stat), unit)| {
+                let integer = !with_stddev
+                    && !format.keep_going
+                    && stat != "mean"
+                    && unit.scale.fract() == 0.0;
+                std::iter::once(integer).chain(with_stddev.then_some(false))
+            })
+            .chain(columns.ratios.iter().map(|_| false))
+            .chain(std::iter::repeat_n(false, num_resource_usage))
+            .chain(std::iter::once(false))
+            .collect();
+        let num_axes = 1 + usize::from(format.z_axis.is_some());
+        self.parquet
+            .set_columns(format.header(), num_axes, integer_values);
+        for (run, failure) in failures {
+            self.write_failed_row(&run, &failure)?;
+        }
+        Ok(())
+    }
+
+    fn push(
+        &mut self,
+        run: &GraphRun,
+        values: Vec<f64>,
+        text: Vec<String>,
+        result: serde_json::Value,
+    ) -> anyhow::Result<()> {
+        if let Some(sidecar) = &mut self.sidecar {
+            sidecar.write(&result)?;
+        }
+        if let Some(reorder) = &mut self.reorder {
+            reorder.push(run);
+        }
+        self.parquet.push(ParquetRow {
+            axes: std::iter::once(run.x.clone())
+                .chain(run.z_value().map(XValue::infer))
+                .collect(),
+            values,
+            text,
+        });
+        Ok(())
+    }
+
+    fn write_failed_row(&mut self, run: &GraphRun, failure: &Failure) -> anyhow::Result<()> {
+        let text =
+            self.format
+                .text_columns(failure.timed_out, failure.attempts, None, &failure.error);
+        let result = self.format.failed_json(run, failure);
+        self.push(run, self.format.failed_values(), text, result)
+    }
+}
+
+impl ResultsSink for ParquetSink {
+    fn write_result(&mut self, x_value: &str, results: &BenchmarkResults) -> anyhow::Result<()> {
+        let run = self.format.run(x_value);
+        self.write_results(&run, std::slice::from_ref(results), None)
+    }
+
+    fn write_results(
+        &mut self,
+        run: &GraphRun,
+        results: &[BenchmarkResults],
+        metadata: Option<&RunMetadata>,
+    ) -> anyhow::Result<()> {
+        let metadata = self.format.metadata(run, metadata)?;
+        let (results, failures) = self.format.start_row(run, results)?;
+        if let Some(failures) = failures {
+            self.start(failures)?;
+        }
+        let row = self.format.row(self.format.fixed_columns(), &results);
+        let text = self
+            .format
+            .text_columns(row.timed_out, row.attempts, metadata, "");
+        let result = self.format.json(run, &row, &results, metadata);
+        self.push(run, self.format.values(&row), text, result)
+    }
+
+    fn write_failure(&mut self, run: &GraphRun, error: &anyhow::Error) -> anyhow::Result<()> {
+        match self.format.failure(run, error)? {
+            Some(failure) => self.write_failed_row(run, &failure),
+            None => Ok(()),
+        }
+    }
+
+    /// Rewrites the file with the rows written so far. Rows are also copied to a
+    /// [sidecar](sidecar_path) as they're written.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.parquet.write()
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.finished = true;
+        if let Some(failures) = self.format.finish_rows() {
+            self.start(failures)?;
+        }
+        if let Some(reorder) = &self.reorder {
+            reorder.sort_tail(self.parquet.rows_mut());
+        }
+        self.flush()?;
+        self.sidecar.take().map_or(Ok(()), Sidecar::remove)
+    }
+}
+
+impl Drop for ParquetSink {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(e) = self.flush() {
+                eprintln!("Failed to write graph results: {e}");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::results_sink;
+    use crate::test_utils::{params, results};
+
+    /// Reads the rows of a Parquet file written by a [`ParquetSink`], which fit in a single
+    /// batch.
+    fn read_parquet(path: &Path) -> arrow_array::RecordBatch {
+        let reader = parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder::try_new(
+            File::open(path).unwrap(),
+        )
+        .unwrap()
+        .build()
+        .unwrap();
+        let mut batches = reader.map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(batches.len(), 1);
+        batches.pop().unwrap()
+    }
+
+    #[test]
+    fn parquet_output() {
+        use arrow_array::{Float64Array, Int64Array, UInt64Array};
+
+        let dir = tempfile::tempdir().unwrap();
+        let rows = [
+            (
+                "100",
+                results(&[("latency", &[1, 2, 3, 4]), ("errors", &[0])]),
+            ),
+            ("200", results(&[("latency", &[5]), ("errors", &[1])])),
+        ];
+        for extension in ["csv", "parquet"] {
+            let path = dir.path().join(format!("graph.{extension}"));
+            let mut writer = results_sink::open(&path, "target-qps").unwrap();
+            for (x_value, results) in &rows {
+                writer.write_result(x_value, results).unwrap();
+            }
+            writer.finish().unwrap();
+        }
+
+        let batch = read_parquet(&dir.path().join("graph.parquet"));
+        assert_eq!(batch.num_rows(), 2);
+        // The columns are named in the same way as the CSV header.
+        let header = csv::Reader::from_path(dir.path().join("graph.csv"))
+            .unwrap()
+            .headers()
+            .unwrap()
+            .iter()
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        let schema = batch.schema();
+        let columns = schema
+            .fields()
+            .iter()
+            .map(|field| field.name().clone())
+            .collect::<Vec<_>>();
+        assert_eq!(columns, header);
+
+        let column = |name: &str| batch.column_by_name(name).unwrap();
+        let x_values = column("target-qps")
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(x_values.iter().flatten().collect::<Vec<_>>(), [100, 200]);
+        let samples = column("latency samples")
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(samples.iter().flatten().collect::<Vec<_>>(), [4, 1]);
+        let p50 = column("latency p50 (us)")
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        let hist = rows[0].1.results["latency"].to_histogram();
+        assert_eq!(p50.value(0), hist.value_at_quantile(0.5));
+        let mean = column("latency mean (us)")
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(mean.value(0), hist.mean());
+    }
+
+    #[test]
+    fn parquet_with_runs_per_value() {
+        use arrow_array::Float64Array;
+        use arrow_schema::DataType;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("@rows", "a,b");
+        params.graph_results_paths = vec![dir.path().join("graph.parquet")];
+        params.runs_per_value = 2;
+        let mut writer = results_sink::from_params(&params).unwrap();
+        for run in params.runs() {
+            let repeated = [
+                results(&[("latency", &[10])]),
+                results(&[("latency", &[21])]),
+            ];
+            writer.write_results(&run, &repeated, None).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let batch = read_parquet(params.graph_results_path());
+        assert_eq!(batch.num_rows(), 2);
+        let schema = batch.schema();
+        assert_eq!(
+            schema.field_with_name("@rows").unwrap().data_type(),
+            &DataType::Utf8
+        );
+        // Averaged statistics aren't necessarily whole numbers.
+        let max = batch
+            .column_by_name("latency max (us)")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(max.iter().flatten().collect::<Vec<_>>(), [15.5, 15.5]);
+        assert!(schema.field_with_name("latency max stddev (us)").is_ok());
+    }
+
+    #[test]
+    fn parquet_cant_be_appended_to() {
+        let path = Path::new("graph.parquet");
+        assert!(results_sink::append(path, "target-qps").is_err());
+        let mut params = params("target-qps", "100");
+        params.graph_results_paths = vec![path.to_owned()];
+        params.append = true;
+        assert!(params.validate().is_err());
+    }
+}
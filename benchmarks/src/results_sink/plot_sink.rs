@@ -0,0 +1,616 @@
+//! Plotting the results of a sweep as a PNG or SVG graph.
+
+use std::ffi::OsStr;
+use std::fmt::Display;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail};
+use itertools::Itertools;
+use plotters::coord::ranged1d::{DefaultFormatting, KeyPointHint};
+use plotters::coord::types::RangedCoordf64;
+use plotters::coord::Shift;
+use plotters::prelude::*;
+
+use super::row_format::{
+    stat_columns, Failure, RowFormat, RunMetadata, SinkOptions, UNPLOTTED_STATS,
+};
+use super::sidecar::{sidecar_path, Sidecar};
+use super::ResultsSink;
+use crate::axis::XValue;
+use crate::benchmark::BenchmarkResults;
+use crate::graph::GraphRun;
+use crate::Quantiles;
+
+/// Plots the rows of results as a PNG or SVG graph, with a line for each statistic of each
+/// metric, and each z-value. The graph is redrawn each time the sink is flushed. Failed runs are
+/// left out.
+pub(crate) struct PlotSink {
+    format: RowFormat,
+    plot: Plot,
+    sidecar: Option<Sidecar>,
+    finished: bool,
+}
+
+impl PlotSink {
+    pub(crate) fn new(
+        path: &Path,
+        format: RowFormat,
+        options: SinkOptions,
+    ) -> anyhow::Result<Self> {
+        let mut plot = Plot::new(path);
+        plot.x_ticks = options.x_ticks;
+        plot.x_achieved_qps = options.x_achieved_qps;
+        Ok(Self {
+            format,
+            plot,
+            sidecar: Some(Sidecar::create(sidecar_path(path))?),
+            finished: false,
+        })
+    }
+
+    /// Sets the plotted columns and the label of the y-axis.
+    fn start(&mut self) {
+        let columns = self.format.stat_columns();
+        self.plot.y_label = columns
+            .iter()
+            .zip(&self.format.fixed_columns().units)
+            .filter(|((_, stat), _)| !UNPLOTTED_STATS.contains(&stat.as_str()))
+            .filter_map(|(_, unit)| unit.label)
+            .unique()
+            .join(", ");
+        self.plot.columns = columns;
+    }
+
+    fn write_failed_row(&mut self, run: &GraphRun, failure: &Failure) -> anyhow::Result<()> {
+        if let Some(sidecar) = &mut self.sidecar {
+            sidecar.write(&self.format.failed_json(run, failure))?;
+        }
+        Ok(())
+    }
+}
+
+impl ResultsSink for PlotSink {
+    fn write_result(&mut self, x_value: &str, results: &BenchmarkResults) -> anyhow::Result<()> {
+        let run = self.format.run(x_value);
+        self.write_results(&run, std::slice::from_ref(results), None)
+    }
+
+    fn write_results(
+        &mut self,
+        run: &GraphRun,
+        results: &[BenchmarkResults],
+        metadata: Option<&RunMetadata>,
+    ) -> anyhow::Result<()> {
+        let metadata = self.format.metadata(run, metadata)?;
+        let (results, failures) = self.format.start_row(run, results)?;
+        if let Some(failures) = failures {
+            self.start();
+            for (run, failure) in failures {
+                self.write_failed_row(&run, &failure)?;
+            }
+        }
+        let row = self.format.row(self.format.fixed_columns(), &results);
+        if let Some(sidecar) = &mut self.sidecar {
+            sidecar.write(&self.format.json(run, &row, &results, metadata))?;
+        }
+        let x = if self.plot.x_achieved_qps {
+            if row.achieved_qps.is_nan() {
+                bail!("Can't plot {run} at its achieved QPS: its elapsed time wasn't recorded");
+            }
+            row.achieved_qps
+        } else {
+            run.x.as_f64().ok_or_else(|| {
+                anyhow!(
+                    "Invalid {} value '{}' for a {} graph: not a number",
+                    self.format.x_axis,
+                    run.x_value,
+                    self.plot.format
+                )
+            })?
+        };
+        self.plot.points.push(PlotPoint {
+            z: run.z_value().map(str::to_owned),
+            x,
+            means: row.means,
+            stddevs: row.stddevs,
+        });
+        Ok(())
+    }
+
+    fn write_failure(&mut self, run: &GraphRun, error: &anyhow::Error) -> anyhow::Result<()> {
+        match self.format.failure(run, error)? {
+            Some(failure) => self.write_failed_row(run, &failure),
+            None => Ok(()),
+        }
+    }
+
+    /// Redraws the graph with the rows written so far. Rows are also copied to a
+    /// [sidecar](sidecar_path) as they're written.
+    fn flush(&mut self) -> anyhow::Result<()> {
+        self.plot
+            .render(&self.format.x_axis, self.format.z_axis.as_deref())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        self.finished = true;
+        if let Some(failures) = self.format.finish_rows() {
+            self.start();
+            for (run, failure) in failures {
+                self.write_failed_row(&run, &failure)?;
+            }
+        }
+        self.flush()?;
+        self.sidecar.take().map_or(Ok(()), Sidecar::remove)
+    }
+}
+
+impl Drop for PlotSink {
+    fn drop(&mut self) {
+        if !self.finished {
+            if let Err(e) = self.flush() {
+                eprintln!("Failed to write graph results: {e}");
+            }
+        }
+    }
+}
+
+/// The image formats that graph results can be plotted to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PlotFormat {
+    Png,
+    Svg,
+}
+
+impl PlotFormat {
+    /// Returns the plot format for `path` based on its extension, or `None` if it isn't an image.
+    pub(crate) fn from_path(path: &Path) -> Option<Self> {
+        match path.extension().and_then(OsStr::to_str) {
+            Some("png") => Some(Self::Png),
+            Some("svg") => Some(Self::Svg),
+            _ => None,
+        }
+    }
+}
+
+impl Display for PlotFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Png => write!(f, "PNG"),
+            Self::Svg => write!(f, "SVG"),
+        }
+    }
+}
+
+/// The label of the x-axis of graphs plotted with `--x-axis-achieved-qps`.
+pub(crate) const ACHIEVED_QPS_LABEL: &str = "achieved QPS";
+
+/// The size, in pixels, of plotted graphs.
+const PLOT_SIZE: (u32, u32) = (1280, 960);
+
+/// Results buffered until they can be plotted as a line for each metric and statistic.
+pub(crate) struct Plot {
+    path: PathBuf,
+    format: PlotFormat,
+    /// The metric and statistic of each value in a point.
+    columns: Vec<(String, String)>,
+    /// The label of the y-axis: the units of the plotted statistics, if they have any.
+    y_label: String,
+    points: Vec<PlotPoint>,
+    /// Where to place ticks on the x-axis. If empty, there is a tick at each point.
+    x_ticks: Vec<f64>,
+    /// Whether points are placed at the throughput their run achieved, rather than its x-value.
+    x_achieved_qps: bool,
+    /// Whether to log-scale the y-axis even if the values span less than two orders of magnitude.
+    pub(crate) log_y: bool,
+}
+
+/// A labeled line of `(x, y, stddev)` points.
+type Series = (String, Vec<(f64, f64, f64)>);
+
+/// The results of a single run, or the mean of repeated runs, to be plotted.
+struct PlotPoint {
+    z: Option<String>,
+    x: f64,
+    /// The value of each of the plot's columns.
+    means: Vec<f64>,
+    /// The standard deviation of each value across repeated runs, drawn as an error bar.
+    stddevs: Vec<f64>,
+}
+
+impl Plot {
+    /// Creates a plot for `path`, which must have an image extension.
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_owned(),
+            format: PlotFormat::from_path(path).unwrap_or(PlotFormat::Png),
+            columns: Vec::new(),
+            y_label: String::new(),
+            points: Vec::new(),
+            x_ticks: Vec::new(),
+            x_achieved_qps: false,
+            log_y: false,
+        }
+    }
+
+    /// Creates a plot for `path` of the statistics of `metrics` in each of `runs`, which are JSON
+    /// objects of the form written to `.json` results (see [`json_result`]). Runs that failed,
+    /// and so have no statistics, aren't plotted.
+    pub(crate) fn from_json(
+        path: &Path,
+        runs: &[serde_json::Value],
+        metrics: &[String],
+        quantiles: &Quantiles,
+    ) -> anyhow::Result<Self> {
+        let mut plot = Self::new(path);
+        plot.columns = stat_columns(metrics, quantiles);
+        plot.y_label = metrics
+            .iter()
+            .filter_map(|metric| {
+                runs.iter()
+                    .find_map(|run| run["metrics"][metric]["unit"].as_str())
+            })
+            .unique()
+            .join(", ");
+        let stat = |stats: &serde_json::Value, stat: &str| {
+            stats
+                .get(stat)
+                .unwrap_or(&stats["quantiles"][stat])
+                .as_f64()
+                .unwrap_or(f64::NAN)
+        };
+        for run in runs.iter().filter(|run| run.get("metrics").is_some()) {
+            let x = match &run["x_value"] {
+                serde_json::Value::String(x) => XValue::infer(x).as_f64(),
+                x => x.as_f64(),
+            }
+            .ok_or_else(|| {
+                anyhow!(
+                    "Invalid x-value {} for a {} graph: not a number",
+                    run["x_value"],
+                    plot.format
+                )
+            })?;
+            let z = run.get("z_value").map(|z| match z {
+                serde_json::Value::String(z) => z.clone(),
+                z => z.to_string(),
+            });
+            let means = plot
+                .columns
+                .iter()
+                .map(|(metric, s)| stat(&run["metrics"][metric], s))
+                .collect();
+            let stddevs = plot
+                .columns
+                .iter()
+                .map(|(metric, s)| match run["metrics"][metric].get("stddev") {
+                    Some(stddevs) => stat(stddevs, s),
+                    None => 0.0,
+                })
+                .collect();
+            plot.points.push(PlotPoint {
+                z,
+                x,
+                means,
+                stddevs,
+            });
+        }
+        Ok(plot)
+    }
+
+    pub(crate) fn render(&mut self, x_axis: &str, z_axis: Option<&str>) -> anyhow::Result<()> {
+        if self.points.is_empty() {
+            return Ok(());
+        }
+        self.points.sort_by(|a, b| a.x.total_cmp(&b.x));
+
+        // A line for each plotted statistic of each metric, for each z-value in the order they
+        // were run.
+        let z_values = self
+            .points
+            .iter()
+            .map(|point| &point.z)
+            .unique()
+            .collect::<Vec<_>>();
+        let columns = self
+            .columns
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, stat))| !UNPLOTTED_STATS.contains(&stat.as_str()))
+            .collect::<Vec<_>>();
+        let series = z_values
+            .iter()
+            .cartesian_product(&columns)
+            .map(|(z_value, (i, (metric, stat)))| {
+                let line = self
+                    .points
+                    .iter()
+                    .filter(|point| point.z == **z_value)
+                    .map(|point| (point.x, point.means[*i], point.stddevs[*i]))
+                    .collect();
+                let label = match (z_axis, z_value) {
+                    (Some(z_axis), Some(z_value)) => {
+                        format!("{metric} {stat} ({z_axis} = {z_value})")
+                    }
+                    _ => format!("{metric} {stat}"),
+                };
+                (label, line)
+            })
+            .collect::<Vec<Series>>();
+
+        match self.format {
+            PlotFormat::Png => {
+                let root = BitMapBackend::new(&self.path, PLOT_SIZE).into_drawing_area();
+                self.present(&root, x_axis, &series)
+            }
+            PlotFormat::Svg => {
+                let root = SVGBackend::new(&self.path, PLOT_SIZE).into_drawing_area();
+                self.present(&root, x_axis, &series)
+            }
+        }
+    }
+
+    fn present<DB: DrawingBackend>(
+        &self,
+        root: &DrawingArea<DB, Shift>,
+        x_axis: &str,
+        series: &[Series],
+    ) -> anyhow::Result<()> {
+        let x_ticks = if self.x_ticks.is_empty() {
+            self.points.iter().map(|point| point.x).dedup().collect()
+        } else {
+            self.x_ticks.clone()
+        };
+        let x_axis = if self.x_achieved_qps {
+            ACHIEVED_QPS_LABEL
+        } else {
+            x_axis
+        };
+        draw_graph(root, x_axis, &self.y_label, x_ticks, series, self.log_y)
+            .map_err(|e| anyhow!("Failed to render graph to {}: {e}", self.path.display()))?;
+        root.present()
+            .map_err(|e| anyhow!("Failed to write graph to {}: {e}", self.path.display()))
+    }
+}
+
+/// Draws each of `series` as a labeled line of `(x, y, stddev)` points, with a tick at each of
+/// `x_ticks` and an error bar of one standard deviation at each point that has one. The y-axis is
+/// labelled `y_label`, and is log-scaled if the values span more than two orders of magnitude, or
+/// if `log_y` is set and every value is positive.
+fn draw_graph<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    x_axis: &str,
+    y_label: &str,
+    x_ticks: Vec<f64>,
+    series: &[Series],
+    log_y: bool,
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>> {
+    let points = || series.iter().flat_map(|(_, line)| line.iter());
+    let (x_min, x_max) = points()
+        .map(|(x, _, _)| *x)
+        .minmax_by(f64::total_cmp)
+        .into_option()
+        .unwrap_or((0.0, 1.0));
+    let (y_min, y_max) = points()
+        .flat_map(|(_, y, stddev)| [y - stddev, y + stddev])
+        .minmax_by(f64::total_cmp)
+        .into_option()
+        .unwrap_or((0.0, 1.0));
+    let x_range = TickedAxis {
+        range: widen(x_min, x_max).into(),
+        ticks: x_ticks,
+    };
+
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(root);
+    chart.margin(20).x_label_area_size(50).y_label_area_size(80);
+
+    if y_min > 0.0 && (log_y || y_max / y_min > 100.0) {
+        let mut chart = chart.build_cartesian_2d(x_range, (y_min..y_max).log_scale())?;
+        chart
+            .configure_mesh()
+            .x_desc(x_axis)
+            .y_desc(y_label)
+            .draw()?;
+        draw_series(&mut chart, series)?;
+    } else {
+        let mut chart = chart.build_cartesian_2d(x_range, widen(y_min.min(0.0), y_max))?;
+        chart
+            .configure_mesh()
+            .x_desc(x_axis)
+            .y_desc(y_label)
+            .draw()?;
+        draw_series(&mut chart, series)?;
+    }
+    Ok(())
+}
+
+fn draw_series<'a, DB, X, Y>(
+    chart: &mut ChartContext<'a, DB, Cartesian2d<X, Y>>,
+    series: &[Series],
+) -> Result<(), DrawingAreaErrorKind<DB::ErrorType>>
+where
+    DB: DrawingBackend + 'a,
+    X: Ranged<ValueType = f64>,
+    Y: Ranged<ValueType = f64>,
+{
+    for (i, (label, line)) in series.iter().enumerate() {
+        let color = Palette99::pick(i).to_rgba();
+        chart
+            .draw_series(LineSeries::new(
+                line.iter().map(|(x, y, _)| (*x, *y)),
+                color.stroke_width(2),
+            ))?
+            .label(label)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+        chart.draw_series(line.iter().filter(|(_, _, stddev)| *stddev > 0.0).map(
+            |(x, y, stddev)| {
+                ErrorBar::new_vertical(*x, y - stddev, *y, y + stddev, color.filled(), 10)
+            },
+        ))?;
+    }
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()
+}
+
+/// A numeric axis with ticks at the given positions, rather than at evenly spaced intervals.
+struct TickedAxis {
+    range: RangedCoordf64,
+    ticks: Vec<f64>,
+}
+
+impl Ranged for TickedAxis {
+    type FormatOption = DefaultFormatting;
+    type ValueType = f64;
+
+    fn map(&self, value: &f64, limit: (i32, i32)) -> i32 {
+        self.range.map(value, limit)
+    }
+
+    fn key_points<Hint: KeyPointHint>(&self, hint: Hint) -> Vec<f64> {
+        if hint.weight().allow_light_points() {
+            vec![]
+        } else {
+            self.ticks.clone()
+        }
+    }
+
+    fn range(&self) -> Range<f64> {
+        self.range.range()
+    }
+}
+
+/// Returns a range from `min` to `max`, padded if they're equal so the range isn't empty.
+fn widen(min: f64, max: f64) -> Range<f64> {
+    if min < max {
+        min..max
+    } else {
+        min - 1.0..max + 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+
+    use super::*;
+    use crate::results_sink;
+    use crate::test_utils::{params, results, timed_results, z_params};
+    use crate::QUANTILES;
+
+    #[test]
+    fn png_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.png");
+        let mut writer = results_sink::open(&path, "target-qps").unwrap();
+        writer
+            .write_result("100", &results(&[("latency", &[1, 2, 3])]))
+            .unwrap();
+        writer
+            .write_result("1000", &results(&[("latency", &[400, 5000, 60000])]))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let info = decoder.read_info().unwrap().info().clone();
+        assert!(info.width > 0 && info.height > 0);
+    }
+
+    #[test]
+    fn png_requires_numeric_x_values() {
+        let mut params = params("target-qps", "100,fast");
+        params.graph_results_paths = vec!["graph.png".into()];
+        params.validate().unwrap_err();
+    }
+
+    #[test]
+    fn svg_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.svg");
+        let mut writer = results_sink::open(&path, "target-qps").unwrap();
+        writer
+            .write_result("100", &results(&[("latency", &[1, 2, 3])]))
+            .unwrap();
+        writer
+            .write_result("200", &results(&[("latency", &[4, 5, 6])]))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let svg = std::fs::read_to_string(&path).unwrap();
+        assert!(svg.contains("target-qps"));
+        assert!(svg.contains("latency p99"));
+        // The y-axis is labelled with the unit of the plotted statistics.
+        assert!(svg.contains("\nus\n"), "{svg}");
+        // One line per plotted series: the mean and each quantile.
+        let series = 1 + QUANTILES.len();
+        assert!(svg.matches("<polyline").count() >= series);
+    }
+
+    #[test]
+    fn svg_with_z_axis() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = z_params("100,200", "workers", "1,2");
+        params.graph_results_paths = vec![dir.path().join("graph.svg")];
+        let mut writer = results_sink::from_params(&params).unwrap();
+        for run in params.runs() {
+            writer
+                .write_results(&run, &[results(&[("latency", &[1, 2])])], None)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let svg = std::fs::read_to_string(params.graph_results_path()).unwrap();
+        assert!(svg.contains("latency mean (workers = 1)"));
+        assert!(svg.contains("latency mean (workers = 2)"));
+    }
+
+    #[test]
+    fn plot_at_achieved_qps() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut qps = params("target-qps", "1000,2000");
+        qps.graph_results_paths = vec![dir.path().join("graph.svg")];
+        qps.x_axis_achieved_qps = true;
+        qps.validate().unwrap();
+        let mut writer = PlotSink::new(
+            qps.graph_results_path(),
+            RowFormat::from_params(&qps),
+            SinkOptions::from_params(&qps),
+        )
+        .unwrap();
+        // The second run fell well short of its target.
+        writer
+            .write_result("1000", &timed_results(1000, 1))
+            .unwrap();
+        writer
+            .write_result("2000", &timed_results(1250, 1))
+            .unwrap();
+        let plot = &writer.plot;
+        assert_eq!(
+            plot.points.iter().map(|point| point.x).collect::<Vec<_>>(),
+            [1000.0, 1250.0]
+        );
+        assert!(plot.x_ticks.is_empty());
+        let err = writer
+            .write_result("3000", &results(&[("latency", &[1])]))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("elapsed time wasn't recorded"), "{err}");
+        writer.finish().unwrap();
+
+        let svg = std::fs::read_to_string(qps.graph_results_path()).unwrap();
+        assert!(svg.contains(ACHIEVED_QPS_LABEL));
+
+        // Runs are placed at their throughput, so their x-values needn't be numbers.
+        let mut modes = params("mode", "fast,slow");
+        modes.graph_results_paths = vec!["graph.png".into()];
+        modes.x_axis_achieved_qps = true;
+        modes.validate().unwrap();
+        modes.graph_results_paths = vec!["graph.csv".into()];
+        let err = modes.validate().unwrap_err().to_string();
+        assert!(err.contains("--x-axis-achieved-qps requires"), "{err}");
+    }
+}
@@ -0,0 +1,62 @@
+//! Pushing the results of a sweep to a Prometheus Pushgateway.
+
+use super::row_format::{RowFormat, RunMetadata};
+use super::ResultsSink;
+use crate::benchmark::BenchmarkResults;
+use crate::graph::{GraphParams, GraphRun};
+use crate::push_gateway::{self, PushGateway};
+
+/// Pushes the statistics of each row of results to a Pushgateway as soon as it's written. Only
+/// the mean of each statistic across repeated runs is pushed, and failed runs aren't pushed.
+pub struct PushGatewaySink {
+    format: RowFormat,
+    gateway: PushGateway,
+}
+
+impl PushGatewaySink {
+    /// Creates a sink for the sweep described by `params` that pushes the results of each run to
+    /// `gateway`.
+    pub fn new(params: &GraphParams, gateway: PushGateway) -> Self {
+        Self {
+            format: RowFormat::from_params(params),
+            gateway,
+        }
+    }
+}
+
+impl ResultsSink for PushGatewaySink {
+    fn write_result(&mut self, x_value: &str, results: &BenchmarkResults) -> anyhow::Result<()> {
+        let run = self.format.run(x_value);
+        self.write_results(&run, std::slice::from_ref(results), None)
+    }
+
+    fn write_results(
+        &mut self,
+        run: &GraphRun,
+        results: &[BenchmarkResults],
+        metadata: Option<&RunMetadata>,
+    ) -> anyhow::Result<()> {
+        self.format.metadata(run, metadata)?;
+        let (results, _) = self.format.start_row(run, results)?;
+        let row = self.format.row(self.format.fixed_columns(), &results);
+        let body = push_gateway::render(
+            run,
+            &self.format.stat_columns(),
+            &row.means,
+            row.achieved_qps,
+            &results[0],
+            self.format.tag.as_ref(),
+        );
+        self.gateway.push(run, body);
+        Ok(())
+    }
+
+    fn write_failure(&mut self, run: &GraphRun, error: &anyhow::Error) -> anyhow::Result<()> {
+        self.format.failure(run, error)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,2243 @@
+//! The rows of results written for each run of a sweep: which columns each row has, the statistics
+//! written for each metric, and the metadata and resource usage written alongside them.
+
+use std::collections::hash_map::Entry;
+use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::process::Command;
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+use anyhow::bail;
+use chrono::{SecondsFormat, Utc};
+use itertools::Itertools;
+use serde::Serialize;
+use serde_json::json;
+
+use crate::benchmark::{
+    query_metric, split_query_metric, unit_label, BenchmarkData, BenchmarkResults, MetricGoal,
+    TimeUnit,
+};
+use crate::graph::{GraphParams, GraphRun};
+use crate::report::Tag;
+use crate::resource_usage::{ProcessUsage, ResourceUsage};
+use crate::sweep::{FailedAttempts, RunTimedOut};
+use crate::Quantiles;
+
+/// The column holding the throughput each run achieved, in operations per second, written after
+/// the statistics and any ratios of `--compare-upstream`.
+pub const ACHIEVED_QPS_COLUMN: &str = "achieved_qps";
+
+/// The column recording whether each run timed out with `--run-timeout`, written after the
+/// [`ACHIEVED_QPS_COLUMN`].
+pub const TIMED_OUT_COLUMN: &str = "timed_out";
+
+/// The column recording how many attempts each run took with `--retries`, written after the
+/// [`TIMED_OUT_COLUMN`].
+pub const ATTEMPTS_COLUMN: &str = "attempts";
+
+/// The column written after the statistics of each run with a `--baseline-tag`, before any
+/// [`METADATA_COLUMNS`].
+pub const TAG_COLUMN: &str = "tag";
+
+/// A constant column added to every row of the results with `--tag key=value`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnTag {
+    /// The name of the column, made of letters, digits, dashes and underscores.
+    pub key: String,
+    pub value: String,
+}
+
+impl FromStr for ColumnTag {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((key, value)) = s.split_once('=') else {
+            bail!("Invalid tag '{s}': expected key=value");
+        };
+        let key = key.trim();
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!("Invalid tag key '{key}': expected letters, digits, dashes and underscores only");
+        }
+        Ok(Self {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+}
+
+/// Returns the `"tags"` object of a JSON row, mapping each of `tags`' keys to its value, or
+/// `None` if there are none.
+fn json_tags(tags: &[ColumnTag]) -> Option<serde_json::Value> {
+    (!tags.is_empty()).then(|| {
+        tags.iter()
+            .map(|tag| (tag.key.clone(), json!(tag.value)))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    })
+}
+
+/// The columns written with `--resource-usage`, after any ratio columns and before the
+/// [`ACHIEVED_QPS_COLUMN`]: the mean and max CPU utilization of the benchmark process over each
+/// run, in percent of a core, and its max resident memory. With `--resource-usage-pid`, they're
+/// followed by the same columns for that process, prefixed with [`TARGET_COLUMN_PREFIX`].
+pub const RESOURCE_USAGE_COLUMNS: &[&str] = &["cpu_mean_pct", "cpu_max_pct", "rss_max_bytes"];
+
+/// The prefix of the [`RESOURCE_USAGE_COLUMNS`] of the `--resource-usage-pid` process.
+pub const TARGET_COLUMN_PREFIX: &str = "target_";
+
+/// What's written of the resource usage sampled with `--resource-usage`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ResourceUsageOutput {
+    /// Whether a `--resource-usage-pid` process was sampled along with the benchmark.
+    target: bool,
+    /// Whether every sample is written to JSON, and not only the summary.
+    samples: bool,
+}
+
+impl ResourceUsageOutput {
+    pub(crate) fn from_params(params: &GraphParams) -> Option<Self> {
+        params.resource_usage.then(|| Self {
+            target: params.resource_usage_pid.is_some(),
+            samples: params.resource_usage_samples,
+        })
+    }
+
+    /// Returns the names of the resource usage columns.
+    pub(crate) fn columns(&self) -> Vec<String> {
+        let target = self.target.then(|| {
+            RESOURCE_USAGE_COLUMNS
+                .iter()
+                .map(|column| format!("{TARGET_COLUMN_PREFIX}{column}"))
+        });
+        RESOURCE_USAGE_COLUMNS
+            .iter()
+            .map(|column| (*column).to_owned())
+            .chain(target.into_iter().flatten())
+            .collect()
+    }
+
+    /// Returns the value of each of the [columns](Self::columns) for repeated runs, summarizing
+    /// the samples of every run together. Values that weren't sampled are NaN.
+    fn values(&self, results: &[BenchmarkResults]) -> Vec<f64> {
+        let usage = merged_resource_usage(results).unwrap_or_default();
+        let target = self
+            .target
+            .then(|| usage.target.unwrap_or_default().summary());
+        usage
+            .benchmark
+            .summary()
+            .into_iter()
+            .chain(target.into_iter().flatten())
+            .collect()
+    }
+
+    /// Returns the `"resource_usage"` object of a row: the summary of the benchmark process's
+    /// usage, and of the target's in a `"target"` object, each with a `"samples"` array of the
+    /// samples of each repeated run if every sample is written.
+    fn json(&self, results: &[BenchmarkResults]) -> serde_json::Value {
+        let process = |usage: &dyn Fn(&ResourceUsage) -> Option<&ProcessUsage>| {
+            let runs = results
+                .iter()
+                .map(|result| result.resource_usage.as_ref().and_then(usage))
+                .collect::<Vec<_>>();
+            let merged = ProcessUsage {
+                samples: runs
+                    .iter()
+                    .flatten()
+                    .flat_map(|usage| usage.samples.iter().copied())
+                    .collect(),
+            };
+            let mut json = RESOURCE_USAGE_COLUMNS
+                .iter()
+                .zip(merged.summary())
+                .map(|(column, value)| ((*column).to_owned(), json_number(value)))
+                .collect::<serde_json::Map<_, _>>();
+            if self.samples {
+                let samples = runs
+                    .iter()
+                    .map(|usage| usage.map_or(&[][..], |usage| &usage.samples))
+                    .collect::<Vec<_>>();
+                json.insert("samples".to_owned(), json!(samples));
+            }
+            serde_json::Value::from(json)
+        };
+        let mut json = process(&|usage| Some(&usage.benchmark));
+        if self.target {
+            json["target"] = process(&|usage| usage.target.as_ref());
+        }
+        json
+    }
+}
+
+/// Returns the resource usage sampled over every one of `results`, if any was.
+fn merged_resource_usage(results: &[BenchmarkResults]) -> Option<ResourceUsage> {
+    results
+        .iter()
+        .filter_map(|result| result.resource_usage.clone())
+        .reduce(ResourceUsage::merge)
+}
+
+/// The column holding the error of each run that failed with `--keep-going`, and empty for runs
+/// that succeeded. It's written last, after any [`METADATA_COLUMNS`].
+pub const ERROR_COLUMN: &str = "error";
+
+/// The longest error message written for a failed run, in characters. Longer messages are
+/// truncated, since the full error is logged when the run fails.
+pub const MAX_ERROR_LEN: usize = 200;
+
+/// The columns written after the statistics of each run with `--with-metadata`, in the order of
+/// [`RunMetadata::values`].
+pub const METADATA_COLUMNS: &[&str] = &[
+    "timestamp",
+    "commit",
+    "hostname",
+    "arguments",
+    "warmup_secs",
+    "cooldown_ms",
+];
+
+/// Provenance for a single run of a sweep, written alongside its results with `--with-metadata`
+/// so that results collected over time can be traced back to what produced them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RunMetadata {
+    /// When the run started, as an RFC 3339 timestamp in UTC.
+    pub timestamp: String,
+    /// The git commit being benchmarked, if it could be determined.
+    pub commit: Option<String>,
+    /// The name of the host the benchmark ran on, if it could be determined.
+    pub hostname: Option<String>,
+    /// The benchmark's arguments for the run, serialized as JSON.
+    pub arguments: String,
+    /// The number of seconds at the start of the run whose results were discarded, if any.
+    pub warmup_secs: Option<u64>,
+    /// How many milliseconds were spent cooling down before the run, if it followed another.
+    pub cooldown_ms: Option<u64>,
+}
+
+impl RunMetadata {
+    /// Returns the metadata for a run starting now with the given serialized `arguments` and
+    /// warmup.
+    ///
+    /// The commit is taken from `git rev-parse HEAD` in the working directory, falling back to
+    /// the `BUILDKITE_COMMIT` environment variable when that fails, and the hostname from the
+    /// `hostname` command or the `HOSTNAME` environment variable. Both are looked up once and
+    /// reused for every later run.
+    pub fn collect(arguments: String, warmup_secs: Option<u64>) -> Self {
+        static HOSTNAME: LazyLock<Option<String>> = LazyLock::new(|| {
+            command_output("hostname", &[]).or_else(|| std::env::var("HOSTNAME").ok())
+        });
+        Self {
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+            commit: current_commit().map(str::to_owned),
+            hostname: HOSTNAME.clone(),
+            arguments,
+            warmup_secs,
+            cooldown_ms: None,
+        }
+    }
+
+    /// Returns the value of each of [`METADATA_COLUMNS`], with an empty string for anything
+    /// that couldn't be determined.
+    fn values(&self) -> [String; 6] {
+        [
+            self.timestamp.clone(),
+            self.commit.clone().unwrap_or_default(),
+            self.hostname.clone().unwrap_or_default(),
+            self.arguments.clone(),
+            self.warmup_secs
+                .map(|secs| secs.to_string())
+                .unwrap_or_default(),
+            self.cooldown_ms
+                .map(|ms| ms.to_string())
+                .unwrap_or_default(),
+        ]
+    }
+}
+
+/// Returns the git commit being benchmarked, from `git rev-parse HEAD` in the working directory or
+/// else the `BUILDKITE_COMMIT` environment variable. It's looked up once and reused.
+pub(crate) fn current_commit() -> Option<&'static str> {
+    static COMMIT: LazyLock<Option<String>> = LazyLock::new(|| {
+        command_output("git", &["rev-parse", "HEAD"])
+            .or_else(|| std::env::var("BUILDKITE_COMMIT").ok())
+    });
+    COMMIT.as_deref()
+}
+
+/// Returns the trimmed standard output of running `program`, or `None` if it couldn't be run,
+/// failed, or printed nothing.
+fn command_output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    let stdout = String::from_utf8(output.stdout).ok()?;
+    let stdout = stdout.trim();
+    (output.status.success() && !stdout.is_empty()).then(|| stdout.to_owned())
+}
+
+/// How the rows of graph results are laid out, for the sink of every format: the statistics
+/// written for each metric, in which units, and the columns that follow them. The metrics, and so
+/// the columns, are fixed by the first results written.
+pub struct RowFormat {
+    pub(crate) x_axis: String,
+    pub(crate) z_axis: Option<String>,
+    /// The columns of every row, once the first results have been written.
+    columns: Option<RowColumns>,
+    /// The metrics to write, if not every metric in the results. Each may contain `*`
+    /// wildcards, as matched by [`metric_matches`].
+    metric_filter: Option<Vec<String>>,
+    /// Whether metrics recorded per query are written for each query, as well as for all of
+    /// them, rather than only for all of them. See [`group_by_query`].
+    breakdown_by_query: bool,
+    /// Whether each statistic is followed by its standard deviation across repeated runs.
+    pub(super) with_stddev: bool,
+    /// The tag written with each row, if any.
+    pub(super) tag: Option<Tag>,
+    /// The `--tag` columns written with each row, after the tag.
+    tags: Vec<ColumnTag>,
+    /// Whether each row is followed by the [`RunMetadata`] of its run.
+    with_metadata: bool,
+    /// The quantiles written for each metric, after the [`STATS`].
+    pub(super) quantiles: Quantiles,
+    /// The unit of time to convert time-valued metrics to, if any.
+    convert_units: Option<TimeUnit>,
+    /// Whether the results are of a `--compare-upstream` sweep, and so are followed by the ratio
+    /// of the targets' statistics.
+    compare_upstream: bool,
+    /// Whether failed runs can be written with `--keep-going`, in which case every row is followed
+    /// by an [`ERROR_COLUMN`].
+    pub(super) keep_going: bool,
+    /// Whether runs can time out, in which case every row has a [`TIMED_OUT_COLUMN`].
+    with_timeout: bool,
+    /// Whether failed runs are retried, in which case every row has an [`ATTEMPTS_COLUMN`].
+    with_retries: bool,
+    /// What's written of the resource usage of each run, if it was sampled.
+    pub(super) resource_usage: Option<ResourceUsageOutput>,
+    /// The runs that failed before any run succeeded. They're written once the metrics, and so
+    /// the header, are known.
+    deferred_failures: Vec<(GraphRun, Failure)>,
+}
+
+/// The columns of the rows of a [`RowFormat`], which are fixed by the first results written.
+#[derive(Clone)]
+pub(super) struct RowColumns {
+    /// The metric names of the first result written, in the order of [`metric_order`]. Every
+    /// later result must have the same set of metrics so that its columns line up with the
+    /// header.
+    metrics: Vec<String>,
+    /// The unit of each statistic written for the metrics, in the order of [`stat_columns`].
+    pub(super) units: Vec<OutputUnit>,
+    /// The ratios written after the statistics of each row, if the sweep compares targets.
+    pub(super) ratios: Vec<RatioColumn>,
+}
+
+/// The values of a row of results for repeated runs, averaged across them and converted to the
+/// units of their columns.
+pub(super) struct Row {
+    pub(super) means: Vec<f64>,
+    pub(super) stddevs: Vec<f64>,
+    ratios: Vec<f64>,
+    resource_usage: Vec<f64>,
+    pub(super) achieved_qps: f64,
+    pub(super) timed_out: bool,
+    /// The most attempts any of the runs took, if failed runs are retried.
+    pub(super) attempts: Option<u32>,
+}
+
+/// How a results sink writes its rows, besides their [`RowFormat`].
+#[derive(Default)]
+pub(crate) struct SinkOptions {
+    /// Whether rows are added to those already in the file, rather than replacing them.
+    pub(crate) append: bool,
+    /// With `--shuffle-runs`, the runs of the sweep in their planned order, which the rows are put
+    /// back in once the sink is finished.
+    pub(crate) planned_runs: Option<Vec<GraphRun>>,
+    /// Whether CSV and JSONL rows are left buffered instead of being flushed as they're written.
+    pub(crate) buffered: bool,
+    /// Where plotted graphs place ticks on the x-axis. If empty, there is a tick at each point.
+    pub(crate) x_ticks: Vec<f64>,
+    /// Whether plots and HTML reports place runs at the throughput they achieved, rather than
+    /// their x-value.
+    pub(crate) x_achieved_qps: bool,
+}
+
+impl SinkOptions {
+    /// Returns the options of the sweep described by `params`, which appends to its results with
+    /// `--resume` or `--append`. Plotted graphs are left to place a tick at each point.
+    pub(crate) fn from_params(params: &GraphParams) -> Self {
+        Self {
+            append: params.resume || params.append,
+            planned_runs: params.shuffle_runs.is_some().then(|| params.runs()),
+            buffered: params.buffered,
+            x_ticks: Vec::new(),
+            x_achieved_qps: params.x_axis_achieved_qps,
+        }
+    }
+}
+
+/// The rows written by a sweep whose runs execute in a shuffled order, which are rewritten in the
+/// planned order of the runs once the sweep finishes.
+pub(super) struct RowOrder {
+    /// The runs of the sweep, in their planned order.
+    planned: Vec<GraphRun>,
+    /// The planned index of the run of each row written, in the order they were written.
+    indices: Vec<usize>,
+    /// For CSV and JSONL files, which are written to as each row arrives, the length of the file
+    /// before the first row was written, and the bytes of each row.
+    start: Option<u64>,
+    rows: Vec<Vec<u8>>,
+}
+
+impl RowOrder {
+    pub(super) fn new(planned: Vec<GraphRun>) -> Self {
+        Self {
+            planned,
+            indices: Vec::new(),
+            start: None,
+            rows: Vec::new(),
+        }
+    }
+
+    /// Records a row for `run` in a buffered output.
+    pub(super) fn push(&mut self, run: &GraphRun) {
+        // Runs that weren't planned, which only tests write, go last.
+        let index = self
+            .planned
+            .iter()
+            .position(|planned| planned == run)
+            .unwrap_or(usize::MAX);
+        self.indices.push(index);
+    }
+
+    /// Records a row for `run` that is about to be written to `file` as `bytes`.
+    pub(super) fn push_bytes(
+        &mut self,
+        run: &GraphRun,
+        file: &File,
+        bytes: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        if self.start.is_none() {
+            self.start = Some(file.metadata()?.len());
+        }
+        self.push(run);
+        self.rows.push(bytes);
+        Ok(())
+    }
+
+    /// The positions of the rows written, in the planned order of their runs. Rows of the same
+    /// run stay in the order they were written.
+    fn sorted_positions(&self) -> Vec<usize> {
+        (0..self.indices.len())
+            .sorted_by_key(|i| self.indices[*i])
+            .collect()
+    }
+
+    /// Puts the last rows of `items`, one for each row recorded, into the planned order.
+    pub(super) fn sort_tail<T>(&self, items: &mut Vec<T>) {
+        let mut tail = items
+            .split_off(items.len() - self.indices.len())
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        items.extend(
+            self.sorted_positions()
+                .into_iter()
+                .map(|i| tail[i].take().unwrap()),
+        );
+    }
+
+    /// Replaces the rows recorded in `file` with the same rows in the planned order. The file's
+    /// writer must have been flushed.
+    pub(super) fn rewrite(&self, file: &File) -> anyhow::Result<()> {
+        let Some(start) = self.start else {
+            return Ok(());
+        };
+        // The clone shares the file's offset, so the writer carries on from the end of the rows.
+        let mut file = file.try_clone()?;
+        file.set_len(start)?;
+        file.seek(SeekFrom::Start(start))?;
+        for i in self.sorted_positions() {
+            file.write_all(&self.rows[i])?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
+
+/// How a run written by [`ResultsSink::write_failure`] failed.
+pub(super) struct Failure {
+    /// The error of the run, truncated to [`MAX_ERROR_LEN`] characters.
+    pub(super) error: String,
+    /// Whether the run failed by timing out.
+    pub(super) timed_out: bool,
+    /// How many attempts the run took, if failed runs are retried.
+    pub(super) attempts: Option<u32>,
+}
+
+/// The per-metric statistics written for each run, before the quantiles.
+pub(crate) const STATS: &[&str] = &["samples", "min", "max", "mean"];
+
+/// Statistics that aren't plotted, since they either aren't in the same units as the metric or
+/// would dominate the scale of the y-axis.
+pub(super) const UNPLOTTED_STATS: &[&str] = &["samples", "min", "max"];
+
+impl RowFormat {
+    /// Returns the format of rows of results along `x_axis` with the default configuration:
+    /// every metric, with the sample count, min, max, mean and each of the
+    /// [`QUANTILES`](crate::QUANTILES) of each, followed by the achieved QPS.
+    pub fn new(x_axis: &str) -> Self {
+        Self {
+            x_axis: x_axis.to_owned(),
+            z_axis: None,
+            columns: None,
+            metric_filter: None,
+            breakdown_by_query: false,
+            with_stddev: false,
+            tag: None,
+            tags: Vec::new(),
+            with_metadata: false,
+            quantiles: Quantiles::default(),
+            convert_units: None,
+            compare_upstream: false,
+            keep_going: false,
+            with_timeout: false,
+            with_retries: false,
+            resource_usage: None,
+            deferred_failures: Vec::new(),
+        }
+    }
+
+    /// Returns the format of the rows of the sweep described by `params`. A z-axis adds a column
+    /// after the x-value. With more than one `--runs-per-value`, every statistic is followed by
+    /// its standard deviation, and only the `--metrics` are written if given, with the
+    /// `--quantiles` of each. With `--with-metadata`, rows must be written with metadata.
+    pub fn from_params(params: &GraphParams) -> Self {
+        Self {
+            z_axis: params.z_axis.clone(),
+            metric_filter: params.metrics.as_ref().map(|metrics| metrics.to_vec()),
+            breakdown_by_query: params.breakdown_by_query,
+            with_stddev: params.runs_per_value > 1,
+            tag: params.baseline_tag.clone(),
+            tags: params.tags.clone(),
+            with_metadata: params.with_metadata,
+            quantiles: params.quantiles.clone(),
+            convert_units: params.convert_units,
+            compare_upstream: params.compare_upstream,
+            keep_going: params.keep_going,
+            with_timeout: params.run_timeout.is_some(),
+            with_retries: params.retries > 0,
+            resource_usage: ResourceUsageOutput::from_params(params),
+            ..Self::new(params.x_axis_name())
+        }
+    }
+
+    /// Returns the run with the x-value `x_value` and no z-value.
+    pub(super) fn run(&self, x_value: &str) -> GraphRun {
+        GraphRun::new(&self.x_axis, x_value)
+    }
+
+    /// Returns the `metadata` to write for `run`, which is only written with `--with-metadata`,
+    /// or an error if rows are written with metadata but `run` has none.
+    pub(super) fn metadata<'a>(
+        &self,
+        run: &GraphRun,
+        metadata: Option<&'a RunMetadata>,
+    ) -> anyhow::Result<Option<&'a RunMetadata>> {
+        Ok(match metadata {
+            Some(metadata) => self.with_metadata.then_some(metadata),
+            None if self.with_metadata => bail!("No metadata to write for {run}"),
+            None => None,
+        })
+    }
+
+    /// Returns `results` with the metrics recorded per query grouped (see [`group_by_query`]),
+    /// along with the columns of their row: those of the first results written, if any have been.
+    /// Returns an error if there are no results, or if they don't all have the same metrics as
+    /// the first results written.
+    pub(super) fn columns(
+        &self,
+        run: &GraphRun,
+        results: &[BenchmarkResults],
+    ) -> anyhow::Result<(Vec<BenchmarkResults>, RowColumns)> {
+        let results = results
+            .iter()
+            .map(|result| group_by_query(result, self.breakdown_by_query))
+            .collect::<Vec<_>>();
+        let Some(first) = results.first() else {
+            bail!("No results to write for {run}");
+        };
+        let columns = match &self.columns {
+            Some(columns) => columns.clone(),
+            None => self.columns_for(select_metrics(first, self.metric_filter.as_deref())?, first),
+        };
+        for result in &results {
+            let metrics = select_metrics(result, self.metric_filter.as_deref())?;
+            if metrics != columns.metrics {
+                bail!(
+                    "Results for {run} have metrics [{}], but previous results had [{}]",
+                    metrics.join(", "),
+                    columns.metrics.join(", ")
+                );
+            }
+        }
+        Ok((results, columns))
+    }
+
+    /// Returns the columns of rows of `metrics`, labelling each statistic with the unit of its
+    /// metric in `result`.
+    fn columns_for(&self, metrics: Vec<String>, result: &BenchmarkResults) -> RowColumns {
+        let stats = stat_columns(&metrics, &self.quantiles);
+        let units = stats
+            .iter()
+            .map(|(metric, stat)| column_unit(&result.results[metric], stat, self.convert_units))
+            .collect();
+        let ratios = if self.compare_upstream {
+            RatioColumn::for_columns(&stats)
+        } else {
+            Vec::new()
+        };
+        RowColumns {
+            metrics,
+            units,
+            ratios,
+        }
+    }
+
+    /// Starts writing a row of `results` for `run`, returning them grouped by query along with,
+    /// if they're the first results written, the failures deferred until then. The columns of
+    /// the first results become those of every row, so a sink writes its header before the
+    /// deferred failures and the row.
+    #[allow(clippy::type_complexity)]
+    pub(super) fn start_row(
+        &mut self,
+        run: &GraphRun,
+        results: &[BenchmarkResults],
+    ) -> anyhow::Result<(Vec<BenchmarkResults>, Option<Vec<(GraphRun, Failure)>>)> {
+        let (results, columns) = self.columns(run, results)?;
+        if self.columns.is_some() {
+            return Ok((results, None));
+        }
+        self.columns = Some(columns);
+        Ok((results, Some(std::mem::take(&mut self.deferred_failures))))
+    }
+
+    /// For a sweep that's finished without any run succeeding, fixes the columns to those of no
+    /// metrics and returns the failed runs, so that a sink can write them after a header.
+    pub(super) fn finish_rows(&mut self) -> Option<Vec<(GraphRun, Failure)>> {
+        if self.columns.is_some() || self.deferred_failures.is_empty() {
+            return None;
+        }
+        self.columns = Some(self.columns_for(Vec::new(), &BenchmarkResults::new()));
+        Some(std::mem::take(&mut self.deferred_failures))
+    }
+
+    /// The columns of the first results written. Only called once there have been some.
+    pub(super) fn fixed_columns(&self) -> &RowColumns {
+        self.columns.as_ref().expect("no results have been written")
+    }
+
+    /// The metric and statistic of each statistic column, in the order of [`stat_columns`].
+    pub(super) fn stat_columns(&self) -> Vec<(String, String)> {
+        stat_columns(&self.fixed_columns().metrics, &self.quantiles)
+    }
+
+    /// Returns the header naming each column: the axes, each statistic of every metric with its
+    /// unit, followed by its standard deviation if there is one, then the ratios, the resource
+    /// usage, the achieved QPS and the text columns (see [`Self::text_columns`]).
+    pub(super) fn header(&self) -> Vec<String> {
+        let columns = self.fixed_columns();
+        let resource_usage_columns = self
+            .resource_usage
+            .map(|output| output.columns())
+            .unwrap_or_default();
+        let metadata_columns = if self.with_metadata {
+            METADATA_COLUMNS
+        } else {
+            &[]
+        };
+        std::iter::once(self.x_axis.clone())
+            .chain(self.z_axis.clone())
+            .chain(self.stat_columns().iter().zip(&columns.units).flat_map(
+                |((metric, stat), unit)| {
+                    let name = format!("{metric} {stat}");
+                    let stddev = self
+                        .with_stddev
+                        .then(|| unit.label_column(format!("{name} stddev")));
+                    std::iter::once(unit.label_column(name)).chain(stddev)
+                },
+            ))
+            .chain(columns.ratios.iter().map(RatioColumn::name))
+            .chain(resource_usage_columns.iter().cloned())
+            .chain(std::iter::once(ACHIEVED_QPS_COLUMN.to_owned()))
+            .chain(self.with_timeout.then(|| TIMED_OUT_COLUMN.to_owned()))
+            .chain(self.with_retries.then(|| ATTEMPTS_COLUMN.to_owned()))
+            .chain(self.tag.as_ref().map(|_| TAG_COLUMN.to_owned()))
+            .chain(self.tags.iter().map(|tag| tag.key.clone()))
+            .chain(metadata_columns.iter().map(|column| (*column).to_owned()))
+            .chain(self.keep_going.then(|| ERROR_COLUMN.to_owned()))
+            .collect()
+    }
+
+    /// Returns the row of repeated runs with the given `columns`, whose `results` are grouped by
+    /// query. Each statistic is computed for every run separately and then averaged, along with
+    /// its sample standard deviation across runs.
+    pub(super) fn row(&self, columns: &RowColumns, results: &[BenchmarkResults]) -> Row {
+        let rows = results
+            .iter()
+            .map(|result| stats_row(result, &columns.metrics, &self.quantiles))
+            .collect::<Vec<_>>();
+        let (means, stddevs) = mean_and_stddev(&rows);
+        let [means, stddevs] = [means, stddevs].map(|values| {
+            values
+                .iter()
+                .zip(&columns.units)
+                .map(|(value, unit)| value * unit.scale)
+                .collect::<Vec<_>>()
+        });
+        Row {
+            ratios: columns
+                .ratios
+                .iter()
+                .map(|column| column.ratio(&means))
+                .collect(),
+            resource_usage: self
+                .resource_usage
+                .map(|output| output.values(results))
+                .unwrap_or_default(),
+            achieved_qps: achieved_qps(results),
+            timed_out: results.iter().any(|result| result.timed_out),
+            attempts: self
+                .with_retries
+                .then(|| results.iter().map(|result| result.attempts).max().unwrap()),
+            means,
+            stddevs,
+        }
+    }
+
+    /// Returns the numeric columns of `row`: each statistic, followed by its standard deviation
+    /// if rows have them, then the ratios, the resource usage and the achieved QPS.
+    pub(super) fn values(&self, row: &Row) -> Vec<f64> {
+        let mut values = if self.with_stddev {
+            row.means.iter().interleave(&row.stddevs).copied().collect()
+        } else {
+            row.means.clone()
+        };
+        values.extend(&row.ratios);
+        values.extend(&row.resource_usage);
+        values.push(row.achieved_qps);
+        values
+    }
+
+    /// Returns the numeric columns of a failed run, which are all NaN.
+    pub(super) fn failed_values(&self) -> Vec<f64> {
+        let columns = self.fixed_columns();
+        let num_resource_usage = self
+            .resource_usage
+            .map_or(0, |output| output.columns().len());
+        let num_values = columns.units.len() * if self.with_stddev { 2 } else { 1 }
+            + columns.ratios.len()
+            + num_resource_usage
+            + 1;
+        vec![f64::NAN; num_values]
+    }
+
+    /// Returns the text columns that follow the numeric columns of a row: whether the run
+    /// `timed_out` and its `attempts`, if runs can time out and are retried, the tags, the run's
+    /// `metadata` with `--with-metadata`, and with `--keep-going`, the `error` of a failed run,
+    /// which is empty for a run that succeeded. Failed runs have no metadata, but its columns are
+    /// still filled.
+    pub(super) fn text_columns(
+        &self,
+        timed_out: bool,
+        attempts: Option<u32>,
+        metadata: Option<&RunMetadata>,
+        error: &str,
+    ) -> Vec<String> {
+        let metadata = match metadata {
+            Some(metadata) => metadata.values().into(),
+            None if self.with_metadata => vec![String::new(); METADATA_COLUMNS.len()],
+            None => Vec::new(),
+        };
+        self.with_timeout
+            .then(|| timed_out.to_string())
+            .into_iter()
+            .chain(attempts.map(|attempts| attempts.to_string()))
+            .chain(self.tag.iter().map(Tag::to_string))
+            .chain(self.tags.iter().map(|tag| tag.value.clone()))
+            .chain(metadata)
+            .chain(self.keep_going.then(|| error.to_owned()))
+            .collect()
+    }
+
+    /// Returns the JSON object written for `row` of the grouped `results` of `run` (see
+    /// [`json_result`]), with its ratios, resource usage, whether it timed out, its attempts and
+    /// its tags.
+    pub(super) fn json(
+        &self,
+        run: &GraphRun,
+        row: &Row,
+        results: &[BenchmarkResults],
+        metadata: Option<&RunMetadata>,
+    ) -> serde_json::Value {
+        let columns = self.fixed_columns();
+        let mut result = json_result(
+            run,
+            &columns.metrics,
+            &columns.units,
+            &self.quantiles,
+            &row.means,
+            self.with_stddev.then_some(row.stddevs.as_slice()),
+            results,
+            json_meta(metadata, self.tag.as_ref()),
+        );
+        if !row.ratios.is_empty() {
+            result["ratios"] = json_ratios(&columns.ratios, &row.ratios);
+        }
+        if let Some(output) = &self.resource_usage {
+            result["resource_usage"] = output.json(results);
+        }
+        if row.timed_out {
+            result["timed_out"] = json!(true);
+        }
+        if let Some(attempts) = row.attempts {
+            result["attempts"] = json!(attempts);
+        }
+        if let Some(tags) = json_tags(&self.tags) {
+            result["tags"] = tags;
+        }
+        result
+    }
+
+    /// Returns the JSON object written for a `run` that failed (see [`json_failure`]).
+    pub(super) fn failed_json(&self, run: &GraphRun, failure: &Failure) -> serde_json::Value {
+        json_failure(run, failure, self.tag.as_ref(), json_tags(&self.tags))
+    }
+
+    /// Records that `run` failed with `error`, which is only written with `--keep-going`. Returns
+    /// how it failed, to write it in a row, unless no run has succeeded yet, in which case it's
+    /// deferred until the columns are known (see [`Self::start_row`]).
+    pub(super) fn failure(
+        &mut self,
+        run: &GraphRun,
+        error: &anyhow::Error,
+    ) -> anyhow::Result<Option<Failure>> {
+        if !self.keep_going {
+            bail!("Can't write the failure of {run} without --keep-going");
+        }
+        let failure = Failure {
+            error: truncate_error(error),
+            timed_out: error.downcast_ref::<RunTimedOut>().is_some(),
+            attempts: self.with_retries.then(|| {
+                error
+                    .downcast_ref::<FailedAttempts>()
+                    .map_or(1, |failed| failed.attempts)
+            }),
+        };
+        if self.columns.is_none() {
+            self.deferred_failures.push((run.clone(), failure));
+            return Ok(None);
+        }
+        Ok(Some(failure))
+    }
+}
+
+/// The unit that the values of a statistic are written in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(super) struct OutputUnit {
+    /// The label of the unit, if it has one.
+    pub(super) label: Option<&'static str>,
+    /// The factor to convert recorded values to this unit by.
+    pub(super) scale: f64,
+}
+
+impl OutputUnit {
+    /// The unit of statistics that aren't in the units of their metric, such as sample counts.
+    const NONE: Self = Self {
+        label: None,
+        scale: 1.0,
+    };
+
+    /// Returns the unit that values recorded in `unit` are written in, which is `convert_to` if
+    /// both are units of time.
+    fn new(unit: Option<metrics::Unit>, convert_to: Option<TimeUnit>) -> Self {
+        let Some(unit) = unit else {
+            return Self::NONE;
+        };
+        match (TimeUnit::from_metric_unit(unit), convert_to) {
+            (Some(from), Some(to)) => Self {
+                label: Some(to.label()),
+                scale: from.scale_to(to),
+            },
+            _ => Self {
+                label: unit_label(unit),
+                scale: 1.0,
+            },
+        }
+    }
+
+    /// Returns `column` followed by the label of the unit in parentheses, if there is one.
+    fn label_column(&self, column: String) -> String {
+        match self.label {
+            Some(label) => format!("{column} ({label})"),
+            None => column,
+        }
+    }
+}
+
+/// The prefix of the metrics recorded against ReadySet in a `--compare-upstream` sweep.
+pub const READYSET_PREFIX: &str = "readyset_";
+
+/// The prefix of the metrics recorded against the upstream database in a `--compare-upstream`
+/// sweep.
+pub const UPSTREAM_PREFIX: &str = "upstream_";
+
+/// A column of a `--compare-upstream` sweep holding the ratio of ReadySet's value of a statistic
+/// to the upstream database's.
+#[derive(Clone)]
+pub(super) struct RatioColumn {
+    /// The metric, without the prefix of its target.
+    metric: String,
+    stat: String,
+    /// The index of ReadySet's and then the upstream database's value of the statistic in a row
+    /// of [`stat_columns`].
+    values: [usize; 2],
+    /// The index of each target's sample count, which is zero if its run failed.
+    samples: [usize; 2],
+}
+
+impl RatioColumn {
+    /// Returns a ratio column for each statistic but the sample count of every metric in
+    /// `columns` that was recorded against both targets.
+    fn for_columns(columns: &[(String, String)]) -> Vec<Self> {
+        let position =
+            |metric: &str, stat: &str| columns.iter().position(|(m, s)| m == metric && s == stat);
+        columns
+            .iter()
+            .filter(|(_, stat)| stat != "samples")
+            .filter_map(|(metric, stat)| {
+                let base = metric.strip_prefix(READYSET_PREFIX)?;
+                let upstream = format!("{UPSTREAM_PREFIX}{base}");
+                Some(Self {
+                    metric: base.to_owned(),
+                    stat: stat.clone(),
+                    values: [position(metric, stat)?, position(&upstream, stat)?],
+                    samples: [
+                        position(metric, "samples")?,
+                        position(&upstream, "samples")?,
+                    ],
+                })
+            })
+            .collect()
+    }
+
+    /// The name of the column, e.g. `latency p99 ratio`.
+    fn name(&self) -> String {
+        format!("{} {} ratio", self.metric, self.stat)
+    }
+
+    /// Returns the ratio for a row of `values` in the order of [`stat_columns`], or NaN if
+    /// either target has no samples.
+    fn ratio(&self, values: &[f64]) -> f64 {
+        if self.samples.iter().any(|i| values[*i] == 0.0) {
+            return f64::NAN;
+        }
+        values[self.values[0]] / values[self.values[1]]
+    }
+}
+
+/// Describes how the `existing` header of a CSV file differs from the `expected` header.
+pub(super) fn header_diff(expected: &[String], existing: &[String]) -> String {
+    let missing = expected
+        .iter()
+        .filter(|column| !existing.contains(column))
+        .collect::<Vec<_>>();
+    let extra = existing
+        .iter()
+        .filter(|column| !expected.contains(column))
+        .collect::<Vec<_>>();
+    let mut diff = Vec::new();
+    if !missing.is_empty() {
+        diff.push(format!("missing columns [{}]", missing.iter().join(", ")));
+    }
+    if !extra.is_empty() {
+        diff.push(format!("unexpected columns [{}]", extra.iter().join(", ")));
+    }
+    if diff.is_empty() {
+        return format!(
+            "the columns are in a different order; expected [{}], but found [{}]",
+            expected.join(", "),
+            existing.join(", ")
+        );
+    }
+    diff.join("; ")
+}
+
+/// Returns the metric and statistic of each value returned by [`stats_row`] for results with the
+/// given (sorted) metrics.
+pub(super) fn stat_columns(metrics: &[String], quantiles: &Quantiles) -> Vec<(String, String)> {
+    metrics
+        .iter()
+        .flat_map(|metric| {
+            STATS
+                .iter()
+                .copied()
+                .chain(quantiles.labels())
+                .map(move |stat| (metric.clone(), stat.to_owned()))
+        })
+        .collect()
+}
+
+/// Returns the names of the metrics to write from `result`, in the order of [`metric_order`]:
+/// those matching `filter` if there is one, or every metric otherwise. Returns an error listing
+/// the available metrics if any metric or pattern in the filter doesn't match a metric in
+/// `result`.
+fn select_metrics(
+    result: &BenchmarkResults,
+    filter: Option<&[String]>,
+) -> anyhow::Result<Vec<String>> {
+    let available = result
+        .results
+        .keys()
+        .sorted_by(|a, b| metric_order(a).cmp(&metric_order(b)))
+        .cloned()
+        .collect::<Vec<_>>();
+    let Some(filter) = filter else {
+        return Ok(available);
+    };
+
+    let unknown = filter
+        .iter()
+        .filter(|pattern| {
+            !available
+                .iter()
+                .any(|metric| metric_matches(pattern, metric))
+        })
+        .collect::<Vec<_>>();
+    if !unknown.is_empty() {
+        bail!(
+            "Unknown --metrics [{}]; the available metrics are [{}]",
+            unknown.iter().join(", "),
+            available.join(", ")
+        );
+    }
+    Ok(available
+        .into_iter()
+        .filter(|metric| filter.iter().any(|pattern| metric_matches(pattern, metric)))
+        .collect())
+}
+
+/// Returns the unit that `stat` of the metric with `data` is written in. Sample counts have no
+/// unit.
+fn column_unit(data: &BenchmarkData, stat: &str, convert_units: Option<TimeUnit>) -> OutputUnit {
+    match stat {
+        "samples" => OutputUnit::NONE,
+        _ => OutputUnit::new(data.metric_unit(), convert_units),
+    }
+}
+
+/// A statistic of a metric, as written for a row of results.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WrittenStat {
+    pub metric: String,
+    pub stat: String,
+    /// The name of the statistic's column, with the label of its unit, e.g. `latency p99 (us)`.
+    pub column: String,
+    /// Whether larger or smaller values of the metric are better.
+    pub goal: MetricGoal,
+    /// The value written, averaged across repeated runs and converted to the column's unit.
+    pub value: f64,
+}
+
+/// Returns every statistic that a results sink created from `params` writes for a row
+/// of the repeated runs `results`, with the same values, in the same order. The standard
+/// deviations and other columns that follow the statistics aren't included.
+pub fn written_stats(
+    results: &[BenchmarkResults],
+    params: &GraphParams,
+) -> anyhow::Result<Vec<WrittenStat>> {
+    let results = results
+        .iter()
+        .map(|result| group_by_query(result, params.breakdown_by_query))
+        .collect::<Vec<_>>();
+    let Some(first) = results.first() else {
+        return Ok(vec![]);
+    };
+    let filter = params.metrics.as_ref().map(|metrics| metrics.to_vec());
+    let metrics = select_metrics(first, filter.as_deref())?;
+    for result in &results[1..] {
+        if select_metrics(result, filter.as_deref())? != metrics {
+            bail!("Repeated runs recorded different metrics");
+        }
+    }
+    let rows = results
+        .iter()
+        .map(|result| stats_row(result, &metrics, &params.quantiles))
+        .collect::<Vec<_>>();
+    let (means, _) = mean_and_stddev(&rows);
+    Ok(stat_columns(&metrics, &params.quantiles)
+        .into_iter()
+        .zip(means)
+        .map(|((metric, stat), value)| {
+            let data = &first.results[&metric];
+            let unit = column_unit(data, &stat, params.convert_units);
+            WrittenStat {
+                column: unit.label_column(format!("{metric} {stat}")),
+                goal: data.desired_action,
+                value: value * unit.scale,
+                metric,
+                stat,
+            }
+        })
+        .collect())
+}
+
+/// Returns each statistic written for a row of repeated runs, named by its `"<metric> <stat>"`
+/// column and averaged across `results` in the same way as [`ResultsSink::write_results`],
+/// with the metrics recorded per query grouped as with `--breakdown-by-query` if
+/// `breakdown_by_query` is set.
+pub fn aggregate_stats(
+    results: &[BenchmarkResults],
+    quantiles: &Quantiles,
+    breakdown_by_query: bool,
+) -> Vec<(String, f64)> {
+    let results = results
+        .iter()
+        .map(|result| group_by_query(result, breakdown_by_query))
+        .collect::<Vec<_>>();
+    let Some(first) = results.first() else {
+        return vec![];
+    };
+    let metrics = first.results.keys().sorted().cloned().collect::<Vec<_>>();
+    let rows = results
+        .iter()
+        .map(|result| stats_row(result, &metrics, quantiles))
+        .collect::<Vec<_>>();
+    let (means, _) = mean_and_stddev(&rows);
+    stat_columns(&metrics, quantiles)
+        .into_iter()
+        .map(|(metric, stat)| format!("{metric} {stat}"))
+        .zip(means)
+        .collect()
+}
+
+/// Combines the results of running a value of a `--compare-upstream` sweep against ReadySet and
+/// against the upstream database, prefixing each metric with [`READYSET_PREFIX`] or
+/// [`UPSTREAM_PREFIX`]. Each of the repeated runs of one target is paired with the same run of
+/// the other.
+///
+/// If the runs against one target failed, the error is logged and that target's metrics are
+/// recorded without any samples, so that the rest of the sweep can still run. Returns the error
+/// of the ReadySet runs if both failed.
+pub fn compare_targets(
+    readyset: anyhow::Result<Vec<BenchmarkResults>>,
+    upstream: anyhow::Result<Vec<BenchmarkResults>>,
+) -> anyhow::Result<Vec<BenchmarkResults>> {
+    let (readyset, upstream) = match (readyset, upstream) {
+        (Ok(readyset), Ok(upstream)) => (readyset, upstream),
+        (Ok(readyset), Err(e)) => {
+            tracing::warn!("Run against the upstream database failed: {e}");
+            let upstream = readyset.iter().map(without_samples).collect();
+            (readyset, upstream)
+        }
+        (Err(e), Ok(upstream)) => {
+            tracing::warn!("Run against ReadySet failed: {e}");
+            let readyset = upstream.iter().map(without_samples).collect();
+            (readyset, upstream)
+        }
+        (Err(e), Err(_)) => return Err(e),
+    };
+    Ok(readyset
+        .into_iter()
+        .zip(upstream)
+        .map(|(readyset, upstream)| {
+            with_prefix(readyset, READYSET_PREFIX).merge(with_prefix(upstream, UPSTREAM_PREFIX))
+        })
+        .collect())
+}
+
+/// The query name of the rollup of every per-query metric (see [`query_metric`]) across all of the
+/// queries, written with `--breakdown-by-query`.
+pub const ALL_QUERIES: &str = "all";
+
+/// Returns `results` with the values of each metric recorded per query merged across every
+/// query, as they're written: with `breakdown`, into an added `query/all/<metric>`, and otherwise
+/// into a `<metric>` that replaces the per-query metrics, merging them with any metric the
+/// benchmark recorded under that name. The metrics of each target of a `--compare-upstream` sweep
+/// are merged separately.
+pub fn group_by_query(results: &BenchmarkResults, breakdown: bool) -> BenchmarkResults {
+    let mut grouped = results.clone();
+    if !breakdown {
+        grouped
+            .results
+            .retain(|name, _| split_target_query_metric(name).is_none());
+    }
+    for (name, data) in &results.results {
+        let Some((target, _, metric)) = split_target_query_metric(name) else {
+            continue;
+        };
+        let rollup = if breakdown {
+            format!("{target}{}", query_metric(ALL_QUERIES, metric))
+        } else {
+            format!("{target}{metric}")
+        };
+        match grouped.results.entry(rollup) {
+            Entry::Occupied(mut entry) => {
+                let existing = entry.get_mut();
+                existing.values.add(&data.values).unwrap();
+                existing.samples.extend(&data.samples);
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(data.clone());
+            }
+        }
+    }
+    grouped
+}
+
+/// Splits the name of a per-query metric into the `--compare-upstream` prefix of its target (or
+/// `""`), its query and its metric, or returns `None` if `name` isn't a per-query metric.
+fn split_target_query_metric(name: &str) -> Option<(&str, &str, &str)> {
+    ["", READYSET_PREFIX, UPSTREAM_PREFIX]
+        .into_iter()
+        .find_map(|target| {
+            let (query, metric) = split_query_metric(name.strip_prefix(target)?)?;
+            Some((target, query, metric))
+        })
+}
+
+/// Returns the key that the metrics written for each run are ordered by: first every metric that
+/// isn't recorded per query, by name, and then those that are, grouped by query with the
+/// [`ALL_QUERIES`] rollup first.
+fn metric_order(name: &str) -> (u8, &str, &str, &str) {
+    match split_target_query_metric(name) {
+        None => (0, name, "", ""),
+        Some((target, ALL_QUERIES, metric)) => (1, ALL_QUERIES, metric, target),
+        Some((target, query, metric)) => (2, query, metric, target),
+    }
+}
+
+/// Whether the metric `name` matches `pattern`, in which each `*` matches any part of a single
+/// level of the `/`-separated metric name. For example, `query/*/latency` matches
+/// `query/q3/latency` but not `query/q3/read/latency`.
+fn metric_matches(pattern: &str, name: &str) -> bool {
+    let Some((prefix, rest)) = pattern.split_once('*') else {
+        return pattern == name;
+    };
+    let Some(name) = name.strip_prefix(prefix) else {
+        return false;
+    };
+    let level = name.find('/').unwrap_or(name.len());
+    (0..=level)
+        .filter(|&i| name.is_char_boundary(i))
+        .any(|i| metric_matches(rest, &name[i..]))
+}
+
+/// Returns `results` with `prefix` added to the name of every metric.
+fn with_prefix(results: BenchmarkResults, prefix: &str) -> BenchmarkResults {
+    let mut prefixed = BenchmarkResults::new();
+    prefixed.elapsed = results.elapsed;
+    prefixed.discarded_samples = results.discarded_samples;
+    prefixed.timed_out = results.timed_out;
+    prefixed.attempts = results.attempts;
+    prefixed.results = results
+        .results
+        .into_iter()
+        .map(|(metric, data)| (format!("{prefix}{metric}"), data))
+        .collect();
+    prefixed
+}
+
+/// Returns results with the same metrics as `results`, but no samples.
+fn without_samples(results: &BenchmarkResults) -> BenchmarkResults {
+    let mut empty = BenchmarkResults::new();
+    empty.results = results
+        .results
+        .iter()
+        .map(|(metric, data)| {
+            let mut values = data.to_histogram();
+            values.reset();
+            let data = BenchmarkData {
+                unit: data.unit.clone(),
+                desired_action: data.desired_action,
+                values,
+                samples: Vec::new(),
+            };
+            (metric.clone(), data)
+        })
+        .collect();
+    empty
+}
+
+/// Returns the sample count, min, max, mean and each of `quantiles` for each of `metrics` in
+/// `results`, which must all be present.
+fn stats_row(results: &BenchmarkResults, metrics: &[String], quantiles: &Quantiles) -> Vec<f64> {
+    let mut row = Vec::new();
+    for metric in metrics {
+        let hist = results.results[metric].to_histogram();
+        row.push(hist.len() as f64);
+        row.push(hist.min() as f64);
+        row.push(hist.max() as f64);
+        row.push(hist.mean());
+        for (_, quantile) in quantiles.iter() {
+            row.push(hist.value_at_quantile(quantile) as f64);
+        }
+    }
+    row
+}
+
+/// Returns the mean and sample standard deviation of each column of `rows`, which must all be the
+/// same length. The standard deviation of a single row is zero.
+fn mean_and_stddev(rows: &[Vec<f64>]) -> (Vec<f64>, Vec<f64>) {
+    let n = rows.len() as f64;
+    let columns = rows.first().map_or(0, Vec::len);
+    (0..columns)
+        .map(|i| {
+            let mean = rows.iter().map(|row| row[i]).sum::<f64>() / n;
+            if rows.len() < 2 {
+                return (mean, 0.0);
+            }
+            let variance = rows.iter().map(|row| (row[i] - mean).powi(2)).sum::<f64>() / (n - 1.0);
+            (mean, variance.sqrt())
+        })
+        .unzip()
+}
+
+/// Returns the results of a run as a JSON object of the form
+/// `{"x_value": x, "metrics": {metric: {"samples", "min", "max", "mean", "quantiles": {..}}}}`,
+/// with a `"z_value"` if the run has one. Axis values are written as numbers if they are numeric.
+///
+/// `values` holds the statistics of each of `metrics` in the order written by [`stats_row`], and
+/// `units` the unit of each of them; metrics with a unit get a `"unit"` field. If `stddevs` is
+/// given, each metric also gets a `"stddev"` object of the same form. If every one of the
+/// `results` recorded its elapsed time, their mean is written as `"elapsed_secs"` along with
+/// their mean `"achieved_qps"`, and any `meta` (see [`json_meta`]) is written as a `"meta"` object.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn json_result(
+    run: &GraphRun,
+    metrics: &[String],
+    units: &[OutputUnit],
+    quantiles: &Quantiles,
+    values: &[f64],
+    stddevs: Option<&[f64]>,
+    results: &[BenchmarkResults],
+    meta: Option<serde_json::Value>,
+) -> serde_json::Value {
+    let columns_per_metric = STATS.len() + quantiles.len();
+    let stats = |values: &[f64]| {
+        let (stats, quantile_values) = values.split_at(STATS.len());
+        let mut stats = STATS
+            .iter()
+            .zip(stats)
+            .map(|(name, v)| ((*name).to_owned(), json_number(*v)))
+            .collect::<serde_json::Map<_, _>>();
+        let quantile_values = quantiles
+            .labels()
+            .zip(quantile_values)
+            .map(|(name, v)| (name.to_owned(), json_number(*v)))
+            .collect::<serde_json::Map<_, _>>();
+        stats.insert("quantiles".to_owned(), quantile_values.into());
+        stats
+    };
+    let metrics = metrics
+        .iter()
+        .enumerate()
+        .map(|(i, metric)| {
+            let columns = i * columns_per_metric..(i + 1) * columns_per_metric;
+            let mut result = stats(&values[columns.clone()]);
+            if let Some(stddevs) = stddevs {
+                result.insert("stddev".to_owned(), stats(&stddevs[columns.clone()]).into());
+            }
+            // The first statistic is the sample count, which has no unit.
+            if let Some(label) = units.get(columns.start + 1).and_then(|unit| unit.label) {
+                result.insert("unit".to_owned(), label.into());
+            }
+            (metric.clone(), result.into())
+        })
+        .collect::<serde_json::Map<_, _>>();
+    let mut result = json!({ "x_value": run.x.to_json(), "metrics": metrics });
+    if let Some(z_value) = run.z_value() {
+        result["z_value"] = json_value(z_value);
+    }
+    let elapsed = results
+        .iter()
+        .map(|result| result.elapsed.map(|elapsed| elapsed.as_secs_f64()))
+        .collect::<Option<Vec<_>>>()
+        .map(|elapsed| elapsed.iter().sum::<f64>() / elapsed.len() as f64);
+    if let Some(elapsed) = elapsed {
+        result["elapsed_secs"] = json!(elapsed);
+        result["achieved_qps"] = json_number(achieved_qps(results));
+    }
+    if let Some(meta) = meta {
+        result["meta"] = meta;
+    }
+    result
+}
+
+/// Returns the JSON object written for a `run` that failed with `--keep-going`: its axis values
+/// and error, whether it timed out, how many attempts it took, and the `tag` and `"tags"` of the
+/// results, if any.
+fn json_failure(
+    run: &GraphRun,
+    failure: &Failure,
+    tag: Option<&Tag>,
+    tags: Option<serde_json::Value>,
+) -> serde_json::Value {
+    let mut result = json!({ "x_value": run.x.to_json(), "error": failure.error });
+    if let Some(z_value) = run.z_value() {
+        result["z_value"] = json_value(z_value);
+    }
+    if failure.timed_out {
+        result["timed_out"] = json!(true);
+    }
+    if let Some(attempts) = failure.attempts {
+        result["attempts"] = json!(attempts);
+    }
+    if let Some(meta) = json_meta(None, tag) {
+        result["meta"] = meta;
+    }
+    if let Some(tags) = tags {
+        result["tags"] = tags;
+    }
+    result
+}
+
+/// Returns the alternate (`{:#}`) form of `error`, with its causes, truncated to
+/// [`MAX_ERROR_LEN`] characters followed by `...` if it's any longer.
+fn truncate_error(error: &anyhow::Error) -> String {
+    let message = format!("{error:#}");
+    match message.char_indices().nth(MAX_ERROR_LEN) {
+        Some((end, _)) => format!("{}...", &message[..end]),
+        None => message,
+    }
+}
+
+/// Returns the mean throughput achieved by `results` in operations per second (see
+/// [`BenchmarkResults::achieved_qps`]), or NaN unless every one of them recorded a nonzero
+/// elapsed time.
+fn achieved_qps(results: &[BenchmarkResults]) -> f64 {
+    results
+        .iter()
+        .map(BenchmarkResults::achieved_qps)
+        .collect::<Option<Vec<_>>>()
+        .filter(|qps| !qps.is_empty())
+        .map_or(f64::NAN, |qps| qps.iter().sum::<f64>() / qps.len() as f64)
+}
+
+/// Returns the `"ratios"` object of a JSON row, of the form `{metric: {stat: ratio}}`, with
+/// `null` for ratios that couldn't be computed.
+fn json_ratios(columns: &[RatioColumn], ratios: &[f64]) -> serde_json::Value {
+    let mut json = json!({});
+    for (column, ratio) in columns.iter().zip(ratios) {
+        json[&column.metric][&column.stat] = json!(ratio);
+    }
+    json
+}
+
+/// Returns the `"meta"` object of a JSON row: the run's metadata, if any, along with the `tag` of
+/// the results, if any.
+fn json_meta(metadata: Option<&RunMetadata>, tag: Option<&Tag>) -> Option<serde_json::Value> {
+    let mut meta = metadata.map(|metadata| json!(metadata));
+    if let Some(tag) = tag {
+        meta.get_or_insert_with(|| json!({}))["tag"] = json!(tag);
+    }
+    meta
+}
+
+/// Returns `value` as a JSON integer if it is a whole number, so that counts and histogram values
+/// aren't written with a trailing `.0`.
+pub(crate) fn json_number(value: f64) -> serde_json::Value {
+    if value.fract() == 0.0 && (0.0..u64::MAX as f64).contains(&value) {
+        json!(value as u64)
+    } else {
+        json!(value)
+    }
+}
+
+/// Returns `value` as a JSON number if it is one, or as a string otherwise.
+pub(crate) fn json_value(value: &str) -> serde_json::Value {
+    serde_json::from_str::<serde_json::Number>(value.trim())
+        .map_or_else(|_| json!(value), serde_json::Value::Number)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use anyhow::anyhow;
+    use hdrhistogram::Histogram;
+    use metrics::Unit;
+
+    use super::*;
+    use crate::results_sink;
+    use crate::test_utils::{metadata, params, results, run, timed_results};
+    use crate::QUANTILES;
+
+    #[test]
+    fn custom_quantiles() {
+        let dir = tempfile::tempdir().unwrap();
+        for extension in ["csv", "json"] {
+            let mut params = params("target-qps", "100");
+            params.graph_results_paths = vec![dir.path().join(format!("graph.{extension}"))];
+            params.quantiles = "0.5,0.9999".parse().unwrap();
+            let mut writer = results_sink::from_params(&params).unwrap();
+            let values = (1..=1000).collect::<Vec<u64>>();
+            writer
+                .write_result("100", &results(&[("latency", &values)]))
+                .unwrap();
+            writer.finish().unwrap();
+
+            if extension == "csv" {
+                let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+                let header = reader.headers().unwrap().clone();
+                assert_eq!(
+                    header.iter().collect::<Vec<_>>(),
+                    [
+                        "target-qps",
+                        "latency samples",
+                        "latency min (us)",
+                        "latency max (us)",
+                        "latency mean (us)",
+                        "latency p50 (us)",
+                        "latency p99.99 (us)",
+                        ACHIEVED_QPS_COLUMN
+                    ]
+                );
+                let row = reader.records().next().unwrap().unwrap();
+                assert_eq!(&row[6], "1000");
+            } else {
+                let json: serde_json::Value =
+                    serde_json::from_reader(File::open(params.graph_results_path()).unwrap())
+                        .unwrap();
+                let quantiles = &json[0]["metrics"]["latency"]["quantiles"];
+                assert_eq!(quantiles, &json!({ "p50": 500, "p99.99": 1000 }));
+            }
+        }
+    }
+
+    #[test]
+    fn to_csv_row() {
+        let results = results(&[("latency", &[1, 2, 3, 4]), ("errors", &[0])]);
+        let row = run("100")
+            .to_csv_row(&[results], &RowFormat::new("target-qps"), None)
+            .unwrap();
+        assert_eq!(
+            row,
+            [
+                "100", // target-qps
+                "1", "0", "0", "0", // errors samples, min, max and mean
+                "0", "0", "0", "0", "0", "0", "0", // errors p10 to p99.9
+                "4", "1", "4", "2.5", // latency samples, min, max and mean
+                "1", "2", "3", "4", "4", "4", "4",   // latency p10 to p99.9
+                "NaN", // achieved QPS, without an elapsed time
+            ]
+        );
+    }
+
+    #[test]
+    fn to_csv_row_matches_writer() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.csv");
+        let mut params = params("target-qps", "100");
+        params.graph_results_paths = vec![path.clone()];
+        params.z_axis = Some("connections".to_owned());
+        params.z_values = Some("8".parse().unwrap());
+        let run = params.runs().remove(0);
+        let mut results = results(&[("latency", &(1..=1000).collect::<Vec<_>>())]);
+        results.elapsed = Some(Duration::from_secs(4));
+
+        let mut writer = results_sink::from_params(&params).unwrap();
+        writer
+            .write_results(&run, std::slice::from_ref(&results), None)
+            .unwrap();
+        writer.finish().unwrap();
+        let row = csv::Reader::from_path(&path)
+            .unwrap()
+            .records()
+            .next()
+            .unwrap()
+            .unwrap();
+        let expected = run
+            .to_csv_row(&[results], &RowFormat::from_params(&params), None)
+            .unwrap();
+        assert_eq!(row.iter().collect::<Vec<_>>(), expected);
+        assert_eq!(&expected[..2], ["100", "8"]);
+        assert_eq!(expected.last().unwrap(), "250");
+    }
+
+    #[test]
+    fn to_csv_row_follows_params() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100");
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        params.quantiles = "0.5".parse().unwrap();
+        params.metrics = Some("query/*/latency".parse().unwrap());
+        params.breakdown_by_query = true;
+        params.convert_units = Some(TimeUnit::Ms);
+        let results = results(&[
+            ("query/q1/latency", &[1000, 2000]),
+            ("query/q2/latency", &[1000, 1000, 1000]),
+            ("errors", &[0]),
+        ]);
+        let row = run("100")
+            .to_csv_row(
+                std::slice::from_ref(&results),
+                &RowFormat::from_params(&params),
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            row,
+            [
+                "100", // target-qps
+                // The sample count, min, max, mean and p50 of each metric, in milliseconds.
+                "5", "1", "2", "1.2", "1", // query/all/latency
+                "2", "1", "2", "1.5", "1", // query/q1/latency
+                "3", "1", "1", "1", "1",   // query/q2/latency
+                "NaN", // achieved QPS
+            ]
+        );
+
+        let mut writer = results_sink::from_params(&params).unwrap();
+        writer.write_result("100", &results).unwrap();
+        writer.finish().unwrap();
+        let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+        let written = reader.records().next().unwrap().unwrap();
+        assert_eq!(written.iter().collect::<Vec<_>>(), row);
+    }
+
+    #[test]
+    fn values_outside_unit_range() {
+        // Metrics aren't scaled or clamped to any range before their statistics are computed, so
+        // values in the thousands and beyond are reported as they were recorded.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.csv");
+        let mut writer = results_sink::open(&path, "target-qps").unwrap();
+        let mut values = (1000..=2000).collect::<Vec<u64>>();
+        values.push(1_000_000);
+        writer
+            .write_result("100", &results(&[("latency", &values)]))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let header = reader.headers().unwrap().clone();
+        let row = reader.records().next().unwrap().unwrap();
+        let value = |column: &str| -> f64 {
+            let i = header.iter().position(|c| c == column).unwrap();
+            row[i].parse().unwrap()
+        };
+        assert_eq!(value("latency min (us)"), 1000.0);
+        assert_eq!(value("latency p50 (us)"), 1500.0);
+        assert_eq!(value("latency p99 (us)"), 1991.0);
+        // Large values are recorded to three significant figures.
+        let max = value("latency max (us)");
+        assert!((max - 1_000_000.0).abs() / 1_000_000.0 < 1e-3, "{max}");
+    }
+
+    #[test]
+    fn csv_header_matches_rows() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.csv");
+        let mut writer = results_sink::open(&path, "target-qps").unwrap();
+        writer
+            .write_result(
+                "100",
+                &results(&[("latency", &[1, 2, 3]), ("errors", &[0])]),
+            )
+            .unwrap();
+        writer
+            .write_result(
+                "200",
+                &results(&[("errors", &[1]), ("latency", &[4, 5, 6])]),
+            )
+            .unwrap();
+        drop(writer);
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let header = reader.headers().unwrap().clone();
+        let columns_per_metric = STATS.len() + QUANTILES.len();
+        assert_eq!(header.len(), 2 + 2 * columns_per_metric);
+        assert_eq!(&header[0], "target-qps");
+        assert_eq!(&header[1], "errors samples");
+        assert_eq!(&header[1 + columns_per_metric], "latency samples");
+        assert_eq!(&header[header.len() - 2], "latency p99.9 (us)");
+        assert_eq!(&header[header.len() - 1], ACHIEVED_QPS_COLUMN);
+
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(rows.len(), 2);
+        for row in &rows {
+            assert_eq!(row.len(), header.len());
+        }
+        let column = |name: &str| header.iter().position(|h| h == name).unwrap();
+        assert_eq!(&rows[0][0], "100");
+        assert_eq!(&rows[0][column("latency samples")], "3");
+        assert_eq!(&rows[0][column("latency max (us)")], "3");
+        assert_eq!(&rows[1][column("errors min (us)")], "1");
+        assert_eq!(&rows[1][column("latency min (us)")], "4");
+    }
+
+    #[test]
+    fn convert_units() {
+        let dir = tempfile::tempdir().unwrap();
+        for extension in ["csv", "json"] {
+            let mut params = params("target-qps", "100");
+            params.graph_results_paths = vec![dir.path().join(format!("graph.{extension}"))];
+            params.quantiles = "0.5".parse().unwrap();
+            params.convert_units = Some(TimeUnit::Ms);
+            let mut writer = results_sink::from_params(&params).unwrap();
+            let mut results = results(&[("latency", &[1500, 2500])]);
+            let mut rows = Histogram::<u64>::new(3).unwrap();
+            rows.record(7).unwrap();
+            results.push("rows", Unit::Count, MetricGoal::Increasing, rows);
+            writer.write_result("100", &results).unwrap();
+            writer.finish().unwrap();
+
+            if extension == "csv" {
+                let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+                let header = reader.headers().unwrap().clone();
+                assert_eq!(
+                    header.iter().collect::<Vec<_>>(),
+                    [
+                        "target-qps",
+                        "latency samples",
+                        "latency min (ms)",
+                        "latency max (ms)",
+                        "latency mean (ms)",
+                        "latency p50 (ms)",
+                        "rows samples",
+                        "rows min",
+                        "rows max",
+                        "rows mean",
+                        "rows p50",
+                        ACHIEVED_QPS_COLUMN,
+                    ]
+                );
+                let row = reader.records().next().unwrap().unwrap();
+                assert_eq!(&row[1], "2");
+                assert_eq!(&row[2], "1.5");
+                assert_eq!(&row[7], "7");
+            } else {
+                let json: serde_json::Value =
+                    serde_json::from_reader(File::open(params.graph_results_path()).unwrap())
+                        .unwrap();
+                let metrics = &json[0]["metrics"];
+                assert_eq!(metrics["latency"]["unit"], "ms");
+                assert_eq!(metrics["latency"]["samples"], 2);
+                assert_eq!(metrics["latency"]["min"], 1.5);
+                assert!(metrics["rows"].get("unit").is_none(), "{metrics}");
+            }
+        }
+    }
+
+    #[test]
+    fn compare_targets_prefixes_metrics() {
+        let mut readyset = results(&[("latency", &[1, 2])]);
+        readyset.record_elapsed_time(Duration::from_secs(1));
+        let mut upstream = results(&[("latency", &[4])]);
+        upstream.record_elapsed_time(Duration::from_secs(2));
+        let combined = compare_targets(Ok(vec![readyset]), Ok(vec![upstream])).unwrap();
+        assert_eq!(combined.len(), 1);
+        assert_eq!(
+            combined[0].metric_names(),
+            ["readyset_latency", "upstream_latency"]
+        );
+        assert_eq!(combined[0].results["upstream_latency"].values.max(), 4);
+        assert_eq!(combined[0].elapsed, Some(Duration::from_secs(3)));
+
+        let combined = compare_targets(
+            Err(anyhow!("unreachable")),
+            Ok(vec![results(&[("latency", &[4])])]),
+        )
+        .unwrap();
+        assert!(combined[0].results["readyset_latency"].values.is_empty());
+        assert_eq!(combined[0].results["upstream_latency"].values.len(), 1);
+
+        let err = compare_targets(Err(anyhow!("readyset")), Err(anyhow!("upstream"))).unwrap_err();
+        assert_eq!(err.to_string(), "readyset");
+    }
+
+    #[test]
+    fn compare_upstream_ratios() {
+        let dir = tempfile::tempdir().unwrap();
+        for extension in ["csv", "json"] {
+            let mut params = params("target-qps", "100,200");
+            params.graph_results_paths = vec![dir.path().join(format!("graph.{extension}"))];
+            params.quantiles = "0.5".parse().unwrap();
+            params.compare_upstream = true;
+            let mut writer = results_sink::from_params(&params).unwrap();
+            let compared = compare_targets(
+                Ok(vec![results(&[("latency", &[100])])]),
+                Ok(vec![results(&[("latency", &[400])])]),
+            )
+            .unwrap();
+            writer.write_results(&run("100"), &compared, None).unwrap();
+            let failed = compare_targets(
+                Ok(vec![results(&[("latency", &[100])])]),
+                Err(anyhow!("connection refused")),
+            )
+            .unwrap();
+            writer.write_results(&run("200"), &failed, None).unwrap();
+            writer.finish().unwrap();
+
+            if extension == "csv" {
+                let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+                let header = reader.headers().unwrap().clone();
+                assert_eq!(&header[1], "readyset_latency samples");
+                assert_eq!(&header[6], "upstream_latency samples");
+                assert_eq!(
+                    header.iter().skip(11).collect::<Vec<_>>(),
+                    [
+                        "latency min ratio",
+                        "latency max ratio",
+                        "latency mean ratio",
+                        "latency p50 ratio",
+                        ACHIEVED_QPS_COLUMN
+                    ]
+                );
+                let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+                assert_eq!(&rows[0][14], "0.25");
+                assert_eq!(&rows[1][6], "0");
+                assert_eq!(&rows[1][14], "NaN");
+            } else {
+                let json: serde_json::Value =
+                    serde_json::from_reader(File::open(params.graph_results_path()).unwrap())
+                        .unwrap();
+                assert_eq!(json[0]["ratios"]["latency"]["p50"], 0.25);
+                assert!(json[1]["ratios"]["latency"]["p50"].is_null());
+                assert_eq!(json[1]["metrics"]["upstream_latency"]["samples"], 0);
+            }
+        }
+    }
+
+    #[test]
+    fn mismatched_metrics_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.csv");
+        let mut writer = results_sink::open(&path, "target-qps").unwrap();
+        writer
+            .write_result("100", &results(&[("latency", &[1])]))
+            .unwrap();
+        let err = writer
+            .write_result("200", &results(&[("latency", &[1]), ("errors", &[0])]))
+            .unwrap_err();
+        assert!(err.to_string().contains("errors, latency"), "{err}");
+    }
+
+    #[test]
+    fn achieved_qps() {
+        let dir = tempfile::tempdir().unwrap();
+        for extension in ["csv", "json", "jsonl"] {
+            let path = dir.path().join(format!("graph.{extension}"));
+            let mut writer = results_sink::open(&path, "target-qps").unwrap();
+            writer
+                .write_result("1000", &timed_results(1000, 2))
+                .unwrap();
+            // Repeated runs are averaged, and runs without an elapsed time have no throughput.
+            writer
+                .write_results(
+                    &run("2000"),
+                    &[timed_results(900, 1), timed_results(300, 1)],
+                    None,
+                )
+                .unwrap();
+            writer
+                .write_result("3000", &results(&[("latency", &[1])]))
+                .unwrap();
+            writer.finish().unwrap();
+
+            if extension == "csv" {
+                let mut reader = csv::Reader::from_path(&path).unwrap();
+                let column = reader
+                    .headers()
+                    .unwrap()
+                    .iter()
+                    .position(|column| column == ACHIEVED_QPS_COLUMN)
+                    .unwrap();
+                let qps = reader
+                    .records()
+                    .map(|row| row.unwrap()[column].to_owned())
+                    .collect::<Vec<_>>();
+                assert_eq!(qps, ["500", "600", "NaN"]);
+            } else {
+                let contents = std::fs::read_to_string(&path).unwrap();
+                let runs: Vec<serde_json::Value> = match extension {
+                    "json" => serde_json::from_str(&contents).unwrap(),
+                    _ => contents
+                        .lines()
+                        .map(|line| serde_json::from_str(line).unwrap())
+                        .collect(),
+                };
+                let qps = runs
+                    .iter()
+                    .map(|run| run.get("achieved_qps"))
+                    .collect::<Vec<_>>();
+                assert_eq!(qps, [Some(&json!(500)), Some(&json!(600)), None]);
+            }
+        }
+    }
+
+    #[test]
+    fn collect_metadata() {
+        let metadata = RunMetadata::collect("{}".to_owned(), None);
+        assert!(
+            chrono::DateTime::parse_from_rfc3339(&metadata.timestamp).is_ok(),
+            "{}",
+            metadata.timestamp
+        );
+        assert_eq!(metadata.arguments, "{}");
+    }
+
+    #[test]
+    fn tagged_results() {
+        let dir = tempfile::tempdir().unwrap();
+        for extension in ["csv", "json", "jsonl"] {
+            let mut params = params("target-qps", "100");
+            params.graph_results_paths = vec![dir.path().join(format!("graph.{extension}"))];
+            params.baseline_tag = Some("main".parse().unwrap());
+            params.with_metadata = extension != "jsonl";
+            let mut writer = results_sink::from_params(&params).unwrap();
+            writer
+                .write_results(
+                    &run("100"),
+                    &[results(&[("latency", &[1, 2])])],
+                    Some(&metadata()),
+                )
+                .unwrap();
+            writer.finish().unwrap();
+
+            let contents = std::fs::read_to_string(params.graph_results_path()).unwrap();
+            match extension {
+                "csv" => {
+                    let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+                    let header = reader.headers().unwrap().clone();
+                    let row = reader.records().next().unwrap().unwrap();
+                    let tag = header.len() - METADATA_COLUMNS.len() - 1;
+                    assert_eq!(&header[tag], TAG_COLUMN);
+                    assert_eq!(&row[tag], "main");
+                    assert_eq!(&header[tag + 1], METADATA_COLUMNS[0]);
+
+                    let graph_results =
+                        crate::compare::GraphResults::from_path(params.graph_results_path())
+                            .unwrap();
+                    let key = ["100".to_owned()];
+                    assert!(graph_results.value(&key, TAG_COLUMN).is_none());
+                    assert!(graph_results.value(&key, "latency max").is_some());
+                }
+                "json" => {
+                    let json: serde_json::Value = serde_json::from_str(&contents).unwrap();
+                    assert_eq!(json[0]["meta"]["tag"], "main");
+                    assert_eq!(json[0]["meta"]["commit"], "0123abc");
+                }
+                _ => {
+                    let row: serde_json::Value =
+                        serde_json::from_str(contents.lines().next().unwrap()).unwrap();
+                    assert_eq!(row["meta"], json!({ "tag": "main" }));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn tag_columns() {
+        let dir = tempfile::tempdir().unwrap();
+        for extension in ["csv", "jsonl"] {
+            let mut params = params("target-qps", "100");
+            params.graph_results_paths = vec![dir.path().join(format!("graph.{extension}"))];
+            params.baseline_tag = Some("main".parse().unwrap());
+            params.tags = vec![
+                "branch=feature".parse().unwrap(),
+                "config=a=b".parse().unwrap(),
+            ];
+            params.validate().unwrap();
+            let mut writer = results_sink::from_params(&params).unwrap();
+            writer
+                .write_result("100", &results(&[("latency", &[1, 2])]))
+                .unwrap();
+            writer.finish().unwrap();
+
+            if extension == "csv" {
+                let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+                let header = reader.headers().unwrap().clone();
+                let row = reader.records().next().unwrap().unwrap();
+                let n = header.len();
+                assert_eq!(
+                    header.iter().skip(n - 3).collect::<Vec<_>>(),
+                    [TAG_COLUMN, "branch", "config"]
+                );
+                assert_eq!(
+                    row.iter().skip(n - 3).collect::<Vec<_>>(),
+                    ["main", "feature", "a=b"]
+                );
+            } else {
+                let contents = std::fs::read_to_string(params.graph_results_path()).unwrap();
+                let row: serde_json::Value = serde_json::from_str(contents.trim()).unwrap();
+                assert_eq!(row["tags"], json!({ "branch": "feature", "config": "a=b" }));
+            }
+        }
+    }
+
+    #[test]
+    fn metadata_is_only_written_when_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.json");
+        let mut writer = results_sink::open(&path, "target-qps").unwrap();
+        writer
+            .write_results(
+                &run("100"),
+                &[results(&[("latency", &[1, 2])])],
+                Some(&metadata()),
+            )
+            .unwrap();
+        writer.finish().unwrap();
+        let json: serde_json::Value = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        assert!(json[0].get("meta").is_none());
+
+        let mut params = params("target-qps", "100");
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        params.with_metadata = true;
+        let mut writer = results_sink::from_params(&params).unwrap();
+        let err = writer
+            .write_result("100", &results(&[("latency", &[1, 2])]))
+            .unwrap_err();
+        assert_eq!(err.to_string(), "No metadata to write for target-qps = 100");
+    }
+
+    #[test]
+    fn metrics_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let result = results(&[
+            ("latency", &[1, 2]),
+            ("errors", &[0]),
+            ("throughput", &[100]),
+        ]);
+        let write = |metrics: Option<&str>| {
+            let mut params = params("target-qps", "100");
+            params.graph_results_paths = vec![dir.path().join("graph.csv")];
+            params.metrics = metrics.map(|m| m.parse().unwrap());
+            let mut writer = results_sink::from_params(&params).unwrap();
+            writer.write_results(&params.runs()[0], std::slice::from_ref(&result), None)?;
+            writer.finish()?;
+            let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+            let header = reader.headers().unwrap().clone();
+            let row = reader.records().next().unwrap().unwrap();
+            assert_eq!(header.len(), row.len());
+            anyhow::Ok(header)
+        };
+        let columns_per_metric = STATS.len() + QUANTILES.len();
+
+        let header = write(None).unwrap();
+        assert_eq!(header.len(), 2 + 3 * columns_per_metric);
+
+        let header = write(Some("throughput,latency")).unwrap();
+        assert_eq!(header.len(), 2 + 2 * columns_per_metric);
+        assert_eq!(&header[1], "latency samples");
+        assert_eq!(&header[1 + columns_per_metric], "throughput samples");
+        assert!(!header.iter().any(|column| column.starts_with("errors")));
+
+        let err = write(Some("latency,qps")).unwrap_err().to_string();
+        assert!(err.contains("[qps]"), "{err}");
+        assert!(err.contains("[errors, latency, throughput]"), "{err}");
+    }
+
+    /// Results of a benchmark running two queries, with a latency for each query, a row count for
+    /// only one of them and a metric that isn't recorded per query.
+    fn multi_query_results() -> BenchmarkResults {
+        results(&[
+            ("query/q1/latency", &[1, 2]),
+            ("query/q2/latency", &[3, 4, 5]),
+            ("query/q2/rows", &[10]),
+            ("errors", &[0]),
+        ])
+    }
+
+    /// Writes `result` to a CSV file with the given `--metrics` and `--breakdown-by-query`,
+    /// returning each metric written along with its sample count and max.
+    fn write_by_query(
+        result: &BenchmarkResults,
+        metrics: Option<&str>,
+        breakdown_by_query: bool,
+    ) -> anyhow::Result<Vec<(String, String, String)>> {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100");
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        params.metrics = metrics.map(|m| m.parse().unwrap());
+        params.breakdown_by_query = breakdown_by_query;
+        let mut writer = results_sink::from_params(&params).unwrap();
+        writer.write_results(&params.runs()[0], std::slice::from_ref(result), None)?;
+        writer.finish()?;
+        let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+        let header = reader.headers().unwrap().clone();
+        let row = reader.records().next().unwrap().unwrap();
+        Ok(header
+            .iter()
+            .enumerate()
+            .filter_map(|(i, column)| {
+                let metric = column.strip_suffix(" samples")?;
+                Some((metric.to_owned(), row[i].to_owned(), row[i + 2].to_owned()))
+            })
+            .collect())
+    }
+
+    fn metric_stats(stats: &[(&str, &str, &str)]) -> Vec<(String, String, String)> {
+        stats
+            .iter()
+            .map(|&(metric, samples, max)| (metric.to_owned(), samples.to_owned(), max.to_owned()))
+            .collect()
+    }
+
+    #[test]
+    fn breakdown_by_query() {
+        let result = multi_query_results();
+        assert_eq!(
+            write_by_query(&result, None, true).unwrap(),
+            metric_stats(&[
+                ("errors", "1", "0"),
+                ("query/all/latency", "5", "5"),
+                ("query/all/rows", "1", "10"),
+                ("query/q1/latency", "2", "2"),
+                ("query/q2/latency", "3", "5"),
+                ("query/q2/rows", "1", "10"),
+            ])
+        );
+        // Without a breakdown, each per-query metric is folded into one for all of the queries.
+        assert_eq!(
+            write_by_query(&result, None, false).unwrap(),
+            metric_stats(&[
+                ("errors", "1", "0"),
+                ("latency", "5", "5"),
+                ("rows", "1", "10"),
+            ])
+        );
+        // Results without per-query metrics are written the same either way.
+        let plain = results(&[("latency", &[1, 2]), ("errors", &[0])]);
+        assert_eq!(
+            write_by_query(&plain, None, true).unwrap(),
+            write_by_query(&plain, None, false).unwrap()
+        );
+    }
+
+    #[test]
+    fn breakdown_by_query_metrics_globs() {
+        let result = multi_query_results();
+        assert_eq!(
+            write_by_query(&result, Some("query/*/latency"), true).unwrap(),
+            metric_stats(&[
+                ("query/all/latency", "5", "5"),
+                ("query/q1/latency", "2", "2"),
+                ("query/q2/latency", "3", "5"),
+            ])
+        );
+        assert_eq!(
+            write_by_query(&result, Some("query/q2/*,errors"), true).unwrap(),
+            metric_stats(&[
+                ("errors", "1", "0"),
+                ("query/q2/latency", "3", "5"),
+                ("query/q2/rows", "1", "10"),
+            ])
+        );
+        assert_eq!(
+            write_by_query(&result, Some("query/all/*"), true).unwrap(),
+            metric_stats(&[
+                ("query/all/latency", "5", "5"),
+                ("query/all/rows", "1", "10")
+            ])
+        );
+        let err = write_by_query(&result, Some("query/*/latency,query/*/bytes"), true)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("[query/*/bytes]"), "{err}");
+        // The per-query metrics aren't written without a breakdown, so can't be selected.
+        let err = write_by_query(&result, Some("query/*/latency"), false)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("[errors, latency, rows]"), "{err}");
+    }
+
+    #[test]
+    fn breakdown_by_query_compare_upstream() {
+        let grouped = group_by_query(
+            &compare_targets(
+                Ok(vec![multi_query_results()]),
+                Ok(vec![multi_query_results()]),
+            )
+            .unwrap()[0],
+            true,
+        );
+        let metrics = grouped
+            .results
+            .keys()
+            .sorted_by(|a, b| metric_order(a).cmp(&metric_order(b)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            metrics,
+            [
+                "readyset_errors",
+                "upstream_errors",
+                "readyset_query/all/latency",
+                "upstream_query/all/latency",
+                "readyset_query/all/rows",
+                "upstream_query/all/rows",
+                "readyset_query/q1/latency",
+                "upstream_query/q1/latency",
+                "readyset_query/q2/latency",
+                "upstream_query/q2/latency",
+                "readyset_query/q2/rows",
+                "upstream_query/q2/rows",
+            ]
+        );
+        assert_eq!(
+            grouped.results["upstream_query/all/latency"].values.len(),
+            5
+        );
+    }
+
+    #[test]
+    fn aggregate_stats_by_query() {
+        let result = [multi_query_results()];
+        let stats = |breakdown| {
+            aggregate_stats(&result, &Quantiles::default(), breakdown)
+                .into_iter()
+                .filter(|(column, _)| column.ends_with(" samples"))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            stats(false),
+            [
+                ("errors samples".to_owned(), 1.0),
+                ("latency samples".to_owned(), 5.0),
+                ("rows samples".to_owned(), 1.0),
+            ]
+        );
+        assert!(stats(true).contains(&("query/all/latency samples".to_owned(), 5.0)));
+        assert!(stats(true).contains(&("query/q1/latency samples".to_owned(), 2.0)));
+    }
+
+    #[test]
+    fn metric_patterns() {
+        for (pattern, name, matches) in [
+            ("latency", "latency", true),
+            ("latency", "latency_p99", false),
+            ("query/*/latency", "query/q3/latency", true),
+            ("query/*/latency", "query/all/latency", true),
+            ("query/*/latency", "query//latency", true),
+            ("query/*/latency", "query/q3/read/latency", false),
+            ("query/*/latency", "query/q3/rows", false),
+            ("query/q*", "query/q3", true),
+            ("query/q*", "query/q3/latency", false),
+            ("*", "errors", true),
+            ("*", "query/q3/latency", false),
+            ("*/*/*", "query/q3/latency", true),
+            ("*_latency", "read_latency", true),
+            ("*_latency", "read_latency_us", false),
+        ] {
+            assert_eq!(
+                metric_matches(pattern, name),
+                matches,
+                "{pattern} matching {name}"
+            );
+        }
+    }
+}
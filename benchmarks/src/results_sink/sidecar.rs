@@ -0,0 +1,121 @@
+//! The JSONL copy of an output that's only written in full, which keeps the rows written so far
+//! if a sweep is interrupted.
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+
+/// A JSONL file that each row of an output which is only written in full (JSON, Parquet, plots
+/// and HTML reports) is appended to as soon as it's written, in the order the rows were written,
+/// so that rows aren't lost if the process is killed before the output is written. It's removed
+/// once the sink is finished.
+pub(super) struct Sidecar {
+    path: PathBuf,
+    writer: BufWriter<File>,
+}
+
+impl Sidecar {
+    pub(super) fn create(path: PathBuf) -> anyhow::Result<Self> {
+        let file =
+            File::create(&path).map_err(|e| anyhow!("Failed to create {}: {e}", path.display()))?;
+        Ok(Self {
+            path,
+            writer: BufWriter::new(file),
+        })
+    }
+
+    /// Appends `row` to the file and flushes it to disk.
+    pub(super) fn write(&mut self, row: &serde_json::Value) -> anyhow::Result<()> {
+        serde_json::to_writer(&mut self.writer, row)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Removes the file, once the output it's a copy of has been written.
+    pub(super) fn remove(self) -> anyhow::Result<()> {
+        drop(self.writer);
+        std::fs::remove_file(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Returns the path of the JSONL file that the rows written to `path` are copied to as they're
+/// written, if `path` is a JSON, Parquet, graph or HTML file: `path` with `.partial.jsonl`
+/// appended. The file is removed once the sink is finished, so it only remains if the sweep was
+/// interrupted.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut sidecar = path.as_os_str().to_owned();
+    sidecar.push(".partial.jsonl");
+    PathBuf::from(sidecar)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::results_sink;
+    use crate::test_utils::results;
+
+    #[test]
+    fn rows_survive_a_crash() {
+        let dir = tempfile::tempdir().unwrap();
+        for extension in ["csv", "jsonl", "json", "svg"] {
+            let path = dir.path().join(format!("graph.{extension}"));
+            let mut writer = results_sink::open(&path, "target-qps").unwrap();
+            for x_value in ["100", "200"] {
+                writer
+                    .write_result(x_value, &results(&[("latency", &[1, 2])]))
+                    .unwrap();
+            }
+            // Neither finishing nor dropping the writer, as if the process had been killed.
+            std::mem::forget(writer);
+
+            let rows = match extension {
+                "csv" => csv::Reader::from_path(&path)
+                    .unwrap()
+                    .records()
+                    .map(|record| record.unwrap()[0].to_owned())
+                    .collect::<Vec<_>>(),
+                _ => {
+                    let jsonl = match extension {
+                        "jsonl" => path.clone(),
+                        _ => sidecar_path(&path),
+                    };
+                    std::fs::read_to_string(jsonl)
+                        .unwrap()
+                        .lines()
+                        .map(|line| {
+                            let row = serde_json::from_str::<serde_json::Value>(line).unwrap();
+                            row["x_value"].to_string()
+                        })
+                        .collect()
+                }
+            };
+            assert_eq!(rows, ["100", "200"], "{extension}");
+        }
+    }
+
+    #[test]
+    fn finish_removes_sidecar() {
+        let dir = tempfile::tempdir().unwrap();
+        for extension in ["json", "svg", "html", "csv"] {
+            let path = dir.path().join(format!("graph.{extension}"));
+            let sidecar = sidecar_path(&path);
+            assert_eq!(
+                sidecar.file_name().unwrap(),
+                format!("graph.{extension}.partial.jsonl").as_str()
+            );
+            let mut writer = results_sink::open(&path, "target-qps").unwrap();
+            writer
+                .write_result("100", &results(&[("latency", &[1])]))
+                .unwrap();
+            assert_eq!(sidecar.exists(), extension != "csv", "{extension}");
+            writer.finish().unwrap();
+            assert!(path.exists(), "{extension}");
+            assert!(!sidecar.exists(), "{extension}");
+        }
+    }
+}
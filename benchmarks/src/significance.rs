@@ -0,0 +1,281 @@
+//! Statistical tests of whether the difference between repeated runs of a baseline and a candidate
+//! is larger than the noise between runs.
+//!
+//! Each side is summarized by the number of runs and the mean and sample standard deviation of a
+//! value across them, and compared with [Welch's t-test], which doesn't assume both sides are
+//! equally noisy.
+//!
+//! [Welch's t-test]: https://en.wikipedia.org/wiki/Welch%27s_t-test
+
+/// The significance level that p-values are compared with if none is given.
+pub const DEFAULT_SIGNIFICANCE_LEVEL: f64 = 0.05;
+
+/// The number of values, mean and sample standard deviation of a value across repeated runs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    pub n: usize,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+impl Summary {
+    /// Summarizes `values`. The standard deviation of fewer than two values is zero, and the mean
+    /// of no values is NaN.
+    pub fn of(values: &[f64]) -> Self {
+        let n = values.len();
+        let mean = values.iter().sum::<f64>() / n as f64;
+        let stddev = if n < 2 {
+            0.0
+        } else {
+            let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+            variance.sqrt()
+        };
+        Self { n, mean, stddev }
+    }
+
+    /// The squared standard error of the mean.
+    fn squared_error(&self) -> f64 {
+        self.stddev.powi(2) / self.n as f64
+    }
+}
+
+/// The result of a two-sided Welch's t-test of whether two sets of runs have different means.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WelchTest {
+    /// The t statistic: positive if the candidate's mean is greater than the baseline's.
+    pub t: f64,
+    /// The Welch–Satterthwaite approximation of the degrees of freedom.
+    pub degrees_of_freedom: f64,
+    /// The probability of a difference at least this large if the means were the same.
+    pub p_value: f64,
+}
+
+impl WelchTest {
+    /// Tests whether the means of `baseline` and `candidate` differ. Returns `None` unless both
+    /// have at least two runs, since the noise between runs can't be estimated from one.
+    ///
+    /// If neither side varies at all, any difference in the means is significant, with a p-value
+    /// of zero, and equal means have a p-value of one.
+    pub fn new(baseline: &Summary, candidate: &Summary) -> Option<Self> {
+        if baseline.n < 2 || candidate.n < 2 {
+            return None;
+        }
+        let (a, b) = (baseline.squared_error(), candidate.squared_error());
+        let difference = candidate.mean - baseline.mean;
+        if a + b == 0.0 {
+            let (t, p_value) = if difference == 0.0 {
+                (0.0, 1.0)
+            } else {
+                (difference.signum() * f64::INFINITY, 0.0)
+            };
+            return Some(Self {
+                t,
+                degrees_of_freedom: (baseline.n + candidate.n - 2) as f64,
+                p_value,
+            });
+        }
+        let t = difference / (a + b).sqrt();
+        let degrees_of_freedom = (a + b).powi(2)
+            / (a.powi(2) / (baseline.n - 1) as f64 + b.powi(2) / (candidate.n - 1) as f64);
+        Some(Self {
+            t,
+            degrees_of_freedom,
+            p_value: student_t_two_sided(t, degrees_of_freedom),
+        })
+    }
+
+    /// Returns whether the difference is significant at the given level, i.e. its p-value is
+    /// below `alpha`.
+    pub fn is_significant(&self, alpha: f64) -> bool {
+        self.p_value < alpha
+    }
+}
+
+/// Returns the probability that a value of Student's t-distribution with `df` degrees of freedom
+/// is further from zero than `t`.
+fn student_t_two_sided(t: f64, df: f64) -> f64 {
+    regularized_incomplete_beta(df / 2.0, 0.5, df / (df + t * t))
+}
+
+/// Returns the regularized incomplete beta function I<sub>x</sub>(a, b), evaluated with the
+/// continued fraction from *Numerical Recipes*, §6.4.
+fn regularized_incomplete_beta(a: f64, b: f64, x: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The continued fraction converges quickly only below this point, so use the symmetry
+    // I_x(a, b) = 1 - I_{1-x}(b, a) above it.
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(a, b, x) / a
+    } else {
+        1.0 - front * beta_continued_fraction(b, a, 1.0 - x) / b
+    }
+}
+
+/// Evaluates the continued fraction for the incomplete beta function with Lentz's method.
+fn beta_continued_fraction(a: f64, b: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 300;
+    const EPSILON: f64 = 1e-15;
+    const TINY: f64 = 1e-300;
+
+    let nonzero = |v: f64| if v.abs() < TINY { TINY } else { v };
+    let mut c = 1.0;
+    let mut d = 1.0 / nonzero(1.0 - (a + b) * x / (a + 1.0));
+    let mut h = d;
+    for m in 1..=MAX_ITERATIONS {
+        let m = m as f64;
+        // Each iteration applies an even and an odd step of the fraction.
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 / nonzero(1.0 + numerator * d);
+            c = nonzero(1.0 + numerator / c);
+            h *= d * c;
+        }
+        if (d * c - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    h
+}
+
+/// Returns the natural log of the gamma function of `x > 0`, with the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.120_865_097_386_617_9e-2,
+        -0.539_523_938_495_3e-5,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000_000_000_190_015, |sum, (i, c)| {
+            sum + c / (x + 1.0 + i as f64)
+        });
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "expected {expected} ± {tolerance}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn summary() {
+        let summary = Summary::of(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert_eq!(summary.n, 8);
+        assert_eq!(summary.mean, 5.0);
+        assert_close(summary.stddev, (32.0f64 / 7.0).sqrt(), 1e-12);
+        assert_eq!(Summary::of(&[3.0]).stddev, 0.0);
+    }
+
+    #[test]
+    fn ln_gamma_of_integers_and_halves() {
+        // Γ(n) = (n - 1)!, and Γ(1/2) = √π.
+        for (x, gamma) in [(1.0, 1.0), (2.0, 1.0), (5.0, 24.0), (10.0, 362_880.0)] {
+            assert_close(ln_gamma(x), f64::ln(gamma), 1e-9);
+        }
+        assert_close(ln_gamma(0.5), std::f64::consts::PI.sqrt().ln(), 1e-9);
+    }
+
+    /// Two-sided p-values of Student's t-distribution: exact ones for one and two degrees of
+    /// freedom, and the familiar 5% and 1% critical values from tables for more.
+    #[test]
+    fn student_t_distribution() {
+        // With one degree of freedom, this is the Cauchy distribution.
+        assert_close(student_t_two_sided(1.0, 1.0), 0.5, 1e-9);
+        // With two, P(|T| > t) = 1 - t / √(2 + t²).
+        assert_close(
+            student_t_two_sided(1.0, 2.0),
+            1.0 - 1.0 / 3.0f64.sqrt(),
+            1e-9,
+        );
+        for (t, df, p) in [
+            (12.706, 1.0, 0.05),
+            (2.228, 10.0, 0.05),
+            (3.169, 10.0, 0.01),
+            (2.042, 30.0, 0.05),
+            (1.962, 1000.0, 0.05),
+        ] {
+            assert_close(student_t_two_sided(t, df), p, 1e-4);
+            assert_close(student_t_two_sided(-t, df), p, 1e-4);
+        }
+        assert_eq!(student_t_two_sided(0.0, 5.0), 1.0);
+    }
+
+    /// The first example from the Wikipedia article on Welch's t-test, and two with noisy and
+    /// unequal numbers of runs. The expected values were computed independently, with the
+    /// t-distribution integrated numerically.
+    #[test]
+    fn welch_t_test_fixtures() {
+        let a1 = [
+            27.5, 21.0, 19.0, 23.6, 17.0, 17.9, 16.9, 20.1, 21.9, 22.6, 23.1, 19.6, 19.0, 21.7,
+            21.4,
+        ];
+        let a2 = [
+            27.1, 22.0, 20.8, 23.4, 23.4, 23.5, 25.8, 22.0, 24.8, 20.2, 21.9, 22.1, 22.9, 20.5,
+            24.4,
+        ];
+        let test = WelchTest::new(&Summary::of(&a1), &Summary::of(&a2)).unwrap();
+        assert_close(test.t, 2.455_356, 1e-6);
+        assert_close(test.degrees_of_freedom, 24.988_529, 1e-6);
+        assert_close(test.p_value, 0.021_378, 1e-6);
+        assert!(test.is_significant(0.05));
+        assert!(!test.is_significant(0.01));
+
+        // Swapping the sides only flips the sign of t.
+        let swapped = WelchTest::new(&Summary::of(&a2), &Summary::of(&a1)).unwrap();
+        assert_eq!(swapped.t, -test.t);
+        assert_eq!(swapped.p_value, test.p_value);
+
+        let baseline = [101.0, 98.5, 103.2, 99.8, 100.4];
+        let candidate = [104.1, 106.3, 102.9, 105.5];
+        let test = WelchTest::new(&Summary::of(&baseline), &Summary::of(&candidate)).unwrap();
+        assert_close(test.t, 3.813_725, 1e-6);
+        assert_close(test.degrees_of_freedom, 6.908_424, 1e-6);
+        assert_close(test.p_value, 0.006_762, 1e-6);
+
+        let baseline = [512.0, 498.0, 530.0];
+        let candidate = [505.0, 541.0, 520.0, 515.0];
+        let test = WelchTest::new(&Summary::of(&baseline), &Summary::of(&candidate)).unwrap();
+        assert_close(test.t, 0.577_714, 1e-6);
+        assert_close(test.degrees_of_freedom, 4.295_367, 1e-6);
+        assert_close(test.p_value, 0.592_399, 1e-6);
+        assert!(!test.is_significant(DEFAULT_SIGNIFICANCE_LEVEL));
+    }
+
+    #[test]
+    fn welch_t_test_degenerate_cases() {
+        let one_run = Summary::of(&[1.0]);
+        let runs = Summary::of(&[1.0, 2.0, 3.0]);
+        assert_eq!(WelchTest::new(&one_run, &runs), None);
+        assert_eq!(WelchTest::new(&runs, &one_run), None);
+
+        let same = WelchTest::new(&runs, &runs).unwrap();
+        assert_eq!((same.t, same.p_value), (0.0, 1.0));
+
+        let constant = |value| Summary::of(&[value; 3]);
+        let test = WelchTest::new(&constant(5.0), &constant(5.0)).unwrap();
+        assert_eq!((test.t, test.p_value), (0.0, 1.0));
+        let test = WelchTest::new(&constant(5.0), &constant(4.0)).unwrap();
+        assert_eq!((test.t, test.p_value), (f64::NEG_INFINITY, 0.0));
+        assert_eq!(test.degrees_of_freedom, 4.0);
+    }
+}
@@ -0,0 +1,344 @@
+//! The table of results printed to the terminal once a `--graph` sweep has finished, unless
+//! `--no-summary` is passed.
+//!
+//! Each run of the sweep is a row, labelled by its x-value (and z-value, if there is a z-axis),
+//! with a column for each of the [`SUMMARY_STATS`] of every metric written. The values are the
+//! [`written_stats`](crate::results_sink::written_stats) of each run, so they match the graph
+//! results file exactly. When the table is colored, the best value of each column is green and
+//! the worst is red.
+
+use std::ffi::OsStr;
+use std::fmt::{self, Display};
+
+use console::{style, Term};
+
+use crate::benchmark::MetricGoal;
+use crate::graph::GraphRun;
+use crate::report::write_table;
+use crate::results_sink::WrittenStat;
+
+/// The statistics of each metric shown in the summary, of those written with `--quantiles`.
+pub const SUMMARY_STATS: &[&str] = &["mean", "p50", "p99"];
+
+/// A table of the [`SUMMARY_STATS`] of every run of a sweep.
+#[derive(Debug, Default)]
+pub struct SummaryTable {
+    /// The axes that label each row: the x-axis, followed by the z-axis if there is one.
+    axes: Vec<String>,
+    /// The column and goal of each statistic shown, as written for the first run.
+    columns: Vec<(String, MetricGoal)>,
+    /// The value of each axis and each column for every run, in the order they were added.
+    rows: Vec<(Vec<String>, Vec<Option<f64>>)>,
+    /// Whether to color the best value of each column green and the worst red.
+    pub colored: bool,
+}
+
+impl SummaryTable {
+    pub fn new(colored: bool) -> Self {
+        Self {
+            colored,
+            ..Self::default()
+        }
+    }
+
+    /// Returns a table to print to stdout, which is colored if [`colors_enabled`] for it.
+    pub fn for_stdout() -> Self {
+        Self::new(colors_enabled(
+            Term::stdout().is_term(),
+            std::env::var_os("NO_COLOR").as_deref(),
+        ))
+    }
+
+    /// Adds a row for `run` with the statistics written for it. The columns are those of the
+    /// first row added: any of `stats` that aren't in them are left out, and any that are missing
+    /// are left blank.
+    pub fn push(&mut self, run: &GraphRun, stats: &[WrittenStat]) {
+        if self.rows.is_empty() {
+            self.axes = std::iter::once(run.x_axis.clone())
+                .chain(run.z.as_ref().map(|(z_axis, _)| z_axis.clone()))
+                .collect();
+            self.columns = stats
+                .iter()
+                .filter(|stat| SUMMARY_STATS.contains(&stat.stat.as_str()))
+                .map(|stat| (stat.column.clone(), stat.goal))
+                .collect();
+        }
+        let axes = std::iter::once(run.x_value.clone())
+            .chain(run.z_value().map(str::to_owned))
+            .collect();
+        let values = self
+            .columns
+            .iter()
+            .map(|(column, _)| {
+                stats
+                    .iter()
+                    .find(|stat| stat.column == *column)
+                    .map(|stat| stat.value)
+            })
+            .collect();
+        self.rows.push((axes, values));
+    }
+
+    /// Whether no rows have been added.
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Returns the best and worst value in `column`, or `None` if there's no difference between
+    /// them to highlight.
+    fn best_and_worst(&self, column: usize) -> Option<(f64, f64)> {
+        let (min, max) = self
+            .rows
+            .iter()
+            .filter_map(|(_, values)| values[column])
+            .filter(|value| !value.is_nan())
+            .fold(None, |extremes, value| match extremes {
+                None => Some((value, value)),
+                Some((min, max)) => Some((value.min(min), value.max(max))),
+            })?;
+        if min == max {
+            return None;
+        }
+        match self.columns[column].1 {
+            MetricGoal::Increasing => Some((max, min)),
+            MetricGoal::Decreasing => Some((min, max)),
+        }
+    }
+}
+
+impl Display for SummaryTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = self
+            .axes
+            .iter()
+            .cloned()
+            .chain(self.columns.iter().map(|(column, _)| column.clone()))
+            .collect::<Vec<_>>();
+        let rows = std::iter::once(header)
+            .chain(self.rows.iter().map(|(axes, values)| {
+                axes.iter()
+                    .cloned()
+                    .chain(
+                        values
+                            .iter()
+                            .map(|value| value.map(|v| v.to_string()).unwrap_or_default()),
+                    )
+                    .collect()
+            }))
+            .collect::<Vec<_>>();
+        let best_and_worst = (0..self.columns.len())
+            .map(|column| self.best_and_worst(column))
+            .collect::<Vec<_>>();
+        write_table(f, &rows, |row, column, value| {
+            if !self.colored || row == 0 || column < self.axes.len() {
+                return value;
+            }
+            let column = column - self.axes.len();
+            let (Some((best, worst)), Some(v)) =
+                (best_and_worst[column], self.rows[row - 1].1[column])
+            else {
+                return value;
+            };
+            if v == best {
+                style(value).force_styling(true).green().to_string()
+            } else if v == worst {
+                style(value).force_styling(true).red().to_string()
+            } else {
+                value
+            }
+        })
+    }
+}
+
+/// Whether to color output to a terminal: only if it is one, and the `NO_COLOR` environment
+/// variable isn't set to a non-empty value (see <https://no-color.org>).
+pub fn colors_enabled(is_terminal: bool, no_color: Option<&OsStr>) -> bool {
+    is_terminal && no_color.is_none_or(OsStr::is_empty)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+    use hdrhistogram::Histogram;
+    use metrics::Unit;
+
+    use super::*;
+    use crate::benchmark::BenchmarkResults;
+    use crate::graph::GraphParams;
+    use crate::results_sink::{self, written_stats};
+    use crate::test_utils::{results, run};
+
+    /// The summary of [`sweep`], as printed without colors.
+    const FIXTURE: &str = include_str!("../tests/fixtures/summary.txt");
+
+    /// Returns results with the given latencies and a count of rows, of which more is better.
+    fn latency_and_rows(latencies: &[u64], rows: u64) -> BenchmarkResults {
+        let mut results = results(&[("latency", latencies)]);
+        let mut count = Histogram::<u64>::new(3).unwrap();
+        count.record(rows).unwrap();
+        results.push("rows", Unit::Count, MetricGoal::Increasing, count);
+        results
+    }
+
+    fn params(args: &str) -> GraphParams {
+        GraphParams::try_parse_from(
+            ["graph", "--graph", "--x-axis", "target-qps"]
+                .into_iter()
+                .chain(args.split_whitespace()),
+        )
+        .unwrap()
+    }
+
+    /// A sweep over three x-values in which latency is best at the first and worst at the last,
+    /// while the most rows are returned in the middle.
+    fn sweep() -> Vec<(GraphRun, BenchmarkResults)> {
+        [
+            ("100", latency_and_rows(&[100, 200, 300, 400], 10)),
+            ("200", latency_and_rows(&[150, 250, 350, 1000], 30)),
+            ("400", latency_and_rows(&[900, 1000, 1100, 5000], 20)),
+        ]
+        .into_iter()
+        .map(|(x_value, results)| (run(x_value), results))
+        .collect()
+    }
+
+    fn summary(params: &GraphParams, colored: bool) -> SummaryTable {
+        let mut summary = SummaryTable::new(colored);
+        for (run, results) in sweep() {
+            let stats = written_stats(std::slice::from_ref(&results), params).unwrap();
+            summary.push(&run, &stats);
+        }
+        summary
+    }
+
+    #[test]
+    fn plain_summary() {
+        let summary = summary(&params("--x-values 100,200,400"), false);
+        assert_eq!(summary.to_string(), FIXTURE);
+    }
+
+    #[test]
+    fn matches_written_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.csv");
+        let params = params(&format!(
+            "--x-values 100,200,400 --convert-units ms --graph-results-path {}",
+            path.display()
+        ));
+        let mut writer = results_sink::from_params(&params).unwrap();
+        for (run, results) in sweep() {
+            writer
+                .write_results(&run, std::slice::from_ref(&results), None)
+                .unwrap();
+        }
+        writer.finish().unwrap();
+
+        let output = summary(&params, false).to_string();
+        let mut summary = output.lines().map(|line| line.split_whitespace());
+        let mut file = csv::Reader::from_path(&path).unwrap();
+        let file_header = file.headers().unwrap().clone();
+        let file_rows = file.records().collect::<Result<Vec<_>, _>>().unwrap();
+
+        // Column names contain spaces, so only compare their number.
+        let header = summary.next().unwrap().collect::<Vec<_>>();
+        assert!(header.join(" ").contains("latency p99 (ms)"), "{output}");
+        for (row, file_row) in summary.zip(&file_rows) {
+            let row = row.collect::<Vec<_>>();
+            assert_eq!(row[0], &file_row[0]);
+            for (value, column) in row[1..].iter().zip([
+                "latency mean (ms)",
+                "latency p50 (ms)",
+                "latency p99 (ms)",
+                "rows mean",
+                "rows p50",
+                "rows p99",
+            ]) {
+                let i = file_header.iter().position(|c| c == column).unwrap();
+                assert_eq!(*value, &file_row[i], "{column}");
+            }
+        }
+    }
+
+    #[test]
+    fn colors_best_and_worst() {
+        let output = summary(&params("--x-values 100,200,400"), true).to_string();
+        let lines = output.lines().collect::<Vec<_>>();
+        // Values are padded before they're colored, so only the last column's padding is left.
+        assert!(console::strip_ansi_codes(&output)
+            .lines()
+            .map(str::trim_end)
+            .eq(FIXTURE.lines()));
+        let green = "\u{1b}[32m";
+        let red = "\u{1b}[31m";
+        // The header and axis values are never colored.
+        assert!(!lines[0].contains('\u{1b}'), "{output}");
+        assert!(lines.iter().all(|line| !line.starts_with('\u{1b}')));
+        // Lower latency is better, and more rows are.
+        assert_eq!(lines[1].matches(green).count(), 3, "{output}");
+        assert_eq!(lines[1].matches(red).count(), 3, "{output}");
+        assert_eq!(lines[2].matches(green).count(), 3, "{output}");
+        assert_eq!(lines[2].matches(red).count(), 0, "{output}");
+        assert_eq!(lines[3].matches(green).count(), 0, "{output}");
+        assert_eq!(lines[3].matches(red).count(), 3, "{output}");
+    }
+
+    #[test]
+    fn no_colors_without_differences() {
+        let mut summary = SummaryTable::new(true);
+        let params = params("--x-values 100,200");
+        for x_value in ["100", "200"] {
+            let stats = written_stats(&[latency_and_rows(&[5], 1)], &params).unwrap();
+            summary.push(&run(x_value), &stats);
+        }
+        assert!(!summary.to_string().contains('\u{1b}'), "{summary}");
+    }
+
+    #[test]
+    fn missing_values_are_blank() {
+        let mut summary = SummaryTable::new(false);
+        let params = params("--x-values 100,200");
+        let stats = written_stats(&[latency_and_rows(&[5], 1)], &params).unwrap();
+        summary.push(&run("100"), &stats);
+        let latency_only = stats
+            .iter()
+            .filter(|stat| stat.metric == "latency")
+            .cloned()
+            .collect::<Vec<_>>();
+        summary.push(&run("200"), &latency_only);
+        let output = summary.to_string();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[1].split_whitespace().count(), 7, "{output}");
+        assert_eq!(lines[2].split_whitespace().count(), 4, "{output}");
+    }
+
+    #[test]
+    fn labels_rows_with_z_values() {
+        let mut summary = SummaryTable::new(false);
+        let params = params("--x-values 100");
+        let stats = written_stats(&[latency_and_rows(&[5], 1)], &params).unwrap();
+        let mut run = run("100");
+        run.z = Some(("threads".to_owned(), "8".to_owned()));
+        summary.push(&run, &stats);
+        let output = summary.to_string();
+        let mut lines = output.lines();
+        assert!(lines.next().unwrap().starts_with("target-qps  threads  "));
+        assert!(lines.next().unwrap().starts_with("100         8        "));
+    }
+
+    #[test]
+    fn no_color() {
+        for (is_terminal, no_color, enabled) in [
+            (true, None, true),
+            (true, Some(""), true),
+            (true, Some("1"), false),
+            (false, None, false),
+            (false, Some("1"), false),
+        ] {
+            assert_eq!(
+                colors_enabled(is_terminal, no_color.map(OsStr::new)),
+                enabled,
+                "{is_terminal} {no_color:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,1299 @@
+//! Running each run of a graph sweep in turn: pausing between runs, timing out and retrying runs,
+//! reporting progress, and checkpointing the runs that completed so an interrupted sweep can be
+//! resumed. Time series sweeps, which write a row of results for each interval of a single run,
+//! are run here too.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use futures::future::BoxFuture;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::task::JoinSet;
+use tracing::Level;
+
+use crate::benchmark::{BenchmarkResults, IntervalSnapshot};
+use crate::graph::{GraphParams, GraphRun};
+
+/// Checks whether the deployment under benchmark is idle, for `--cooldown-until-idle`.
+pub type IdleCheck = Arc<dyn Fn() -> BoxFuture<'static, anyhow::Result<bool>> + Send + Sync>;
+
+/// How often the [`IdleCheck`] of a [`Cooldown`] is polled until it reports idle.
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// A pause between consecutive runs of a sweep, from `--cooldown`, `--cooldown-secs` and
+/// `--cooldown-until-idle`.
+#[derive(Clone, Default)]
+pub struct Cooldown {
+    pub duration: Duration,
+    /// The level to log the pause at, or `None` to not log it.
+    pub log_level: Option<Level>,
+    /// How long to keep polling `is_idle` after `duration`, or `None` to not poll it.
+    pub idle_timeout: Option<Duration>,
+    /// Polled after `duration` until it reports idle or `idle_timeout` expires.
+    pub is_idle: Option<IdleCheck>,
+    /// How often `is_idle` is polled.
+    pub poll_interval: Option<Duration>,
+}
+
+impl std::fmt::Debug for Cooldown {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cooldown")
+            .field("duration", &self.duration)
+            .field("log_level", &self.log_level)
+            .field("idle_timeout", &self.idle_timeout)
+            .field("is_idle", &self.is_idle.is_some())
+            .field("poll_interval", &self.poll_interval)
+            .finish()
+    }
+}
+
+impl Cooldown {
+    /// Sets the check polled until the deployment is idle, if `--cooldown-until-idle` is set.
+    pub fn with_idle_check<F>(mut self, is_idle: F) -> Self
+    where
+        F: Fn() -> BoxFuture<'static, anyhow::Result<bool>> + Send + Sync + 'static,
+    {
+        self.is_idle = Some(Arc::new(is_idle));
+        self
+    }
+
+    /// Waits out the pause before `run`, then polls the idle check until it reports idle or the
+    /// idle timeout expires, and logs how long it all took. Errors from the idle check are logged
+    /// and treated as not idle. Returns how long the cooldown took, or `None` if there is no
+    /// cooldown to wait out.
+    async fn wait(&self, run: &GraphRun) -> Option<Duration> {
+        let idle_check = self.idle_timeout.zip(self.is_idle.as_ref());
+        if self.duration.is_zero() && idle_check.is_none() {
+            return None;
+        }
+        let start = tokio::time::Instant::now();
+        tokio::time::sleep(self.duration).await;
+        if let Some((timeout, is_idle)) = idle_check {
+            let deadline = tokio::time::Instant::now() + timeout;
+            let poll_interval = self.poll_interval.unwrap_or(IDLE_POLL_INTERVAL);
+            loop {
+                match is_idle().await {
+                    Ok(true) => break,
+                    Ok(false) => {}
+                    Err(e) => tracing::warn!("Failed to check whether the deployment is idle: {e}"),
+                }
+                if tokio::time::Instant::now() >= deadline {
+                    tracing::warn!("Deployment still not idle after {timeout:?}, starting {run}");
+                    break;
+                }
+                tokio::time::sleep_until(deadline.min(tokio::time::Instant::now() + poll_interval))
+                    .await;
+            }
+        }
+        let elapsed = start.elapsed();
+        match self.log_level {
+            Some(Level::ERROR) => tracing::error!("Cooled down for {elapsed:?} before {run}"),
+            Some(Level::WARN) => tracing::warn!("Cooled down for {elapsed:?} before {run}"),
+            Some(Level::INFO) => tracing::info!("Cooled down for {elapsed:?} before {run}"),
+            Some(Level::DEBUG) => tracing::debug!("Cooled down for {elapsed:?} before {run}"),
+            Some(_) => tracing::trace!("Cooled down for {elapsed:?} before {run}"),
+            None => {}
+        }
+        Some(elapsed)
+    }
+}
+
+/// Executes `runs` with up to `parallelism` of them running at once on separate tokio tasks,
+/// passing the result of each to `on_result` in the order of `runs`. Runs that finish early are
+/// buffered until every run before them has been handled. Each task waits out the `cooldown`
+/// between the runs it executes, and passes how long it took to `run`, or `None` for the first
+/// run of each task or if there is no cooldown.
+///
+/// Stops at the first error, either from a run or from `on_result`, after handling the results
+/// of every run before it; runs that are still in progress are then cancelled. With
+/// `keep_going`, the errors of runs are passed to `on_result` like any other result instead, and
+/// only an error from `on_result` stops the sweep.
+pub async fn run_in_order<T, F, Fut>(
+    runs: Vec<GraphRun>,
+    parallelism: usize,
+    cooldown: Cooldown,
+    keep_going: bool,
+    run: F,
+    mut on_result: impl FnMut(GraphRun, anyhow::Result<T>) -> anyhow::Result<()>,
+) -> anyhow::Result<()>
+where
+    F: Fn(GraphRun, Option<Duration>) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = anyhow::Result<T>> + Send + 'static,
+    T: Send + 'static,
+{
+    let queue = Arc::new(Mutex::new(runs.into_iter().enumerate()));
+    let run = Arc::new(run);
+    let (sender, mut receiver) = unbounded_channel();
+    // Dropping the set aborts any runs that are still in progress if we return early.
+    let mut workers = JoinSet::new();
+    for _ in 0..parallelism.max(1) {
+        let (queue, run, sender) = (Arc::clone(&queue), Arc::clone(&run), sender.clone());
+        let cooldown = cooldown.clone();
+        workers.spawn(async move {
+            let mut first = true;
+            loop {
+                let next = queue.lock().unwrap().next();
+                let Some((index, graph_run)) = next else {
+                    break;
+                };
+                let cooled_down = if first {
+                    None
+                } else {
+                    cooldown.wait(&graph_run).await
+                };
+                first = false;
+                let result = run(graph_run.clone(), cooled_down).await;
+                if sender.send((index, graph_run, result)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+    drop(sender);
+
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    while let Some((index, graph_run, result)) = receiver.recv().await {
+        pending.insert(index, (graph_run, result));
+        while let Some((graph_run, result)) = pending.remove(&next) {
+            match result {
+                Err(e) if !keep_going => return Err(e),
+                result => on_result(graph_run, result)?,
+            }
+            next += 1;
+        }
+    }
+    // The channel only closes early if a worker panicked.
+    while let Some(worker) = workers.join_next().await {
+        worker?;
+    }
+    Ok(())
+}
+
+/// The x-axis of `--time-series` results: how many seconds the benchmark had been running at the
+/// end of each interval.
+pub const TIME_SERIES_X_AXIS: &str = "elapsed_secs";
+
+/// Awaits `benchmark` for `--time-series`, passing the results of each interval that it sends to
+/// `snapshots` to `on_interval` as they arrive, along with the [time-series
+/// run](GraphRun::time_series) to write them as. Snapshots sent just before the benchmark
+/// finishes are handled before its results are returned. Stops at the first error, either from
+/// the benchmark or from `on_interval`.
+pub async fn run_time_series<Fut>(
+    benchmark: Fut,
+    mut snapshots: UnboundedReceiver<IntervalSnapshot>,
+    mut on_interval: impl FnMut(GraphRun, BenchmarkResults) -> anyhow::Result<()>,
+) -> anyhow::Result<BenchmarkResults>
+where
+    Fut: Future<Output = anyhow::Result<BenchmarkResults>>,
+{
+    tokio::pin!(benchmark);
+    loop {
+        tokio::select! {
+            Some(snapshot) = snapshots.recv() => {
+                on_interval(GraphRun::time_series(snapshot.elapsed), snapshot.results)?;
+            }
+            results = &mut benchmark => {
+                let results = results?;
+                while let Ok(snapshot) = snapshots.try_recv() {
+                    on_interval(GraphRun::time_series(snapshot.elapsed), snapshot.results)?;
+                }
+                return Ok(results);
+            }
+        }
+    }
+}
+
+/// The error of a run that didn't finish within the `--run-timeout`.
+#[derive(Debug)]
+pub struct RunTimedOut {
+    pub timeout: Duration,
+}
+
+impl Display for RunTimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Timed out after {:?}", self.timeout)
+    }
+}
+
+impl std::error::Error for RunTimedOut {}
+
+/// Awaits `benchmark`, cancelling it if it hasn't finished within `timeout`, if there is one. A
+/// cancelled benchmark is followed by `cleanup`, which should tear down anything it started, and
+/// a [`RunTimedOut`] error is returned. Errors from `cleanup` are logged rather than returned.
+pub async fn with_run_timeout<T>(
+    timeout: Option<Duration>,
+    benchmark: impl Future<Output = anyhow::Result<T>>,
+    cleanup: impl Future<Output = anyhow::Result<()>>,
+) -> anyhow::Result<T> {
+    let Some(timeout) = timeout else {
+        return benchmark.await;
+    };
+    match tokio::time::timeout(timeout, benchmark).await {
+        Ok(result) => result,
+        Err(_) => {
+            if let Err(e) = cleanup.await {
+                tracing::warn!("Failed to clean up after a run timed out: {e:#}");
+            }
+            Err(RunTimedOut { timeout }.into())
+        }
+    }
+}
+
+/// How failed runs of a sweep are retried, from `--retries` and `--retry-backoff`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a failed run is retried.
+    pub retries: u32,
+    /// The wait before the first retry, which doubles before each later one.
+    pub backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Calls `attempt` with the number of the attempt, starting at 1, until it succeeds or the
+    /// run has been retried `retries` times, returning its result along with the number of
+    /// attempts it took. An attempt that fails because of [`InvalidRunArgs`] isn't retried. The
+    /// error of a run that failed more than once has a [`FailedAttempts`] context.
+    pub async fn run<T, Fut>(
+        &self,
+        run: &GraphRun,
+        mut attempt: impl FnMut(u32) -> Fut,
+    ) -> anyhow::Result<(T, u32)>
+    where
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut backoff = self.backoff;
+        let mut attempts = 1;
+        loop {
+            let error = match attempt(attempts).await {
+                Ok(value) => return Ok((value, attempts)),
+                Err(e) => e,
+            };
+            if attempts > self.retries || error.is::<InvalidRunArgs>() {
+                return Err(if attempts > 1 {
+                    error.context(FailedAttempts { attempts })
+                } else {
+                    error
+                });
+            }
+            tracing::warn!(
+                "Attempt {attempts} of the run with {run} failed, retrying in {backoff:?}: \
+                 {error:#}"
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = backoff.saturating_mul(2);
+            attempts += 1;
+        }
+    }
+}
+
+/// The error of a run whose arguments couldn't be applied to the benchmark, which retrying the
+/// run can't fix.
+#[derive(Debug)]
+pub struct InvalidRunArgs;
+
+impl Display for InvalidRunArgs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid arguments for the run")
+    }
+}
+
+impl std::error::Error for InvalidRunArgs {}
+
+/// The context of the error of a run that was retried with `--retries` and failed every time.
+#[derive(Debug)]
+pub struct FailedAttempts {
+    pub attempts: u32,
+}
+
+impl Display for FailedAttempts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Failed after {} attempts", self.attempts)
+    }
+}
+
+impl std::error::Error for FailedAttempts {}
+
+/// Returns the results of a run that ended with `result`, having finished the repetitions in
+/// `completed`. With `keep_going`, a run that timed out after finishing some of its repetitions
+/// returns them, marked as [timed out](BenchmarkResults::timed_out), instead of its error.
+pub fn completed_results(
+    result: anyhow::Result<()>,
+    mut completed: Vec<BenchmarkResults>,
+    keep_going: bool,
+) -> anyhow::Result<Vec<BenchmarkResults>> {
+    match result {
+        Ok(()) => Ok(completed),
+        Err(e) if keep_going && e.is::<RunTimedOut>() && !completed.is_empty() => {
+            for results in &mut completed {
+                results.timed_out = true;
+            }
+            Ok(completed)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Prints a one-line summary of each run of a sweep to stderr as it finishes, so that bad results
+/// can be spotted without waiting for the whole sweep: how far through the sweep it is, the run's
+/// values and wall time, and the mean, p50 and p99 of a headline metric, followed by an estimate
+/// of how long the rest of the sweep will take.
+pub struct Progress {
+    completed: usize,
+    total: usize,
+    metric: Option<String>,
+    params: GraphParams,
+}
+
+impl Progress {
+    /// Returns the progress of a sweep of `total` runs, of which `completed` have already
+    /// finished, for example before the sweep was resumed. Returns `None` with `--quiet`.
+    pub fn new(params: &GraphParams, completed: usize, total: usize) -> Option<Self> {
+        (!params.quiet).then(|| Self {
+            completed,
+            total,
+            metric: params.progress_metric.clone(),
+            params: params.clone(),
+        })
+    }
+
+    /// Prints the summary of `run`, which took `elapsed` to produce `results`, and the estimated
+    /// time remaining if there are runs left.
+    pub fn report(&mut self, run: &GraphRun, elapsed: Duration, results: &[BenchmarkResults]) {
+        eprintln!("{}", self.summary(run, elapsed, results));
+        if let Some(remaining) = self.estimated_remaining(run, results) {
+            eprintln!("Estimated time remaining: {}", format_eta(remaining));
+        }
+    }
+
+    /// Returns the estimated time until the rest of the sweep finishes, based on the `results`
+    /// of the latest `run`, or `None` if it was the last or there's nothing to estimate from.
+    fn estimated_remaining(
+        &self,
+        run: &GraphRun,
+        results: &[BenchmarkResults],
+    ) -> Option<Duration> {
+        let remaining_values = self.total.checked_sub(self.completed).filter(|n| *n > 0)?;
+        let per_run = run.estimated_duration(results.last())?;
+        let remaining_runs = remaining_values * self.params.runs_per_value.max(1) as usize;
+        Some(self.params.remaining_duration(remaining_runs, per_run))
+    }
+
+    fn summary(
+        &mut self,
+        run: &GraphRun,
+        elapsed: Duration,
+        results: &[BenchmarkResults],
+    ) -> String {
+        self.completed += 1;
+        let mut summary = format!("[{}/{}] {run} in {elapsed:.2?}", self.completed, self.total);
+        let Some(metric) = self.metric.as_deref().or_else(|| {
+            results
+                .iter()
+                .flat_map(|result| result.metric_names())
+                .min()
+        }) else {
+            return summary;
+        };
+        // Repeated runs are summarized together, as if they were a single run.
+        let hist = results
+            .iter()
+            .filter_map(|result| result.results.get(metric))
+            .map(|data| data.to_histogram())
+            .reduce(|mut hist, other| {
+                hist.add(other).unwrap();
+                hist
+            });
+        match hist {
+            Some(hist) => summary.push_str(&format!(
+                ": {metric} mean {:.2}, p50 {}, p99 {}",
+                hist.mean(),
+                hist.value_at_quantile(0.5),
+                hist.value_at_quantile(0.99)
+            )),
+            None => summary.push_str(&format!(": no results for {metric}")),
+        }
+        summary
+    }
+}
+
+/// Formats an estimated duration to the second, e.g. `1h 5m 12s`.
+fn format_eta(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}h {minutes}m {secs}s")
+    } else if minutes > 0 {
+        format!("{minutes}m {secs}s")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+/// Records which runs of a sweep have completed in a file alongside its results, so that an
+/// interrupted sweep can be resumed without rerunning them. The checkpoint is only meaningful for
+/// the sweep that wrote it, and is removed once the sweep finishes.
+pub struct Checkpoint {
+    path: PathBuf,
+    completed: HashSet<String>,
+    file: File,
+}
+
+impl Checkpoint {
+    /// Returns the path of the checkpoint for a sweep writing its results to `results_path`.
+    pub fn path_for(results_path: &Path) -> PathBuf {
+        let mut path = results_path.as_os_str().to_owned();
+        path.push(".checkpoint");
+        path.into()
+    }
+
+    /// Opens the checkpoint for a sweep writing its results to `results_path`. When resuming,
+    /// the runs recorded by the interrupted sweep are loaded, and it is an error for there to be
+    /// no checkpoint; otherwise any existing checkpoint is discarded.
+    pub fn open(results_path: &Path, resume: bool) -> anyhow::Result<Self> {
+        let path = Self::path_for(results_path);
+        let mut completed = HashSet::new();
+        if resume {
+            let file = File::open(&path).map_err(|e| {
+                anyhow!(
+                    "Can't resume without a checkpoint at {}: {e}",
+                    path.display()
+                )
+            })?;
+            for line in BufReader::new(file).lines() {
+                completed.insert(line?);
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(resume)
+            .write(true)
+            .truncate(!resume)
+            .open(&path)?;
+        Ok(Self {
+            path,
+            completed,
+            file,
+        })
+    }
+
+    /// Whether `run` was completed before the sweep was resumed.
+    pub fn is_completed(&self, run: &GraphRun) -> bool {
+        self.completed.contains(&run.to_string())
+    }
+
+    /// Records that `run` has completed. Its results should already have been written.
+    pub fn record(&mut self, run: &GraphRun) -> anyhow::Result<()> {
+        writeln!(self.file, "{run}")?;
+        self.file.sync_data()?;
+        self.completed.insert(run.to_string());
+        Ok(())
+    }
+
+    /// Removes the checkpoint once the sweep has completed successfully.
+    pub fn remove(self) -> anyhow::Result<()> {
+        std::fs::remove_file(&self.path)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::axis::parse_duration_arg;
+    use crate::benchmark::MetricGoal;
+    use crate::results_sink::ACHIEVED_QPS_COLUMN;
+    use crate::results_sink::ATTEMPTS_COLUMN;
+    use crate::results_sink::ERROR_COLUMN;
+    use crate::results_sink::MAX_ERROR_LEN;
+    use crate::results_sink::TIMED_OUT_COLUMN;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::time::Instant;
+
+    use anyhow::bail;
+    use hdrhistogram::Histogram;
+    use metrics::Unit;
+    use serde_json::json;
+    use tracing::level_filters::LevelFilter;
+
+    use super::*;
+    use crate::benchmark::IntervalResults;
+    use crate::results_sink;
+    use crate::test_utils::{params, results, run};
+    use crate::utils::multi_thread::{run_multithread_benchmark, MultithreadBenchmark};
+
+    #[test]
+    fn resume_requires_checkpoint() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.csv");
+        let err = Checkpoint::open(&path, true).err().unwrap().to_string();
+        assert!(err.contains("checkpoint"), "{err}");
+    }
+
+    #[test]
+    fn progress_summary() {
+        let params = params("target-qps", "100,200");
+        let mut progress = Progress::new(&params, 0, 2).unwrap();
+        let latency = (1..=100).collect::<Vec<_>>();
+        let results = results(&[("write latency", &[1]), ("read latency", &latency)]);
+
+        assert_eq!(
+            progress.summary(&run("100"), Duration::from_millis(1500), &[results]),
+            "[1/2] target-qps = 100 in 1.50s: read latency mean 50.50, p50 50, p99 99"
+        );
+        assert_eq!(
+            progress.summary(&run("200"), Duration::from_secs(2), &[]),
+            "[2/2] target-qps = 200 in 2.00s"
+        );
+    }
+
+    #[test]
+    fn progress_summary_with_metric() {
+        let params = GraphParams {
+            progress_metric: Some("write latency".to_owned()),
+            ..params("target-qps", "100,200,300")
+        };
+        // The first run completed before the sweep was resumed.
+        let mut progress = Progress::new(&params, 1, 3).unwrap();
+        let runs = [
+            results(&[("write latency", &[10, 20])]),
+            results(&[("write latency", &[30, 40])]),
+        ];
+        assert_eq!(
+            progress.summary(&run("200"), Duration::from_secs(1), &runs),
+            "[2/3] target-qps = 200 in 1.00s: write latency mean 25.00, p50 20, p99 40"
+        );
+        assert_eq!(
+            progress.summary(&run("300"), Duration::from_secs(1), &[results(&[])]),
+            "[3/3] target-qps = 300 in 1.00s: no results for write latency"
+        );
+
+        let quiet = GraphParams {
+            quiet: true,
+            ..params
+        };
+        assert!(Progress::new(&quiet, 0, 3).is_none());
+    }
+
+    #[test]
+    fn total_estimated_duration() {
+        let mut params = params("target-qps", "100,200,300");
+        params.runs_per_value = 2;
+        params.cooldown_secs = 1.0;
+        let timed = |secs| {
+            let mut results = results(&[("latency", &[1])]);
+            results.record_elapsed_time(Duration::from_secs(secs));
+            results
+        };
+        assert_eq!(params.total_estimated_duration(&[]), None);
+        // Five runs of the latest duration remain, with a pause before each of three values.
+        assert_eq!(
+            params.total_estimated_duration(&[timed(10)]),
+            Some(Duration::from_secs(53))
+        );
+        assert_eq!(
+            params.total_estimated_duration(&[timed(10), timed(20)]),
+            Some(Duration::from_secs(82))
+        );
+        let finished = (0..6).map(|_| timed(10)).collect::<Vec<_>>();
+        assert_eq!(
+            params.total_estimated_duration(&finished),
+            Some(Duration::ZERO)
+        );
+
+        params.graph_parallelism = 2;
+        let mut progress = Progress::new(&params, 0, 3).unwrap();
+        progress.summary(
+            &run("100"),
+            Duration::from_secs(20),
+            &[timed(10), timed(10)],
+        );
+        assert_eq!(
+            progress.estimated_remaining(&run("100"), &[timed(10), timed(10)]),
+            Some(Duration::from_secs(21))
+        );
+        progress.summary(
+            &run("200"),
+            Duration::from_secs(20),
+            &[timed(10), timed(10)],
+        );
+        progress.summary(
+            &run("300"),
+            Duration::from_secs(20),
+            &[timed(10), timed(10)],
+        );
+        assert_eq!(
+            progress.estimated_remaining(&run("300"), &[timed(10), timed(10)]),
+            None
+        );
+        assert_eq!(format_eta(Duration::from_secs(21)), "21s");
+        assert_eq!(format_eta(Duration::from_secs(3912)), "1h 5m 12s");
+    }
+
+    #[tokio::test]
+    async fn run_in_order_preserves_order() {
+        let mut params = params("target-qps", "1,2,3,4,5,6,7,8,9,10");
+        params.graph_parallelism = 4;
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+
+        let mut written = Vec::new();
+        let benchmark = {
+            let (in_flight, max_in_flight) = (Arc::clone(&in_flight), Arc::clone(&max_in_flight));
+            move |run: GraphRun, _| {
+                let (in_flight, max_in_flight) =
+                    (Arc::clone(&in_flight), Arc::clone(&max_in_flight));
+                async move {
+                    let running = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_in_flight.fetch_max(running, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(rand::random_range(10..50))).await;
+                    in_flight.fetch_sub(1, Ordering::SeqCst);
+                    anyhow::Ok(run.x_value.clone())
+                }
+            }
+        };
+        run_in_order(
+            params.runs(),
+            4,
+            Cooldown::default(),
+            false,
+            benchmark,
+            |run, x_value| {
+                let x_value = x_value?;
+                assert_eq!(run.x_value, x_value);
+                written.push(x_value);
+                Ok(())
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(written, *params.x_values.unwrap());
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn run_in_order_stops_at_first_error() {
+        let runs = params("target-qps", "1,2,3,4").runs();
+        let mut written = Vec::new();
+        let err = run_in_order(
+            runs,
+            2,
+            Cooldown::default(),
+            false,
+            |run: GraphRun, _| async move {
+                tokio::time::sleep(Duration::from_millis(rand::random_range(1..20))).await;
+                if run.x_value == "3" {
+                    bail!("Benchmark failed running {run}");
+                }
+                Ok(())
+            },
+            |run, result| {
+                result?;
+                written.push(run.x_value);
+                Ok(())
+            },
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("target-qps = 3"), "{err}");
+        assert_eq!(written, ["1", "2"]);
+    }
+
+    #[tokio::test]
+    async fn keep_going_writes_failed_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100,200,300");
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        params.keep_going = true;
+        let mut writer = results_sink::from_params(&params).unwrap();
+        let mut failed = Vec::new();
+        run_in_order(
+            params.runs(),
+            2,
+            Cooldown::default(),
+            true,
+            |run: GraphRun, _| async move {
+                if run.x_value == "200" {
+                    bail!("Deployment restarted{}", "!".repeat(MAX_ERROR_LEN));
+                }
+                Ok(results(&[("latency", &[1, 2, 3])]))
+            },
+            |run, result| match result {
+                Ok(results) => writer.write_results(&run, std::slice::from_ref(&results), None),
+                Err(e) => {
+                    failed.push(run.x_value.clone());
+                    writer.write_failure(&run, &e)
+                }
+            },
+        )
+        .await
+        .unwrap();
+        writer.finish().unwrap();
+        assert_eq!(failed, ["200"]);
+
+        let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+        let header = reader.headers().unwrap().clone();
+        assert_eq!(&header[header.len() - 1], ERROR_COLUMN);
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(
+            rows.iter().map(|row| &row[0]).collect::<Vec<_>>(),
+            ["100", "200", "300"]
+        );
+        assert_eq!(&rows[0][1], "3");
+        assert_eq!(&rows[2][header.len() - 1], "");
+        let failed = &rows[1];
+        assert!((1..header.len() - 1).all(|i| &failed[i] == "NaN"));
+        let error = &failed[header.len() - 1];
+        assert!(error.starts_with("Deployment restarted!"), "{error}");
+        assert_eq!(error.chars().count(), MAX_ERROR_LEN + "...".len());
+    }
+
+    #[test]
+    fn failures_before_first_result() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100,200");
+        params.graph_results_paths = vec![dir.path().join("graph.jsonl")];
+        params.keep_going = true;
+        let error = anyhow!("Benchmark failed");
+
+        // A failure is written after the header, once a run has succeeded.
+        let mut writer = results_sink::from_params(&params).unwrap();
+        writer.write_failure(&run("100"), &error).unwrap();
+        writer
+            .write_result("200", &results(&[("latency", &[1])]))
+            .unwrap();
+        writer.finish().unwrap();
+        let contents = std::fs::read_to_string(params.graph_results_path()).unwrap();
+        let rows = contents
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows[0],
+            json!({ "x_value": 100, "error": "Benchmark failed" })
+        );
+        assert_eq!(rows[1]["x_value"], 200);
+        assert!(rows[1].get("error").is_none());
+
+        // If every run failed, a header without any metrics is written.
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        let mut writer = results_sink::from_params(&params).unwrap();
+        writer.write_failure(&run("100"), &error).unwrap();
+        writer.finish().unwrap();
+        let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+        assert_eq!(
+            reader.headers().unwrap(),
+            vec!["target-qps", ACHIEVED_QPS_COLUMN, ERROR_COLUMN]
+        );
+        let row = reader.records().next().unwrap().unwrap();
+        assert_eq!(row, vec!["100", "NaN", "Benchmark failed"]);
+
+        params.keep_going = false;
+        let mut writer = results_sink::from_params(&params).unwrap();
+        assert!(writer.write_failure(&run("100"), &error).is_err());
+    }
+
+    #[tokio::test]
+    async fn run_timeout_cancels_benchmark() {
+        let cleaned_up = AtomicBool::new(false);
+        let cleanup = async {
+            cleaned_up.store(true, Ordering::SeqCst);
+            Ok(())
+        };
+        let err = with_run_timeout(
+            Some(Duration::from_millis(10)),
+            std::future::pending::<anyhow::Result<()>>(),
+            cleanup,
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<RunTimedOut>().unwrap().timeout,
+            Duration::from_millis(10)
+        );
+        assert!(cleaned_up.load(Ordering::SeqCst));
+
+        // Benchmarks that finish in time aren't cleaned up.
+        let cleaned_up = AtomicBool::new(false);
+        let result = with_run_timeout(Some(Duration::from_secs(10)), async { Ok(1) }, async {
+            cleaned_up.store(true, Ordering::SeqCst);
+            Ok(())
+        })
+        .await;
+        assert_eq!(result.unwrap(), 1);
+        assert!(!cleaned_up.load(Ordering::SeqCst));
+
+        assert_eq!(parse_duration_arg("90s"), Ok(Duration::from_secs(90)));
+        assert!(parse_duration_arg("soon").is_err());
+    }
+
+    #[test]
+    fn timed_out_runs_keep_completed_results() {
+        let timed_out = || {
+            Err(RunTimedOut {
+                timeout: Duration::from_secs(1),
+            }
+            .into())
+        };
+        let completed = || vec![results(&[("latency", &[1])])];
+
+        let results = completed_results(timed_out(), completed(), true).unwrap();
+        assert!(results.iter().all(|results| results.timed_out));
+        // Without --keep-going, or without any finished repetitions, the run fails.
+        assert!(completed_results(timed_out(), completed(), false).is_err());
+        assert!(completed_results(timed_out(), Vec::new(), true).is_err());
+        assert!(completed_results(Err(anyhow!("Failed")), completed(), true).is_err());
+        let results = completed_results(Ok(()), completed(), false).unwrap();
+        assert!(!results[0].timed_out);
+    }
+
+    #[tokio::test]
+    async fn run_timeout_writes_timed_out_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100,200,300");
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        params.keep_going = true;
+        params.run_timeout = Some(Duration::from_millis(10));
+        let mut writer = results_sink::from_params(&params).unwrap();
+        run_in_order(
+            params.runs(),
+            1,
+            Cooldown::default(),
+            true,
+            |run: GraphRun, _| async move {
+                let mut completed = vec![results(&[("latency", &[1, 2, 3])])];
+                let benchmark = async {
+                    match run.x_value.as_str() {
+                        "100" => Ok(()),
+                        // Never finishes, after finishing a repetition.
+                        "200" => std::future::pending().await,
+                        // Never finishes, without finishing any repetitions.
+                        _ => {
+                            completed.clear();
+                            std::future::pending().await
+                        }
+                    }
+                };
+                let result =
+                    with_run_timeout(Some(Duration::from_millis(10)), benchmark, async { Ok(()) })
+                        .await;
+                completed_results(result, completed, true)
+            },
+            |run, result| match result {
+                Ok(results) => writer.write_results(&run, &results, None),
+                Err(e) => writer.write_failure(&run, &e),
+            },
+        )
+        .await
+        .unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+        let header = reader.headers().unwrap().clone();
+        let column = header.iter().position(|c| c == TIMED_OUT_COLUMN).unwrap();
+        let error_column = header.len() - 1;
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(
+            rows.iter()
+                .map(|row| (&row[0], &row[1], &row[column]))
+                .collect::<Vec<_>>(),
+            [
+                ("100", "3", "false"),
+                ("200", "3", "true"),
+                ("300", "NaN", "true")
+            ]
+        );
+        assert_eq!(&rows[2][error_column], "Timed out after 10ms");
+    }
+
+    /// A fake benchmark for `x_value` that fails until its `failures`th attempt, recording the
+    /// number of every attempt it was called with.
+    async fn flaky_benchmark(
+        attempts: &Mutex<Vec<u32>>,
+        attempt: u32,
+        failures: u32,
+    ) -> anyhow::Result<BenchmarkResults> {
+        attempts.lock().unwrap().push(attempt);
+        if attempt <= failures {
+            bail!("Connection refused");
+        }
+        Ok(results(&[("latency", &[1, 2, 3])]))
+    }
+
+    #[tokio::test]
+    async fn retries_failed_runs() {
+        let policy = RetryPolicy {
+            retries: 3,
+            backoff: Duration::from_millis(1),
+        };
+        let attempts = Mutex::new(Vec::new());
+        let (results, num_attempts) = policy
+            .run(&run("100"), |attempt| {
+                flaky_benchmark(&attempts, attempt, 2)
+            })
+            .await
+            .unwrap();
+        assert_eq!(num_attempts, 3);
+        assert_eq!(*attempts.lock().unwrap(), [1, 2, 3]);
+        assert_eq!(results.results["latency"].to_histogram().len(), 3);
+
+        // A run that fails on every attempt is retried `retries` times.
+        let attempts = Mutex::new(Vec::new());
+        let err = policy
+            .run(&run("100"), |attempt| {
+                flaky_benchmark(&attempts, attempt, 10)
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(*attempts.lock().unwrap(), [1, 2, 3, 4]);
+        assert_eq!(err.downcast_ref::<FailedAttempts>().unwrap().attempts, 4);
+        assert_eq!(
+            format!("{err:#}"),
+            "Failed after 4 attempts: Connection refused"
+        );
+
+        // Invalid arguments are never retried.
+        let attempts = Mutex::new(Vec::new());
+        let err = policy
+            .run(&run("100"), |attempt| {
+                attempts.lock().unwrap().push(attempt);
+                async { Err::<(), _>(anyhow!("Unknown flag --qps").context(InvalidRunArgs)) }
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(*attempts.lock().unwrap(), [1]);
+        assert!(err.downcast_ref::<FailedAttempts>().is_none());
+        assert!(err.is::<InvalidRunArgs>());
+
+        // Without retries, a failed run is attempted once.
+        let attempts = Mutex::new(Vec::new());
+        let err = RetryPolicy::default()
+            .run(&run("100"), |attempt| {
+                flaky_benchmark(&attempts, attempt, 1)
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(*attempts.lock().unwrap(), [1]);
+        assert_eq!(err.to_string(), "Connection refused");
+    }
+
+    #[tokio::test]
+    async fn retries_write_attempts_column() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = params("target-qps", "100,200,300");
+        params.graph_results_paths = vec![dir.path().join("graph.csv")];
+        params.keep_going = true;
+        params.retries = 2;
+        params.retry_backoff = Duration::from_millis(1);
+        params.validate().unwrap();
+        let policy = params.retry_policy();
+        let mut writer = results_sink::from_params(&params).unwrap();
+        run_in_order(
+            params.runs(),
+            1,
+            Cooldown::default(),
+            true,
+            move |run: GraphRun, _| async move {
+                let attempts = Mutex::new(Vec::new());
+                let failures = match run.x_value.as_str() {
+                    "100" => 2,
+                    _ => 10,
+                };
+                let (mut results, num_attempts) = policy
+                    .run(&run, |attempt| {
+                        flaky_benchmark(&attempts, attempt, failures)
+                    })
+                    .await?;
+                results.attempts = num_attempts;
+                Ok(vec![results])
+            },
+            |run, result| match result {
+                Ok(results) => writer.write_results(&run, &results, None),
+                Err(e) => writer.write_failure(&run, &e),
+            },
+        )
+        .await
+        .unwrap();
+        writer.finish().unwrap();
+
+        let mut reader = csv::Reader::from_path(params.graph_results_path()).unwrap();
+        let header = reader.headers().unwrap().clone();
+        let column = header.iter().position(|c| c == ATTEMPTS_COLUMN).unwrap();
+        let error_column = header.len() - 1;
+        let rows = reader.records().map(Result::unwrap).collect::<Vec<_>>();
+        assert_eq!(
+            rows.iter()
+                .map(|row| (&row[0], &row[1], &row[column], &row[error_column]))
+                .collect::<Vec<_>>(),
+            [
+                ("100", "3", "3", ""),
+                (
+                    "200",
+                    "NaN",
+                    "3",
+                    "Failed after 3 attempts: Connection refused"
+                ),
+                (
+                    "300",
+                    "NaN",
+                    "3",
+                    "Failed after 3 attempts: Connection refused"
+                ),
+            ]
+        );
+    }
+
+    /// Runs `params` one at a time, returning when each run started and finished.
+    async fn run_with_cooldown(params: &GraphParams) -> Vec<(Instant, Instant)> {
+        params.validate().unwrap();
+        let mut times = Vec::new();
+        run_in_order(
+            params.runs(),
+            1,
+            params.cooldown(),
+            false,
+            |_, _| async {
+                let start = Instant::now();
+                tokio::time::sleep(Duration::from_millis(1)).await;
+                anyhow::Ok((start, Instant::now()))
+            },
+            |_, run_times| {
+                times.push(run_times?);
+                Ok(())
+            },
+        )
+        .await
+        .unwrap();
+        times
+    }
+
+    #[tokio::test]
+    async fn no_cooldown() {
+        let params = params("target-qps", "1,2,3");
+        assert_eq!(params.cooldown().duration, Duration::ZERO);
+        assert_eq!(run_with_cooldown(&params).await.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn cooldown_between_runs() {
+        let params = GraphParams {
+            cooldown_secs: 0.01,
+            cooldown_log_level: LevelFilter::OFF,
+            ..params("target-qps", "1,2,3")
+        };
+        assert_eq!(params.cooldown().log_level, None);
+
+        let times = run_with_cooldown(&params).await;
+        assert_eq!(times.len(), 3);
+        for pair in times.windows(2) {
+            let ((_, finished), (started, _)) = (pair[0], pair[1]);
+            assert!(started - finished >= Duration::from_millis(10));
+        }
+    }
+
+    #[tokio::test]
+    async fn cooldown_until_idle() {
+        let params = GraphParams {
+            cooldown: Some(Duration::from_millis(5)),
+            cooldown_until_idle: Some(Duration::from_secs(10)),
+            cooldown_log_level: LevelFilter::OFF,
+            ..params("target-qps", "1,2,3")
+        };
+        params.validate().unwrap();
+        let finished = Arc::new(AtomicUsize::new(0));
+        // The number of runs that had finished at each check, which reports the deployment busy
+        // on the first check after each run.
+        let checks = Arc::new(Mutex::new(Vec::new()));
+        let cooldown = Cooldown {
+            poll_interval: Some(Duration::from_millis(1)),
+            ..params.cooldown()
+        }
+        .with_idle_check({
+            let (finished, checks) = (Arc::clone(&finished), Arc::clone(&checks));
+            move || {
+                let mut checks = checks.lock().unwrap();
+                let finished = finished.load(Ordering::SeqCst);
+                let idle = checks.last() == Some(&finished);
+                checks.push(finished);
+                Box::pin(async move { anyhow::Ok(idle) })
+            }
+        });
+
+        let mut cooldowns = Vec::new();
+        run_in_order(
+            params.runs(),
+            1,
+            cooldown,
+            false,
+            {
+                let finished = Arc::clone(&finished);
+                move |_, cooled_down| {
+                    finished.fetch_add(1, Ordering::SeqCst);
+                    async move { anyhow::Ok(cooled_down) }
+                }
+            },
+            |_, cooled_down| {
+                cooldowns.push(cooled_down?);
+                Ok(())
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(*checks.lock().unwrap(), [1, 1, 2, 2]);
+        assert_eq!(cooldowns[0], None);
+        for cooled_down in &cooldowns[1..] {
+            assert!(
+                cooled_down.unwrap() >= Duration::from_millis(5),
+                "{cooled_down:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn cooldown_until_idle_times_out() {
+        let checks = Arc::new(AtomicUsize::new(0));
+        let cooldown = Cooldown {
+            idle_timeout: Some(Duration::from_millis(20)),
+            poll_interval: Some(Duration::from_millis(1)),
+            ..Cooldown::default()
+        }
+        .with_idle_check({
+            let checks = Arc::clone(&checks);
+            move || {
+                checks.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async { Err(anyhow!("not reachable")) })
+            }
+        });
+        let run = &params("target-qps", "1").runs()[0];
+        let cooled_down = cooldown.wait(run).await.unwrap();
+        assert!(cooled_down >= Duration::from_millis(20), "{cooled_down:?}");
+        assert!(checks.load(Ordering::SeqCst) > 1);
+
+        // Without an idle check, the timeout alone doesn't cause a cooldown.
+        let cooldown = Cooldown {
+            is_idle: None,
+            ..cooldown
+        };
+        assert_eq!(cooldown.wait(run).await, None);
+    }
+
+    /// Sends a latency of 100 every 100ms for the first second, and of 1000 after that.
+    struct SlowingBenchmark;
+
+    impl MultithreadBenchmark for SlowingBenchmark {
+        type BenchmarkResult = u64;
+        type Parameters = ();
+
+        async fn handle_benchmark_results(
+            results: Vec<u64>,
+            _interval: Duration,
+            benchmark_results: &mut BenchmarkResults,
+        ) -> anyhow::Result<()> {
+            let mut hist = Histogram::<u64>::new(3).unwrap();
+            for value in results {
+                hist.record(value).unwrap();
+            }
+            benchmark_results.push("latency", Unit::Microseconds, MetricGoal::Decreasing, hist);
+            Ok(())
+        }
+
+        async fn benchmark_thread(
+            _params: (),
+            sender: tokio::sync::mpsc::UnboundedSender<u64>,
+        ) -> anyhow::Result<()> {
+            let start = tokio::time::Instant::now();
+            // Stay clear of the ends of the intervals.
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            loop {
+                let latency = if start.elapsed() < Duration::from_secs(1) {
+                    100
+                } else {
+                    1000
+                };
+                sender.send(latency)?;
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn time_series_rows_follow_each_interval() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("graph.csv");
+        let mut writer = results_sink::open(&path, TIME_SERIES_X_AXIS).unwrap();
+        let (intervals, snapshots) = IntervalResults::new(Duration::from_secs(1));
+        let benchmark = run_multithread_benchmark::<SlowingBenchmark>(
+            1,
+            (),
+            Some(Duration::from_millis(2500)),
+            None,
+            Some(intervals),
+        );
+        let results = run_time_series(benchmark, snapshots, |run, results| {
+            writer.write_results(&run, std::slice::from_ref(&results), None)
+        })
+        .await
+        .unwrap();
+        writer.finish().unwrap();
+        assert_eq!(results.sample_count(), 20);
+
+        let mut reader = csv::Reader::from_path(&path).unwrap();
+        let header = reader.headers().unwrap().clone();
+        let column = |name: &str| header.iter().position(|column| column == name).unwrap();
+        let (mean, qps) = (column("latency mean (us)"), column(ACHIEVED_QPS_COLUMN));
+        let rows = reader
+            .records()
+            .map(|row| {
+                let row = row.unwrap();
+                [&row[0], &row[mean], &row[qps]].map(str::to_owned)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [["1", "100", "10"], ["2", "1000", "10"]].map(|row| row.map(str::to_owned))
+        );
+    }
+
+    #[tokio::test]
+    async fn time_series_stops_at_the_first_error() {
+        let (intervals, snapshots) = IntervalResults::new(Duration::from_secs(1));
+        intervals.send(
+            Duration::from_secs(1),
+            Duration::from_secs(1),
+            &results(&[]),
+        );
+        intervals.send(
+            Duration::from_secs(2),
+            Duration::from_secs(1),
+            &results(&[]),
+        );
+        let mut handled = vec![];
+        let err = run_time_series(
+            async { anyhow::Ok(BenchmarkResults::new()) },
+            snapshots,
+            |run, _| {
+                handled.push(run.x_value);
+                bail!("Failed to write")
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Failed to write");
+        assert_eq!(handled, ["1"]);
+
+        let (_, snapshots) = IntervalResults::new(Duration::from_secs(1));
+        let failing = async { Err::<BenchmarkResults, _>(anyhow!("Benchmark failed")) };
+        let err = run_time_series(failing, snapshots, |_, _| Ok(()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Benchmark failed");
+    }
+}
@@ -0,0 +1,153 @@
+//! Helpers shared by the unit tests of the crate's modules.
+
+use std::time::Duration;
+
+use hdrhistogram::Histogram;
+use metrics::Unit;
+use tracing::level_filters::LevelFilter;
+
+use crate::benchmark::{BenchmarkResults, MetricGoal};
+use crate::graph::{DryRunFormat, GraphParams, GraphRun};
+use crate::histograms::HistogramFormat;
+use crate::resource_usage::{ProcessUsage, ResourceSample, ResourceUsage};
+use crate::results_sink::RunMetadata;
+use crate::Quantiles;
+
+/// Returns results with a histogram of the given values for each metric, recorded in
+/// microseconds and better the lower they are.
+pub(crate) fn results(metrics: &[(&str, &[u64])]) -> BenchmarkResults {
+    let mut results = BenchmarkResults::new();
+    for (metric, values) in metrics {
+        let mut histogram = Histogram::<u64>::new(3).unwrap();
+        for value in *values {
+            histogram.record(*value).unwrap();
+        }
+        results.push(
+            metric,
+            Unit::Microseconds,
+            MetricGoal::Decreasing,
+            histogram,
+        );
+    }
+    results
+}
+
+/// Returns the run of a sweep over `target-qps` at `x_value`.
+pub(crate) fn run(x_value: &str) -> GraphRun {
+    GraphRun::new("target-qps", x_value)
+}
+
+/// Returns the parameters of a sweep over `x_axis` at `x_values`, written to `graph.csv`, with
+/// every other option at its default.
+pub(crate) fn params(x_axis: &str, x_values: &str) -> GraphParams {
+    GraphParams {
+        graph: true,
+        time_series: false,
+        time_series_interval: Duration::from_secs(10),
+        x_axis: Some(x_axis.to_owned()),
+        x_values: Some(x_values.parse().unwrap()),
+        x_values_file: None,
+        x_type: None,
+        x_axis_log_scale: false,
+        x_axis_min: None,
+        x_axis_max: None,
+        x_axis_steps: None,
+        z_axis: None,
+        z_values: None,
+        graph_results_paths: vec!["graph.csv".into()],
+        graph_results_path2: None,
+        x_axis_achieved_qps: false,
+        push_gateway: None,
+        push_gateway_retries: 3,
+        runs_per_value: 1,
+        fail_if: vec![],
+        metrics: None,
+        breakdown_by_query: false,
+        convert_units: None,
+        compare_upstream: false,
+        resume: false,
+        buffered: false,
+        append: false,
+        graph_parallelism: 1,
+        cooldown_secs: 0.0,
+        cooldown: None,
+        cooldown_until_idle: None,
+        cooldown_log_level: LevelFilter::INFO,
+        keep_going: false,
+        run_timeout: None,
+        retries: 0,
+        retry_backoff: Duration::from_secs(1),
+        warmup_secs: None,
+        warmup_samples: None,
+        with_metadata: false,
+        baseline_tag: None,
+        tags: vec![],
+        histogram_dir: None,
+        histogram_format: vec![HistogramFormat::Hist],
+        resource_usage: false,
+        resource_usage_interval: Duration::from_secs(1),
+        resource_usage_pid: None,
+        resource_usage_samples: false,
+        quantiles: Quantiles::default(),
+        progress_metric: None,
+        quiet: false,
+        no_summary: false,
+        dry_run: false,
+        shuffle_runs: None,
+        dry_run_format: DryRunFormat::Text,
+    }
+}
+
+/// Returns the parameters of a sweep over `target-qps` at `x_values` and over `z_axis` at
+/// `z_values`.
+pub(crate) fn z_params(x_values: &str, z_axis: &str, z_values: &str) -> GraphParams {
+    GraphParams {
+        z_axis: Some(z_axis.to_owned()),
+        z_values: Some(z_values.parse().unwrap()),
+        ..params("target-qps", x_values)
+    }
+}
+
+/// Results with `samples` latency samples recorded over `secs` seconds.
+pub(crate) fn timed_results(samples: u64, secs: u64) -> BenchmarkResults {
+    let mut results = results(&[("latency", &(1..=samples).collect::<Vec<_>>())]);
+    results.record_elapsed_time(Duration::from_secs(secs));
+    results
+}
+
+/// Returns results with the resource usage samples `benchmark` and `target`, each a list of
+/// CPU utilization and resident memory pairs.
+pub(crate) fn results_with_usage(
+    benchmark: &[(f64, u64)],
+    target: Option<&[(f64, u64)]>,
+) -> BenchmarkResults {
+    let usage = |samples: &[(f64, u64)]| ProcessUsage {
+        samples: samples
+            .iter()
+            .enumerate()
+            .map(|(i, (cpu_percent, rss_bytes))| ResourceSample {
+                elapsed_ms: i as u64 * 100,
+                cpu_percent: *cpu_percent,
+                rss_bytes: *rss_bytes,
+            })
+            .collect(),
+    };
+    let mut results = results(&[("latency", &[1, 2])]);
+    results.resource_usage = Some(ResourceUsage {
+        benchmark: usage(benchmark),
+        target: target.map(usage),
+    });
+    results
+}
+
+/// Returns the metadata of a run, as written with `--with-metadata`.
+pub(crate) fn metadata() -> RunMetadata {
+    RunMetadata {
+        timestamp: "2024-05-01T12:00:00Z".to_owned(),
+        commit: Some("0123abc".to_owned()),
+        hostname: None,
+        arguments: r#"{"target-qps":100}"#.to_owned(),
+        warmup_secs: Some(5),
+        cooldown_ms: Some(250),
+    }
+}
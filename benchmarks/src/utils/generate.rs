@@ -6,9 +6,11 @@ use std::collections::HashMap;
 use std::convert::TryInto;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use anyhow::{anyhow, Result};
 use clap::{Parser, ValueHint};
+use data_generator::{ColumnGenerationSpec, DistributionAnnotation, SchemaCompatibilityError};
 use database_utils::{DatabaseConnection, DatabaseURL, QueryableConnection};
 use futures::StreamExt;
 use itertools::Itertools;
@@ -20,7 +22,9 @@ use nom_sql::whitespace::whitespace0;
 use nom_sql::NomSqlResult;
 use query_generator::{ColumnName, TableName, TableSpec};
 use readyset_data::DfValue;
-use readyset_sql::ast::{Column, Expr, InsertStatement, ItemPlaceholder, Relation, SqlQuery};
+use readyset_sql::ast::{
+    Column, ColumnSpecification, Expr, InsertStatement, ItemPlaceholder, Relation, SqlQuery,
+};
 use readyset_sql::{Dialect, DialectDisplay};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -43,6 +47,12 @@ pub struct DataGenerator {
     /// The format is a json map, for example "{ 'user_rows': '10000', 'article_rows': '100' }"
     #[arg(long)]
     var_overrides: Option<serde_json::Value>,
+
+    /// Columns whose values are computed from the row index rather than generated according to
+    /// the schema. See [`DataGenerator::with_custom_column`].
+    #[arg(skip)]
+    #[serde(skip)]
+    custom_columns: Vec<(ColumnName, ColumnGenerationSpec)>,
 }
 
 fn multi_ddl(input: LocatedSpan<&[u8]>, dialect: Dialect) -> NomSqlResult<&[u8], Vec<SqlQuery>> {
@@ -54,18 +64,30 @@ impl DataGenerator {
         DataGenerator {
             schema: schema.into(),
             var_overrides: None,
+            custom_columns: vec![],
         }
     }
 
+    /// Generates the values of every column named `name`, in any table, by calling `f` with the
+    /// index of the row being generated.
+    pub fn with_custom_column<F>(mut self, name: &str, f: F) -> Self
+    where
+        F: Fn(usize) -> DfValue + Send + Sync + 'static,
+    {
+        self.custom_columns
+            .push((name.into(), ColumnGenerationSpec::Custom(Arc::new(f))));
+        self
+    }
+
+    /// Adds the variable overrides in `json`, which take precedence over any already set. Nested
+    /// objects are merged, so that `{"tables": {"users": {"rows": "10"}}}` only replaces the
+    /// `tables.users.rows` variable.
     pub fn update_from(&mut self, json: serde_json::Value) -> anyhow::Result<()> {
-        match self.var_overrides.as_mut().and_then(|x| x.as_object_mut()) {
-            Some(x) => {
-                let mut override_map = json;
-                let m = override_map
-                    .as_object_mut()
-                    .ok_or_else(|| anyhow!("json was not a map"))?;
-                x.append(m);
-            }
+        if !json.is_object() {
+            return Err(anyhow!("json was not a map"));
+        }
+        match self.var_overrides.as_mut().filter(|x| x.is_object()) {
+            Some(x) => merge_json(x, json),
             None => self.var_overrides = Some(json),
         }
         Ok(())
@@ -164,24 +186,36 @@ impl DataGenerator {
                 DatabaseSchema::new(&ddl, SchemaKind::PostgreSQL)?
             }
             Dialect::MySQL => {
-                let user_vars: HashMap<String, String> = self
-                    .var_overrides
-                    .clone()
-                    .unwrap_or_else(|| json!({}))
-                    .as_object()
-                    .expect("var-overrides should be formatted as a json map")
-                    .into_iter()
-                    .map(|(key, value)| (key.to_owned(), value.as_str().unwrap().to_owned()))
-                    .collect();
+                let mut user_vars = HashMap::new();
+                flatten_vars(
+                    "",
+                    self.var_overrides
+                        .as_ref()
+                        .unwrap_or(&json!({}))
+                        .as_object()
+                        .expect("var-overrides should be formatted as a json map"),
+                    &mut user_vars,
+                );
 
                 let ddl = std::fs::read_to_string(benchmark_path(&self.schema)?)?;
                 DatabaseSchema::new(&ddl, SchemaKind::MySQL { user_vars })?
             }
         };
 
+        self.validate_schema_compatibility(schema.columns())?;
+
         let old_size = Self::adjust_upstream_vars(&db_url).await;
 
-        let database_spec = DatabaseGenerationSpec::new(schema);
+        let mut database_spec = DatabaseGenerationSpec::new(schema);
+        for (column, spec) in &self.custom_columns {
+            for table in database_spec.tables.values_mut() {
+                if table.table.columns.contains_key(column) {
+                    table
+                        .table
+                        .set_column_generator_spec(column.clone(), spec.clone());
+                }
+            }
+        }
         let status = parallel_load(db_url.clone(), database_spec.clone()).await;
 
         Self::revert_upstream_vars(&db_url, old_size).await;
@@ -191,6 +225,48 @@ impl DataGenerator {
         Ok(database_spec)
     }
 
+    /// Checks that every column in `schema` can be generated by its spec: the custom spec given
+    /// by [`DataGenerator::with_custom_column`] if there is one, or else the annotation in its
+    /// comment. Columns without either are generated with values of their own type.
+    ///
+    /// Returns an error listing every incompatible column and every annotation that can't be
+    /// parsed, rather than only the first, so that a schema can be fixed before any data is
+    /// generated.
+    pub fn validate_schema_compatibility(&self, schema: &[ColumnSpecification]) -> Result<()> {
+        let errors = schema
+            .iter()
+            .filter_map(|col| {
+                let name = ColumnName::from(&col.column.name);
+                let column_name = match &col.column.table {
+                    Some(table) => format!("{}.{}", table.name, col.column.name),
+                    None => col.column.name.to_string(),
+                };
+                let spec = match self.custom_columns.iter().rev().find(|(c, _)| *c == name) {
+                    Some((_, spec)) => spec.clone(),
+                    None => match col.comment.as_deref()?.parse::<DistributionAnnotation>() {
+                        Ok(annotation) => annotation.spec,
+                        Err(e) => {
+                            return Some(format!(
+                                "Column {column_name} has an invalid annotation: {e:#}"
+                            ))
+                        }
+                    },
+                };
+                SchemaCompatibilityError::check(&column_name, &col.sql_type, &spec)
+                    .map(|e| e.to_string())
+            })
+            .collect::<Vec<_>>();
+
+        if errors.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "{} column(s) can't be generated:\n{}",
+            errors.len(),
+            errors.iter().join("\n")
+        ))
+    }
+
     pub fn labels(&self) -> HashMap<String, String> {
         let mut labels = HashMap::new();
         labels.insert(
@@ -201,6 +277,49 @@ impl DataGenerator {
     }
 }
 
+/// Adds every variable in `vars` to `user_vars`, naming variables nested within objects by their
+/// dotted path, e.g. `tables.users.rows`, so that they can be referenced as `@tables.users.rows`.
+fn flatten_vars(
+    prefix: &str,
+    vars: &serde_json::Map<String, serde_json::Value>,
+    user_vars: &mut HashMap<String, String>,
+) {
+    for (key, value) in vars {
+        let name = if prefix.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            serde_json::Value::Object(nested) => flatten_vars(&name, nested, user_vars),
+            serde_json::Value::String(value) => {
+                user_vars.insert(name, value.to_owned());
+            }
+            value => {
+                user_vars.insert(name, value.to_string());
+            }
+        }
+    }
+}
+
+/// Merges `other` into `json`, recursing into objects present in both so that nested variables
+/// set by either are kept. Anything else in `other` replaces the corresponding value in `json`.
+pub(crate) fn merge_json(json: &mut serde_json::Value, other: serde_json::Value) {
+    match (json, other) {
+        (serde_json::Value::Object(json), serde_json::Value::Object(other)) => {
+            for (key, value) in other {
+                match json.get_mut(&key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        json.insert(key, value);
+                    }
+                }
+            }
+        }
+        (json, other) => *json = other,
+    }
+}
+
 async fn get_postgres_version(conn: &mut DatabaseConnection) -> Option<usize> {
     match conn {
         DatabaseConnection::PostgreSQL(_, _) => {
@@ -376,3 +495,89 @@ pub async fn parallel_load(db: DatabaseURL, spec: DatabaseGenerationSpec) -> Res
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(ddl: &str) -> DatabaseSchema {
+        DatabaseSchema::new(
+            ddl,
+            SchemaKind::MySQL {
+                user_vars: HashMap::new(),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn compatible_schema() {
+        let schema = schema(
+            r#"CREATE TABLE users (
+                id int NOT NULL PRIMARY KEY,
+                age int COMMENT 'UNIFORM 18 100',
+                name varchar(40) COMMENT 'REGEX "[a-z]{5,20}"',
+                bio text
+            );"#,
+        );
+        DataGenerator::new("schema.sql")
+            .validate_schema_compatibility(schema.columns())
+            .unwrap();
+    }
+
+    #[test]
+    fn incompatible_columns_are_all_reported() {
+        let schema = schema(
+            r#"CREATE TABLE users (
+                id int NOT NULL PRIMARY KEY COMMENT 'REGEX "[a-z]{5}"',
+                name varchar(40) COMMENT 'UNIFORM 0 100',
+                age int COMMENT 'UNIFORM 18 100'
+            );
+            CREATE TABLE posts (
+                flag varchar(10) COMMENT 'BOOLEAN 0.5'
+            );"#,
+        );
+        let err = DataGenerator::new("schema.sql")
+            .validate_schema_compatibility(schema.columns())
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("3 column(s) can't be generated:"), "{err}");
+        for column in ["users.id", "users.name", "posts.flag"] {
+            assert!(err.contains(&format!("Column {column} of type")), "{err}");
+        }
+        assert!(!err.contains("users.age"), "{err}");
+    }
+
+    #[test]
+    fn invalid_annotations_are_reported() {
+        let schema = schema(
+            r#"CREATE TABLE users (
+                name varchar(40) COMMENT 'REGEX "[a-z"',
+                age varchar(10) COMMENT 'UNIFORM 18 100'
+            );"#,
+        );
+        let err = DataGenerator::new("schema.sql")
+            .validate_schema_compatibility(schema.columns())
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("2 column(s) can't be generated:"), "{err}");
+        assert!(
+            err.contains("Column users.name has an invalid annotation"),
+            "{err}"
+        );
+        assert!(err.contains("Column users.age of type"), "{err}");
+    }
+
+    #[test]
+    fn custom_columns_replace_annotations() {
+        let schema = schema(
+            r#"CREATE TABLE users (
+                id int NOT NULL PRIMARY KEY COMMENT 'REGEX "[a-z]{5}"'
+            );"#,
+        );
+        DataGenerator::new("schema.sql")
+            .with_custom_column("id", |i| DfValue::from(i as i64))
+            .validate_schema_compatibility(schema.columns())
+            .unwrap();
+    }
+}
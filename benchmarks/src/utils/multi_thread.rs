@@ -2,14 +2,13 @@ use std::future::Future;
 use std::time::Duration;
 
 use anyhow::Result;
-use futures::stream::futures_unordered::FuturesUnordered;
-use futures::StreamExt;
 use tokio::select;
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
-use tokio::time::Interval;
+use tokio::task::JoinSet;
+use tokio::time::{Instant, Interval};
 use tracing::error;
 
-use crate::benchmark::BenchmarkResults;
+use crate::benchmark::{BenchmarkResults, IntervalResults};
 
 /// A group of methods that facilitate executing a single benchmark from multiple
 /// threads. This should be used in conjunction with `run_multithread_benchmark`
@@ -48,40 +47,70 @@ async fn return_after_duration(duration: Option<Duration>) {
 /// Aggregates benchmark results received over `THREAD_UPDATE_INTERVAL`.
 /// Every `THREAD_UPDATE_INTERVAL`, the batch of benchmark results is
 /// passed to the MultithreadBenchmark's function: handle_benchmark_results().
+///
+/// Results received during the first `warmup` are discarded, and the benchmark then runs for
+/// `run_for` after the warmup.
+///
+/// With `intervals`, batches are handled every [`IntervalResults::interval`] instead, and the
+/// results of each are sent to `intervals` as well as being added to the returned results.
 async fn benchmark_results_thread<B>(
     mut receiver: UnboundedReceiver<B::BenchmarkResult>,
     run_for: Option<Duration>,
+    warmup: Option<Duration>,
+    intervals: Option<IntervalResults>,
 ) -> Result<BenchmarkResults>
 where
     B: MultithreadBenchmark,
 {
     const THREAD_UPDATE_INTERVAL: Duration = Duration::from_secs(10);
-    let mut interval = tokio::time::interval(THREAD_UPDATE_INTERVAL);
+    let mut interval = tokio::time::interval(
+        intervals
+            .as_ref()
+            .map_or(THREAD_UPDATE_INTERVAL, IntervalResults::interval),
+    );
     interval.tick().await; // First tick is immediate
     let mut updates = Vec::new();
 
     // Pin the future so we can poll on it repeatedly in the select loop.
-    let return_after = return_after_duration(run_for);
+    let return_after = return_after_duration(run_for.map(|d| d + warmup.unwrap_or_default()));
     tokio::pin!(return_after);
 
-    let mut results = BenchmarkResults::new();
+    let mut results = match warmup {
+        Some(warmup) => BenchmarkResults::with_warmup(warmup),
+        None => BenchmarkResults::new(),
+    };
 
-    let mut last_update = std::time::Instant::now();
+    let started = Instant::now();
+    let mut last_update = started;
     loop {
         select! {
             // If we reach our thread update interval, run the provided function
             // to handle a batch of updates.
             _ = interval.tick() => {
+                // There's nothing to handle if every update so far was part of the warmup.
+                if updates.is_empty() {
+                    continue;
+                }
                 let mut new_updates = Vec::new();
                 std::mem::swap(&mut new_updates, &mut updates);
                 let elapsed = last_update.elapsed();
-                last_update = std::time::Instant::now();
-                B::handle_benchmark_results(new_updates, elapsed, &mut results).await?;
+                last_update = Instant::now();
+                match &intervals {
+                    Some(intervals) => {
+                        let mut batch = BenchmarkResults::new();
+                        B::handle_benchmark_results(new_updates, elapsed, &mut batch).await?;
+                        intervals.send(started.elapsed(), elapsed, &batch);
+                        results = std::mem::take(&mut results).merge(batch);
+                    }
+                    None => B::handle_benchmark_results(new_updates, elapsed, &mut results).await?,
+                }
             }
             // If we receive an update push it to the next batch of updates.
             r = receiver.recv() => {
                 if let Some(r) = r {
-                    updates.push(r);
+                    if !results.is_warming_up() {
+                        updates.push(r);
+                    }
                 } else {
                     break; // All threads have dropped their sender.
                 }
@@ -98,25 +127,35 @@ where
 /// Spawns a multi-threaded benchmark across `num_threads` threads running
 /// MultithreadBenchmark's `benchmark_results_thread`. An additional thread
 /// used to aggregates results over an interval is created from
-/// `benchmark_results_thread`.
+/// `benchmark_results_thread`. Results sent during the first `warmup` are discarded, and with
+/// `intervals`, a snapshot of the results is sent to it every interval.
+///
+/// Every spawned task is aborted if the returned future is dropped, such as when a run exceeds
+/// its `--run-timeout`, so a cancelled benchmark doesn't keep sending queries.
 pub(crate) async fn run_multithread_benchmark<B>(
     num_threads: u64,
     params: B::Parameters,
     run_for: Option<Duration>,
+    warmup: Option<Duration>,
+    intervals: Option<IntervalResults>,
 ) -> Result<BenchmarkResults>
 where
     B: MultithreadBenchmark + 'static,
 {
     let (sender, receiver) = unbounded_channel::<B::BenchmarkResult>();
 
-    let mut workers: FuturesUnordered<_> = (0..num_threads)
-        .map(|_| tokio::spawn(B::benchmark_thread(params.clone(), sender.clone())))
-        .collect();
-    let mut results = tokio::spawn(benchmark_results_thread::<B>(receiver, run_for));
+    let mut workers = JoinSet::new();
+    for _ in 0..num_threads {
+        workers.spawn(B::benchmark_thread(params.clone(), sender.clone()));
+    }
+    let mut results = JoinSet::new();
+    results.spawn(benchmark_results_thread::<B>(
+        receiver, run_for, warmup, intervals,
+    ));
 
     loop {
         select! {
-            w = workers.next() => {
+            w = workers.join_next() => {
                 match w {
                     // Error returned from future.
                     Some(Err(e)) => {
@@ -134,7 +173,7 @@ where
                     None => break,
                 }
             }
-            res = &mut results => {
+            Some(res) = results.join_next() => {
                 return res?;
             }
         }
@@ -147,3 +186,142 @@ pub(crate) fn throttle_interval(target_qps: Option<u64>, num_threads: u64) -> Op
         .as_ref()
         .map(|qps| tokio::time::interval(Duration::from_nanos(1000000000 * num_threads / qps)))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use hdrhistogram::Histogram;
+    use metrics::Unit;
+
+    use super::*;
+    use crate::benchmark::MetricGoal;
+
+    /// Sends a latency of 1 immediately and of 2 after a second, then waits forever.
+    struct FakeBenchmark;
+
+    impl MultithreadBenchmark for FakeBenchmark {
+        type BenchmarkResult = u64;
+        type Parameters = ();
+
+        async fn handle_benchmark_results(
+            results: Vec<u64>,
+            _interval: Duration,
+            benchmark_results: &mut BenchmarkResults,
+        ) -> Result<()> {
+            let mut hist = Histogram::<u64>::new(3).unwrap();
+            for value in results {
+                hist.record(value).unwrap();
+            }
+            benchmark_results.push("latency", Unit::Microseconds, MetricGoal::Decreasing, hist);
+            Ok(())
+        }
+
+        async fn benchmark_thread(_params: (), sender: UnboundedSender<u64>) -> Result<()> {
+            sender.send(1)?;
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            sender.send(2)?;
+            futures::future::pending().await
+        }
+    }
+
+    /// The number of results sent by every [`CountingBenchmark`] so far.
+    static SENT: AtomicU64 = AtomicU64::new(0);
+
+    /// Sends a latency of 1 every second, forever.
+    struct CountingBenchmark;
+
+    impl MultithreadBenchmark for CountingBenchmark {
+        type BenchmarkResult = u64;
+        type Parameters = ();
+
+        async fn handle_benchmark_results(
+            _results: Vec<u64>,
+            _interval: Duration,
+            _benchmark_results: &mut BenchmarkResults,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        async fn benchmark_thread(_params: (), sender: UnboundedSender<u64>) -> Result<()> {
+            loop {
+                sender.send(1)?;
+                SENT.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelling_stops_workers() {
+        let benchmark = run_multithread_benchmark::<CountingBenchmark>(2, (), None, None, None);
+        tokio::time::timeout(Duration::from_millis(2500), benchmark)
+            .await
+            .unwrap_err();
+        let sent = SENT.load(Ordering::SeqCst);
+        assert_eq!(sent, 6);
+
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        assert_eq!(SENT.load(Ordering::SeqCst), sent);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn without_warmup() {
+        let results = run_multithread_benchmark::<FakeBenchmark>(
+            1,
+            (),
+            Some(Duration::from_secs(15)),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+        let hist = results.results["latency"].to_histogram();
+        assert_eq!((hist.len(), hist.min(), hist.max()), (2, 1, 2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn discards_results_during_warmup() {
+        let results = run_multithread_benchmark::<FakeBenchmark>(
+            1,
+            (),
+            Some(Duration::from_secs(15)),
+            Some(Duration::from_millis(500)),
+            None,
+        )
+        .await
+        .unwrap();
+        let hist = results.results["latency"].to_histogram();
+        assert_eq!((hist.len(), hist.min(), hist.max()), (1, 2, 2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sends_interval_snapshots() {
+        let (intervals, mut snapshots) = IntervalResults::new(Duration::from_millis(400));
+        let results = run_multithread_benchmark::<FakeBenchmark>(
+            1,
+            (),
+            Some(Duration::from_secs(2)),
+            None,
+            Some(intervals),
+        )
+        .await
+        .unwrap();
+        let hist = results.results["latency"].to_histogram();
+        assert_eq!((hist.len(), hist.min(), hist.max()), (2, 1, 2));
+
+        // Nothing is recorded in the intervals ending at 0.8s and after 1.2s.
+        let mut received = vec![];
+        while let Ok(snapshot) = snapshots.try_recv() {
+            let hist = snapshot.results.results["latency"].to_histogram();
+            received.push((snapshot.elapsed, hist.len(), hist.max()));
+        }
+        assert_eq!(
+            received,
+            [
+                (Duration::from_millis(400), 1, 1),
+                (Duration::from_millis(1200), 1, 2),
+            ]
+        );
+    }
+}
@@ -8,6 +8,7 @@
 use std::collections::HashMap;
 
 use anyhow::{anyhow, bail};
+use data_generator::{ColumnGenerator, DistributionAnnotation};
 use database_utils::{DatabaseConnection, DatabaseType};
 use itertools::{Either, Itertools};
 use nom::multi::many1;
@@ -15,8 +16,11 @@ use nom::sequence::{preceded, terminated};
 use nom_locate::LocatedSpan;
 use nom_sql::sql_query;
 use nom_sql::whitespace::whitespace0;
-use query_generator::{TableName, TableSpec};
-use readyset_sql::ast::{CommentStatement, CreateTableOption, Expr, SqlQuery, VariableScope};
+use query_generator::{ColumnName, TableName, TableSpec};
+use readyset_sql::ast::{
+    ColumnSpecification, CommentStatement, CreateTableOption, CreateTableStatement, Expr, SqlQuery,
+    VariableScope,
+};
 use readyset_sql::{Dialect, DialectDisplay};
 
 /// Set of parameters used to generate a single table's data.
@@ -66,6 +70,22 @@ impl DatabaseGenerationSpec {
 #[derive(Clone)]
 pub struct DatabaseSchema {
     tables: HashMap<TableName, TableGenerationSpec>,
+    /// The definition of every column of every table, including its annotation comment and the
+    /// table it belongs to.
+    columns: Vec<ColumnSpecification>,
+}
+
+/// The estimated number of distinct values of a column, from
+/// [`DatabaseSchema::estimated_cardinalities`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnCardinality {
+    pub table: String,
+    pub column: String,
+    /// The number of rows generated for the column's table.
+    pub rows: u64,
+    /// The annotation the column's values are generated with, if it has one.
+    pub annotation: Option<String>,
+    pub cardinality: u64,
 }
 
 pub enum SchemaKind {
@@ -149,6 +169,7 @@ impl DatabaseSchema {
 
         let mut schema = DatabaseSchema {
             tables: HashMap::new(),
+            columns: vec![],
         };
 
         for query in ddl {
@@ -192,6 +213,7 @@ impl DatabaseSchema {
                         }
                     }
 
+                    schema.add_columns(&s);
                     let spec = TableSpec::from(s);
 
                     schema.tables.insert(
@@ -235,6 +257,7 @@ impl DatabaseSchema {
 
         let mut schema = DatabaseSchema {
             tables: HashMap::new(),
+            columns: vec![],
         };
 
         for mut query in ddl.into_iter() {
@@ -306,6 +329,7 @@ impl DatabaseSchema {
                         }
                     }
 
+                    schema.add_columns(s);
                     let spec = TableSpec::from(s.clone());
 
                     schema.tables.insert(
@@ -327,12 +351,72 @@ impl DatabaseSchema {
     pub fn tables(&self) -> &HashMap<TableName, TableGenerationSpec> {
         &self.tables
     }
+
+    /// Returns the definition of every column in the schema, qualified by its table.
+    pub fn columns(&self) -> &[ColumnSpecification] {
+        &self.columns
+    }
+
+    /// Estimates the number of distinct values generated for every column in the schema, in the
+    /// order the columns are defined, with [`DistributionAnnotation::estimated_cardinality`].
+    /// Columns without an annotation get a unique value in every row if they're part of a key,
+    /// and the same value in every row otherwise.
+    pub fn estimated_cardinalities(&self) -> Vec<ColumnCardinality> {
+        self.columns
+            .iter()
+            .filter_map(|col| {
+                let table = col.column.table.as_ref()?;
+                let spec = self.tables.get(table.name.as_str())?;
+                let rows = spec.num_rows as u64;
+                let annotation = col
+                    .comment
+                    .as_deref()
+                    .and_then(|comment| comment.parse::<DistributionAnnotation>().ok());
+                let cardinality = match &annotation {
+                    Some(annotation) => annotation.estimated_cardinality(rows),
+                    None => {
+                        let unique = spec
+                            .table
+                            .columns
+                            .get(&ColumnName::from(&col.column.name))
+                            .is_some_and(|column| {
+                                matches!(
+                                    column.gen_spec.lock().generator,
+                                    ColumnGenerator::Unique(_)
+                                )
+                            });
+                        if unique {
+                            rows
+                        } else {
+                            rows.min(1)
+                        }
+                    }
+                };
+                Some(ColumnCardinality {
+                    table: table.name.to_string(),
+                    column: col.column.name.to_string(),
+                    rows,
+                    annotation: annotation.map(|annotation| annotation.to_string()),
+                    cardinality,
+                })
+            })
+            .collect()
+    }
+
+    fn add_columns(&mut self, stmt: &CreateTableStatement) {
+        let Ok(body) = &stmt.body else {
+            return;
+        };
+        self.columns.extend(body.fields.iter().map(|field| {
+            let mut field = field.clone();
+            field.column.table = Some(stmt.table.clone());
+            field
+        }));
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use data_generator::ColumnGenerator;
-
     use super::*;
 
     #[test]
@@ -506,4 +590,67 @@ mod tests {
             ColumnGenerator::NonRepeating(_)
         ));
     }
+
+    #[test]
+    fn estimated_cardinalities() {
+        let ddl = r#"
+            CREATE TABLE orders (
+                id int(11) NOT NULL PRIMARY KEY,
+                customer_id int(11) NOT NULL COMMENT 'UNIFORM 0 200',
+                status varchar(20) NOT NULL COMMENT 'enum new shipped delivered',
+                reference varchar(12) NOT NULL COMMENT 'REGEX "[A-Z]{12}" UNIQUE',
+                note varchar(255) DEFAULT NULL
+            ) COMMENT = 'ROWS=1000';
+
+            CREATE TABLE customers (
+                id int(11) NOT NULL PRIMARY KEY,
+                region int(11) NOT NULL COMMENT 'UNIFORM 0 200'
+            ) COMMENT = 'ROWS=50';
+        "#;
+        let schema = DatabaseSchema::new(
+            ddl,
+            SchemaKind::MySQL {
+                user_vars: HashMap::new(),
+            },
+        )
+        .unwrap();
+
+        let cardinalities = schema.estimated_cardinalities();
+        assert_eq!(
+            cardinalities
+                .iter()
+                .map(|c| (
+                    c.table.as_str(),
+                    c.column.as_str(),
+                    c.rows,
+                    c.annotation.as_deref(),
+                    c.cardinality
+                ))
+                .collect::<Vec<_>>(),
+            [
+                ("orders", "id", 1000, None, 1000),
+                ("orders", "customer_id", 1000, Some("uniform 0 200"), 200),
+                (
+                    "orders",
+                    "status",
+                    1000,
+                    Some("enum new shipped delivered"),
+                    3
+                ),
+                (
+                    "orders",
+                    "reference",
+                    1000,
+                    Some("regex [A-Z]{12} unique"),
+                    1000
+                ),
+                ("orders", "note", 1000, None, 1),
+                ("customers", "id", 50, None, 50),
+                ("customers", "region", 50, Some("uniform 0 200"), 50),
+            ]
+        );
+        for c in &cardinalities {
+            assert!((1..=c.rows).contains(&c.cardinality), "{c:?}");
+        }
+    }
 }
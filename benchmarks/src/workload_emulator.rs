@@ -25,7 +25,10 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc::UnboundedSender;
 use tracing::info;
 
-use crate::benchmark::{BenchmarkControl, BenchmarkResults, DeploymentParameters, MetricGoal};
+use crate::benchmark::{
+    BenchmarkControl, BenchmarkResults, DeploymentParameters, IntervalResults, MetricGoal,
+    TimestampedResult,
+};
 use crate::spec::WorkloadSpec;
 use crate::utils::generate::DataGenerator;
 use crate::utils::multi_thread::{self, MultithreadBenchmark};
@@ -107,6 +110,23 @@ pub struct WorkloadEmulator {
     #[serde(skip)]
     #[arg(skip)]
     query_set: Arc<Mutex<Option<Arc<QuerySet>>>>,
+
+    /// How long to run before recording results, set by [`BenchmarkControl::set_warmup`].
+    #[serde(skip)]
+    #[arg(skip)]
+    warmup: Option<Duration>,
+
+    /// How many of the first latencies of each query to record with when they were recorded, set
+    /// by [`BenchmarkControl::set_warmup_samples`].
+    #[serde(skip)]
+    #[arg(skip)]
+    warmup_samples: usize,
+
+    /// Where to send snapshots of the results as they're recorded, set by
+    /// [`BenchmarkControl::set_interval_results`].
+    #[serde(skip)]
+    #[arg(skip)]
+    intervals: Option<IntervalResults>,
 }
 
 /// A query with its index and generator
@@ -131,6 +151,8 @@ pub(crate) struct WorkloadThreadParams {
     query_execution_mode: QueryExecutionMode,
     target_qps: Option<u64>,
     workers: u64,
+    warmup_until: Instant,
+    warmup_samples: usize,
 }
 
 pub enum Sampler {
@@ -150,12 +172,16 @@ pub(crate) struct ColGenerator {
 pub(crate) struct WorkloadResultBatch {
     /// Number of queries tested
     queries: Vec<Histogram<u64>>,
+    /// The first latencies of each query after the warmup, with when they were recorded, for
+    /// `--warmup-samples`. These aren't in `queries`.
+    samples: Vec<Vec<TimestampedResult>>,
 }
 
 impl WorkloadResultBatch {
     fn new(n: usize) -> Self {
         Self {
             queries: vec![Histogram::<u64>::new(3).unwrap(); n],
+            samples: vec![Vec::new(); n],
         }
     }
 }
@@ -226,12 +252,16 @@ impl BenchmarkControl for WorkloadEmulator {
             query_execution_mode: self.query_execution_mode,
             target_qps: self.target_qps,
             workers: self.workers,
+            warmup_until: Instant::now() + self.warmup.unwrap_or_default(),
+            warmup_samples: self.warmup_samples,
         };
 
         multi_thread::run_multithread_benchmark::<Self>(
             self.workers,
             thread_data.clone(),
             self.run_for,
+            self.warmup,
+            self.intervals.clone(),
         )
         .await
     }
@@ -267,6 +297,21 @@ impl BenchmarkControl for WorkloadEmulator {
     fn data_generator(&mut self) -> Option<&mut DataGenerator> {
         self.data_generator.as_mut()
     }
+
+    fn set_warmup(&mut self, warmup: Duration) -> anyhow::Result<()> {
+        self.warmup = Some(warmup);
+        Ok(())
+    }
+
+    fn set_warmup_samples(&mut self, samples: usize) -> anyhow::Result<()> {
+        self.warmup_samples = samples;
+        Ok(())
+    }
+
+    fn set_interval_results(&mut self, intervals: IntervalResults) -> anyhow::Result<()> {
+        self.intervals = Some(intervals);
+        Ok(())
+    }
 }
 
 impl Sampler {
@@ -414,6 +459,22 @@ impl MultithreadBenchmark for WorkloadEmulator {
                 query.clone(),
             );
         }
+        for result in &results {
+            for (i, samples) in result.samples.iter().enumerate() {
+                for sample in samples {
+                    for key in ["duration_overall".to_owned(), format!("duration_{i}")] {
+                        benchmark_results.record(
+                            &key,
+                            Unit::Microseconds,
+                            MetricGoal::Decreasing,
+                            *sample,
+                        );
+                    }
+                    overall.record(sample.value).unwrap();
+                    per_query[i].record(sample.value).unwrap();
+                }
+            }
+        }
 
         let qps = overall.len() as f64 / interval.as_secs() as f64;
         info!(
@@ -467,6 +528,7 @@ impl MultithreadBenchmark for WorkloadEmulator {
             multi_thread::throttle_interval(params.target_qps, params.workers);
         let mut last_report = Instant::now();
         let mut result_batch = WorkloadResultBatch::new(query_set.queries.len());
+        let mut samples_recorded = vec![0; query_set.queries.len()];
 
         loop {
             // Report results every REPORT_RESULTS_INTERVAL.
@@ -512,9 +574,18 @@ impl MultithreadBenchmark for WorkloadEmulator {
                 }
             };
 
-            result_batch.queries[query.idx]
-                .record(duration.as_micros() as u64)
-                .unwrap();
+            let latency = duration.as_micros() as u64;
+            if samples_recorded[query.idx] < params.warmup_samples
+                && Instant::now() >= params.warmup_until
+            {
+                samples_recorded[query.idx] += 1;
+                result_batch.samples[query.idx].push(TimestampedResult {
+                    value: latency,
+                    recorded_at: tokio::time::Instant::now(),
+                });
+            } else {
+                result_batch.queries[query.idx].record(latency).unwrap();
+            }
         }
     }
 }
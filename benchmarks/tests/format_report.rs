@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+
+use benchmarks::report::{Report, SavedResults, SortColumn, Tag, TagComparison};
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn fixture(name: &str) -> SavedResults {
+    SavedResults::from_path(&fixture_path(name)).unwrap()
+}
+
+fn report(sort_by: &str, baseline: Option<&str>) -> Vec<Vec<String>> {
+    let report = Report {
+        results: fixture("results.json"),
+        baseline: baseline.map(fixture),
+        sort_by: sort_by.parse::<SortColumn>().unwrap(),
+        colored: false,
+    };
+    report
+        .to_string()
+        .lines()
+        .map(|line| {
+            // Metric names contain a single space, and columns are separated by at least two.
+            line.split("  ")
+                .map(str::trim)
+                .filter(|column| !column.is_empty())
+                .map(str::to_owned)
+                .collect()
+        })
+        .collect()
+}
+
+#[test]
+fn format_report() {
+    let rows = report("metric", None);
+    assert_eq!(
+        rows,
+        [
+            vec!["metric", "min", "p50", "p95", "p99", "p99.9", "max", "mean", "samples"],
+            vec![
+                "read latency",
+                "80",
+                "190",
+                "400",
+                "950",
+                "4100",
+                "9100",
+                "212.50",
+                "120000"
+            ],
+            vec![
+                "write latency",
+                "300",
+                "900",
+                "2800",
+                "7600",
+                "18000",
+                "25000",
+                "1250.75",
+                "6000"
+            ],
+        ]
+    );
+}
+
+#[test]
+fn format_report_sort_by() {
+    let metrics = |sort_by| {
+        report(sort_by, None)
+            .into_iter()
+            .skip(1)
+            .map(|row| row[0].clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(metrics("p99"), ["write latency", "read latency"]);
+    assert_eq!(metrics("samples"), ["read latency", "write latency"]);
+}
+
+#[test]
+fn format_report_compare() {
+    let rows = report("metric", Some("baseline.json"));
+    assert_eq!(
+        rows[0],
+        ["metric", "stat", "baseline", "value", "delta", "delta %"]
+    );
+    let row = |stat: &str| {
+        rows.iter()
+            .find(|row| row[0] == "read latency" && row[1] == stat)
+            .unwrap()
+            .clone()
+    };
+    assert_eq!(
+        row("p50"),
+        ["read latency", "p50", "200", "190", "-10", "-5.0%"]
+    );
+    assert_eq!(
+        row("max"),
+        ["read latency", "max", "8000", "9100", "+1100", "+13.8%"]
+    );
+    assert_eq!(
+        row("mean"),
+        ["read latency", "mean", "200", "212.50", "+12.50", "+6.2%"]
+    );
+    assert_eq!(
+        row("samples"),
+        ["read latency", "samples", "120000", "120000", "+0", "+0.0%"]
+    );
+    assert!(rows.iter().all(|row| row[0] != "write latency"));
+    assert!(rows.contains(&vec!["Only in baseline: errors".to_owned()]));
+    assert!(rows.contains(&vec!["Only in results: write latency".to_owned()]));
+}
+
+#[test]
+fn format_report_compare_tags() {
+    let tags = ["main", "feature"].map(|tag| tag.parse::<Tag>().unwrap());
+    let comparison = TagComparison::load(&fixture_path("tagged.json"), &tags).unwrap();
+    assert_eq!(
+        comparison
+            .results
+            .iter()
+            .map(|(_, results)| results.tag.as_ref().unwrap().as_str())
+            .collect::<Vec<_>>(),
+        ["main", "feature"]
+    );
+
+    let output = comparison.to_string();
+    let rows = output
+        .lines()
+        .map(|line| line.split("  ").map(str::trim).filter(|c| !c.is_empty()))
+        .map(|row| row.collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    assert_eq!(rows[0], ["metric", "stat", "main", "feature"]);
+    assert!(rows.contains(&vec!["read latency", "p50", "200", "190"]));
+    assert!(rows.contains(&vec!["read latency", "max", "8000", "9100"]));
+    // Metrics missing from some results are still shown, with an empty value for those tags.
+    assert!(rows.contains(&vec!["errors", "samples", "1"]));
+    assert!(rows.contains(&vec!["write latency", "samples", "6000"]));
+}
@@ -1,9 +1,12 @@
+use std::collections::HashSet;
+use std::fmt;
 use std::str::FromStr;
 
 use anyhow::bail;
+use rand::Rng;
 use readyset_data::DfValue;
 
-use crate::ColumnGenerationSpec;
+use crate::{ColumnGenerationSpec, RandomStringGenerator};
 
 /// An annotation for how to generate a parameter's value for a query. A
 /// parameter annotation takes the following form:
@@ -13,18 +16,297 @@ use crate::ColumnGenerationSpec;
 /// for example, `uniform` is a annotation type that may be used to generate
 /// uniformly random values over a minimum and maximum value that can
 /// be specified via the parameters, i.e. `uniform 4 100`.
+///
+/// The `regex` annotation takes a single pattern without whitespace, optionally surrounded by
+/// double quotes, e.g. `regex "\d{3}-\d{4}"`. The pattern is validated when the annotation is
+/// parsed; see [`RandomStringGenerator`] for the supported syntax.
+///
+/// `zipf_over <num_unique> <alpha> <annotation>` generates `num_unique` values with another
+/// annotation up front and then picks from them with a zipfian distribution, e.g.
+/// `zipf_over 1000 1.1 regex [a-z]{12}` for skewed string keys. A trailing `unique` applies to
+/// the `zipf_over` annotation as a whole.
+///
+/// `enum <value> ...` picks one of the given values uniformly at random, e.g.
+/// `enum new processing shipped delivered` for a status column. Values are strings, which are
+/// converted to the column's type.
+///
+/// Values that contain whitespace, such as `"two words"`, must be surrounded by double quotes,
+/// within which a double quote is escaped as `\"`. Quoting also makes a value named `unique`
+/// a value rather than the trailing `unique`.
+///
+/// `boolean <probability>` generates booleans that are true with a probability between 0 and 1,
+/// e.g. `boolean 0.1` for a rarely set flag. The probability defaults to 0.5, and
+/// `boolean_true` and `boolean_false` always generate true and false respectively. None of them
+/// can be `unique`, as there are only two booleans.
+///
+/// An annotation is formatted back into its string form by
+/// [`DistributionAnnotation::to_annotation_string`], which [`Display`](fmt::Display) also uses.
+#[derive(Debug, PartialEq)]
 pub struct DistributionAnnotation {
     pub spec: ColumnGenerationSpec,
     pub unique: bool,
 }
 
+/// The names of every annotation type, used to suggest a correction for unrecognized ones.
+const ANNOTATION_TYPES: &[&str] = &[
+    "uniform",
+    "zipf",
+    "zipf_over",
+    "regex",
+    "chars",
+    "group",
+    "constant",
+    "enum",
+    "boolean",
+    "boolean_true",
+    "boolean_false",
+];
+
+/// Splits an annotation into whitespace-separated tokens. A token starting with a double quote
+/// extends to the matching closing quote, including any whitespace, and keeps its quotes so that
+/// it can be told apart from an unquoted token; see [`unquote`].
+fn tokenize(s: &str) -> anyhow::Result<Vec<&str>> {
+    let mut tokens = vec![];
+    let mut rest = s.trim_start();
+    while !rest.is_empty() {
+        let len = if let Some(quoted) = rest.strip_prefix('"') {
+            let mut escaped = false;
+            let Some(end) = quoted.find(|c| {
+                let closes = c == '"' && !escaped;
+                escaped = c == '\\' && !escaped;
+                closes
+            }) else {
+                bail!("Unterminated quote in annotation: '{rest}'");
+            };
+            // The opening and closing quotes.
+            let len = end + 2;
+            if rest[len..].starts_with(|c: char| !c.is_ascii_whitespace()) {
+                bail!(
+                    "Expected whitespace after the quoted value {}",
+                    &rest[..len]
+                );
+            }
+            len
+        } else {
+            rest.find(|c: char| c.is_ascii_whitespace())
+                .unwrap_or(rest.len())
+        };
+        tokens.push(&rest[..len]);
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+/// Returns the value of a token from [`tokenize`], without its surrounding quotes if it has any.
+fn unquote(token: &str) -> String {
+    match token.strip_prefix('"').and_then(|t| t.strip_suffix('"')) {
+        Some(quoted) => quoted.replace("\\\"", "\""),
+        None => token.to_owned(),
+    }
+}
+
+/// Formats `value` as a token that [`unquote`] turns back into it, quoting it if it would
+/// otherwise be split up, be empty or be taken for a trailing `unique`.
+fn quote(value: &str) -> String {
+    if value.is_empty()
+        || value.contains(|c: char| c == '"' || c.is_ascii_whitespace())
+        || value.eq_ignore_ascii_case("unique")
+    {
+        format!("\"{}\"", value.replace('"', "\\\""))
+    } else {
+        value.to_owned()
+    }
+}
+
+/// The number of duplicate values in a row after which [`DistributionAnnotation::sample_n`] gives
+/// up on generating another unique value.
+const MAX_DUPLICATE_DRAWS: usize = 1000;
+
+/// Unrecognized annotation types are only corrected to a known type within this edit distance.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// Returns the annotation type closest to `name`, if any is within [`MAX_SUGGESTION_DISTANCE`].
+fn suggest_annotation_type(name: &str) -> Option<&'static str> {
+    ANNOTATION_TYPES
+        .iter()
+        .map(|ty| (edit_distance(name, ty), *ty))
+        .filter(|(distance, _)| *distance <= MAX_SUGGESTION_DISTANCE)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, ty)| ty)
+}
+
+/// Returns the Levenshtein distance between `a` and `b`: the smallest number of single-character
+/// insertions, deletions and substitutions that turn one into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut row = vec![i + 1; b.len() + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = prev[j] + usize::from(a != *b);
+            row[j + 1] = substitution.min(prev[j + 1] + 1).min(row[j] + 1);
+        }
+        prev = row;
+    }
+    prev[b.len()]
+}
+
+impl DistributionAnnotation {
+    /// Returns the annotation in the form it is parsed from, such that parsing the returned
+    /// string produces an equal annotation.
+    ///
+    /// This only holds for annotations that can be parsed in the first place. Specs without an
+    /// annotation type, such as [`ColumnGenerationSpec::Random`] or
+    /// [`ColumnGenerationSpec::Custom`], are formatted as a description that fails to parse, e.g.
+    /// `random`, and values that parsing wouldn't produce, such as a pattern containing
+    /// whitespace or a non-integer `uniform` bound, are formatted as they are.
+    pub fn to_annotation_string(&self) -> String {
+        let mut annotation = spec_annotation(&self.spec);
+        if self.unique {
+            annotation.push_str(" unique");
+        }
+        annotation
+    }
+
+    /// Returns a rough upper bound on the number of distinct values the annotation generates for
+    /// `row_count` rows, between 1 and `row_count`; see
+    /// [`ColumnGenerationSpec::estimated_cardinality`] for the assumptions it makes. A `unique`
+    /// annotation generates a different value for every row.
+    pub fn estimated_cardinality(&self, row_count: u64) -> u64 {
+        if self.unique {
+            row_count
+        } else {
+            self.spec.estimated_cardinality(row_count)
+        }
+    }
+
+    /// Generates `n` values with the annotation, drawing them from `rng` so that a seeded rng
+    /// always generates the same values. Values are generated as they would be for a column of
+    /// the type the spec naturally produces: integers for integer ranges and sequences, strings
+    /// for patterns, and so on.
+    ///
+    /// A `unique` annotation generates `n` distinct values, and returns an error if it can't,
+    /// such as for `enum a b unique` with an `n` of 3.
+    pub fn sample_n(&self, n: usize, rng: &mut impl Rng) -> anyhow::Result<Vec<DfValue>> {
+        // Without a batch size, this spec panics once it runs out of values rather than repeating
+        // them, so it has to be treated as unique too.
+        let unique = self.unique
+            || matches!(
+                self.spec,
+                ColumnGenerationSpec::UniformWithoutReplacement {
+                    batch_size: None,
+                    ..
+                }
+            );
+        let cardinality = self.spec.estimated_cardinality(n as u64);
+        if unique && cardinality < n as u64 {
+            bail!(
+                "Can't generate {n} unique values with '{self}', which generates at most \
+                 {cardinality}"
+            );
+        }
+
+        let mut generator = self
+            .spec
+            .generator_for_col_with(self.spec.sample_type(), rng);
+        if !unique {
+            return Ok((0..n).map(|_| generator.gen_with(rng)).collect());
+        }
+
+        let mut seen = HashSet::with_capacity(n);
+        let mut values = Vec::with_capacity(n);
+        let mut duplicates = 0;
+        while values.len() < n {
+            let value = generator.gen_with(rng);
+            if seen.insert(value.clone()) {
+                values.push(value);
+                duplicates = 0;
+            } else {
+                duplicates += 1;
+                if duplicates >= MAX_DUPLICATE_DRAWS {
+                    bail!(
+                        "Couldn't generate {n} unique values with '{self}': only found {} after \
+                         {MAX_DUPLICATE_DRAWS} duplicates in a row",
+                        values.len()
+                    );
+                }
+            }
+        }
+        Ok(values)
+    }
+
+    /// Generates a single value with the annotation; see [`Self::sample_n`].
+    pub fn sample_one(&self, rng: &mut impl Rng) -> DfValue {
+        let mut generator = self
+            .spec
+            .generator_for_col_with(self.spec.sample_type(), rng);
+        generator.gen_with(rng)
+    }
+}
+
+/// Formats `spec` as the annotation it is parsed from, without the trailing `unique`.
+fn spec_annotation(spec: &ColumnGenerationSpec) -> String {
+    match spec {
+        ColumnGenerationSpec::Uniform(from, to) => format!("uniform {from} {to}"),
+        ColumnGenerationSpec::Zipfian { min, max, alpha } => format!("zipf {min} {max} {alpha}"),
+        ColumnGenerationSpec::ZipfianIndexed {
+            inner,
+            num_unique,
+            alpha,
+        } => format!("zipf_over {num_unique} {alpha} {}", spec_annotation(inner)),
+        ColumnGenerationSpec::RandomString(regex) => format!("regex {regex}"),
+        ColumnGenerationSpec::RandomChar {
+            min_length,
+            max_length,
+            charset,
+        } => format!("chars {min_length} {max_length} {charset}"),
+        ColumnGenerationSpec::UniqueRepeated(num) => format!("group {num}"),
+        ColumnGenerationSpec::Constant(val) => format!("constant {val}"),
+        ColumnGenerationSpec::Enum(values) => {
+            let mut annotation = "enum".to_owned();
+            for val in values {
+                annotation.push(' ');
+                match <&str>::try_from(val) {
+                    Ok(s) => annotation.push_str(&quote(s)),
+                    Err(_) => annotation.push_str(&val.to_string()),
+                }
+            }
+            annotation
+        }
+        ColumnGenerationSpec::Boolean { true_probability } => {
+            format!("boolean {true_probability}")
+        }
+        // These have no annotation type.
+        ColumnGenerationSpec::Unique => "unique".to_owned(),
+        ColumnGenerationSpec::UniqueFrom(index) => format!("unique_from {index}"),
+        ColumnGenerationSpec::UniformWithoutReplacement {
+            min,
+            max,
+            batch_size,
+        } => match batch_size {
+            Some(batch_size) => format!("uniform_without_replacement {min} {max} {batch_size}"),
+            None => format!("uniform_without_replacement {min} {max}"),
+        },
+        ColumnGenerationSpec::Random => "random".to_owned(),
+        ColumnGenerationSpec::Custom(_) => "custom".to_owned(),
+    }
+}
+
+impl fmt::Display for DistributionAnnotation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_annotation_string())
+    }
+}
+
 impl FromStr for DistributionAnnotation {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut chunks = s.split_ascii_whitespace();
+        let mut chunks = tokenize(s)?.into_iter().peekable();
+        let mut unique = false;
 
-        let spec = match chunks.next().unwrap().to_ascii_lowercase().as_str() {
+        let annotation_type = chunks.next().unwrap();
+        let spec = match annotation_type.to_ascii_lowercase().as_str() {
             "uniform" => {
                 let from: i64 = chunks.next().unwrap().parse().unwrap();
                 let to: i64 = chunks.next().unwrap().parse().unwrap();
@@ -40,9 +322,39 @@ impl FromStr for DistributionAnnotation {
                     alpha,
                 }
             }
+            "zipf_over" => {
+                let (Some(num_unique), Some(alpha)) = (chunks.next(), chunks.next()) else {
+                    bail!("The zipf_over annotation requires a number of values and an alpha");
+                };
+                let num_unique: u64 = match num_unique.parse() {
+                    Ok(n) if n > 0 => n,
+                    _ => bail!("Invalid number of values for zipf_over: '{num_unique}'"),
+                };
+                let alpha: f64 = match alpha.parse() {
+                    Ok(alpha) if alpha >= 0.0 => alpha,
+                    _ => bail!("Invalid alpha for zipf_over: '{alpha}'"),
+                };
+                let inner = chunks.by_ref().collect::<Vec<_>>().join(" ");
+                if inner.is_empty() {
+                    bail!(
+                        "The zipf_over annotation requires an annotation to generate values with"
+                    );
+                }
+                let inner = inner.parse::<DistributionAnnotation>()?;
+                unique = inner.unique;
+                ColumnGenerationSpec::ZipfianIndexed {
+                    inner: Box::new(inner.spec),
+                    num_unique,
+                    alpha,
+                }
+            }
             "regex" => {
-                let regex = chunks.next().unwrap().trim_matches('"');
-                ColumnGenerationSpec::RandomString(regex.to_owned())
+                let Some(regex) = chunks.next() else {
+                    bail!("The regex annotation requires a pattern");
+                };
+                let regex = unquote(regex);
+                RandomStringGenerator::new(&regex)?;
+                ColumnGenerationSpec::RandomString(regex)
             }
             "chars" => {
                 let min_length: usize = chunks.next().unwrap().parse().unwrap();
@@ -63,10 +375,62 @@ impl FromStr for DistributionAnnotation {
                 let val: DfValue = chunks.next().unwrap().into();
                 ColumnGenerationSpec::Constant(val)
             }
-            _ => bail!("Unrecognized annotation"),
+            "enum" => {
+                let mut values = chunks.by_ref().collect::<Vec<_>>();
+                // An unquoted trailing `unique` applies to the annotation, and isn't a value.
+                if values
+                    .last()
+                    .is_some_and(|value| value.eq_ignore_ascii_case("unique"))
+                {
+                    values.pop();
+                    unique = true;
+                }
+                if values.is_empty() {
+                    bail!(
+                        "The enum annotation requires at least one value to choose from, e.g. \
+                         'enum new shipped delivered'"
+                    );
+                }
+                ColumnGenerationSpec::Enum(values.into_iter().map(|v| unquote(v).into()).collect())
+            }
+            "boolean" => {
+                // The probability is optional, so a trailing `unique` isn't mistaken for one and
+                // is rejected below instead.
+                let true_probability =
+                    match chunks.next_if(|chunk| !chunk.eq_ignore_ascii_case("unique")) {
+                        Some(probability) => match probability.parse::<f64>() {
+                            Ok(p) if (0.0..=1.0).contains(&p) => p,
+                            _ => bail!(
+                                "Invalid probability for boolean: '{probability}'; expected a \
+                                 number between 0 and 1"
+                            ),
+                        },
+                        None => 0.5,
+                    };
+                ColumnGenerationSpec::Boolean { true_probability }
+            }
+            "boolean_true" => ColumnGenerationSpec::Boolean {
+                true_probability: 1.0,
+            },
+            "boolean_false" => ColumnGenerationSpec::Boolean {
+                true_probability: 0.0,
+            },
+            name => match suggest_annotation_type(name) {
+                Some(suggestion) => bail!(
+                    "Unrecognized annotation '{annotation_type}'; did you mean '{suggestion}'?"
+                ),
+                None => bail!("Unrecognized annotation '{annotation_type}'"),
+            },
         };
 
-        let unique = chunks.next().map(str::to_ascii_lowercase).as_deref() == Some("unique");
+        let unique =
+            unique || chunks.next().map(str::to_ascii_lowercase).as_deref() == Some("unique");
+        if unique && matches!(spec, ColumnGenerationSpec::Boolean { .. }) {
+            bail!(
+                "The {} annotation can't be unique, as it only generates true and false",
+                annotation_type.to_ascii_lowercase()
+            );
+        }
 
         Ok(Self { spec, unique })
     }
@@ -74,6 +438,11 @@ impl FromStr for DistributionAnnotation {
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
     use super::*;
 
     #[test]
@@ -105,4 +474,527 @@ mod tests {
         let s = q.parse::<DistributionAnnotation>().unwrap();
         assert!(matches!(s.spec, ColumnGenerationSpec::Constant(dt) if dt == DfValue::from("5")));
     }
+
+    fn regex_generator(annotation: &str) -> RandomStringGenerator {
+        match annotation.parse::<DistributionAnnotation>().unwrap().spec {
+            ColumnGenerationSpec::RandomString(regex) => {
+                RandomStringGenerator::new(&regex).unwrap()
+            }
+            spec => panic!("Expected a regex spec, got {spec:?}"),
+        }
+    }
+
+    /// Checks that every string generated by `annotation` is matched by `expected`.
+    fn assert_generates(annotation: &str, expected: &str) {
+        let generator = regex_generator(annotation);
+        let expected = regex::Regex::new(expected).unwrap();
+        for _ in 0..100 {
+            let val = generator.gen();
+            let s = <&str>::try_from(&val).unwrap();
+            assert!(expected.is_match(s), "{annotation} generated {s:?}");
+        }
+    }
+
+    #[test]
+    fn parse_regex_annotation_common_patterns() {
+        assert_generates(
+            r#"regex "[a-z0-9._]{1,16}@[a-z]{2,10}\.(com|org|net)""#,
+            r"^[a-z0-9._]+@[a-z]+\.(com|org|net)$",
+        );
+        assert_generates(r"regex \(\d{3}\)\d{3}-\d{4}", r"^\(\d{3}\)\d{3}-\d{4}$");
+        assert_generates(
+            r"regex (19|20)\d\d-(0[1-9]|1[0-2])-(0[1-9]|[12]\d|3[01])",
+            r"^\d{4}-\d{2}-\d{2}$",
+        );
+        assert_generates(r"regex \w{8}", r"^\w{8}$");
+    }
+
+    #[test]
+    fn parse_regex_annotation_with_anchors() {
+        assert_generates(r"regex ^[A-Z]\d{2}$", r"^[A-Z]\d{2}$");
+        // An escaped `$` is a literal, not an anchor.
+        assert_generates(r"regex ^\d\$", r"^\d\$$");
+
+        let err = r"regex a^b"
+            .parse::<DistributionAnnotation>()
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("anchors"), "{err}");
+        let err = r"regex \bword"
+            .parse::<DistributionAnnotation>()
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("word boundaries"), "{err}");
+    }
+
+    #[test]
+    fn parse_invalid_regex_annotation() {
+        let err = "regex [a-z"
+            .parse::<DistributionAnnotation>()
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Invalid regex '[a-z'"), "{err}");
+        assert!("regex".parse::<DistributionAnnotation>().is_err());
+    }
+
+    #[test]
+    fn parse_zipf_over_annotation() {
+        let s = "zipf_over 100 1.1 regex [a-z]{8}"
+            .parse::<DistributionAnnotation>()
+            .unwrap();
+        assert_eq!(
+            s.spec,
+            ColumnGenerationSpec::ZipfianIndexed {
+                inner: Box::new(ColumnGenerationSpec::RandomString("[a-z]{8}".to_owned())),
+                num_unique: 100,
+                alpha: 1.1,
+            }
+        );
+        assert!(!s.unique);
+
+        let s = "zipf_over 10 1 uniform 1 1000 UNIQUE"
+            .parse::<DistributionAnnotation>()
+            .unwrap();
+        assert_eq!(
+            s.spec,
+            ColumnGenerationSpec::ZipfianIndexed {
+                inner: Box::new(ColumnGenerationSpec::Uniform(
+                    DfValue::Int(1),
+                    DfValue::Int(1000)
+                )),
+                num_unique: 10,
+                alpha: 1.0,
+            }
+        );
+        assert!(s.unique);
+    }
+
+    #[test]
+    fn parse_invalid_zipf_over_annotation() {
+        for (annotation, expected) in [
+            ("zipf_over 100", "requires a number of values and an alpha"),
+            ("zipf_over 0 1.1 regex a", "Invalid number of values"),
+            ("zipf_over 100 -1 regex a", "Invalid alpha"),
+            ("zipf_over 100 1.1", "requires an annotation"),
+            ("zipf_over 100 1.1 regex [a-z", "Invalid regex"),
+        ] {
+            let err = annotation.parse::<DistributionAnnotation>().err().unwrap();
+            assert!(err.to_string().contains(expected), "{annotation}: {err}");
+        }
+    }
+
+    #[test]
+    fn parse_boolean_annotation() {
+        for (annotation, true_probability) in [
+            ("boolean 0.25", 0.25),
+            ("boolean 0", 0.0),
+            ("BOOLEAN 1", 1.0),
+            ("boolean", 0.5),
+            ("boolean_true", 1.0),
+            ("boolean_false", 0.0),
+        ] {
+            let s = annotation.parse::<DistributionAnnotation>().unwrap();
+            assert_eq!(
+                s.spec,
+                ColumnGenerationSpec::Boolean { true_probability },
+                "{annotation}"
+            );
+            assert!(!s.unique);
+        }
+
+        for annotation in [
+            "boolean UNIQUE",
+            "boolean 0.5 unique",
+            "boolean_true unique",
+            "boolean_false unique",
+            "zipf_over 10 1.1 boolean unique",
+        ] {
+            let err = annotation
+                .parse::<DistributionAnnotation>()
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains("can't be unique"), "{annotation}: {err}");
+        }
+    }
+
+    #[test]
+    fn parse_invalid_boolean_annotation() {
+        for probability in ["1.5", "-0.1", "NaN", "often"] {
+            let err = format!("boolean {probability}")
+                .parse::<DistributionAnnotation>()
+                .err()
+                .unwrap();
+            assert_eq!(
+                err.to_string(),
+                format!(
+                    "Invalid probability for boolean: '{probability}'; expected a number between \
+                     0 and 1"
+                )
+            );
+        }
+    }
+
+    fn enum_values(annotation: &str) -> (Vec<DfValue>, bool) {
+        let s = annotation.parse::<DistributionAnnotation>().unwrap();
+        match s.spec {
+            ColumnGenerationSpec::Enum(values) => (values, s.unique),
+            spec => panic!("Expected an enum spec, got {spec:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_enum_annotation() {
+        let (values, unique) = enum_values("enum new processing shipped delivered");
+        assert_eq!(
+            values,
+            ["new", "processing", "shipped", "delivered"].map(DfValue::from)
+        );
+        assert!(!unique);
+
+        let (values, unique) = enum_values("ENUM 1 2 3 UNIQUE");
+        assert_eq!(values, ["1", "2", "3"].map(DfValue::from));
+        assert!(unique);
+    }
+
+    #[test]
+    fn parse_enum_annotation_with_quoted_values() {
+        let (values, unique) =
+            enum_values(r#"enum "two words" plain   "  padded " "" "say \"hi\"" "unique""#);
+        assert_eq!(
+            values,
+            [
+                "two words",
+                "plain",
+                "  padded ",
+                "",
+                r#"say "hi""#,
+                "unique"
+            ]
+            .map(DfValue::from)
+        );
+        assert!(!unique);
+
+        let (values, unique) = enum_values(r#"enum "in progress" done unique"#);
+        assert_eq!(values, ["in progress", "done"].map(DfValue::from));
+        assert!(unique);
+    }
+
+    #[test]
+    fn parse_invalid_enum_annotation() {
+        for annotation in ["enum", "enum unique", "enum   "] {
+            let err = annotation.parse::<DistributionAnnotation>().err().unwrap();
+            assert_eq!(
+                err.to_string(),
+                "The enum annotation requires at least one value to choose from, e.g. 'enum new \
+                 shipped delivered'",
+                "{annotation}"
+            );
+        }
+        let err = r#"enum a "b c"#.parse::<DistributionAnnotation>().err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            r#"Unterminated quote in annotation: '"b c'"#
+        );
+        let err = r#"enum "b c"d"#.parse::<DistributionAnnotation>().err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            r#"Expected whitespace after the quoted value "b c""#
+        );
+    }
+
+    #[test]
+    fn tokenize_quoted_tokens() {
+        assert_eq!(
+            tokenize(r#"  a "b c"  d"e "f\"g" "#).unwrap(),
+            ["a", r#""b c""#, r#"d"e"#, r#""f\"g""#]
+        );
+        assert_eq!(unquote(r#""f\"g""#), r#"f"g"#);
+        assert_eq!(unquote(r#"d"e"#), r#"d"e"#);
+        assert!(tokenize("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn custom_is_not_an_annotation() {
+        assert!("custom".parse::<DistributionAnnotation>().is_err());
+    }
+
+    #[test]
+    fn suggest_annotation_type_for_typos() {
+        for (annotation, suggestion) in [
+            ("uniiform 1 100", "uniform"),
+            ("zipg 1 100 1.1", "zipf"),
+            ("zipf_ovr 100 1.1 regex a", "zipf_over"),
+            ("regx [a-z]", "regex"),
+            ("char 1 10 abc", "chars"),
+            ("grop 5", "group"),
+            ("Constnt 5", "constant"),
+            ("bolean 0.5", "boolean"),
+            ("boolean_tru", "boolean_true"),
+            ("enun a b", "enum"),
+        ] {
+            let err = annotation.parse::<DistributionAnnotation>().err().unwrap();
+            let name = annotation.split(' ').next().unwrap();
+            assert_eq!(
+                err.to_string(),
+                format!("Unrecognized annotation '{name}'; did you mean '{suggestion}'?")
+            );
+        }
+    }
+
+    #[test]
+    fn no_suggestion_for_distant_annotation_types() {
+        let err = "normal 1 100"
+            .parse::<DistributionAnnotation>()
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "Unrecognized annotation 'normal'");
+    }
+
+    #[test]
+    fn annotation_string_round_trip() {
+        for annotation in [
+            "uniform 4 100",
+            "uniform -10 10 unique",
+            "zipf 1 1000 1.1",
+            "zipf 0 10 2 unique",
+            "zipf_over 100 1.1 regex [a-z]{8}",
+            "zipf_over 10 1 uniform 1 1000 unique",
+            "zipf_over 5 0.5 zipf_over 50 1.5 chars 1 4 abc",
+            r"regex \(\d{3}\)\d{3}-\d{4}",
+            r"regex ^[A-Z]\d{2}$ unique",
+            "chars 1 10 abcdef",
+            "group 5",
+            "constant 5",
+            "constant hello unique",
+            "boolean 0.25",
+            "boolean 1",
+            "enum new processing shipped",
+            r#"enum "two words" "" "say \"hi\"" "UNIQUE" unique"#,
+        ] {
+            let parsed = annotation.parse::<DistributionAnnotation>().unwrap();
+            let formatted = parsed.to_annotation_string();
+            assert_eq!(formatted, annotation);
+            assert_eq!(parsed.to_string(), formatted);
+            assert_eq!(
+                formatted.parse::<DistributionAnnotation>().unwrap(),
+                parsed,
+                "{annotation}"
+            );
+        }
+    }
+
+    #[test]
+    fn annotation_string_normalizes() {
+        for (annotation, formatted) in [
+            ("UNIFORM 4 100 UNIQUE", "uniform 4 100 unique"),
+            ("  zipf   1 100   1.10", "zipf 1 100 1.1"),
+            (r#"regex "[a-z]{3}""#, "regex [a-z]{3}"),
+            ("boolean", "boolean 0.5"),
+            ("boolean_false", "boolean 0"),
+            (
+                r#"enum  "new"   "in progress""#,
+                r#"enum new "in progress""#,
+            ),
+        ] {
+            let parsed = annotation.parse::<DistributionAnnotation>().unwrap();
+            assert_eq!(parsed.to_annotation_string(), formatted);
+            assert_eq!(formatted.parse::<DistributionAnnotation>().unwrap(), parsed);
+        }
+    }
+
+    #[test]
+    fn specs_without_annotations_do_not_parse() {
+        for spec in [
+            ColumnGenerationSpec::Unique,
+            ColumnGenerationSpec::UniqueFrom(5),
+            ColumnGenerationSpec::UniformWithoutReplacement {
+                min: DfValue::Int(1),
+                max: DfValue::Int(10),
+                batch_size: Some(5),
+            },
+            ColumnGenerationSpec::Random,
+            ColumnGenerationSpec::Custom(std::sync::Arc::new(|i| DfValue::from(i as i64))),
+        ] {
+            let annotation = DistributionAnnotation {
+                spec,
+                unique: false,
+            };
+            let formatted = annotation.to_annotation_string();
+            assert!(
+                formatted.parse::<DistributionAnnotation>().is_err(),
+                "{formatted}"
+            );
+        }
+    }
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("uniform", "uniform"), 0);
+        assert_eq!(edit_distance("uniiform", "uniform"), 1);
+        assert_eq!(edit_distance("zpif", "zipf"), 2);
+        assert_eq!(edit_distance("", "group"), 5);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn annotation_cardinality() {
+        for (annotation, expected) in [
+            ("uniform 1 11", 10),
+            ("uniform 1 11 unique", 500),
+            ("group 10", 50),
+            ("constant 5", 1),
+            ("enum new shipped delivered", 3),
+            ("zipf_over 100 1.1 regex [a-z]{8}", 100),
+            ("boolean 0.1", 2),
+            ("boolean_true", 1),
+            ("regex [a-z]{8}", 500),
+        ] {
+            let annotation = annotation.parse::<DistributionAnnotation>().unwrap();
+            assert_eq!(
+                annotation.estimated_cardinality(500),
+                expected,
+                "{annotation}"
+            );
+            for rows in [1, 3, 500, 100_000] {
+                let estimate = annotation.estimated_cardinality(rows);
+                assert!((1..=rows).contains(&estimate), "{annotation}: {estimate}");
+            }
+        }
+    }
+
+    fn annotated(spec: ColumnGenerationSpec, unique: bool) -> DistributionAnnotation {
+        DistributionAnnotation { spec, unique }
+    }
+
+    fn assert_samples(
+        annotation: &DistributionAnnotation,
+        n: usize,
+        check: impl Fn(&DfValue) -> bool,
+    ) -> Vec<DfValue> {
+        let values = annotation
+            .sample_n(n, &mut StdRng::seed_from_u64(42))
+            .unwrap();
+        assert_eq!(values.len(), n, "{annotation}");
+        for value in &values {
+            assert!(check(value), "{annotation} generated {value:?}");
+        }
+        if annotation.unique {
+            let distinct = values.iter().collect::<HashSet<_>>();
+            assert_eq!(distinct.len(), n, "{annotation} generated duplicates");
+        }
+        values
+    }
+
+    fn in_range(value: &DfValue, min: i64, max: i64) -> bool {
+        matches!(value, DfValue::Int(i) if (min..max).contains(i))
+    }
+
+    fn str_len(value: &DfValue) -> Option<usize> {
+        <&str>::try_from(value).ok().map(str::len)
+    }
+
+    #[test]
+    fn sample_parsed_annotations() {
+        let cases: [(&str, usize, fn(&DfValue) -> bool); 15] = [
+            ("uniform 4 100", 50, |v| in_range(v, 4, 100)),
+            ("uniform 4 100 unique", 96, |v| in_range(v, 4, 100)),
+            ("zipf 1 50 1.1", 50, |v| in_range(v, 1, 50)),
+            ("zipf 1 50 1.1 unique", 20, |v| in_range(v, 1, 50)),
+            ("regex [a-z]{8}", 50, |v| str_len(v) == Some(8)),
+            ("regex [a-z]{8} unique", 50, |v| str_len(v) == Some(8)),
+            ("chars 1 10 ascii", 50, |v| {
+                str_len(v).is_some_and(|len| (1..10).contains(&len))
+            }),
+            ("zipf_over 20 1.1 regex [a-z]{8}", 50, |v| {
+                str_len(v) == Some(8)
+            }),
+            ("group 10", 50, |v| in_range(v, 0, 5)),
+            ("constant 5", 10, |v| *v == DfValue::from("5")),
+            ("enum new shipped delivered", 50, |v| {
+                ["new", "shipped", "delivered"]
+                    .map(DfValue::from)
+                    .contains(v)
+            }),
+            ("enum new shipped delivered unique", 3, DfValue::is_string),
+            ("boolean 0.3", 50, |v| {
+                [false, true].map(DfValue::from).contains(v)
+            }),
+            ("boolean_true", 10, |v| *v == DfValue::from(true)),
+        ];
+        for (text, n, check) in cases {
+            let annotation = text.parse::<DistributionAnnotation>().unwrap();
+            assert_samples(&annotation, n, check);
+        }
+    }
+
+    #[test]
+    fn sample_specs_without_annotations() {
+        use ColumnGenerationSpec::*;
+        let values = assert_samples(&annotated(Unique, false), 10, |v| in_range(v, 0, 10));
+        assert_eq!(values[..3], [0, 1, 2].map(DfValue::from));
+        let values = assert_samples(&annotated(UniqueFrom(10), true), 10, |v| {
+            in_range(v, 10, 20)
+        });
+        assert_eq!(values[0], DfValue::from(10));
+        assert_samples(&annotated(UniqueRepeated(3), false), 10, |v| {
+            in_range(v, 0, 4)
+        });
+        assert_samples(&without_replacement(), 10, |v| in_range(v, 0, 10));
+        assert_samples(&annotated(Random, true), 50, DfValue::is_integer);
+        assert_samples(
+            &annotated(
+                Custom(Arc::new(|i: usize| DfValue::from(i as i64 * 2))),
+                true,
+            ),
+            5,
+            |v| in_range(v, 0, 10),
+        );
+    }
+
+    #[test]
+    fn sample_is_reproducible() {
+        let annotation = "regex [a-z]{8}".parse::<DistributionAnnotation>().unwrap();
+        let sample = |seed| {
+            annotation
+                .sample_n(20, &mut StdRng::seed_from_u64(seed))
+                .unwrap()
+        };
+        assert_eq!(sample(1), sample(1));
+        assert_ne!(sample(1), sample(2));
+
+        let mut rng = StdRng::seed_from_u64(1);
+        assert!(annotation.sample_one(&mut rng).is_string());
+    }
+
+    #[test]
+    fn sample_too_many_unique_values() {
+        let mut rng = StdRng::seed_from_u64(42);
+        for (text, n) in [
+            ("enum a b unique", 3),
+            ("uniform 1 5 unique", 5),
+            ("constant 5 unique", 2),
+            ("zipf_over 4 1.1 uniform 1 100 unique", 5),
+        ] {
+            let annotation = text.parse::<DistributionAnnotation>().unwrap();
+            let err = annotation.sample_n(n, &mut rng).unwrap_err().to_string();
+            assert!(err.contains(&format!("{n} unique values")), "{text}: {err}");
+        }
+
+        // Without `unique`, values repeat instead.
+        let annotation = "enum a b".parse::<DistributionAnnotation>().unwrap();
+        assert_eq!(annotation.sample_n(3, &mut rng).unwrap().len(), 3);
+        // Running out of values without a batch size is an error rather than a panic.
+        assert!(without_replacement().sample_n(11, &mut rng).is_err());
+    }
+
+    fn without_replacement() -> DistributionAnnotation {
+        annotated(
+            ColumnGenerationSpec::UniformWithoutReplacement {
+                min: 0.into(),
+                max: 10.into(),
+                batch_size: None,
+            },
+            false,
+        )
+    }
 }
@@ -1,20 +1,23 @@
 use std::cmp::min;
 use std::collections::HashSet;
+use std::fmt;
 use std::net::{IpAddr, Ipv4Addr};
 use std::sync::Arc;
 
+use anyhow::anyhow;
 use bit_vec::BitVec;
 use chrono::{Duration, FixedOffset, NaiveDate, NaiveTime, TimeZone};
 use eui48::{MacAddress, MacAddressFormat};
 use rand::distr::uniform::SampleRange as _;
 use rand::distr::{StandardUniform, Uniform};
 use rand::prelude::Distribution;
-use rand::seq::SliceRandom;
+use rand::seq::{IndexedRandom, SliceRandom};
 use rand::{Rng, RngCore};
 use rand_distr::Zipf;
 use readyset_data::{encoding::Encoding, DfType, DfValue, Dialect};
 use readyset_decimal::Decimal;
 use readyset_sql::ast::SqlType;
+use readyset_sql::DialectDisplay;
 
 mod distribution_annotation;
 
@@ -22,7 +25,7 @@ pub use crate::distribution_annotation::DistributionAnnotation;
 
 /// Variants and their parameters used to construct
 /// their respective ColumnGenerator.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Clone)]
 pub enum ColumnGenerationSpec {
     /// Generates a unique value for every row.
     Unique,
@@ -45,7 +48,8 @@ pub enum ColumnGenerationSpec {
     },
     /// Generates a random value for the row.
     Random,
-    /// Generate a random string from a regex
+    /// Generate a random string from a regex. See [`RandomStringGenerator`] for the supported
+    /// syntax.
     RandomString(String),
     /// Generate random characters of a specified max length and a specified charset
     RandomChar {
@@ -60,12 +64,173 @@ pub enum ColumnGenerationSpec {
         max: DfValue,
         alpha: f64,
     },
+    /// Generates values from a pool of `num_unique` values, generated up front by `inner`,
+    /// choosing each value with a zipfian distribution over its position in the pool. Unlike
+    /// [`ColumnGenerationSpec::Zipfian`], this can produce skewed distributions of any type of
+    /// value, such as string keys.
+    ///
+    /// The pool only holds `num_unique` distinct values if `inner` doesn't repeat itself.
+    ZipfianIndexed {
+        inner: Box<ColumnGenerationSpec>,
+        num_unique: u64,
+        alpha: f64,
+    },
     /// Always generate the same value
     Constant(DfValue),
+    /// Generates one of a fixed set of values, each chosen with equal probability, such as the
+    /// states of a `status` column.
+    Enum(Vec<DfValue>),
+    /// Generates a boolean, as `1` or `0`, which is true with the given probability between 0
+    /// and 1.
+    Boolean { true_probability: f64 },
+    /// Generates a value by calling a function with the index of the row being generated.
+    ///
+    /// This can only be constructed programmatically; there is no corresponding
+    /// [`DistributionAnnotation`].
+    Custom(Arc<dyn Fn(usize) -> DfValue + Send + Sync>),
+}
+
+impl fmt::Debug for ColumnGenerationSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unique => write!(f, "Unique"),
+            Self::UniqueFrom(index) => f.debug_tuple("UniqueFrom").field(index).finish(),
+            Self::UniqueRepeated(n) => f.debug_tuple("UniqueRepeated").field(n).finish(),
+            Self::Uniform(min, max) => f.debug_tuple("Uniform").field(min).field(max).finish(),
+            Self::UniformWithoutReplacement {
+                min,
+                max,
+                batch_size,
+            } => f
+                .debug_struct("UniformWithoutReplacement")
+                .field("min", min)
+                .field("max", max)
+                .field("batch_size", batch_size)
+                .finish(),
+            Self::Random => write!(f, "Random"),
+            Self::RandomString(regex) => f.debug_tuple("RandomString").field(regex).finish(),
+            Self::RandomChar {
+                min_length,
+                max_length,
+                charset,
+            } => f
+                .debug_struct("RandomChar")
+                .field("min_length", min_length)
+                .field("max_length", max_length)
+                .field("charset", charset)
+                .finish(),
+            Self::Zipfian { min, max, alpha } => f
+                .debug_struct("Zipfian")
+                .field("min", min)
+                .field("max", max)
+                .field("alpha", alpha)
+                .finish(),
+            Self::ZipfianIndexed {
+                inner,
+                num_unique,
+                alpha,
+            } => f
+                .debug_struct("ZipfianIndexed")
+                .field("inner", inner)
+                .field("num_unique", num_unique)
+                .field("alpha", alpha)
+                .finish(),
+            Self::Constant(val) => f.debug_tuple("Constant").field(val).finish(),
+            Self::Enum(values) => f.debug_tuple("Enum").field(values).finish(),
+            Self::Boolean { true_probability } => f
+                .debug_struct("Boolean")
+                .field("true_probability", true_probability)
+                .finish(),
+            Self::Custom(_) => write!(f, "Custom(..)"),
+        }
+    }
+}
+
+impl PartialEq for ColumnGenerationSpec {
+    fn eq(&self, other: &Self) -> bool {
+        use ColumnGenerationSpec::*;
+        match (self, other) {
+            (Unique, Unique) | (Random, Random) => true,
+            (UniqueFrom(a), UniqueFrom(b)) | (UniqueRepeated(a), UniqueRepeated(b)) => a == b,
+            (Uniform(min_a, max_a), Uniform(min_b, max_b)) => min_a == min_b && max_a == max_b,
+            (
+                UniformWithoutReplacement {
+                    min: min_a,
+                    max: max_a,
+                    batch_size: batch_a,
+                },
+                UniformWithoutReplacement {
+                    min: min_b,
+                    max: max_b,
+                    batch_size: batch_b,
+                },
+            ) => min_a == min_b && max_a == max_b && batch_a == batch_b,
+            (RandomString(a), RandomString(b)) => a == b,
+            (
+                RandomChar {
+                    min_length: min_a,
+                    max_length: max_a,
+                    charset: charset_a,
+                },
+                RandomChar {
+                    min_length: min_b,
+                    max_length: max_b,
+                    charset: charset_b,
+                },
+            ) => min_a == min_b && max_a == max_b && charset_a == charset_b,
+            (
+                Zipfian {
+                    min: min_a,
+                    max: max_a,
+                    alpha: alpha_a,
+                },
+                Zipfian {
+                    min: min_b,
+                    max: max_b,
+                    alpha: alpha_b,
+                },
+            ) => min_a == min_b && max_a == max_b && alpha_a == alpha_b,
+            (
+                ZipfianIndexed {
+                    inner: inner_a,
+                    num_unique: num_a,
+                    alpha: alpha_a,
+                },
+                ZipfianIndexed {
+                    inner: inner_b,
+                    num_unique: num_b,
+                    alpha: alpha_b,
+                },
+            ) => inner_a == inner_b && num_a == num_b && alpha_a == alpha_b,
+            (Constant(a), Constant(b)) => a == b,
+            (Enum(a), Enum(b)) => a == b,
+            (
+                Boolean {
+                    true_probability: a,
+                },
+                Boolean {
+                    true_probability: b,
+                },
+            ) => a == b,
+            // Functions can't be compared, so a custom spec is only equal to itself (or a clone).
+            (Custom(a), Custom(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
 }
 
 impl ColumnGenerationSpec {
     pub fn generator_for_col(&self, col_type: SqlType) -> ColumnGenerator {
+        self.generator_for_col_with(col_type, &mut rand::rng())
+    }
+
+    /// Like [`Self::generator_for_col`], but draws any random values needed to set up the
+    /// generator, such as the pool of a [`ColumnGenerationSpec::ZipfianIndexed`] spec, from `rng`.
+    pub(crate) fn generator_for_col_with<R: Rng>(
+        &self,
+        col_type: SqlType,
+        rng: &mut R,
+    ) -> ColumnGenerator {
         match self {
             ColumnGenerationSpec::Unique => ColumnGenerator::Unique(col_type.into()),
             ColumnGenerationSpec::UniqueFrom(index) => {
@@ -103,8 +268,17 @@ impl ColumnGenerationSpec {
                 *max_length,
                 charset,
             )),
-            ColumnGenerationSpec::Zipfian { min, max, alpha } => {
-                ColumnGenerator::Zipfian(ZipfianGenerator::new(min.clone(), max.clone(), *alpha))
+            ColumnGenerationSpec::Zipfian { min, max, alpha } => ColumnGenerator::Zipfian(
+                ZipfianGenerator::new(min.clone(), max.clone(), *alpha, rng),
+            ),
+            ColumnGenerationSpec::ZipfianIndexed {
+                inner,
+                num_unique,
+                alpha,
+            } => {
+                let mut inner = inner.generator_for_col_with(col_type, rng);
+                let values = (0..*num_unique).map(|_| inner.gen_with(rng)).collect();
+                ColumnGenerator::ZipfianIndexed(ZipfianIndexedGenerator::new(values, *alpha))
             }
             ColumnGenerationSpec::Constant(val) => {
                 let col_type =
@@ -113,8 +287,315 @@ impl ColumnGenerationSpec {
                 let val = val.coerce_to(&col_type, &DfType::Unknown).unwrap();
                 ColumnGenerator::Constant(val.into())
             }
+            ColumnGenerationSpec::Enum(values) => {
+                let col_type =
+                    DfType::from_sql_type(&col_type, Dialect::DEFAULT_MYSQL, |_| None, None)
+                        .unwrap();
+                let values = values
+                    .iter()
+                    .map(|val| val.coerce_to(&col_type, &DfType::Unknown).unwrap())
+                    .collect();
+                ColumnGenerator::Enum(EnumGenerator { values })
+            }
+            ColumnGenerationSpec::Boolean { true_probability } => {
+                ColumnGenerator::Boolean(BooleanGenerator {
+                    true_probability: *true_probability,
+                })
+            }
+            ColumnGenerationSpec::Custom(f) => ColumnGenerator::Custom(CustomGenerator {
+                f: Arc::clone(f),
+                index: 0,
+            }),
         }
     }
+
+    /// The type of column that [`DistributionAnnotation::sample_n`] generates values for, since it
+    /// has no column to generate them for: integers for integer ranges and sequences, the type of
+    /// the values of constant and enum specs, booleans for boolean specs, and text otherwise.
+    pub(crate) fn sample_type(&self) -> SqlType {
+        use ColumnGenerationSpec::*;
+        let value_type = |value: &DfValue| match value {
+            DfValue::Int(_) => SqlType::BigInt(None),
+            DfValue::UnsignedInt(_) => SqlType::BigIntUnsigned(None),
+            _ => SqlType::Text,
+        };
+        match self {
+            Unique | UniqueFrom(_) | UniqueRepeated(_) | Random | Custom(_) => {
+                SqlType::BigInt(None)
+            }
+            Uniform(min, _) | UniformWithoutReplacement { min, .. } | Zipfian { min, .. } => {
+                value_type(min)
+            }
+            ZipfianIndexed { inner, .. } => inner.sample_type(),
+            RandomString(_) | RandomChar { .. } => SqlType::Text,
+            Constant(value) => value_type(value),
+            Enum(values) => values.first().map_or(SqlType::Text, value_type),
+            Boolean { .. } => SqlType::Bool,
+        }
+    }
+
+    /// Returns why this spec can't generate values for a column of type `col_type`, such as
+    /// strings for an integer column, or `None` if it can. The reason completes the sentence "The
+    /// spec ...".
+    ///
+    /// Custom specs can't be checked, and are always considered compatible.
+    pub fn incompatibility(&self, col_type: &SqlType) -> Option<String> {
+        use ColumnGenerationSpec::*;
+        match self {
+            Unique | UniqueFrom(_) | UniqueRepeated(_) => (!supports_unique_values(col_type))
+                .then(|| "can't generate unique values of the column's type".to_owned()),
+            Random => (!supports_random_values(col_type))
+                .then(|| "can't generate random values of the column's type".to_owned()),
+            Uniform(min, max)
+            | UniformWithoutReplacement { min, max, .. }
+            | Zipfian { min, max, .. } => {
+                if !is_numeric(col_type) {
+                    return Some("generates integers".to_owned());
+                }
+                match (min, max) {
+                    (DfValue::Int(min), DfValue::Int(max)) if min < max => None,
+                    (DfValue::UnsignedInt(min), DfValue::UnsignedInt(max)) if min < max => None,
+                    (DfValue::Int(_), DfValue::Int(_))
+                    | (DfValue::UnsignedInt(_), DfValue::UnsignedInt(_)) => {
+                        Some(format!("has an empty range, from {min} to {max}"))
+                    }
+                    _ => Some(format!(
+                        "has bounds {min} and {max}, which aren't integers of the same signedness"
+                    )),
+                }
+            }
+            RandomString(pattern) => {
+                if !accepts_strings(col_type) {
+                    return Some("generates strings".to_owned());
+                }
+                RandomStringGenerator::new(pattern)
+                    .err()
+                    .map(|e| format!("has an invalid pattern: {e}"))
+            }
+            RandomChar {
+                min_length,
+                max_length,
+                charset,
+            } => {
+                if !accepts_strings(col_type) {
+                    return Some("generates strings".to_owned());
+                }
+                if !["ascii", "utf8", "latin1", "binary"].contains(&charset.as_str()) {
+                    return Some(format!("has an unknown charset {charset}"));
+                }
+                if min_length >= max_length {
+                    return Some(format!(
+                        "has an empty range of lengths, from {min_length} to {max_length}"
+                    ));
+                }
+                // Generated strings are shorter than `max_length`.
+                match col_type {
+                    SqlType::Char(Some(len)) | SqlType::VarChar(Some(len))
+                        if max_length - 1 > *len as usize =>
+                    {
+                        Some(format!(
+                            "generates strings of up to {} characters",
+                            max_length - 1
+                        ))
+                    }
+                    _ => None,
+                }
+            }
+            ZipfianIndexed { inner, .. } => inner.incompatibility(col_type),
+            Constant(val) => {
+                let Ok(df_type) =
+                    DfType::from_sql_type(col_type, Dialect::DEFAULT_MYSQL, |_| None, None)
+                else {
+                    return Some("can't generate values of the column's type".to_owned());
+                };
+                val.coerce_to(&df_type, &DfType::Unknown).is_err().then(|| {
+                    format!("generates {val}, which can't be converted to the column's type")
+                })
+            }
+            Enum(values) => {
+                if values.is_empty() {
+                    return Some("has no values to choose from".to_owned());
+                }
+                let Ok(df_type) =
+                    DfType::from_sql_type(col_type, Dialect::DEFAULT_MYSQL, |_| None, None)
+                else {
+                    return Some("can't generate values of the column's type".to_owned());
+                };
+                values
+                    .iter()
+                    .find(|val| val.coerce_to(&df_type, &DfType::Unknown).is_err())
+                    .map(|val| {
+                        format!("generates {val}, which can't be converted to the column's type")
+                    })
+            }
+            Boolean { true_probability } => {
+                if !is_numeric(col_type) {
+                    Some("generates 1 or 0".to_owned())
+                } else if !(0.0..=1.0).contains(true_probability) {
+                    Some(format!(
+                        "has a probability of {true_probability}, which isn't between 0 and 1"
+                    ))
+                } else {
+                    None
+                }
+            }
+            Custom(_) => None,
+        }
+    }
+
+    /// Returns a rough upper bound on the number of distinct values this spec generates for
+    /// `row_count` rows, such as for planning the size of an index on the column. Estimates are
+    /// between 1 and `row_count`, and an empty table has no values at all.
+    ///
+    /// The estimate assumes:
+    /// - Every value of an integer range is generated, given enough rows. Ranges are half-open,
+    ///   so `uniform 1 11` generates 10 values.
+    /// - Specs whose set of values isn't known up front, such as random values, strings from a
+    ///   pattern or custom specs, generate a different value for every row.
+    /// - A `zipf_over` pool holds as many distinct values as its inner spec generates for the
+    ///   size of the pool, although rare values of the pool may never be chosen.
+    pub fn estimated_cardinality(&self, row_count: u64) -> u64 {
+        use ColumnGenerationSpec::*;
+        if row_count == 0 {
+            return 0;
+        }
+        let estimate = match self {
+            Unique | UniqueFrom(_) | Random | RandomString(_) | RandomChar { .. } | Custom(_) => {
+                row_count
+            }
+            UniqueRepeated(num) => row_count.div_ceil(u64::from(*num).max(1)),
+            Uniform(min, max)
+            | UniformWithoutReplacement { min, max, .. }
+            | Zipfian { min, max, .. } => range_len(min, max).unwrap_or(row_count),
+            ZipfianIndexed {
+                inner, num_unique, ..
+            } => inner.estimated_cardinality(*num_unique),
+            Constant(_) => 1,
+            Enum(values) => values.iter().collect::<HashSet<_>>().len() as u64,
+            Boolean { true_probability } => {
+                if *true_probability > 0.0 && *true_probability < 1.0 {
+                    2
+                } else {
+                    1
+                }
+            }
+        };
+        estimate.clamp(1, row_count)
+    }
+}
+
+/// Returns the number of integers from `min` up to but excluding `max`, or `None` if they aren't
+/// integers of the same signedness.
+fn range_len(min: &DfValue, max: &DfValue) -> Option<u64> {
+    match (min, max) {
+        (DfValue::Int(min), DfValue::Int(max)) => {
+            u64::try_from(i128::from(*max) - i128::from(*min)).ok()
+        }
+        (DfValue::UnsignedInt(min), DfValue::UnsignedInt(max)) => max.checked_sub(*min),
+        _ => None,
+    }
+}
+
+/// A column whose [`ColumnGenerationSpec`] can't generate values of the column's type, which
+/// would otherwise only be found when the generated values are inserted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaCompatibilityError {
+    pub column_name: String,
+    pub expected_type: SqlType,
+    pub actual_spec: ColumnGenerationSpec,
+    /// Why the spec is incompatible with the column; see
+    /// [`ColumnGenerationSpec::incompatibility`].
+    pub message: String,
+}
+
+impl SchemaCompatibilityError {
+    /// Returns the error for the column `column_name`, of type `col_type`, if `spec` can't
+    /// generate values for it.
+    pub fn check(
+        column_name: &str,
+        col_type: &SqlType,
+        spec: &ColumnGenerationSpec,
+    ) -> Option<Self> {
+        spec.incompatibility(col_type).map(|message| Self {
+            column_name: column_name.to_owned(),
+            expected_type: col_type.clone(),
+            actual_spec: spec.clone(),
+            message,
+        })
+    }
+}
+
+impl fmt::Display for SchemaCompatibilityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Column {} of type {} can't be generated with {:?}: the spec {}",
+            self.column_name,
+            self.expected_type.display(readyset_sql::Dialect::MySQL),
+            self.actual_spec,
+            self.message
+        )
+    }
+}
+
+impl std::error::Error for SchemaCompatibilityError {}
+
+/// Whether the values of integer ranges and [`ColumnGenerationSpec::Boolean`] can be written to
+/// a column of type `typ`.
+fn is_numeric(typ: &SqlType) -> bool {
+    use SqlType::*;
+    matches!(
+        typ,
+        Bool | Int(_)
+            | Signed
+            | Unsigned
+            | UnsignedInteger
+            | SignedInteger
+            | IntUnsigned(_)
+            | BigInt(_)
+            | BigIntUnsigned(_)
+            | TinyInt(_)
+            | TinyIntUnsigned(_)
+            | SmallInt(_)
+            | SmallIntUnsigned(_)
+            | MediumInt(_)
+            | MediumIntUnsigned(_)
+            | Int2
+            | Int4
+            | Int8
+            | Double
+            | Float
+            | Real
+            | Numeric(_)
+            | Decimal(..)
+            | Serial
+            | BigSerial
+    )
+}
+
+/// Whether generated strings can be written to a column of type `typ`.
+fn accepts_strings(typ: &SqlType) -> bool {
+    use SqlType::*;
+    typ.is_any_text()
+        || typ.is_any_binary()
+        || matches!(
+            typ,
+            Citext | ByteArray | Json | Jsonb | MacAddr | Inet | Uuid
+        )
+}
+
+/// Whether [`random_value_of_type`] can generate values of type `typ`.
+fn supports_random_values(typ: &SqlType) -> bool {
+    use SqlType::*;
+    !matches!(
+        typ,
+        Enum(_) | Interval { .. } | Array(_) | Other(_) | Point | PostgisPoint
+    )
+}
+
+/// Whether [`unique_value_of_type`] can generate values of type `typ`.
+fn supports_unique_values(typ: &SqlType) -> bool {
+    !matches!(typ, SqlType::Bool) && supports_random_values(typ)
 }
 
 /// Method to use to generate column information.
@@ -136,21 +617,38 @@ pub enum ColumnGenerator {
     RandomChars(RandomCharsGenerator),
     /// Returns a value generated from a zipfian distribution.
     Zipfian(ZipfianGenerator),
+    /// Returns a value from a fixed pool, chosen with a zipfian distribution.
+    ZipfianIndexed(ZipfianIndexedGenerator),
     /// Generate a unique value for every row from a non unique generator
     NonRepeating(NonRepeatingGenerator),
+    /// Returns the result of a function of the row index.
+    Custom(CustomGenerator),
+    /// Returns a random boolean.
+    Boolean(BooleanGenerator),
+    /// Returns one of a fixed set of values, chosen uniformly.
+    Enum(EnumGenerator),
 }
 
 impl ColumnGenerator {
     pub fn gen(&mut self) -> DfValue {
+        self.gen_with(&mut rand::rng())
+    }
+
+    /// Generates the next value, drawing any randomness from `rng`.
+    pub fn gen_with<R: Rng>(&mut self, rng: &mut R) -> DfValue {
         match self {
             ColumnGenerator::Constant(g) => g.gen(),
             ColumnGenerator::Unique(g) => g.gen(),
-            ColumnGenerator::Uniform(g) => g.gen(),
-            ColumnGenerator::Random(g) => g.gen(),
-            ColumnGenerator::RandomString(g) => g.gen(),
-            ColumnGenerator::RandomChars(g) => g.gen(),
-            ColumnGenerator::Zipfian(g) => g.gen(),
-            ColumnGenerator::NonRepeating(g) => g.gen(),
+            ColumnGenerator::Uniform(g) => g.gen_with(rng),
+            ColumnGenerator::Random(g) => g.gen_with(rng),
+            ColumnGenerator::RandomString(g) => g.gen_with(rng),
+            ColumnGenerator::RandomChars(g) => g.gen_with(rng),
+            ColumnGenerator::Zipfian(g) => g.gen_with(rng),
+            ColumnGenerator::ZipfianIndexed(g) => g.gen_with(rng),
+            ColumnGenerator::NonRepeating(g) => g.gen_with(rng),
+            ColumnGenerator::Custom(g) => g.gen(),
+            ColumnGenerator::Boolean(g) => g.gen_with(rng),
+            ColumnGenerator::Enum(g) => g.gen_with(rng),
         }
     }
 }
@@ -159,12 +657,16 @@ impl ColumnGenerator {
     pub fn into_unique(self) -> Self {
         match self {
             ColumnGenerator::Constant(_) => panic!("Can't make unique over Constant"),
+            ColumnGenerator::Boolean(_) => panic!("Can't make unique over Boolean"),
             u @ ColumnGenerator::Unique(_) | u @ ColumnGenerator::NonRepeating(_) => u, /* nothing to do */
             u @ ColumnGenerator::Uniform(_)
             | u @ ColumnGenerator::Zipfian(_)
+            | u @ ColumnGenerator::ZipfianIndexed(_)
+            | u @ ColumnGenerator::Enum(_)
             | u @ ColumnGenerator::Random(_)
             | u @ ColumnGenerator::RandomString(_)
-            | u @ ColumnGenerator::RandomChars(_) => {
+            | u @ ColumnGenerator::RandomChars(_)
+            | u @ ColumnGenerator::Custom(_) => {
                 ColumnGenerator::NonRepeating(NonRepeatingGenerator {
                     generator: Box::new(u),
                     generated: growable_bloom_filter::GrowableBloom::new(0.01, 1_000_000),
@@ -174,15 +676,101 @@ impl ColumnGenerator {
     }
 }
 
+#[derive(Clone)]
+pub struct CustomGenerator {
+    f: Arc<dyn Fn(usize) -> DfValue + Send + Sync>,
+    /// The index of the next row to generate a value for.
+    index: usize,
+}
+
+impl fmt::Debug for CustomGenerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomGenerator")
+            .field("index", &self.index)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Eq for CustomGenerator {}
+
+impl PartialEq for CustomGenerator {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.f, &other.f) && self.index == other.index
+    }
+}
+
+impl CustomGenerator {
+    /// Generates the value for the next row, starting from row 0.
+    pub fn gen(&mut self) -> DfValue {
+        let val = (self.f)(self.index);
+        self.index += 1;
+        val
+    }
+
+    /// Generates the value for the row at `index`.
+    pub fn gen_for_row(&self, index: usize) -> DfValue {
+        (self.f)(index)
+    }
+}
+
+/// Generates `1` with a fixed probability, and `0` otherwise.
+#[derive(Debug, PartialEq, Clone)]
+pub struct BooleanGenerator {
+    true_probability: f64,
+}
+
+impl Eq for BooleanGenerator {}
+
+impl BooleanGenerator {
+    pub fn gen(&self) -> DfValue {
+        self.gen_with(&mut rand::rng())
+    }
+
+    pub fn gen_with<R: Rng>(&self, rng: &mut R) -> DfValue {
+        DfValue::from(rng.random_bool(self.true_probability))
+    }
+}
+
+/// Generates one of a fixed, non-empty set of values, each with equal probability.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct EnumGenerator {
+    values: Vec<DfValue>,
+}
+
+impl EnumGenerator {
+    /// The values that are generated.
+    pub fn values(&self) -> &[DfValue] {
+        &self.values
+    }
+
+    pub fn gen(&self) -> DfValue {
+        self.gen_with(&mut rand::rng())
+    }
+
+    pub fn gen_with<R: Rng>(&self, rng: &mut R) -> DfValue {
+        self.values.choose(rng).unwrap().clone()
+    }
+}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct ConstantGenerator {
     value: DfValue,
 }
 
+/// Generates random strings that match a regex, written in the syntax of the `regex` crate.
+/// Character classes such as `\d`, `\w` and `[a-z]`, alternation, groups and repetition are
+/// supported, with unbounded repetitions (`*`, `+`, `{n,}`) repeating at most 256 extra times.
+/// Since each generated string is a whole match, a leading `^` and trailing `$` are accepted and
+/// ignored; anchors and word boundaries anywhere else aren't supported.
+///
+/// Named capture groups, e.g. `(?P<user>\w+)@(?P<domain>\w+)\.com`, label the parts of each
+/// generated string, which can be retrieved with [`RandomStringGenerator::gen_with_fields`].
 #[derive(Debug, Clone)]
 pub struct RandomStringGenerator {
     regex: String,
     inner: rand_regex::Regex,
+    /// Matches whole generated strings, to find the value of each named capture group.
+    matcher: regex::Regex,
 }
 
 impl Eq for RandomStringGenerator {}
@@ -195,19 +783,72 @@ impl PartialEq for RandomStringGenerator {
 
 impl<S: AsRef<str>> From<S> for RandomStringGenerator {
     fn from(s: S) -> Self {
-        let s = s.as_ref();
-        Self {
-            regex: s.to_string(),
-            inner: rand_regex::Regex::compile(s, 256).unwrap(),
-        }
+        Self::new(s.as_ref()).unwrap()
     }
 }
 
 impl RandomStringGenerator {
+    /// Compiles `pattern`, returning an error describing the problem if it isn't a valid regex
+    /// or uses syntax that can't be generated.
+    pub fn new(pattern: &str) -> anyhow::Result<Self> {
+        let unanchored = strip_anchors(pattern);
+        regex::Regex::new(unanchored).map_err(|e| anyhow!("Invalid regex '{pattern}': {e}"))?;
+        let inner = rand_regex::Regex::compile(unanchored, 256).map_err(|e| match e {
+            rand_regex::Error::Anchor => anyhow!(
+                "Invalid regex '{pattern}': anchors and word boundaries are only supported as a \
+                 leading `^` or trailing `$`"
+            ),
+            e => anyhow!("Invalid regex '{pattern}': {e}"),
+        })?;
+        let matcher = regex::Regex::new(&format!("^(?:{unanchored})$"))?;
+        Ok(Self {
+            regex: pattern.to_owned(),
+            inner,
+            matcher,
+        })
+    }
+
     pub fn gen(&self) -> DfValue {
-        let val: String = rand::rng().sample(&self.inner);
+        self.gen_with(&mut rand::rng())
+    }
+
+    pub fn gen_with<R: Rng>(&self, rng: &mut R) -> DfValue {
+        let val: String = rng.sample(&self.inner);
         val.into()
     }
+
+    /// The names of the named capture groups in the regex, in the order they appear.
+    pub fn field_names(&self) -> Vec<&str> {
+        self.matcher.capture_names().flatten().collect()
+    }
+
+    /// Generates a random string, along with the name and value of each named capture group
+    /// that took part in generating it.
+    pub fn gen_with_fields(&self) -> (DfValue, Vec<(String, String)>) {
+        let val: String = rand::rng().sample(&self.inner);
+        let fields = match self.matcher.captures(&val) {
+            Some(captures) => self
+                .field_names()
+                .into_iter()
+                .filter_map(|name| {
+                    Some((name.to_owned(), captures.name(name)?.as_str().to_owned()))
+                })
+                .collect(),
+            None => vec![],
+        };
+        (val.into(), fields)
+    }
+}
+
+/// Removes a leading `^` and an unescaped trailing `$` from `pattern`, which are implied when
+/// generating whole strings.
+fn strip_anchors(pattern: &str) -> &str {
+    let pattern = pattern.strip_prefix('^').unwrap_or(pattern);
+    match pattern.strip_suffix('$') {
+        // A `$` preceded by an odd number of backslashes is an escaped literal `$`.
+        Some(rest) if rest.chars().rev().take_while(|c| *c == '\\').count() % 2 == 0 => rest,
+        _ => pattern,
+    }
 }
 
 impl From<SqlType> for ConstantGenerator {
@@ -287,13 +928,17 @@ pub struct UniformGenerator {
 
 impl UniformGenerator {
     pub fn gen(&mut self) -> DfValue {
+        self.gen_with(&mut rand::rng())
+    }
+
+    pub fn gen_with<R: Rng>(&mut self, rng: &mut R) -> DfValue {
         if self.with_replacement {
-            uniform_random_value(&self.min, &self.max)
+            uniform_random_value(&self.min, &self.max, rng)
         } else {
-            let mut val = uniform_random_value(&self.min, &self.max);
+            let mut val = uniform_random_value(&self.min, &self.max, rng);
             let mut iters = 0;
             while self.pulled.contains(&val) {
-                val = uniform_random_value(&self.min, &self.max);
+                val = uniform_random_value(&self.min, &self.max, rng);
                 iters += 1;
 
                 assert!(
@@ -326,16 +971,16 @@ pub struct ZipfianGenerator {
 }
 
 impl ZipfianGenerator {
-    fn new(min: DfValue, max: DfValue, alpha: f64) -> Self {
+    fn new<R: Rng>(min: DfValue, max: DfValue, alpha: f64, rng: &mut R) -> Self {
         let (num_elements, mapping): (u64, Vec<DfValue>) = match (&min, &max) {
             (DfValue::Int(i), DfValue::Int(j)) => {
                 let mut mapping: Vec<_> = (*i..*j).map(DfValue::Int).collect();
-                mapping.shuffle(&mut rand::rng());
+                mapping.shuffle(rng);
                 ((j - i) as u64, mapping)
             }
             (DfValue::UnsignedInt(i), DfValue::UnsignedInt(j)) => {
                 let mut mapping: Vec<_> = (*i..*j).map(DfValue::UnsignedInt).collect();
-                mapping.shuffle(&mut rand::rng());
+                mapping.shuffle(rng);
                 ((j - i), mapping)
             }
             (_, _) => unimplemented!("DfValues unsupported for discrete zipfian value generation"),
@@ -351,8 +996,11 @@ impl ZipfianGenerator {
     }
 
     pub fn gen(&mut self) -> DfValue {
-        let mut rng = rand::rng();
-        let offset = self.dist.sample(&mut rng);
+        self.gen_with(&mut rand::rng())
+    }
+
+    pub fn gen_with<R: Rng>(&mut self, rng: &mut R) -> DfValue {
+        let offset = self.dist.sample(rng);
         self.mapping.get(offset.round() as usize).unwrap().clone()
     }
 }
@@ -365,6 +1013,48 @@ impl PartialEq for ZipfianGenerator {
 
 impl Eq for ZipfianGenerator {}
 
+/// Generates values from a fixed pool with a zipfian distribution over their position in it, so
+/// the first value is the most common, the second the next most common, and so on.
+#[derive(Debug, Clone)]
+pub struct ZipfianIndexedGenerator {
+    values: Vec<DfValue>,
+    alpha: f64,
+    dist: Zipf<f64>,
+}
+
+impl ZipfianIndexedGenerator {
+    fn new(values: Vec<DfValue>, alpha: f64) -> Self {
+        Self {
+            dist: Zipf::new(values.len() as _, alpha).unwrap(),
+            values,
+            alpha,
+        }
+    }
+
+    /// The pool of values that are generated, from most to least common.
+    pub fn values(&self) -> &[DfValue] {
+        &self.values
+    }
+
+    pub fn gen(&self) -> DfValue {
+        self.gen_with(&mut rand::rng())
+    }
+
+    pub fn gen_with<R: Rng>(&self, rng: &mut R) -> DfValue {
+        // Samples are ranks from 1 to the number of values.
+        let rank = self.dist.sample(rng) as usize;
+        self.values[rank - 1].clone()
+    }
+}
+
+impl PartialEq for ZipfianIndexedGenerator {
+    fn eq(&self, other: &Self) -> bool {
+        self.values == other.values && self.alpha == other.alpha
+    }
+}
+
+impl Eq for ZipfianIndexedGenerator {}
+
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct RandomGenerator {
     sql_type: SqlType,
@@ -378,7 +1068,11 @@ impl From<SqlType> for RandomGenerator {
 
 impl RandomGenerator {
     pub fn gen(&self) -> DfValue {
-        random_value_of_type(&self.sql_type, rand::rng())
+        self.gen_with(&mut rand::rng())
+    }
+
+    pub fn gen_with<R: Rng>(&self, rng: &mut R) -> DfValue {
+        random_value_of_type(&self.sql_type, rng)
     }
 }
 
@@ -398,16 +1092,24 @@ impl PartialEq for NonRepeatingGenerator {
 
 impl NonRepeatingGenerator {
     pub fn gen(&mut self) -> DfValue {
+        self.gen_with(&mut rand::rng())
+    }
+
+    pub fn gen_with<R: Rng>(&mut self, rng: &mut R) -> DfValue {
         let mut reps = 0;
         loop {
             let d = match &mut *self.generator {
-                ColumnGenerator::Uniform(u) => u.gen(),
-                ColumnGenerator::Zipfian(z) => z.gen(),
-                ColumnGenerator::Random(r) => r.gen(),
-                ColumnGenerator::RandomString(r) => r.gen(),
-                ColumnGenerator::RandomChars(r) => r.gen(),
+                ColumnGenerator::Uniform(u) => u.gen_with(rng),
+                ColumnGenerator::Zipfian(z) => z.gen_with(rng),
+                ColumnGenerator::ZipfianIndexed(z) => z.gen_with(rng),
+                ColumnGenerator::Enum(e) => e.gen_with(rng),
+                ColumnGenerator::Random(r) => r.gen_with(rng),
+                ColumnGenerator::RandomString(r) => r.gen_with(rng),
+                ColumnGenerator::RandomChars(r) => r.gen_with(rng),
+                ColumnGenerator::Custom(c) => c.gen(),
                 ColumnGenerator::Unique(_) => panic!("Non repeating over Unique"),
                 ColumnGenerator::Constant(_) => panic!("Non repeating over Constant"),
+                ColumnGenerator::Boolean(_) => panic!("Non repeating over Boolean"),
                 ColumnGenerator::NonRepeating(_) => panic!("Nested NonRepeating"),
             };
 
@@ -454,12 +1156,15 @@ impl RandomCharsGenerator {
     }
 
     pub fn gen(&self) -> DfValue {
-        let mut rng = rand::rng();
+        self.gen_with(&mut rand::rng())
+    }
+
+    pub fn gen_with<R: Rng>(&self, rng: &mut R) -> DfValue {
         let len = (self.min_length..self.max_length)
-            .sample_single(&mut rng)
+            .sample_single(rng)
             .unwrap();
         let sampler = Uniform::new_inclusive(self.low, self.high).unwrap();
-        let bytes: Vec<u8> = (0..len).map(|_| sampler.sample(&mut rng)).collect();
+        let bytes: Vec<u8> = (0..len).map(|_| sampler.sample(rng)).collect();
 
         // XXX: Hack alert! This goes through [`benchmarks::utils::generate::load_table_part`] as a
         // prepared statement parameter, which means it will be interpreted according to the client
@@ -709,8 +1414,7 @@ where
 /// Generate a random value from a uniform distribution with the given integer
 /// [`SqlType`] for a given range of values.If the range of `min` and `max`
 /// exceeds the storage of the type, this truncates to fit.
-fn uniform_random_value(min: &DfValue, max: &DfValue) -> DfValue {
-    let mut rng = rand::rng();
+fn uniform_random_value<R: Rng>(min: &DfValue, max: &DfValue, rng: &mut R) -> DfValue {
     match (min, max) {
         (DfValue::Int(i), DfValue::Int(j)) => rng.random_range(*i..*j).into(),
         (DfValue::UnsignedInt(i), DfValue::UnsignedInt(j)) => rng.random_range(*i..*j).into(),
@@ -854,3 +1558,467 @@ pub fn unique_value_of_type(typ: &SqlType, idx: u32) -> DfValue {
         SqlType::Tsvector => DfValue::None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    #[test]
+    fn regex_named_groups_label_fields() {
+        let generator =
+            RandomStringGenerator::new(r"^(?P<user>[a-z]{3,8})@(?P<domain>[a-z]{3,8})\.com$")
+                .unwrap();
+        assert_eq!(generator.field_names(), ["user", "domain"]);
+        for _ in 0..20 {
+            let (val, fields) = generator.gen_with_fields();
+            let [(user_label, user), (domain_label, domain)] = fields.as_slice() else {
+                panic!("Expected two fields, got {fields:?}");
+            };
+            assert_eq!(
+                (user_label.as_str(), domain_label.as_str()),
+                ("user", "domain")
+            );
+            assert_eq!(
+                <&str>::try_from(&val).unwrap(),
+                format!("{user}@{domain}.com")
+            );
+        }
+    }
+
+    #[test]
+    fn custom_spec_receives_sequential_indices() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let spec = {
+            let seen = Arc::clone(&seen);
+            ColumnGenerationSpec::Custom(Arc::new(move |i| {
+                seen.lock().unwrap().push(i);
+                DfValue::from((i * i) as i64)
+            }))
+        };
+
+        let mut generator = spec.generator_for_col(SqlType::BigInt(None));
+        let values = (0..5).map(|_| generator.gen()).collect::<Vec<_>>();
+
+        assert_eq!(*seen.lock().unwrap(), vec![0, 1, 2, 3, 4]);
+        assert_eq!(values, [0, 1, 4, 9, 16].map(DfValue::from).to_vec());
+    }
+
+    #[test]
+    fn custom_generator_for_row() {
+        let spec = ColumnGenerationSpec::Custom(Arc::new(|i| format!("row-{i}").into()));
+        let ColumnGenerator::Custom(generator) = spec.generator_for_col(SqlType::Text) else {
+            panic!("Expected a custom generator");
+        };
+
+        assert_eq!(generator.gen_for_row(42), DfValue::from("row-42"));
+    }
+
+    #[test]
+    fn custom_spec_equality() {
+        let f: Arc<dyn Fn(usize) -> DfValue + Send + Sync> = Arc::new(|i| (i as i64).into());
+        let spec = ColumnGenerationSpec::Custom(f);
+        assert_eq!(spec, spec.clone());
+        assert_ne!(
+            spec,
+            ColumnGenerationSpec::Custom(Arc::new(|i| (i as i64).into()))
+        );
+        assert_ne!(spec, ColumnGenerationSpec::Unique);
+    }
+
+    #[test]
+    fn boolean_true_rate() {
+        const SAMPLES: usize = 100_000;
+        for true_probability in [0.0, 0.1, 0.5, 0.9, 1.0] {
+            let mut generator =
+                ColumnGenerationSpec::Boolean { true_probability }.generator_for_col(SqlType::Bool);
+            let mut trues = 0;
+            for _ in 0..SAMPLES {
+                match generator.gen() {
+                    DfValue::UnsignedInt(1) => trues += 1,
+                    DfValue::UnsignedInt(0) => {}
+                    val => panic!("Expected a boolean, got {val:?}"),
+                }
+            }
+            let observed = trues as f64 / SAMPLES as f64;
+            assert!(
+                (observed - true_probability).abs() < 0.01,
+                "expected {true_probability}, observed {observed:.4}"
+            );
+        }
+    }
+
+    /// A column of each kind of type, by name.
+    fn column_types() -> Vec<(&'static str, SqlType)> {
+        vec![
+            ("int", SqlType::Int(None)),
+            ("bigint_unsigned", SqlType::BigIntUnsigned(None)),
+            ("double", SqlType::Double),
+            ("decimal", SqlType::Decimal(10, 2)),
+            ("bool", SqlType::Bool),
+            ("varchar", SqlType::VarChar(Some(10))),
+            ("text", SqlType::Text),
+            ("blob", SqlType::Blob),
+            ("json", SqlType::Json),
+            ("uuid", SqlType::Uuid),
+            ("date", SqlType::Date),
+            ("timestamp", SqlType::Timestamp),
+            ("enum", SqlType::from_enum_variants(vec!["a".to_owned()])),
+            ("array", SqlType::Array(Box::new(SqlType::Int(None)))),
+        ]
+    }
+
+    #[test]
+    fn spec_compatibility() {
+        use ColumnGenerationSpec::*;
+        const NUMERIC: &[&str] = &["int", "bigint_unsigned", "double", "decimal", "bool"];
+        const STRINGS: &[&str] = &["varchar", "text", "blob", "json", "uuid"];
+        let random_string = RandomString("[a-z]{3}".to_owned());
+        let specs: Vec<(ColumnGenerationSpec, &[&str])> = vec![
+            (
+                Unique,
+                &[
+                    "int",
+                    "bigint_unsigned",
+                    "double",
+                    "decimal",
+                    "varchar",
+                    "text",
+                    "blob",
+                    "json",
+                    "uuid",
+                    "date",
+                    "timestamp",
+                ],
+            ),
+            (UniqueFrom(10), &["int", "text", "date"]),
+            (UniqueRepeated(3), &["int", "text", "date"]),
+            (
+                Random,
+                &[
+                    "int",
+                    "bigint_unsigned",
+                    "double",
+                    "decimal",
+                    "bool",
+                    "varchar",
+                    "text",
+                    "blob",
+                    "json",
+                    "uuid",
+                    "date",
+                    "timestamp",
+                ],
+            ),
+            (Uniform(1.into(), 10.into()), NUMERIC),
+            (
+                UniformWithoutReplacement {
+                    min: 1u32.into(),
+                    max: 10u32.into(),
+                    batch_size: None,
+                },
+                NUMERIC,
+            ),
+            (
+                Zipfian {
+                    min: 1.into(),
+                    max: 10.into(),
+                    alpha: 1.1,
+                },
+                NUMERIC,
+            ),
+            (random_string.clone(), STRINGS),
+            (
+                RandomChar {
+                    min_length: 1,
+                    max_length: 5,
+                    charset: "ascii".to_owned(),
+                },
+                STRINGS,
+            ),
+            (
+                ZipfianIndexed {
+                    inner: Box::new(random_string),
+                    num_unique: 10,
+                    alpha: 1.1,
+                },
+                STRINGS,
+            ),
+            (Constant(5.into()), &["int", "text"]),
+            (Enum(vec!["1".into(), "2".into()]), &["int", "text"]),
+            (
+                Boolean {
+                    true_probability: 0.5,
+                },
+                NUMERIC,
+            ),
+            (
+                Custom(Arc::new(|i| (i as i64).into())),
+                &["int", "text", "enum", "array"],
+            ),
+        ];
+
+        for (spec, compatible) in specs {
+            for (name, col_type) in column_types() {
+                let incompatibility = spec.incompatibility(&col_type);
+                if compatible.contains(&name) {
+                    assert_eq!(incompatibility, None, "{spec:?} for {name}");
+                } else if !matches!(
+                    spec,
+                    UniqueFrom(_) | UniqueRepeated(_) | Constant(_) | Enum(_) | Custom(_)
+                ) {
+                    // Only the types listed for these specs are checked.
+                    assert!(incompatibility.is_some(), "{spec:?} for {name}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_spec_parameters() {
+        use ColumnGenerationSpec::*;
+        let cases = [
+            (
+                Uniform(10.into(), 1.into()),
+                SqlType::Int(None),
+                "has an empty range, from 10 to 1",
+            ),
+            (
+                Zipfian {
+                    min: 1.into(),
+                    max: 10u32.into(),
+                    alpha: 1.0,
+                },
+                SqlType::Int(None),
+                "has bounds 1 and 10, which aren't integers of the same signedness",
+            ),
+            (
+                RandomChar {
+                    min_length: 1,
+                    max_length: 20,
+                    charset: "ascii".to_owned(),
+                },
+                SqlType::VarChar(Some(10)),
+                "generates strings of up to 19 characters",
+            ),
+            (
+                RandomChar {
+                    min_length: 1,
+                    max_length: 5,
+                    charset: "ebcdic".to_owned(),
+                },
+                SqlType::Text,
+                "has an unknown charset ebcdic",
+            ),
+            (
+                Constant("abc".into()),
+                SqlType::Int(None),
+                "generates abc, which can't be converted to the column's type",
+            ),
+            (Enum(vec![]), SqlType::Text, "has no values to choose from"),
+            (
+                Enum(vec!["1".into(), "shipped".into()]),
+                SqlType::Int(None),
+                "generates shipped, which can't be converted to the column's type",
+            ),
+            (
+                Boolean {
+                    true_probability: 1.5,
+                },
+                SqlType::Bool,
+                "has a probability of 1.5, which isn't between 0 and 1",
+            ),
+        ];
+        for (spec, col_type, expected) in cases {
+            assert_eq!(spec.incompatibility(&col_type).as_deref(), Some(expected));
+        }
+        assert!(RandomString("[a-z".to_owned())
+            .incompatibility(&SqlType::Text)
+            .unwrap()
+            .starts_with("has an invalid pattern"));
+    }
+
+    #[test]
+    fn schema_compatibility_error_message() {
+        let spec = ColumnGenerationSpec::RandomString("[a-z]{3}".to_owned());
+        assert_eq!(
+            SchemaCompatibilityError::check("users.id", &SqlType::Text, &spec),
+            None
+        );
+        let err =
+            SchemaCompatibilityError::check("users.id", &SqlType::BigInt(None), &spec).unwrap();
+        assert_eq!(err.column_name, "users.id");
+        assert_eq!(err.expected_type, SqlType::BigInt(None));
+        assert_eq!(err.actual_spec, spec);
+        assert_eq!(
+            err.to_string(),
+            "Column users.id of type BIGINT can't be generated with RandomString(\"[a-z]{3}\"): \
+             the spec generates strings"
+        );
+    }
+
+    fn zipfian_indexed(num_unique: u64, alpha: f64) -> ZipfianIndexedGenerator {
+        let spec = ColumnGenerationSpec::ZipfianIndexed {
+            inner: Box::new(ColumnGenerationSpec::Unique),
+            num_unique,
+            alpha,
+        };
+        let ColumnGenerator::ZipfianIndexed(generator) = spec.generator_for_col(SqlType::Text)
+        else {
+            panic!("Expected a zipfian indexed generator");
+        };
+        generator
+    }
+
+    #[test]
+    fn zipfian_indexed_draws_from_pool() {
+        let generator = zipfian_indexed(50, 1.1);
+        let pool = generator.values();
+        assert_eq!(pool.len(), 50);
+        assert!(pool.iter().all(|val| <&str>::try_from(val).is_ok()));
+        for _ in 0..1000 {
+            assert!(pool.contains(&generator.gen()));
+        }
+    }
+
+    #[test]
+    fn zipfian_indexed_rank_frequency() {
+        const SAMPLES: usize = 20_000;
+        let generator = zipfian_indexed(10, 1.0);
+        let pool = generator.values();
+        let mut counts = vec![0; pool.len()];
+        for _ in 0..SAMPLES {
+            let val = generator.gen();
+            counts[pool.iter().position(|v| *v == val).unwrap()] += 1;
+        }
+
+        // Zipf's law: the frequency of the value with rank k is proportional to 1 / k^alpha.
+        let harmonic = (1..=pool.len()).map(|k| 1.0 / k as f64).sum::<f64>();
+        for (i, count) in counts.iter().enumerate() {
+            let expected = 1.0 / ((i + 1) as f64 * harmonic);
+            let observed = *count as f64 / SAMPLES as f64;
+            assert!(
+                (observed - expected).abs() < 0.02,
+                "rank {}: expected {expected:.3}, observed {observed:.3}",
+                i + 1
+            );
+        }
+    }
+
+    #[test]
+    fn enum_uniform_distribution() {
+        const SAMPLES: usize = 20_000;
+        let values = ["new", "processing", "shipped", "delivered"];
+        let spec = ColumnGenerationSpec::Enum(values.iter().map(|&v| v.into()).collect());
+        let ColumnGenerator::Enum(generator) = spec.generator_for_col(SqlType::Text) else {
+            panic!("Expected an enum generator");
+        };
+        let mut counts = [0; 4];
+        for _ in 0..SAMPLES {
+            let val = generator.gen();
+            let val = <&str>::try_from(&val).unwrap();
+            counts[values.iter().position(|v| *v == val).unwrap()] += 1;
+        }
+
+        // Pearson's chi-square test against equal frequencies. With 3 degrees of freedom, a
+        // uniform generator exceeds 16.27 with a probability of 0.001.
+        let expected = SAMPLES as f64 / values.len() as f64;
+        let chi_square = counts
+            .iter()
+            .map(|&count| (count as f64 - expected).powi(2) / expected)
+            .sum::<f64>();
+        assert!(chi_square < 16.27, "chi-square {chi_square} for {counts:?}");
+    }
+
+    #[test]
+    fn enum_values_are_coerced_to_the_column_type() {
+        let spec = ColumnGenerationSpec::Enum(vec!["1".into(), "2".into()]);
+        let ColumnGenerator::Enum(generator) = spec.generator_for_col(SqlType::Int(None)) else {
+            panic!("Expected an enum generator");
+        };
+        assert_eq!(generator.values(), [DfValue::Int(1), DfValue::Int(2)]);
+    }
+
+    #[test]
+    fn estimated_cardinality() {
+        use ColumnGenerationSpec::*;
+        const ROWS: u64 = 1000;
+        let specs = [
+            (Unique, ROWS),
+            (UniqueFrom(10), ROWS),
+            (UniqueRepeated(3), 334),
+            (UniqueRepeated(0), ROWS),
+            (Uniform(DfValue::Int(-5), DfValue::Int(5)), 10),
+            (Uniform(DfValue::Int(0), DfValue::Int(1_000_000)), ROWS),
+            (Uniform(DfValue::Int(5), DfValue::Int(5)), 1),
+            (Uniform(DfValue::Float(0.0), DfValue::Float(1.0)), ROWS),
+            (
+                UniformWithoutReplacement {
+                    min: DfValue::UnsignedInt(10),
+                    max: DfValue::UnsignedInt(60),
+                    batch_size: None,
+                },
+                50,
+            ),
+            (
+                Zipfian {
+                    min: DfValue::Int(0),
+                    max: DfValue::Int(100),
+                    alpha: 1.1,
+                },
+                100,
+            ),
+            (
+                ZipfianIndexed {
+                    inner: Box::new(RandomString("[a-z]{12}".to_owned())),
+                    num_unique: 200,
+                    alpha: 1.1,
+                },
+                200,
+            ),
+            (
+                ZipfianIndexed {
+                    inner: Box::new(Uniform(DfValue::Int(0), DfValue::Int(20))),
+                    num_unique: 200,
+                    alpha: 1.1,
+                },
+                20,
+            ),
+            (Random, ROWS),
+            (RandomString("[a-z]{12}".to_owned()), ROWS),
+            (
+                RandomChar {
+                    min_length: 1,
+                    max_length: 10,
+                    charset: "ascii".to_owned(),
+                },
+                ROWS,
+            ),
+            (Constant(DfValue::from("a")), 1),
+            (Enum(vec!["a".into(), "b".into(), "a".into()]), 2),
+            (Enum(vec![]), 1),
+            (
+                Boolean {
+                    true_probability: 0.5,
+                },
+                2,
+            ),
+            (
+                Boolean {
+                    true_probability: 1.0,
+                },
+                1,
+            ),
+            (Custom(Arc::new(|i| (i as i64).into())), ROWS),
+        ];
+        for (spec, expected) in specs {
+            assert_eq!(spec.estimated_cardinality(ROWS), expected, "{spec:?}");
+            for rows in [1, 2, 7, ROWS, 1_000_000] {
+                let estimate = spec.estimated_cardinality(rows);
+                assert!((1..=rows).contains(&estimate), "{spec:?} with {rows} rows");
+            }
+            assert_eq!(spec.estimated_cardinality(0), 0, "{spec:?}");
+        }
+    }
+}
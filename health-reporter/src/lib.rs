@@ -1,11 +1,34 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt::Display;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{Arc, LazyLock};
 
 use chrono::Utc;
 use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "metrics")]
+mod metrics;
+#[cfg(feature = "persistence")]
+mod persistence;
+mod status;
+#[cfg(feature = "watch")]
+mod watch;
+
+#[cfg(feature = "metrics")]
+pub use crate::metrics::{HEALTH_STATE_METRIC, HEALTH_TRANSITIONS_METRIC, HealthMetricsProvider};
+#[cfg(feature = "persistence")]
+pub use crate::persistence::PERSISTENCE_FORMAT_VERSION;
+pub use crate::status::{STATUS_SCHEMA_VERSION, StatusPayload, StatusPayloadBuilder};
+#[cfg(feature = "watch")]
+pub use crate::watch::EXPECT_STATE_MAX_BACKOFF;
 
 type TransitionTime = chrono::DateTime<Utc>;
 
+/// Approximately when this process started: the first time a [`HealthReporter`] was created.
+static PROCESS_START: LazyLock<TransitionTime> = LazyLock::new(Utc::now);
+
 /// Indicates the current state along with a transition time. The transition time can be
 /// used to infer how long the current state has persisted for.
 #[derive(Clone, Copy)]
@@ -16,17 +39,51 @@ pub struct Health {
 
 impl Health {
     fn new(state: State) -> Health {
+        Health::new_with_time(state, Utc::now())
+    }
+
+    /// Returns a Health that transitioned to `state` at the given time, rather than now.
+    pub fn new_with_time(state: State, transition_time: TransitionTime) -> Health {
         Health {
             state,
-            transition_time: Utc::now(),
+            transition_time,
         }
     }
+
+    /// Returns how long ago the transition to the current state happened.
+    pub fn age(&self) -> chrono::Duration {
+        Utc::now() - self.transition_time
+    }
+
+    /// Returns true if the current state has persisted for longer than `max_age`.
+    pub fn is_stale(&self, max_age: chrono::Duration) -> bool {
+        self.age() > max_age
+    }
+}
+
+/// Formats as, e.g., `healthy since 2024-01-01T00:00:00Z`.
+impl Display for Health {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} since {}",
+            self.state,
+            self.transition_time
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+        )
+    }
 }
 
 /// All known states.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+///
+/// States are ordered by [`severity`](State::severity), so the greatest of several states is the
+/// worst of them. Note that this is not the order in which the variants are declared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum State {
     Healthy,
+    /// Still serving, but not fully healthy; see [`ScoreThresholds`].
+    Degraded,
     Unhealthy,
     ShuttingDown,
     Unknown,
@@ -36,6 +93,7 @@ impl Display for State {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let s = match self {
             State::Healthy => "healthy",
+            State::Degraded => "degraded",
             State::Unhealthy => "unhealthy",
             State::ShuttingDown => "shutting down",
             State::Unknown => "unknown",
@@ -44,33 +102,235 @@ impl Display for State {
     }
 }
 
+impl State {
+    /// Returns true if the process is going away and will not return to any other state. Only
+    /// [`State::ShuttingDown`] is terminal.
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            State::ShuttingDown => true,
+            State::Healthy | State::Degraded | State::Unhealthy | State::Unknown => false,
+        }
+    }
+
+    /// Returns true if the process is serving requests: it is either [`State::Healthy`] or
+    /// [`State::Degraded`].
+    pub fn is_operational(&self) -> bool {
+        match self {
+            State::Healthy | State::Degraded => true,
+            State::Unhealthy | State::ShuttingDown | State::Unknown => false,
+        }
+    }
+
+    /// Returns how severe this state is, for sorting and comparing states in alerts. Higher is
+    /// worse: 0 for [`State::Healthy`], 1 for [`State::Degraded`], 2 for [`State::Unknown`], 3
+    /// for [`State::Unhealthy`] and 4 for [`State::ShuttingDown`].
+    pub fn severity(&self) -> u8 {
+        match self {
+            State::Healthy => 0,
+            State::Degraded => 1,
+            State::Unknown => 2,
+            State::Unhealthy => 3,
+            State::ShuttingDown => 4,
+        }
+    }
+
+    /// Returns the worse of the two states, by [`severity`](State::severity).
+    pub fn max(a: State, b: State) -> State {
+        Ord::max(a, b)
+    }
+}
+
+impl Ord for State {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.severity().cmp(&other.severity())
+    }
+}
+
+impl PartialOrd for State {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Returns the worst of the given states, such as those of several subsystems, by
+/// [`severity`](State::severity). Returns [`State::Unknown`] if there are no states.
+pub fn aggregate_states(states: impl Iterator<Item = State>) -> State {
+    states.max().unwrap_or(State::Unknown)
+}
+
+/// Maps a health score between 0 and 100 to a [`State`].
+///
+/// A score is [`State::Healthy`] if it is greater than or equal to `healthy`, [`State::Degraded`]
+/// if it is greater than or equal to `degraded` but less than `healthy`, and
+/// [`State::Unhealthy`] otherwise. Both bounds are inclusive, so with the defaults a score of
+/// exactly 90 is healthy and a score of exactly 50 is degraded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScoreThresholds {
+    pub healthy: f64,
+    pub degraded: f64,
+}
+
+impl Default for ScoreThresholds {
+    fn default() -> Self {
+        ScoreThresholds {
+            healthy: 90.0,
+            degraded: 50.0,
+        }
+    }
+}
+
+impl ScoreThresholds {
+    /// Returns the state corresponding to the given score.
+    pub fn state_for(&self, score: f64) -> State {
+        if score >= self.healthy {
+            State::Healthy
+        } else if score >= self.degraded {
+            State::Degraded
+        } else {
+            State::Unhealthy
+        }
+    }
+}
+
+/// A single component's contribution to the health score.
+#[derive(Clone, Copy)]
+struct ComponentScore {
+    weight: f64,
+    score: f64,
+}
+
+/// A callback run when a [`HealthReporter`] transitions to [`State::ShuttingDown`]; see
+/// [`HealthReporter::set_on_shutdown`].
+type ShutdownHook = Arc<dyn Fn() + Send + Sync>;
+
+/// The error returned by [`HealthReporter::try_set_state`] for a transition that isn't one of
+/// the reporter's [allowed transitions](HealthReporter::with_allowed_transitions).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TransitionError {
+    pub from: State,
+    pub to: State,
+}
+
+impl Display for TransitionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "transition from {} to {} is not allowed",
+            self.from, self.to
+        )
+    }
+}
+
+impl std::error::Error for TransitionError {}
+
 /// The HealthReporter can be used to record the current state, and report the current state.
+///
+/// Instead of setting the state directly, components may each report a score between 0 and 100
+/// with [`HealthReporter::set_score`]. The state is then derived from the weighted mean of all
+/// component scores using the reporter's [`ScoreThresholds`].
+///
+/// When a process has several reporters, each can be given a name with
+/// [`HealthReporter::with_name`] to tell their health apart in logs and metrics.
 #[derive(Clone)]
 pub struct HealthReporter {
     health: Arc<RwLock<Health>>,
+    /// The number of state transitions since the reporter was created.
+    transitions: Arc<AtomicU64>,
+    scores: Arc<RwLock<HashMap<String, ComponentScore>>>,
+    thresholds: ScoreThresholds,
+    name: Option<Arc<str>>,
+    on_shutdown: Arc<RwLock<Option<ShutdownHook>>>,
+    /// The `(from, to)` transitions the state may make, or `None` if any transition is allowed.
+    allowed_transitions: Option<Arc<[(State, State)]>>,
+}
+
+/// Formats the current health, prefixed with the reporter's name if it has one, e.g.
+/// `replication: healthy since 2024-01-01T00:00:00Z`.
+impl Display for HealthReporter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(name) = self.name() {
+            write!(f, "{name}: ")?;
+        }
+        write!(f, "{}", self.health())
+    }
 }
 
+/// Returns a HealthReporter in the Unknown state, unlike [`HealthReporter::new`] which starts
+/// out Unhealthy.
 impl Default for HealthReporter {
     fn default() -> Self {
-        HealthReporter::new()
+        HealthReporter::with_initial_state(State::Unknown)
     }
 }
 
 impl HealthReporter {
-    /// Returns a new HealthReporter with the Unhealthy state set.
+    /// Returns a new, unnamed HealthReporter with the Unhealthy state set.
     pub fn new() -> HealthReporter {
-        let health = Health::new(State::Unhealthy);
+        HealthReporter::with_initial_state(State::Unhealthy)
+    }
+
+    /// Returns a new HealthReporter with the given initial state, for components whose health
+    /// isn't known until their first health check.
+    pub fn with_initial_state(state: State) -> HealthReporter {
+        LazyLock::force(&PROCESS_START);
+        let health = Health::new(state);
         HealthReporter {
             health: Arc::new(RwLock::new(health)),
+            transitions: Default::default(),
+            scores: Default::default(),
+            thresholds: ScoreThresholds::default(),
+            name: None,
+            on_shutdown: Default::default(),
+            allowed_transitions: None,
         }
     }
 
+    /// Names the component whose health this reporter records. The name is included when the
+    /// reporter is displayed, and in its metrics.
+    pub fn with_name(mut self, name: impl Into<Arc<str>>) -> HealthReporter {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Returns the name of the component whose health this reporter records, if it has one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the name of this reporter, or `default` if it has none.
+    pub fn name_or_default<'a>(&'a self, default: &'a str) -> &'a str {
+        self.name().unwrap_or(default)
+    }
+
+    /// Sets the thresholds used to map the health score to a state.
+    pub fn with_score_thresholds(mut self, thresholds: ScoreThresholds) -> HealthReporter {
+        self.thresholds = thresholds;
+        self
+    }
+
+    /// Only allows the state to transition from one state to another if the pair is one of
+    /// `transitions`, e.g. to rule out going from [`State::ShuttingDown`] back to
+    /// [`State::Healthy`]. [`HealthReporter::set_state`] ignores any other transition, and
+    /// [`HealthReporter::try_set_state`] returns an error for it. By default, every transition is
+    /// allowed.
+    pub fn with_allowed_transitions(mut self, transitions: &[(State, State)]) -> HealthReporter {
+        self.allowed_transitions = Some(transitions.into());
+        self
+    }
+
+    /// Returns true if the state may transition from `from` to `to`.
+    fn is_allowed(&self, from: State, to: State) -> bool {
+        self.allowed_transitions
+            .as_ref()
+            .is_none_or(|allowed| allowed.contains(&(from, to)))
+    }
+
     /// Returns the current state of the HealthReporter.
     pub fn state(&self) -> State {
         self.health.read().state
     }
 
-    /// Returns the current health, which includes both the state and the last transition time.
+    /// Returns the current health, which includes the state and the last transition time.
     pub fn health(&self) -> Health {
         *self.health.read()
     }
@@ -79,21 +339,100 @@ impl HealthReporter {
     /// is the same as the provided state, then no write operation occurs. If the new state
     /// facilitates a state transition, then the state is updated with a current timestamp
     /// indicating the transition time.
+    ///
+    /// The comparison and the update happen under a single write lock, so concurrent calls with
+    /// the same state result in exactly one transition. A transition to [`State::ShuttingDown`]
+    /// runs the hook registered with [`HealthReporter::set_on_shutdown`] once the lock has been
+    /// released.
+    ///
+    /// Transitions that aren't [allowed](HealthReporter::with_allowed_transitions) are ignored;
+    /// use [`HealthReporter::try_set_state`] to find out about them.
     pub fn set_state(&mut self, new_state: State) {
-        {
-            let health = self.health.read();
-            if health.state == new_state {
-                // We only want to update our health if we have a state transition.
-                return;
+        let _ = self.try_set_state(new_state);
+    }
+
+    /// Like [`HealthReporter::set_state`], but returns whether the state transitioned, or an
+    /// error if the transition isn't [allowed](HealthReporter::with_allowed_transitions). Setting
+    /// the current state again is never an error.
+    pub fn try_set_state(&mut self, new_state: State) -> Result<bool, TransitionError> {
+        let mut health = self.health.write();
+        // We only want to update our health if we have a state transition.
+        if health.state == new_state {
+            return Ok(false);
+        }
+        if !self.is_allowed(health.state, new_state) {
+            return Err(TransitionError {
+                from: health.state,
+                to: new_state,
+            });
+        }
+        *health = Health::new(new_state);
+        self.transitions.fetch_add(1, AtomicOrdering::Relaxed);
+        drop(health);
+
+        if new_state == State::ShuttingDown {
+            // Clone the hook out of the lock, so it may itself register a new hook.
+            let on_shutdown = self.on_shutdown.read().clone();
+            if let Some(on_shutdown) = on_shutdown {
+                on_shutdown();
             }
         }
-        let new_health = Health::new(new_state);
-        *self.health.write() = new_health;
+        Ok(true)
+    }
+
+    /// Registers `f` to be called synchronously each time the reporter, or any clone of it,
+    /// transitions to [`State::ShuttingDown`], after the state has been updated. Replaces any
+    /// previously registered callback.
+    pub fn set_on_shutdown(&mut self, f: impl Fn() + Send + Sync + 'static) {
+        *self.on_shutdown.write() = Some(Arc::new(f));
+    }
+
+    /// Records the score, between 0 and 100, of the given component, with the given weight
+    /// relative to other components. The state is then updated via [`HealthReporter::set_state`]
+    /// to match the new overall [`score`](HealthReporter::score), unless the reporter is shutting
+    /// down.
+    pub fn set_score(&mut self, component: &str, weight: f64, score: f64) {
+        // Hold the lock until the state has been updated, so concurrent score changes are applied
+        // in the same order as the state changes they cause.
+        let scores = Arc::clone(&self.scores);
+        let mut scores = scores.write();
+        scores.insert(
+            component.to_owned(),
+            ComponentScore {
+                weight: weight.max(0.0),
+                score: score.clamp(0.0, 100.0),
+            },
+        );
+
+        if self.state() != State::ShuttingDown {
+            let new_state = self.thresholds.state_for(Self::weighted_score(&scores));
+            self.set_state(new_state);
+        }
+    }
+
+    /// Returns the weighted mean of all component scores. A reporter with no component scores
+    /// (or only zero weights) has a score of 0, matching its initial unhealthy state.
+    pub fn score(&self) -> f64 {
+        Self::weighted_score(&self.scores.read())
+    }
+
+    fn weighted_score(scores: &HashMap<String, ComponentScore>) -> f64 {
+        let total_weight: f64 = scores.values().map(|c| c.weight).sum();
+        if total_weight == 0.0 {
+            return 0.0;
+        }
+        scores.values().map(|c| c.weight * c.score).sum::<f64>() / total_weight
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Barrier;
+    use std::thread;
+    use std::time::Instant;
+
+    use chrono::Duration;
+
     use super::*;
 
     #[test]
@@ -104,6 +443,21 @@ mod tests {
         assert_eq!(got, State::Unhealthy);
     }
 
+    #[test]
+    fn default_reporter_starts_with_unknown() {
+        let reporter = HealthReporter::default();
+        assert_eq!(reporter.state(), State::Unknown);
+    }
+
+    #[test]
+    fn reporter_starts_with_initial_state() {
+        for state in ALL_STATES {
+            let reporter = HealthReporter::with_initial_state(state);
+            assert_eq!(reporter.state(), state);
+            assert_eq!(reporter.health().state, state);
+        }
+    }
+
     #[test]
     fn can_change_state() {
         let mut reporter = HealthReporter::new();
@@ -128,4 +482,445 @@ mod tests {
         let second = reporter.health().transition_time;
         assert_eq!(first, second);
     }
+
+    #[test]
+    fn concurrent_set_state_single_transition() {
+        const THREADS: usize = 16;
+        const ROUNDS: usize = 200;
+
+        let reporter = HealthReporter::new();
+        let barrier = Arc::new(Barrier::new(THREADS));
+        let handles = (0..THREADS)
+            .map(|_| {
+                let mut reporter = reporter.clone();
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    let mut observed = vec![];
+                    for round in 0..ROUNDS {
+                        let state = if round % 2 == 0 {
+                            State::Healthy
+                        } else {
+                            State::Unhealthy
+                        };
+                        barrier.wait();
+                        reporter.set_state(state);
+                        observed.push(reporter.health());
+                    }
+                    observed
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let observed = handles
+            .into_iter()
+            .map(|h| h.join().unwrap())
+            .collect::<Vec<_>>();
+
+        // Every thread set the same state in each round, so they must all have observed the
+        // same single transition.
+        for round in 0..ROUNDS {
+            let first = &observed[0][round];
+            for thread in &observed {
+                assert_eq!(thread[round].state, first.state);
+                assert_eq!(thread[round].transition_time, first.transition_time);
+            }
+        }
+    }
+
+    /// How long each thread of the contention tests below keeps reading and writing.
+    const CONTENTION_DURATION: std::time::Duration = std::time::Duration::from_millis(100);
+
+    /// The states written by the contention tests. `ShuttingDown` is left out since nothing
+    /// transitions out of it.
+    const OPERATIONAL_STATES: [State; 4] = [
+        State::Healthy,
+        State::Degraded,
+        State::Unhealthy,
+        State::Unknown,
+    ];
+
+    #[test]
+    fn concurrent_reads_and_writes() {
+        const THREADS: usize = 16;
+
+        let reporter = HealthReporter::new();
+        let barrier = Arc::new(Barrier::new(THREADS));
+        let handles = (0..THREADS)
+            .map(|i| {
+                let mut reporter = reporter.clone();
+                let barrier = Arc::clone(&barrier);
+                thread::spawn(move || {
+                    barrier.wait();
+                    let start = Instant::now();
+                    let mut writes = i;
+                    while start.elapsed() < CONTENTION_DURATION {
+                        reporter.set_state(OPERATIONAL_STATES[writes % OPERATIONAL_STATES.len()]);
+                        writes += 1;
+                        let state = reporter.state();
+                        assert!(ALL_STATES.contains(&state), "read {state:?}");
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let health = reporter.health();
+        assert!(OPERATIONAL_STATES.contains(&health.state));
+        assert!(health.transition_time <= Utc::now());
+    }
+
+    #[test]
+    fn reads_during_writes() {
+        let mut writer = HealthReporter::new();
+        let reader = writer.clone();
+        let barrier = Arc::new(Barrier::new(2));
+
+        let read_barrier = Arc::clone(&barrier);
+        let read_handle = thread::spawn(move || {
+            read_barrier.wait();
+            let start = Instant::now();
+            let mut reads = 0;
+            while start.elapsed() < CONTENTION_DURATION {
+                let health = reader.health();
+                assert!(
+                    ALL_STATES.contains(&health.state),
+                    "read {:?}",
+                    health.state
+                );
+                assert!(health.transition_time <= Utc::now());
+                reads += 1;
+            }
+            reads
+        });
+
+        barrier.wait();
+        let start = Instant::now();
+        for state in OPERATIONAL_STATES.iter().cycle() {
+            if start.elapsed() >= CONTENTION_DURATION {
+                break;
+            }
+            writer.set_state(*state);
+        }
+
+        assert!(read_handle.join().unwrap() > 0);
+    }
+
+    #[test]
+    fn state_is_terminal() {
+        assert!(!State::Healthy.is_terminal());
+        assert!(!State::Degraded.is_terminal());
+        assert!(!State::Unhealthy.is_terminal());
+        assert!(State::ShuttingDown.is_terminal());
+        assert!(!State::Unknown.is_terminal());
+    }
+
+    #[test]
+    fn state_is_operational() {
+        assert!(State::Healthy.is_operational());
+        assert!(State::Degraded.is_operational());
+        assert!(!State::Unhealthy.is_operational());
+        assert!(!State::ShuttingDown.is_operational());
+        assert!(!State::Unknown.is_operational());
+    }
+
+    #[test]
+    fn state_severity() {
+        assert_eq!(State::Healthy.severity(), 0);
+        assert_eq!(State::Degraded.severity(), 1);
+        assert_eq!(State::Unknown.severity(), 2);
+        assert_eq!(State::Unhealthy.severity(), 3);
+        assert_eq!(State::ShuttingDown.severity(), 4);
+    }
+
+    const ALL_STATES: [State; 5] = [
+        State::Healthy,
+        State::Degraded,
+        State::Unhealthy,
+        State::ShuttingDown,
+        State::Unknown,
+    ];
+
+    #[test]
+    fn state_ordering() {
+        let mut states = ALL_STATES;
+        states.sort();
+        assert_eq!(
+            states,
+            [
+                State::Healthy,
+                State::Degraded,
+                State::Unknown,
+                State::Unhealthy,
+                State::ShuttingDown,
+            ]
+        );
+
+        for a in ALL_STATES {
+            for b in ALL_STATES {
+                assert_eq!(a.cmp(&b), a.severity().cmp(&b.severity()), "{a} vs {b}");
+                assert_eq!(a.partial_cmp(&b), Some(a.cmp(&b)), "{a} vs {b}");
+                assert_eq!(a == b, a.cmp(&b) == Ordering::Equal, "{a} vs {b}");
+            }
+        }
+    }
+
+    #[test]
+    fn state_max() {
+        for a in ALL_STATES {
+            for b in ALL_STATES {
+                let max = State::max(a, b);
+                assert_eq!(max, State::max(b, a), "{a} vs {b}");
+                assert!(max >= a && max >= b, "{a} vs {b}");
+                assert!(max == a || max == b, "{a} vs {b}");
+            }
+        }
+        assert_eq!(State::max(State::Healthy, State::Unknown), State::Unknown);
+        assert_eq!(
+            State::max(State::ShuttingDown, State::Unhealthy),
+            State::ShuttingDown
+        );
+    }
+
+    #[test]
+    fn aggregate_states_empty() {
+        assert_eq!(aggregate_states(std::iter::empty()), State::Unknown);
+    }
+
+    #[test]
+    fn aggregate_states_identical() {
+        for state in ALL_STATES {
+            assert_eq!(aggregate_states([state; 3].into_iter()), state);
+            assert_eq!(aggregate_states(std::iter::once(state)), state);
+        }
+    }
+
+    #[test]
+    fn aggregate_states_mixed() {
+        assert_eq!(
+            aggregate_states([State::Healthy, State::Degraded, State::Healthy].into_iter()),
+            State::Degraded
+        );
+        assert_eq!(
+            aggregate_states([State::Unknown, State::Healthy].into_iter()),
+            State::Unknown
+        );
+        assert_eq!(
+            aggregate_states([State::Unhealthy, State::Unknown, State::Degraded].into_iter()),
+            State::Unhealthy
+        );
+        assert_eq!(
+            aggregate_states(ALL_STATES.into_iter()),
+            State::ShuttingDown
+        );
+    }
+
+    #[test]
+    fn health_age() {
+        let health = Health::new_with_time(State::Healthy, Utc::now() - Duration::minutes(5));
+
+        assert!(health.age() >= Duration::minutes(5));
+        assert!(health.age() < Duration::minutes(6));
+        assert!(health.is_stale(Duration::minutes(1)));
+        assert!(!health.is_stale(Duration::minutes(10)));
+    }
+
+    #[test]
+    fn reporter_is_unnamed_by_default() {
+        let reporter = HealthReporter::new();
+        assert_eq!(reporter.name(), None);
+        assert_eq!(reporter.name_or_default("adapter"), "adapter");
+        assert!(HealthReporter::default().name().is_none());
+    }
+
+    #[test]
+    fn name_is_preserved_across_clone() {
+        let reporter = HealthReporter::with_initial_state(State::Healthy).with_name("replication");
+        let mut clone = reporter.clone();
+        assert_eq!(clone.name(), Some("replication"));
+        assert_eq!(clone.name_or_default("adapter"), "replication");
+
+        // Clones share the health, and each reports it under the same name.
+        clone.set_state(State::Degraded);
+        assert_eq!(reporter.state(), State::Degraded);
+        assert!(
+            reporter
+                .to_string()
+                .starts_with("replication: degraded since ")
+        );
+    }
+
+    #[test]
+    fn health_display() {
+        let time = chrono::DateTime::parse_from_rfc3339("2024-01-02T03:04:05Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let health = Health::new_with_time(State::ShuttingDown, time);
+        assert_eq!(
+            health.to_string(),
+            "shutting down since 2024-01-02T03:04:05Z"
+        );
+
+        let reporter = HealthReporter::new().with_name("replication");
+        *reporter.health.write() = health;
+        assert_eq!(
+            reporter.to_string(),
+            "replication: shutting down since 2024-01-02T03:04:05Z"
+        );
+        let unnamed = HealthReporter::new();
+        *unnamed.health.write() = health;
+        assert_eq!(unnamed.to_string(), health.to_string());
+    }
+
+    #[test]
+    fn score_thresholds_boundaries() {
+        let thresholds = ScoreThresholds::default();
+        assert_eq!(thresholds.state_for(100.0), State::Healthy);
+        assert_eq!(thresholds.state_for(90.0), State::Healthy);
+        assert_eq!(thresholds.state_for(89.99), State::Degraded);
+        assert_eq!(thresholds.state_for(50.0), State::Degraded);
+        assert_eq!(thresholds.state_for(49.99), State::Unhealthy);
+        assert_eq!(thresholds.state_for(0.0), State::Unhealthy);
+    }
+
+    #[test]
+    fn score_is_weighted_mean() {
+        let mut reporter = HealthReporter::new();
+        assert_eq!(reporter.score(), 0.0);
+
+        reporter.set_score("replication", 3.0, 100.0);
+        reporter.set_score("cache", 1.0, 20.0);
+
+        assert_eq!(reporter.score(), 80.0);
+        assert_eq!(reporter.state(), State::Degraded);
+    }
+
+    #[test]
+    fn score_changes_set_state() {
+        let mut reporter = HealthReporter::new().with_score_thresholds(ScoreThresholds {
+            healthy: 80.0,
+            degraded: 40.0,
+        });
+
+        reporter.set_score("a", 1.0, 80.0);
+        assert_eq!(reporter.state(), State::Healthy);
+        let first = reporter.health().transition_time;
+
+        // A score change that maps to the same state is not a transition.
+        reporter.set_score("a", 1.0, 95.0);
+        assert_eq!(reporter.health().transition_time, first);
+
+        reporter.set_score("a", 1.0, 40.0);
+        assert_eq!(reporter.state(), State::Degraded);
+
+        reporter.set_score("a", 1.0, 39.0);
+        assert_eq!(reporter.state(), State::Unhealthy);
+    }
+
+    #[test]
+    fn set_state_runs_shutdown_hook() {
+        let mut reporter = HealthReporter::new();
+        let called = Arc::new(AtomicU64::new(0));
+        reporter.set_on_shutdown({
+            let called = Arc::clone(&called);
+            move || {
+                called.fetch_add(1, AtomicOrdering::Relaxed);
+            }
+        });
+        reporter.set_state(State::Healthy);
+        assert_eq!(called.load(AtomicOrdering::Relaxed), 0);
+        reporter.clone().set_state(State::ShuttingDown);
+        assert_eq!(called.load(AtomicOrdering::Relaxed), 1);
+    }
+
+    /// Transitions between operational states and into shutting down, but never out of it.
+    const NO_RESTART: &[(State, State)] = &[
+        (State::Unhealthy, State::Healthy),
+        (State::Healthy, State::Degraded),
+        (State::Degraded, State::Healthy),
+        (State::Healthy, State::ShuttingDown),
+        (State::Degraded, State::ShuttingDown),
+    ];
+
+    #[test]
+    fn allowed_transitions() {
+        let mut reporter = HealthReporter::new().with_allowed_transitions(NO_RESTART);
+        assert_eq!(reporter.try_set_state(State::Healthy), Ok(true));
+        assert_eq!(reporter.try_set_state(State::Healthy), Ok(false));
+        assert_eq!(reporter.try_set_state(State::Degraded), Ok(true));
+        assert_eq!(
+            reporter.try_set_state(State::Unknown),
+            Err(TransitionError {
+                from: State::Degraded,
+                to: State::Unknown
+            })
+        );
+        assert_eq!(reporter.state(), State::Degraded);
+        assert_eq!(reporter.try_set_state(State::ShuttingDown), Ok(true));
+
+        let err = reporter.try_set_state(State::Healthy).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "transition from shutting down to healthy is not allowed"
+        );
+        // Setting the state ignores the transition, as does every clone of the reporter.
+        let transition_time = reporter.health().transition_time;
+        reporter.set_state(State::Healthy);
+        reporter.clone().set_state(State::Healthy);
+        assert_eq!(reporter.state(), State::ShuttingDown);
+        assert_eq!(reporter.health().transition_time, transition_time);
+    }
+
+    #[test]
+    fn disallowed_transitions_are_not_counted() {
+        let mut reporter = HealthReporter::new().with_allowed_transitions(NO_RESTART);
+        let called = Arc::new(AtomicU64::new(0));
+        reporter.set_on_shutdown({
+            let called = Arc::clone(&called);
+            move || {
+                called.fetch_add(1, AtomicOrdering::Relaxed);
+            }
+        });
+        // Shutting down straight from unhealthy isn't allowed, so doesn't run the hook.
+        reporter.set_state(State::ShuttingDown);
+        assert_eq!(reporter.state(), State::Unhealthy);
+        assert_eq!(called.load(AtomicOrdering::Relaxed), 0);
+        reporter.set_score("a", 1.0, 100.0);
+        assert_eq!(reporter.state(), State::Healthy);
+        reporter.set_state(State::ShuttingDown);
+        assert_eq!(called.load(AtomicOrdering::Relaxed), 1);
+        assert_eq!(reporter.transitions.load(AtomicOrdering::Relaxed), 2);
+    }
+
+    #[test]
+    fn every_transition_allowed_by_default() {
+        let all = ALL_STATES
+            .iter()
+            .flat_map(|&from| ALL_STATES.map(|to| (from, to)))
+            .collect::<Vec<_>>();
+        let mut default = HealthReporter::new();
+        let mut allowing_all = HealthReporter::new().with_allowed_transitions(&all);
+        for &(from, to) in &all {
+            for reporter in [&mut default, &mut allowing_all] {
+                reporter.set_state(from);
+                assert_eq!(reporter.try_set_state(to), Ok(from != to), "{from} to {to}");
+                assert_eq!(reporter.state(), to);
+            }
+        }
+        assert_eq!(
+            default.transitions.load(AtomicOrdering::Relaxed),
+            allowing_all.transitions.load(AtomicOrdering::Relaxed)
+        );
+    }
+
+    #[test]
+    fn score_does_not_leave_shutting_down() {
+        let mut reporter = HealthReporter::new();
+        reporter.set_state(State::ShuttingDown);
+
+        reporter.set_score("a", 1.0, 100.0);
+        assert_eq!(reporter.score(), 100.0);
+        assert_eq!(reporter.state(), State::ShuttingDown);
+    }
 }
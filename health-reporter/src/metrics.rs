@@ -0,0 +1,232 @@
+//! A Prometheus collector exposing the health of a [`HealthReporter`], so that it can be scraped
+//! without any additional middleware.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+
+use parking_lot::{Mutex, RwLock};
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{IntCounter, IntGauge, Opts};
+
+use crate::{Health, HealthReporter, State};
+
+/// The name of the gauge holding the reporter's current state; see
+/// [`HealthMetricsProvider::state_value`] for its values.
+pub const HEALTH_STATE_METRIC: &str = "readyset_health_state";
+
+/// The name of the counter of the reporter's transitions between states.
+pub const HEALTH_TRANSITIONS_METRIC: &str = "readyset_health_transition_total";
+
+/// A Prometheus [`Collector`] for the health of a [`HealthReporter`], returned by
+/// [`HealthReporter::into_metrics_provider`]. It exposes two metrics, with a `component` label of
+/// the reporter's name if it has one:
+///
+/// - [`HEALTH_STATE_METRIC`], a gauge of the current state.
+/// - [`HEALTH_TRANSITIONS_METRIC`], a counter of the transitions between states since the
+///   reporter was created.
+///
+/// The provider only holds weak references to the reporter's health, and collects nothing once
+/// the reporter has been dropped.
+pub struct HealthMetricsProvider {
+    health: Weak<RwLock<Health>>,
+    transitions: Weak<AtomicU64>,
+    state: IntGauge,
+    transition_total: IntCounter,
+    /// Held while collecting, so that concurrent scrapes don't both advance the counter.
+    collecting: Mutex<()>,
+}
+
+impl HealthMetricsProvider {
+    pub(crate) fn new(
+        health: Weak<RwLock<Health>>,
+        transitions: Weak<AtomicU64>,
+        name: Option<&str>,
+    ) -> Self {
+        let opts = |metric: &str, help: &str| {
+            let opts = Opts::new(metric, help);
+            match name {
+                Some(name) => opts.const_label("component", name),
+                None => opts,
+            }
+        };
+        // The names and help are valid, so creating the metrics can't fail.
+        let state = IntGauge::with_opts(opts(
+            HEALTH_STATE_METRIC,
+            "The health state: 0 = unhealthy, 1 = healthy, 2 = shutting down, 3 = unknown, \
+             4 = degraded",
+        ))
+        .unwrap();
+        let transition_total = IntCounter::with_opts(opts(
+            HEALTH_TRANSITIONS_METRIC,
+            "The number of transitions between health states",
+        ))
+        .unwrap();
+        Self {
+            health,
+            transitions,
+            state,
+            transition_total,
+            collecting: Mutex::new(()),
+        }
+    }
+
+    /// Returns the value of the [`HEALTH_STATE_METRIC`] gauge for `state`: 0 for
+    /// [`State::Unhealthy`], 1 for [`State::Healthy`], 2 for [`State::ShuttingDown`], 3 for
+    /// [`State::Unknown`] and 4 for [`State::Degraded`].
+    pub fn state_value(state: State) -> i64 {
+        match state {
+            State::Unhealthy => 0,
+            State::Healthy => 1,
+            State::ShuttingDown => 2,
+            State::Unknown => 3,
+            State::Degraded => 4,
+        }
+    }
+
+    /// Whether the reporter whose health this collects still exists.
+    pub fn is_active(&self) -> bool {
+        self.health.strong_count() > 0
+    }
+}
+
+impl Collector for HealthMetricsProvider {
+    fn desc(&self) -> Vec<&Desc> {
+        self.state
+            .desc()
+            .into_iter()
+            .chain(self.transition_total.desc())
+            .collect()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        let (Some(health), Some(transitions)) = (self.health.upgrade(), self.transitions.upgrade())
+        else {
+            return vec![];
+        };
+        let _collecting = self.collecting.lock();
+        self.state.set(Self::state_value(health.read().state));
+        let transitions = transitions.load(Ordering::Relaxed);
+        self.transition_total
+            .inc_by(transitions.saturating_sub(self.transition_total.get()));
+        self.state
+            .collect()
+            .into_iter()
+            .chain(self.transition_total.collect())
+            .collect()
+    }
+}
+
+impl HealthReporter {
+    /// Returns a Prometheus collector exposing this reporter's state and the number of
+    /// transitions between states; see [`HealthMetricsProvider`].
+    ///
+    /// The provider only holds weak references to the reporter's health, so it stops reporting
+    /// anything once every clone of the reporter has been dropped. To keep using the reporter,
+    /// call this on a clone of it.
+    pub fn into_metrics_provider(self) -> HealthMetricsProvider {
+        HealthMetricsProvider::new(
+            Arc::downgrade(&self.health),
+            Arc::downgrade(&self.transitions),
+            self.name.as_deref(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use prometheus::Registry;
+
+    use super::*;
+    use crate::HealthReporter;
+
+    /// Returns the value of each metric gathered from `registry`, by name.
+    fn gather(registry: &Registry) -> Vec<(String, f64)> {
+        registry
+            .gather()
+            .iter()
+            .flat_map(|family| {
+                family.get_metric().iter().map(|metric| {
+                    let value = match family.name() {
+                        HEALTH_STATE_METRIC => metric.get_gauge().get_value(),
+                        _ => metric.get_counter().get_value(),
+                    };
+                    (family.name().to_owned(), value)
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn collects_state_and_transitions() {
+        let mut reporter = HealthReporter::new();
+        let registry = Registry::new();
+        registry
+            .register(Box::new(reporter.clone().into_metrics_provider()))
+            .unwrap();
+        assert_eq!(
+            gather(&registry),
+            [
+                (HEALTH_STATE_METRIC.to_owned(), 0.0),
+                (HEALTH_TRANSITIONS_METRIC.to_owned(), 0.0)
+            ]
+        );
+
+        reporter.set_state(State::Healthy);
+        reporter.set_state(State::Healthy);
+        reporter.set_state(State::ShuttingDown);
+        assert_eq!(
+            gather(&registry),
+            [
+                (HEALTH_STATE_METRIC.to_owned(), 2.0),
+                (HEALTH_TRANSITIONS_METRIC.to_owned(), 2.0)
+            ]
+        );
+        // Gathering again doesn't count the same transitions twice.
+        assert_eq!(gather(&registry)[1].1, 2.0);
+    }
+
+    #[test]
+    fn inactive_once_reporter_is_dropped() {
+        let reporter = HealthReporter::with_initial_state(State::Unknown);
+        let provider = reporter.clone().into_metrics_provider();
+        assert!(provider.is_active());
+        let registry = Registry::new();
+        registry.register(Box::new(provider)).unwrap();
+        assert_eq!(gather(&registry)[0].1, 3.0);
+
+        drop(reporter);
+        assert!(gather(&registry).is_empty());
+
+        // Deregistering needs a collector with the same descriptions.
+        let unregistered = HealthReporter::new().into_metrics_provider();
+        assert!(!unregistered.is_active());
+        registry.unregister(Box::new(unregistered)).unwrap();
+        assert!(registry.gather().is_empty());
+    }
+
+    #[test]
+    fn labelled_with_name() {
+        let registry = Registry::new();
+        let reporters = ["adapter", "server"].map(|name| HealthReporter::new().with_name(name));
+        for reporter in &reporters {
+            registry
+                .register(Box::new(reporter.clone().into_metrics_provider()))
+                .unwrap();
+        }
+        let families = registry.gather();
+        let labels = families[0]
+            .get_metric()
+            .iter()
+            .map(|metric| {
+                let label = &metric.get_label()[0];
+                (label.name().to_owned(), label.value().to_owned())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            [("component", "adapter"), ("component", "server")]
+                .map(|(name, value)| (name.to_owned(), value.to_owned()))
+        );
+    }
+}
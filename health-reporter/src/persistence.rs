@@ -0,0 +1,158 @@
+//! Saving a [`HealthReporter`]'s health to a file and restoring it after a restart, so that a
+//! process which was shutting down keeps reporting so to load balancers when it comes back up.
+//!
+//! The file starts with a little-endian `u32` format version, followed by the health encoded
+//! with `bincode`. Files written by an older version are migrated when loaded; files written by a
+//! newer version are rejected.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, anyhow, bail};
+use chrono::DateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{Health, HealthReporter, State};
+
+/// The version of the persisted health format. This must be incremented whenever
+/// [`PersistedHealth`] changes, including when [`State`]'s variants are reordered, since they
+/// are encoded by index, and loading the previous version must be kept working.
+pub const PERSISTENCE_FORMAT_VERSION: u32 = 1;
+
+/// The health of a reporter as it's written to disk, in version 1 of the format.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct PersistedHealth {
+    state: State,
+    /// The transition time, in microseconds since the Unix epoch.
+    transition_time_micros: i64,
+    name: Option<String>,
+}
+
+impl HealthReporter {
+    /// Writes the reporter's state, transition time and name to `path`, replacing the file if it
+    /// exists. The file is written under a temporary name first and then renamed, so a crash
+    /// leaves either the previous file or the new one.
+    pub fn save_to_path(&self, path: &Path) -> anyhow::Result<()> {
+        let health = self.health();
+        let persisted = PersistedHealth {
+            state: health.state,
+            transition_time_micros: health.transition_time.timestamp_micros(),
+            name: self.name().map(str::to_owned),
+        };
+        let mut bytes = PERSISTENCE_FORMAT_VERSION.to_le_bytes().to_vec();
+        bincode::serialize_into(&mut bytes, &persisted)?;
+
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, bytes)
+            .with_context(|| format!("Failed to write health to {}", tmp_path.display()))?;
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to write health to {}", path.display()))
+    }
+
+    /// Returns a reporter with the state, transition time and name saved to `path` by
+    /// [`HealthReporter::save_to_path`]. Component scores, score thresholds and shutdown hooks
+    /// aren't saved, and start out empty or at their defaults.
+    pub fn load_from_path(path: &Path) -> anyhow::Result<HealthReporter> {
+        let bytes = fs::read(path)
+            .with_context(|| format!("Failed to read health from {}", path.display()))?;
+        let Some((version, payload)) = bytes.split_first_chunk::<4>() else {
+            bail!(
+                "Health file {} is truncated: it has no format version",
+                path.display()
+            );
+        };
+        let persisted: PersistedHealth = match u32::from_le_bytes(*version) {
+            1 => bincode::deserialize(payload)
+                .with_context(|| format!("Health file {} is corrupt", path.display()))?,
+            version => bail!(
+                "Health file {} has unknown format version {version}; this build supports \
+                 versions up to {PERSISTENCE_FORMAT_VERSION}",
+                path.display()
+            ),
+        };
+
+        let transition_time = DateTime::from_timestamp_micros(persisted.transition_time_micros)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Health file {} has an out of range transition time",
+                    path.display()
+                )
+            })?;
+        let mut reporter = HealthReporter::with_initial_state(persisted.state);
+        *reporter.health.write() = Health::new_with_time(persisted.state, transition_time);
+        if let Some(name) = persisted.name {
+            reporter = reporter.with_name(name);
+        }
+        Ok(reporter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("health");
+        let transition_time =
+            DateTime::from_timestamp_micros((Utc::now() - Duration::minutes(5)).timestamp_micros())
+                .unwrap();
+        let reporter = HealthReporter::new().with_name("adapter");
+        *reporter.health.write() = Health::new_with_time(State::ShuttingDown, transition_time);
+
+        reporter.save_to_path(&path).unwrap();
+        let loaded = HealthReporter::load_from_path(&path).unwrap();
+        let health = loaded.health();
+        assert_eq!(health.state, State::ShuttingDown);
+        assert_eq!(health.transition_time, transition_time);
+        assert_eq!(loaded.name(), Some("adapter"));
+        assert!(!path.with_extension("tmp").exists());
+
+        // Saving again replaces the file.
+        HealthReporter::with_initial_state(State::Healthy)
+            .save_to_path(&path)
+            .unwrap();
+        let loaded = HealthReporter::load_from_path(&path).unwrap();
+        assert_eq!(loaded.state(), State::Healthy);
+        assert_eq!(loaded.name(), None);
+    }
+
+    #[test]
+    fn unknown_version() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("health");
+        HealthReporter::new().save_to_path(&path).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[..4].copy_from_slice(&(PERSISTENCE_FORMAT_VERSION + 1).to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+
+        let err = HealthReporter::load_from_path(&path)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("unknown format version 2"), "{err}");
+        assert!(err.contains(&path.display().to_string()), "{err}");
+    }
+
+    #[test]
+    fn truncated_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("health");
+        fs::write(&path, [1, 0]).unwrap();
+        let err = HealthReporter::load_from_path(&path)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("truncated"), "{err}");
+
+        fs::write(&path, PERSISTENCE_FORMAT_VERSION.to_le_bytes()).unwrap();
+        let err = HealthReporter::load_from_path(&path)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("corrupt"), "{err}");
+    }
+}
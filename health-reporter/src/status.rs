@@ -0,0 +1,194 @@
+//! A JSON status payload describing the current health of a process, suitable for serving from a
+//! `/status` endpoint.
+
+use chrono::Utc;
+use serde::Serialize;
+
+use crate::{HealthReporter, PROCESS_START, State, TransitionTime};
+
+/// The version of the [`StatusPayload`] schema. This must be incremented whenever a field is
+/// removed, renamed, or changes meaning, so consumers can tell which schema they are reading.
+pub const STATUS_SCHEMA_VERSION: u32 = 1;
+
+/// The serialized body of a status response.
+///
+/// Every field is always present, with optional fields serialized as `null` when unset, so the
+/// shape of the payload does not depend on how the process was configured.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StatusPayload {
+    pub schema_version: u32,
+    pub state: State,
+    pub reason: Option<String>,
+    /// The time of the last state transition, in RFC 3339 format.
+    pub transition_time: String,
+    pub seconds_in_state: i64,
+    pub uptime_seconds: i64,
+    pub version: Option<String>,
+    pub git_sha: Option<String>,
+    pub deployment: Option<String>,
+}
+
+impl StatusPayload {
+    /// Returns a builder for the status of the given reporter.
+    pub fn builder(reporter: &HealthReporter) -> StatusPayloadBuilder {
+        StatusPayloadBuilder {
+            reporter: reporter.clone(),
+            reason: None,
+            started_at: None,
+            version: None,
+            git_sha: None,
+            deployment: None,
+        }
+    }
+
+    /// Serializes the payload to a JSON value.
+    pub fn to_json(&self) -> serde_json::Value {
+        // Serializing this struct can't fail: it contains no maps with non-string keys.
+        serde_json::to_value(self).unwrap()
+    }
+}
+
+/// Builder for a [`StatusPayload`]. The health is read from the reporter when the payload is
+/// built, so a builder can be kept around and built once per request.
+#[derive(Clone)]
+pub struct StatusPayloadBuilder {
+    reporter: HealthReporter,
+    reason: Option<String>,
+    started_at: Option<TransitionTime>,
+    version: Option<String>,
+    git_sha: Option<String>,
+    deployment: Option<String>,
+}
+
+impl StatusPayloadBuilder {
+    /// A human-readable explanation of the current state.
+    pub fn reason(mut self, reason: impl Into<String>) -> Self {
+        self.reason = Some(reason.into());
+        self
+    }
+
+    /// The time the process started, used to compute the uptime. Defaults to the time the first
+    /// [`HealthReporter`] in the process was created.
+    pub fn started_at(mut self, started_at: TransitionTime) -> Self {
+        self.started_at = Some(started_at);
+        self
+    }
+
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
+
+    pub fn git_sha(mut self, git_sha: impl Into<String>) -> Self {
+        self.git_sha = Some(git_sha.into());
+        self
+    }
+
+    pub fn deployment(mut self, deployment: impl Into<String>) -> Self {
+        self.deployment = Some(deployment.into());
+        self
+    }
+
+    /// Builds the payload from the reporter's current health.
+    pub fn build(&self) -> StatusPayload {
+        self.build_at(Utc::now())
+    }
+
+    fn build_at(&self, now: TransitionTime) -> StatusPayload {
+        let health = self.reporter.health();
+        let started_at = self.started_at.unwrap_or(*PROCESS_START);
+        StatusPayload {
+            schema_version: STATUS_SCHEMA_VERSION,
+            state: health.state,
+            reason: self.reason.clone(),
+            transition_time: health.transition_time.to_rfc3339(),
+            seconds_in_state: (now - health.transition_time).num_seconds(),
+            uptime_seconds: (now - started_at).num_seconds(),
+            version: self.version.clone(),
+            git_sha: self.git_sha.clone(),
+            deployment: self.deployment.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    use super::*;
+    use crate::Health;
+
+    fn reporter_in(state: State, transition_time: TransitionTime) -> HealthReporter {
+        let reporter = HealthReporter::new();
+        *reporter.health.write() = Health::new_with_time(state, transition_time);
+        reporter
+    }
+
+    #[test]
+    fn healthy_with_static_fields() {
+        let started_at = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let transition_time = Utc.with_ymd_and_hms(2024, 1, 1, 0, 1, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 1, 0, 0).unwrap();
+        let reporter = reporter_in(State::Healthy, transition_time);
+
+        let payload = StatusPayload::builder(&reporter)
+            .started_at(started_at)
+            .version("1.2.3")
+            .git_sha("abc123")
+            .deployment("prod")
+            .build_at(now);
+
+        assert_eq!(
+            payload.to_json(),
+            json!({
+                "schema_version": 1,
+                "state": "healthy",
+                "reason": null,
+                "transition_time": "2024-01-01T00:01:00+00:00",
+                "seconds_in_state": 3540,
+                "uptime_seconds": 3600,
+                "version": "1.2.3",
+                "git_sha": "abc123",
+                "deployment": "prod",
+            })
+        );
+    }
+
+    #[test]
+    fn shutting_down_without_static_fields() {
+        let started_at = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let now = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 30).unwrap();
+        let reporter = reporter_in(State::ShuttingDown, now);
+
+        let payload = StatusPayload::builder(&reporter)
+            .started_at(started_at)
+            .reason("received SIGTERM")
+            .build_at(now);
+
+        assert_eq!(
+            payload.to_json(),
+            json!({
+                "schema_version": 1,
+                "state": "shutting_down",
+                "reason": "received SIGTERM",
+                "transition_time": "2024-01-01T00:00:30+00:00",
+                "seconds_in_state": 0,
+                "uptime_seconds": 30,
+                "version": null,
+                "git_sha": null,
+                "deployment": null,
+            })
+        );
+    }
+
+    #[test]
+    fn builder_reads_current_health() {
+        let mut reporter = HealthReporter::new();
+        let builder = StatusPayload::builder(&reporter);
+        assert_eq!(builder.build().state, State::Unhealthy);
+
+        reporter.set_state(State::Healthy);
+        assert_eq!(builder.build().state, State::Healthy);
+    }
+}
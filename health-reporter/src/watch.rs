@@ -0,0 +1,225 @@
+//! Waiting for and streaming a [`HealthReporter`]'s state, and draining requests on shutdown,
+//! all of which need a tokio runtime.
+
+use std::future::Future;
+use std::sync::{Arc, Weak};
+
+use futures::{Stream, StreamExt};
+use parking_lot::RwLock;
+use tokio::time::{Duration, MissedTickBehavior};
+
+use crate::{Health, HealthReporter, State};
+
+/// The longest [`HealthReporter::expect_state`] waits between checks of the state.
+pub const EXPECT_STATE_MAX_BACKOFF: Duration = Duration::from_millis(100);
+
+impl HealthReporter {
+    /// Sets the state to [`State::ShuttingDown`], then returns a future that completes after
+    /// `drain_timeout`, to give in-flight requests time to finish before the process exits.
+    ///
+    /// The state is updated when this is called, not when the future is first polled. Calling
+    /// this when already shutting down doesn't transition again or rerun the
+    /// [`on_shutdown`](HealthReporter::set_on_shutdown) callback, but still waits.
+    pub fn shutdown(&mut self, drain_timeout: Duration) -> impl Future<Output = ()> + use<> {
+        self.set_state(State::ShuttingDown);
+        tokio::time::sleep(drain_timeout)
+    }
+
+    /// Returns a stream that yields the current health every `interval`, starting immediately,
+    /// whether or not it has changed. The stream ends once every clone of this reporter has been
+    /// dropped.
+    pub fn poll_state(&self, interval: Duration) -> impl Stream<Item = Health> + use<> {
+        let health = Arc::downgrade(&self.health);
+        let mut ticks = tokio::time::interval(interval);
+        // A slow consumer gets the current health when it catches up, not a burst of stale ones.
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        futures::stream::unfold(
+            (ticks, health),
+            |(mut ticks, health): (_, Weak<RwLock<Health>>)| async move {
+                ticks.tick().await;
+                let current = *health.upgrade()?.read();
+                Some((current, (ticks, health)))
+            },
+        )
+    }
+
+    /// Like [`HealthReporter::poll_state`], but only yields the health when it has transitioned
+    /// since the last health yielded, starting with the current health. Transitions are detected
+    /// by their transition time, so several transitions between two polls are yielded as one.
+    pub fn stream_state_changes(&self, interval: Duration) -> impl Stream<Item = Health> + use<> {
+        let mut last_transition = None;
+        self.poll_state(interval).filter(move |health| {
+            let changed = last_transition != Some(health.transition_time);
+            last_transition = Some(health.transition_time);
+            futures::future::ready(changed)
+        })
+    }
+
+    /// Waits for the reporter to reach `state`, returning true once it has, or false if it hasn't
+    /// within `timeout`. Returns true immediately if the reporter is already in `state`.
+    ///
+    /// The state is polled, starting every millisecond and backing off to every
+    /// [`EXPECT_STATE_MAX_BACKOFF`], so a state held only briefly between two polls may be missed.
+    pub async fn expect_state(&self, state: State, timeout: Duration) -> bool {
+        let reached = async {
+            let mut backoff = Duration::from_millis(1);
+            while self.state() != state {
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(EXPECT_STATE_MAX_BACKOFF);
+            }
+        };
+        tokio::time::timeout(timeout, reached).await.is_ok()
+    }
+
+    /// Waits for the reporter to become [`State::Healthy`]; see [`HealthReporter::expect_state`].
+    pub async fn wait_for_healthy(&self, timeout: Duration) -> bool {
+        self.expect_state(State::Healthy, timeout).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn poll_state_yields_every_interval() {
+        let reporter = HealthReporter::new();
+        let stream = reporter.poll_state(Duration::from_secs(1));
+        let mut updater = reporter.clone();
+        drop(reporter);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(1500)).await;
+            updater.set_state(State::Healthy);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            updater.set_state(State::Degraded);
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        // Polls at 0s, 1s, 2s and 3s see the reporter, and the one at 4s doesn't.
+        let polled = stream.collect::<Vec<_>>().await;
+        assert_eq!(
+            polled.iter().map(|health| health.state).collect::<Vec<_>>(),
+            [
+                State::Unhealthy,
+                State::Unhealthy,
+                State::Healthy,
+                State::Degraded,
+            ]
+        );
+        assert_eq!(polled[0].transition_time, polled[1].transition_time);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stream_state_changes_yields_transitions() {
+        let reporter = HealthReporter::new();
+        let stream = reporter.stream_state_changes(Duration::from_secs(1));
+        let mut updater = reporter.clone();
+        drop(reporter);
+        tokio::spawn(async move {
+            for state in [
+                State::Unhealthy,
+                State::Healthy,
+                State::Healthy,
+                State::Degraded,
+            ] {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                updater.set_state(state);
+            }
+            tokio::time::sleep(Duration::from_secs(3)).await;
+        });
+
+        let changes = stream.collect::<Vec<_>>().await;
+        assert_eq!(
+            changes
+                .iter()
+                .map(|health| health.state)
+                .collect::<Vec<_>>(),
+            [State::Unhealthy, State::Healthy, State::Degraded]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn streams_end_when_reporter_is_dropped() {
+        let reporter = HealthReporter::new();
+        let mut polled = std::pin::pin!(reporter.poll_state(Duration::from_secs(1)));
+        let mut changes = std::pin::pin!(reporter.stream_state_changes(Duration::from_secs(1)));
+        drop(reporter);
+        assert!(polled.next().await.is_none());
+        assert!(changes.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn expect_state_reached() {
+        tokio::time::pause();
+        let mut reporter = HealthReporter::new();
+        let waiter = tokio::spawn({
+            let reporter = reporter.clone();
+            async move { reporter.wait_for_healthy(Duration::from_secs(1)).await }
+        });
+        tokio::time::advance(Duration::from_millis(500)).await;
+        assert!(!waiter.is_finished());
+
+        reporter.set_state(State::Healthy);
+        tokio::time::advance(EXPECT_STATE_MAX_BACKOFF).await;
+        assert!(waiter.await.unwrap());
+
+        // A reporter already in the expected state doesn't wait at all.
+        assert!(reporter.expect_state(State::Healthy, Duration::ZERO).await);
+    }
+
+    #[tokio::test]
+    async fn expect_state_times_out() {
+        tokio::time::pause();
+        let mut reporter = HealthReporter::new();
+        let start = tokio::time::Instant::now();
+        let waiter = tokio::spawn({
+            let reporter = reporter.clone();
+            async move {
+                reporter
+                    .expect_state(State::Degraded, Duration::from_secs(1))
+                    .await
+            }
+        });
+        tokio::time::advance(Duration::from_millis(500)).await;
+        // Passing through other states doesn't satisfy the wait.
+        reporter.set_state(State::Healthy);
+        tokio::time::advance(Duration::from_secs(1)).await;
+
+        assert!(!waiter.await.unwrap());
+        assert_eq!(reporter.state(), State::Healthy);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn shutdown_runs_hook_and_drains() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let mut reporter = HealthReporter::new();
+        reporter.set_state(State::Healthy);
+        let calls = Arc::new(AtomicUsize::new(0));
+        reporter.set_on_shutdown({
+            let reporter = reporter.clone();
+            let calls = Arc::clone(&calls);
+            move || {
+                // The state has already been updated when the hook runs.
+                assert_eq!(reporter.state(), State::ShuttingDown);
+                calls.fetch_add(1, Ordering::Relaxed);
+            }
+        });
+
+        let start = tokio::time::Instant::now();
+        let drain = reporter.shutdown(Duration::from_secs(5));
+        assert_eq!(reporter.state(), State::ShuttingDown);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        drain.await;
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+
+        // Shutting down again waits, but neither transitions nor reruns the hook.
+        let transition_time = reporter.health().transition_time;
+        reporter.clone().shutdown(Duration::from_secs(1)).await;
+        assert_eq!(reporter.state(), State::ShuttingDown);
+        assert_eq!(reporter.health().transition_time, transition_time);
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+        assert_eq!(start.elapsed(), Duration::from_secs(6));
+    }
+}
@@ -560,6 +560,8 @@ impl TableSpec {
                         // Allow using the `index` for key columns which are specified
                         // as Unique.
                         ColumnGenerator::Unique(u) => u.gen(),
+                        // Custom generators are a function of the row index.
+                        ColumnGenerator::Custom(c) => c.gen_for_row(index),
                         _ if index.is_multiple_of(2) && !expected_values.is_empty() => {
                             expected_values
                                 .iter()
@@ -574,6 +576,9 @@ impl TableSpec {
                         ColumnGenerator::RandomString(r) => r.gen(),
                         ColumnGenerator::RandomChars(r) => r.gen(),
                         ColumnGenerator::Zipfian(z) => z.gen(),
+                        ColumnGenerator::ZipfianIndexed(z) => z.gen(),
+                        ColumnGenerator::Enum(e) => e.gen(),
+                        ColumnGenerator::Boolean(b) => b.gen(),
                         ColumnGenerator::NonRepeating(r) => r.gen(),
                     };
 
@@ -3052,6 +3057,23 @@ mod tests {
         gen.generate_query(seed).statement
     }
 
+    #[test]
+    fn custom_column_uses_row_index() {
+        let mut table = TableSpec::new("t".into());
+        let col = table.fresh_column_with_type(SqlType::BigInt(None));
+        table.set_column_generator_spec(
+            col.clone(),
+            ColumnGenerationSpec::Custom(Arc::new(|i| ((i * 2) as i64).into())),
+        );
+
+        let values = table
+            .generate_data_from_index(3, 10, false)
+            .into_iter()
+            .map(|mut row| row.remove(&col).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(values, vec![20.into(), 22.into(), 24.into()]);
+    }
+
     #[test]
     fn parse_operation_list() {
         let src = "aggregates,joins";
@@ -228,7 +228,7 @@ where
                 Box::pin(async move {
                     let body = format!("Adapter is in {} state", &state).into();
                     let res = match state {
-                        State::Healthy | State::ShuttingDown => res
+                        State::Healthy | State::Degraded | State::ShuttingDown => res
                             .status(StatusCode::OK)
                             .header(CONTENT_TYPE, "text/plain")
                             .body(body),
@@ -163,7 +163,7 @@ impl Service<Request<Body>> for NoriaServerHttpRouter {
                 Box::pin(async move {
                     let body = format!("Server is in {} state", &state).into();
                     let res = match state {
-                        State::Healthy | State::ShuttingDown => res
+                        State::Healthy | State::Degraded | State::ShuttingDown => res
                             .status(StatusCode::OK)
                             .header(CONTENT_TYPE, "text/plain")
                             .body(body),
@@ -1,34 +1,326 @@
 #![warn(clippy::panic)]
 
+use std::future::Future;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::Duration;
 
-use anyhow::anyhow;
-use clap::Parser;
+use anyhow::{anyhow, bail};
+use clap::builder::{PossibleValuesParser, TypedValueParser};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use hyper::HeaderMap;
 use readyset_client::ReadySetHandle;
 use readyset_client::consensus::AuthorityType;
+use serde_json::{Value, json};
+use tracing::warn;
+use tracing_subscriber::EnvFilter;
+use url::Url;
 
 #[derive(Parser)]
 #[command(name = "controller_request")]
 struct ControllerRequest {
-    #[arg(short, long, env("AUTHORITY_ADDRESS"), default_value("127.0.0.1:8500"))]
+    #[arg(
+        short,
+        long,
+        env("AUTHORITY_ADDRESS"),
+        default_value("127.0.0.1:8500"),
+        global = true
+    )]
     authority_address: String,
 
-    #[arg(long, env("AUTHORITY"), default_value("consul"), value_parser = ["consul"])]
+    /// A file listing the addresses of several instances of the authority, one per line, which
+    /// are tried in turn until one can be connected to. Blank lines and lines starting with `#`
+    /// are skipped. Takes precedence over `--authority-address`.
+    #[arg(long, env("AUTHORITY_ADDRESSES_FILE"), global = true)]
+    authority_addresses_file: Option<PathBuf>,
+
+    /// How long to wait, in milliseconds, after failing to connect to one of the addresses in
+    /// `--authority-addresses-file` before trying the next.
+    #[arg(long, default_value = "0", global = true)]
+    authority_failover_delay_ms: u64,
+
+    #[arg(
+        long,
+        env("AUTHORITY"),
+        default_value("consul"),
+        global = true,
+        value_parser = PossibleValuesParser::new(["consul"]).try_map(|s| s.parse::<AuthorityType>())
+    )]
     authority: AuthorityType,
 
-    #[arg(short, long, env("DEPLOYMENT"))]
-    deployment: String,
+    /// The deployment to issue the request to. Required for every subcommand but `list`.
+    #[arg(short, long, env("DEPLOYMENT"), global = true)]
+    deployment: Option<String>,
 
-    /// The name of the endpoint to issue a controller request to.
-    /// This currently only supports endpoints without parameters.
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// The path of the endpoint to issue a controller request to, e.g. `/healthy_workers`.
+    /// Equivalent to the subcommand of the same name, which should be preferred.
     #[arg(short, long)]
-    endpoint: Request,
+    endpoint: Option<Request>,
+
+    /// Issue every request that needs no arguments at once and print a summary of their results.
+    /// Equivalent to `--endpoint /all` or the `all-info` subcommand.
+    #[arg(long, conflicts_with = "endpoint")]
+    all: bool,
+
+    /// How long to wait for the authority to respond, in seconds, before giving up. If not set,
+    /// waits indefinitely.
+    #[arg(long)]
+    authority_timeout_secs: Option<f64>,
+
+    /// The number of times to retry connecting to the authority if it fails or times out.
+    #[arg(long, default_value = "0")]
+    authority_retry_count: u32,
+
+    /// Print the authority, deployment and endpoint that would be used, then exit without
+    /// connecting to anything.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Print the headers of the HTTP request sent to the controller and of its response.
+    #[arg(short, long)]
+    verbose: bool,
+
+    /// Exit with an error unless the controller responds with this HTTP status code. Only
+    /// supported for `custom` requests and with `--verbose`, since other requests don't expose
+    /// the status of the response.
+    #[arg(long, global = true)]
+    expect_status: Option<u16>,
+
+    /// Print only these comma-separated top-level fields of the response, e.g. `--output-fields
+    /// host,port`, instead of all of it. URLs have the fields `url`, `scheme`, `host`, `port` and
+    /// `path`, and the fields of a response that's a list are printed for each of its elements.
+    /// Fields that aren't in the response are warned about and skipped.
+    #[arg(long, value_delimiter = ',', global = true)]
+    output_fields: Option<Vec<String>>,
+
+    /// The delimiter to join the elements of a field with when its value is a list, with
+    /// `--output-fields`.
+    #[arg(long, default_value_t = ',', requires = "output_fields", global = true)]
+    output_fields_separator: char,
+}
+
+/// The addresses of the instances of an authority to try connecting to, in order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct AuthorityAddressList {
+    addresses: Vec<String>,
+}
+
+impl AuthorityAddressList {
+    /// Parses a newline-delimited list of addresses, skipping blank lines and `#` comments.
+    fn parse(contents: &str) -> anyhow::Result<Self> {
+        let addresses = contents
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        if addresses.is_empty() {
+            bail!("No authority addresses given");
+        }
+        Ok(Self { addresses })
+    }
+
+    /// Reads the list of addresses from the file at `path`.
+    fn from_file(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path).map_err(|e| {
+            anyhow!(
+                "Failed to read authority addresses from {}: {e}",
+                path.display()
+            )
+        })?;
+        Self::parse(&contents).map_err(|e| anyhow!("{e} in {}", path.display()))
+    }
+}
+
+impl From<&str> for AuthorityAddressList {
+    fn from(address: &str) -> Self {
+        Self {
+            addresses: vec![address.to_owned()],
+        }
+    }
+}
+
+/// The fields of a response to print, given by `--output-fields`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct OutputFields {
+    fields: Vec<String>,
+    /// The delimiter between the elements of a field whose value is an array.
+    separator: char,
+}
+
+impl OutputFields {
+    /// Removes every top-level field but [`Self::fields`] from `value`, or from each of its
+    /// elements if it's an array. Returns the names of the fields that none of them had.
+    fn filter(&self, value: &mut Value) -> Vec<String> {
+        let records = match value {
+            Value::Array(elements) => elements.iter_mut().collect(),
+            value => vec![value],
+        };
+        let mut found = vec![false; self.fields.len()];
+        for record in records {
+            if let Some(object) = record.as_object_mut() {
+                object.retain(|key, _| self.fields.contains(key));
+                for (found, field) in found.iter_mut().zip(&self.fields) {
+                    *found |= object.contains_key(field);
+                }
+            }
+        }
+        self.fields
+            .iter()
+            .zip(found)
+            .filter(|(_, found)| !found)
+            .map(|(field, _)| field.clone())
+            .collect()
+    }
+
+    /// Formats the fields of `value`, or of each of its elements if it's an array, with a line for
+    /// each field in the order they were given. The lines are labelled with the field name unless
+    /// only one field was given.
+    fn format(&self, value: &Value) -> String {
+        let records = match value {
+            Value::Array(elements) => elements.iter().collect(),
+            value => vec![value],
+        };
+        let mut lines = vec![];
+        for record in records {
+            for field in &self.fields {
+                let Some(value) = record.get(field) else {
+                    continue;
+                };
+                let value = self.format_value(value);
+                match self.fields.len() {
+                    1 => lines.push(value),
+                    _ => lines.push(format!("{field}: {value}")),
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Formats the value of a field: strings without quotes, and arrays as their elements joined
+    /// with the separator.
+    fn format_value(&self, value: &Value) -> String {
+        match value {
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            Value::Array(elements) => elements
+                .iter()
+                .map(|element| self.format_value(element))
+                .collect::<Vec<_>>()
+                .join(&self.separator.to_string()),
+            value => value.to_string(),
+        }
+    }
+
+    /// Prints only the requested fields of `value`, warning about any that it doesn't have.
+    fn print(&self, mut value: Value) {
+        for field in self.filter(&mut value) {
+            warn!(%field, "The response has no such field");
+        }
+        println!("{}", self.format(&value));
+    }
 }
 
-#[derive(Clone, Copy, Debug)]
+/// Returns `url` as an object of its parts, so that they can be selected with `--output-fields`.
+fn url_fields(url: &Url) -> Value {
+    json!({
+        "url": url.as_str(),
+        "scheme": url.scheme(),
+        "host": url.host_str(),
+        "port": url.port_or_known_default(),
+        "path": url.path(),
+    })
+}
+
+#[derive(Subcommand, Clone, Debug, PartialEq, Eq)]
+enum Command {
+    #[command(flatten)]
+    Request(Request),
+    /// Print the names of all available subcommands.
+    List,
+}
+
+#[derive(Subcommand, Clone, Debug, PartialEq, Eq)]
 enum Request {
+    /// Print the addresses of the healthy workers.
     HealthyWorkers,
+    /// Print the URI of the current controller.
     ControllerUri,
+    /// Issue the `healthy-workers` and `controller-uri` requests at once and print a summary of
+    /// their results. A request that fails doesn't stop the other, and its error is printed in
+    /// its place.
+    AllInfo,
+    /// Send a request to any controller endpoint, such as one that this tool doesn't know about
+    /// yet, and print the body of the response.
+    Custom {
+        /// The path of the endpoint, e.g. `/workers`.
+        path: String,
+        /// The HTTP method to send the request with.
+        #[arg(long, value_enum, default_value_t = HttpMethod::Post)]
+        method: HttpMethod,
+        /// A JSON body to send with the request.
+        #[arg(long, value_parser = parse_json)]
+        body: Option<serde_json::Value>,
+    },
+}
+
+/// The HTTP methods that `custom` requests can be sent with.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum HttpMethod {
+    Get,
+    Post,
+    Put,
+    Delete,
+}
+
+impl From<HttpMethod> for reqwest::Method {
+    fn from(method: HttpMethod) -> Self {
+        match method {
+            HttpMethod::Get => reqwest::Method::GET,
+            HttpMethod::Post => reqwest::Method::POST,
+            HttpMethod::Put => reqwest::Method::PUT,
+            HttpMethod::Delete => reqwest::Method::DELETE,
+        }
+    }
+}
+
+fn parse_json(s: &str) -> anyhow::Result<serde_json::Value> {
+    serde_json::from_str(s).map_err(|e| anyhow!("Invalid JSON body: {e}"))
+}
+
+/// Returns an error if the controller at `url` responded with `status` when `expected` was
+/// expected, or with an unsuccessful status if nothing in particular was expected.
+fn check_status(url: &Url, status: hyper::StatusCode, expected: Option<u16>) -> anyhow::Result<()> {
+    match expected {
+        Some(expected) if status.as_u16() != expected => {
+            bail!("Controller at {url} returned HTTP status {status}, expected {expected}")
+        }
+        None if !status.is_success() => {
+            bail!("Controller at {url} returned HTTP status {status}")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The controller RPCs that requests are dispatched to. Implemented by [`ReadySetHandle`], and by
+/// a mock in tests so that requests can be issued without an authority. Handles are cloned to
+/// issue several requests at once.
+trait ControllerHandle: Clone {
+    async fn healthy_workers(&mut self) -> anyhow::Result<Vec<Url>>;
+    async fn controller_uri(&mut self) -> anyhow::Result<Url>;
+}
+
+impl ControllerHandle for ReadySetHandle {
+    async fn healthy_workers(&mut self) -> anyhow::Result<Vec<Url>> {
+        Ok(ReadySetHandle::healthy_workers(self).await?)
+    }
+
+    async fn controller_uri(&mut self) -> anyhow::Result<Url> {
+        Ok(ReadySetHandle::controller_uri(self).await?)
+    }
 }
 
 impl FromStr for Request {
@@ -37,21 +329,186 @@ impl FromStr for Request {
         match s {
             "/healthy_workers" => Ok(Request::HealthyWorkers),
             "/controller_uri" => Ok(Request::ControllerUri),
+            "/all" => Ok(Request::AllInfo),
             _ => Err(anyhow!("Unsupported request")),
         }
     }
 }
 
 impl Request {
-    async fn issue_and_print(&self, mut handle: ReadySetHandle) -> anyhow::Result<()> {
+    /// The path of the controller endpoint that serves this request.
+    fn path(&self) -> &str {
+        match self {
+            Request::HealthyWorkers => "healthy_workers",
+            Request::ControllerUri => "controller_uri",
+            Request::AllInfo => "all",
+            Request::Custom { path, .. } => path.trim_start_matches('/'),
+        }
+    }
+
+    /// Issues the requests of [`Request::AllInfo`] concurrently, returning a summary with a line
+    /// for the result of each, along with how many of them failed.
+    async fn all_info(handle: &mut impl ControllerHandle) -> (String, usize) {
+        let mut other = handle.clone();
+        let (workers, controller_uri) =
+            tokio::join!(handle.healthy_workers(), other.controller_uri());
+        let mut failed = 0;
+        let mut summary = String::new();
+        let mut line = |path: &str, result: anyhow::Result<String>| {
+            let result = result.unwrap_or_else(|e| {
+                failed += 1;
+                format!("error: {e:#}")
+            });
+            summary.push_str(&format!("/{path}: {result}\n"));
+        };
+        let workers = workers.map(|workers| {
+            let workers = workers.iter().map(Url::as_str).collect::<Vec<_>>();
+            format!("[{}]", workers.join(", "))
+        });
+        line(Request::HealthyWorkers.path(), workers);
+        line(
+            Request::ControllerUri.path(),
+            controller_uri.map(String::from),
+        );
+        (summary, failed)
+    }
+
+    async fn issue_and_print(
+        &self,
+        mut handle: impl ControllerHandle,
+        expect_status: Option<u16>,
+        output_fields: Option<&OutputFields>,
+    ) -> anyhow::Result<()> {
         match self {
             Request::HealthyWorkers => {
                 let res = handle.healthy_workers().await?;
-                println!("{res:?}");
+                print_workers(&res, output_fields);
             }
             Request::ControllerUri => {
                 let res = handle.controller_uri().await?;
-                println!("{res:?}");
+                print_url(&res, output_fields);
+            }
+            Request::AllInfo => {
+                let (summary, failed) = Self::all_info(&mut handle).await;
+                print!("{summary}");
+                if failed > 0 {
+                    bail!("{failed} of the requests failed");
+                }
+            }
+            Request::Custom { .. } => {
+                let controller_url = handle.controller_uri().await?;
+                self.issue_custom(&controller_url, false, expect_status, output_fields)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends a `custom` request to the controller at `controller_url` and prints the body of the
+    /// response, printing the headers of the request and response to stderr if `verbose` is set.
+    /// With `output_fields`, the body must be JSON, and only those fields of it are printed.
+    async fn issue_custom(
+        &self,
+        controller_url: &Url,
+        verbose: bool,
+        expect_status: Option<u16>,
+        output_fields: Option<&OutputFields>,
+    ) -> anyhow::Result<()> {
+        let Request::Custom { method, body, .. } = self else {
+            bail!("Not a custom request");
+        };
+        let url = controller_url.join(self.path())?;
+        let client = reqwest::Client::builder().http2_prior_knowledge().build()?;
+        let mut request = client.request((*method).into(), url.clone());
+        if let Some(body) = body {
+            request = request
+                .header(hyper::header::CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(body)?);
+        }
+        let request = request.build()?;
+        if verbose {
+            eprintln!("> {} {}", request.method(), request.url());
+            print_headers(">", request.headers());
+        }
+
+        let response = client.execute(request).await?;
+        if verbose {
+            eprintln!("< {:?} {}", response.version(), response.status());
+            print_headers("<", response.headers());
+        }
+        let status = response.status();
+        let text = response.text().await?;
+        match output_fields {
+            Some(output_fields) => {
+                let body = serde_json::from_str(&text).map_err(|e| {
+                    anyhow!("Can't select --output-fields of a response that isn't JSON: {e}")
+                })?;
+                output_fields.print(body);
+            }
+            None => println!("{text}"),
+        }
+        check_status(&url, status, expect_status)
+    }
+
+    /// Issues the request directly to the controller at `controller_url`, printing the headers
+    /// of the HTTP request and response to stderr.
+    async fn issue_and_print_verbose(
+        &self,
+        controller_url: &Url,
+        expect_status: Option<u16>,
+        output_fields: Option<&OutputFields>,
+    ) -> anyhow::Result<()> {
+        if let Request::Custom { .. } = self {
+            return self
+                .issue_custom(controller_url, true, expect_status, output_fields)
+                .await;
+        }
+        if let Request::AllInfo = self {
+            let mut failed = 0;
+            for request in [Request::HealthyWorkers, Request::ControllerUri] {
+                print!("/{}: ", request.path());
+                let result = Box::pin(request.issue_and_print_verbose(
+                    controller_url,
+                    expect_status,
+                    output_fields,
+                ))
+                .await;
+                if let Err(e) = result {
+                    println!("error: {e:#}");
+                    failed += 1;
+                }
+            }
+            if failed > 0 {
+                bail!("{failed} of the requests failed");
+            }
+            return Ok(());
+        }
+        let url = controller_url.join(self.path())?;
+        let request =
+            hyper::Request::post(url.as_str()).body(hyper::Body::from(bincode::serialize(&())?))?;
+        eprintln!("> {} {}", request.method(), request.uri());
+        print_headers(">", request.headers());
+
+        let client = hyper::Client::builder().http2_only(true).build_http();
+        let response = client.request(request).await?;
+        eprintln!("< {:?} {}", response.version(), response.status());
+        print_headers("<", response.headers());
+
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await?;
+        check_status(&url, status, expect_status)?;
+        match self {
+            Request::HealthyWorkers => {
+                let res: Vec<Url> = bincode::deserialize(&body)?;
+                print_workers(&res, output_fields);
+            }
+            Request::ControllerUri => {
+                let res: Url = bincode::deserialize(&body)?;
+                print_url(&res, output_fields);
+            }
+            Request::AllInfo | Request::Custom { .. } => {
+                unreachable!("custom and all-info requests are issued above")
             }
         }
 
@@ -59,23 +516,723 @@ impl Request {
     }
 }
 
+/// Prints the addresses of the healthy workers, or only the `output_fields` of each.
+fn print_workers(workers: &[Url], output_fields: Option<&OutputFields>) {
+    match output_fields {
+        Some(output_fields) => output_fields.print(workers.iter().map(url_fields).collect()),
+        None => println!("{workers:?}"),
+    }
+}
+
+/// Prints the URI of the controller, or only its `output_fields`.
+fn print_url(url: &Url, output_fields: Option<&OutputFields>) {
+    match output_fields {
+        Some(output_fields) => output_fields.print(url_fields(url)),
+        None => println!("{url:?}"),
+    }
+}
+
+/// Prints the name of every subcommand, one per line.
+fn print_subcommands() {
+    for subcommand in ControllerRequest::command().get_subcommands() {
+        println!("{}", subcommand.get_name());
+    }
+}
+
+fn print_headers(prefix: &str, headers: &HeaderMap) {
+    for (name, value) in headers {
+        eprintln!(
+            "{prefix} {name}: {}",
+            String::from_utf8_lossy(value.as_bytes())
+        );
+    }
+}
+
 impl ControllerRequest {
     pub async fn run_command(self) -> anyhow::Result<()> {
-        let authority = self
-            .authority
-            .to_authority(&self.authority_address, &self.deployment);
+        match self.authority_timeout_secs {
+            Some(secs) => {
+                let timeout = Duration::try_from_secs_f64(secs)
+                    .map_err(|e| anyhow!("Invalid --authority-timeout-secs {secs}: {e}"))?;
+                self.run_command_with_timeout(timeout).await
+            }
+            None => self.run(None).await,
+        }
+    }
+
+    /// Issues the request, giving up on each attempt to connect to the authority if it takes
+    /// longer than `timeout`.
+    pub async fn run_command_with_timeout(self, timeout: Duration) -> anyhow::Result<()> {
+        self.run(Some(timeout)).await
+    }
+
+    async fn run(self, timeout: Option<Duration>) -> anyhow::Result<()> {
+        // Listing subcommands and dry runs are handled without connecting to the authority.
+        if self.request_to_issue()?.is_none() {
+            return Ok(());
+        }
+        // The file of addresses is only read once, rather than on every retry.
+        let addresses = self.authority_addresses()?;
+        let handle = self.connect(&addresses, timeout).await?;
+        self.run_with_handle(handle).await
+    }
+
+    /// Issues the request with `handle`, an already connected controller handle, instead of
+    /// connecting to the authority.
+    pub async fn run_with_handle(self, mut handle: impl ControllerHandle) -> anyhow::Result<()> {
+        let Some(request) = self.request_to_issue()? else {
+            return Ok(());
+        };
+        let output_fields = self.output_fields();
+        if self.verbose {
+            let controller_url = handle.controller_uri().await?;
+            request
+                .issue_and_print_verbose(
+                    &controller_url,
+                    self.expect_status,
+                    output_fields.as_ref(),
+                )
+                .await
+        } else {
+            request
+                .issue_and_print(handle, self.expect_status, output_fields.as_ref())
+                .await
+        }
+    }
 
-        let mut handle: ReadySetHandle = ReadySetHandle::new(authority).await;
-        handle.ready().await.unwrap();
+    /// Returns the fields of the response to print, if `--output-fields` was given.
+    fn output_fields(&self) -> Option<OutputFields> {
+        self.output_fields.as_ref().map(|fields| OutputFields {
+            fields: fields.clone(),
+            separator: self.output_fields_separator,
+        })
+    }
 
-        self.endpoint.issue_and_print(handle).await?;
+    /// Returns the request to issue to the controller, or `None` if there's nothing to issue
+    /// because the subcommands were listed or the request was printed for `--dry-run`. Doesn't
+    /// print anything if there is a request to issue.
+    fn request_to_issue(&self) -> anyhow::Result<Option<Request>> {
+        let Some(request) = self.request()? else {
+            print_subcommands();
+            return Ok(None);
+        };
 
+        if self.expect_status.is_some()
+            && !self.verbose
+            && !matches!(request, Request::Custom { .. })
+        {
+            bail!("--expect-status is only supported for custom requests or with --verbose");
+        }
+        if self.output_fields.is_some() && request == Request::AllInfo {
+            bail!("--output-fields isn't supported for all-info requests, which print a summary");
+        }
+
+        if self.dry_run {
+            self.print_dry_run(&request)?;
+            return Ok(None);
+        }
+        Ok(Some(request))
+    }
+
+    /// Returns the request given by the subcommand, `--endpoint` or `--all`, or `None` for
+    /// `list`.
+    fn request(&self) -> anyhow::Result<Option<Request>> {
+        let endpoint = match self.all {
+            true => Some(&Request::AllInfo),
+            false => self.endpoint.as_ref(),
+        };
+        match (&self.command, endpoint) {
+            (Some(Command::List), _) => Ok(None),
+            (Some(Command::Request(_)), Some(_)) => {
+                bail!("--endpoint and --all can't be combined with a subcommand")
+            }
+            (Some(Command::Request(request)), None) | (None, Some(request)) => {
+                Ok(Some(request.clone()))
+            }
+            (None, None) => bail!("A subcommand or --endpoint is required; see `list`"),
+        }
+    }
+
+    fn deployment(&self) -> anyhow::Result<&str> {
+        self.deployment
+            .as_deref()
+            .ok_or_else(|| anyhow!("--deployment is required to issue a controller request"))
+    }
+
+    /// Returns the addresses of the authority to try connecting to: those listed in
+    /// `--authority-addresses-file` if it was given, or else `--authority-address`.
+    fn authority_addresses(&self) -> anyhow::Result<AuthorityAddressList> {
+        match &self.authority_addresses_file {
+            Some(path) => AuthorityAddressList::from_file(path),
+            None => Ok(self.authority_address.as_str().into()),
+        }
+    }
+
+    fn print_dry_run(&self, request: &Request) -> anyhow::Result<()> {
+        let deployment = self.deployment()?;
+        for address in self.authority_addresses()?.addresses {
+            let authority_url = match self.authority {
+                AuthorityType::Consul => format!("http://{address}/{deployment}"),
+                _ => address,
+            };
+            println!("Authority:  {} ({authority_url})", self.authority);
+        }
+        println!("Deployment: {deployment}");
+        println!("Endpoint:   /{}", request.path());
+        if let Request::Custom { method, body, .. } = request {
+            println!("Method:     {method:?}");
+            if let Some(body) = body {
+                println!("Body:       {body}");
+            }
+        }
         Ok(())
     }
+
+    /// Connects to the authority at one of `addresses`, retrying up to `--authority-retry-count`
+    /// times.
+    async fn connect(
+        &self,
+        addresses: &AuthorityAddressList,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<ReadySetHandle> {
+        let mut attempt = 0;
+        loop {
+            match self.try_connect(addresses, timeout).await {
+                Ok(handle) => return Ok(handle),
+                Err(e) if attempt < self.authority_retry_count => {
+                    attempt += 1;
+                    warn!(
+                        error = %e,
+                        attempt,
+                        retries = self.authority_retry_count,
+                        "Failed to connect to the authority; retrying"
+                    );
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Tries connecting to each of `addresses` in turn, waiting `--authority-failover-delay-ms`
+    /// between them, until one succeeds.
+    async fn try_connect(
+        &self,
+        addresses: &AuthorityAddressList,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<ReadySetHandle> {
+        let addresses = &addresses.addresses;
+        let failover_delay = Duration::from_millis(self.authority_failover_delay_ms);
+        let mut last_error = None;
+        for (i, address) in addresses.iter().enumerate() {
+            if i > 0 && !failover_delay.is_zero() {
+                tokio::time::sleep(failover_delay).await;
+            }
+            match self.try_connect_to(address, timeout).await {
+                Ok(handle) => return Ok(handle),
+                Err(e) if addresses.len() > 1 => {
+                    warn!(%address, error = %e, "Failed to connect to authority");
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        match last_error {
+            Some(e) => Err(anyhow!(
+                "Failed to connect to any of {} authority addresses; last error: {e}",
+                addresses.len()
+            )),
+            None => bail!("No authority addresses given"),
+        }
+    }
+
+    async fn try_connect_to(
+        &self,
+        address: &str,
+        timeout: Option<Duration>,
+    ) -> anyhow::Result<ReadySetHandle> {
+        let authority = self.authority.to_authority(address, self.deployment()?);
+
+        let mut handle: ReadySetHandle = self
+            .with_timeout(address, timeout, ReadySetHandle::new(authority))
+            .await?;
+        self.with_timeout(address, timeout, handle.ready())
+            .await??;
+
+        Ok(handle)
+    }
+
+    async fn with_timeout<F: Future>(
+        &self,
+        address: &str,
+        timeout: Option<Duration>,
+        f: F,
+    ) -> anyhow::Result<F::Output> {
+        match timeout {
+            Some(timeout) => tokio::time::timeout(timeout, f).await.map_err(|_| {
+                anyhow!(
+                    "Timed out after {}s waiting for authority at {address} (deployment {})",
+                    timeout.as_secs_f64(),
+                    self.deployment.as_deref().unwrap_or_default()
+                )
+            }),
+            None => Ok(f.await),
+        }
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
+    // Responses are printed to stdout, so log to stderr to keep them parseable.
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_env("LOG_LEVEL").unwrap_or_else(|_| EnvFilter::new("warn")),
+        )
+        .with_writer(std::io::stderr)
+        .init();
     let controller_requester = ControllerRequest::parse();
     controller_requester.run_command().await
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<ControllerRequest, clap::Error> {
+        ControllerRequest::try_parse_from(
+            std::iter::once("controller_request").chain(args.iter().copied()),
+        )
+    }
+
+    #[test]
+    fn request_subcommands() {
+        let request = parse(&["--deployment", "noria", "healthy-workers"]).unwrap();
+        assert_eq!(
+            request.command,
+            Some(Command::Request(Request::HealthyWorkers))
+        );
+        assert_eq!(request.deployment.as_deref(), Some("noria"));
+
+        // Shared flags may also be passed after the subcommand.
+        let request = parse(&[
+            "controller-uri",
+            "-d",
+            "noria",
+            "--authority-address",
+            "10.0.0.1:8500",
+        ])
+        .unwrap();
+        assert_eq!(
+            request.command,
+            Some(Command::Request(Request::ControllerUri))
+        );
+        assert_eq!(request.deployment.as_deref(), Some("noria"));
+        assert_eq!(request.authority_address, "10.0.0.1:8500");
+    }
+
+    #[test]
+    fn authority_addresses_file() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("authority_addresses.txt");
+        let request = parse(&[
+            "-d",
+            "noria",
+            "--authority-addresses-file",
+            path.to_str().unwrap(),
+            "--authority-failover-delay-ms",
+            "500",
+            "healthy-workers",
+        ])
+        .unwrap();
+        assert_eq!(request.authority_failover_delay_ms, 500);
+        let addresses = request.authority_addresses().unwrap().addresses;
+        assert_eq!(addresses.len(), 3);
+        assert_eq!(
+            addresses,
+            ["10.0.0.1:8500", "10.0.0.2:8500", "consul.internal:8500"]
+        );
+
+        // Without a file, the single `--authority-address` is used.
+        let request = parse(&["-d", "noria", "healthy-workers"]).unwrap();
+        assert_eq!(
+            request.authority_addresses().unwrap().addresses,
+            ["127.0.0.1:8500"]
+        );
+
+        AuthorityAddressList::parse("# no addresses\n\n").unwrap_err();
+        let err = AuthorityAddressList::from_file(&path.with_extension("missing"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("Failed to read authority addresses"), "{err}");
+    }
+
+    #[test]
+    fn endpoint_flag_is_still_supported() {
+        let request = parse(&["--deployment", "noria", "--endpoint", "/healthy_workers"]).unwrap();
+        assert_eq!(request.request().unwrap(), Some(Request::HealthyWorkers));
+
+        assert!(parse(&["-d", "noria", "--endpoint", "/unknown"]).is_err());
+        let request = parse(&["-d", "noria", "-e", "/controller_uri", "healthy-workers"]).unwrap();
+        request.request().unwrap_err();
+        parse(&["-d", "noria"]).unwrap().request().unwrap_err();
+    }
+
+    #[test]
+    fn list_subcommand() {
+        let request = parse(&["list"]).unwrap();
+        assert_eq!(request.request().unwrap(), None);
+
+        let names = ControllerRequest::command()
+            .get_subcommands()
+            .map(|subcommand| subcommand.get_name().to_owned())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            [
+                "healthy-workers",
+                "controller-uri",
+                "all-info",
+                "custom",
+                "list"
+            ]
+        );
+    }
+
+    #[test]
+    fn custom_request() {
+        let request = parse(&[
+            "-d",
+            "noria",
+            "custom",
+            "/workers",
+            "--body",
+            r#"{"worker": 1}"#,
+        ])
+        .unwrap();
+        let request = request.request().unwrap().unwrap();
+        assert_eq!(
+            request,
+            Request::Custom {
+                path: "/workers".to_owned(),
+                method: HttpMethod::Post,
+                body: Some(serde_json::json!({ "worker": 1 })),
+            }
+        );
+        assert_eq!(request.path(), "workers");
+
+        let request = parse(&[
+            "-d",
+            "noria",
+            "custom",
+            "status",
+            "--method",
+            "get",
+            "--expect-status",
+            "404",
+        ])
+        .unwrap();
+        assert_eq!(request.expect_status, Some(404));
+        assert!(matches!(
+            request.request().unwrap(),
+            Some(Request::Custom {
+                method: HttpMethod::Get,
+                body: None,
+                ..
+            })
+        ));
+
+        let err = parse(&["-d", "noria", "custom", "/workers", "--body", "{"])
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("Invalid JSON body"), "{err}");
+    }
+
+    #[test]
+    fn expect_status() {
+        let url = Url::parse("http://127.0.0.1:6033/workers").unwrap();
+        check_status(&url, hyper::StatusCode::OK, None).unwrap();
+        check_status(&url, hyper::StatusCode::NOT_FOUND, Some(404)).unwrap();
+        let err = check_status(&url, hyper::StatusCode::OK, Some(201)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Controller at http://127.0.0.1:6033/workers returned HTTP status 200 OK, expected 201"
+        );
+        check_status(&url, hyper::StatusCode::INTERNAL_SERVER_ERROR, None).unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn expect_status_requires_a_status() {
+        let request = parse(&[
+            "-d",
+            "noria",
+            "--expect-status",
+            "200",
+            "--dry-run",
+            "healthy-workers",
+        ])
+        .unwrap();
+        let err = request.run_command().await.unwrap_err();
+        assert!(err.to_string().contains("--expect-status"), "{err}");
+    }
+
+    /// A controller handle that records the name of each RPC made with it.
+    #[derive(Clone, Default)]
+    struct MockHandle {
+        calls: Arc<Mutex<Vec<&'static str>>>,
+        /// Whether every RPC fails.
+        fail: bool,
+    }
+
+    impl MockHandle {
+        fn calls(&self) -> Vec<&'static str> {
+            self.calls.lock().unwrap().clone()
+        }
+
+        fn record(&self, call: &'static str) -> anyhow::Result<()> {
+            self.calls.lock().unwrap().push(call);
+            if self.fail {
+                bail!("{call} failed");
+            }
+            Ok(())
+        }
+    }
+
+    impl ControllerHandle for MockHandle {
+        async fn healthy_workers(&mut self) -> anyhow::Result<Vec<Url>> {
+            self.record("healthy_workers")?;
+            Ok(vec![Url::parse("http://127.0.0.1:6033").unwrap()])
+        }
+
+        async fn controller_uri(&mut self) -> anyhow::Result<Url> {
+            self.record("controller_uri")?;
+            // Nothing listens on port 1, so requests sent directly to the controller fail.
+            Ok(Url::parse("http://127.0.0.1:1").unwrap())
+        }
+    }
+
+    async fn run_with_mock(args: &[&str], handle: &MockHandle) -> anyhow::Result<()> {
+        parse(args).unwrap().run_with_handle(handle.clone()).await
+    }
+
+    #[tokio::test]
+    async fn healthy_workers_request() {
+        let handle = MockHandle::default();
+        run_with_mock(&["-d", "noria", "healthy-workers"], &handle)
+            .await
+            .unwrap();
+        assert_eq!(handle.calls(), ["healthy_workers"]);
+    }
+
+    #[tokio::test]
+    async fn controller_uri_request() {
+        let handle = MockHandle::default();
+        run_with_mock(&["-d", "noria", "--endpoint", "/controller_uri"], &handle)
+            .await
+            .unwrap();
+        assert_eq!(handle.calls(), ["controller_uri"]);
+    }
+
+    #[tokio::test]
+    async fn custom_request_is_sent_to_the_controller() {
+        let handle = MockHandle::default();
+        run_with_mock(&["-d", "noria", "custom", "/workers"], &handle)
+            .await
+            .unwrap_err();
+        assert_eq!(handle.calls(), ["controller_uri"]);
+    }
+
+    #[tokio::test]
+    async fn handle_errors_are_returned() {
+        let handle = MockHandle {
+            fail: true,
+            ..Default::default()
+        };
+        let err = run_with_mock(&["-d", "noria", "healthy-workers"], &handle)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "healthy_workers failed");
+    }
+
+    #[tokio::test]
+    async fn all_info_request() {
+        for args in [
+            &["-d", "noria", "--all"][..],
+            &["-d", "noria", "-e", "/all"],
+            &["all-info"],
+        ] {
+            assert_eq!(
+                parse(args).unwrap().request().unwrap(),
+                Some(Request::AllInfo)
+            );
+        }
+        parse(&["--all", "-e", "/controller_uri"]).err().unwrap();
+        parse(&["--all", "healthy-workers"])
+            .unwrap()
+            .request()
+            .unwrap_err();
+
+        let handle = MockHandle::default();
+        run_with_mock(&["-d", "noria", "--all"], &handle)
+            .await
+            .unwrap();
+        let mut calls = handle.calls();
+        calls.sort();
+        assert_eq!(calls, ["controller_uri", "healthy_workers"]);
+
+        let (summary, failed) = Request::all_info(&mut MockHandle::default()).await;
+        assert_eq!(failed, 0);
+        assert_eq!(
+            summary,
+            "/healthy_workers: [http://127.0.0.1:6033/]\n\
+             /controller_uri: http://127.0.0.1:1/\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn all_info_prints_errors_inline() {
+        let mut handle = MockHandle {
+            fail: true,
+            ..Default::default()
+        };
+        let (summary, failed) = Request::all_info(&mut handle).await;
+        assert_eq!(failed, 2);
+        assert_eq!(
+            summary,
+            "/healthy_workers: error: healthy_workers failed\n\
+             /controller_uri: error: controller_uri failed\n"
+        );
+        // Both requests are issued even though the first fails.
+        assert_eq!(handle.calls().len(), 2);
+
+        let err = run_with_mock(&["-d", "noria", "--all"], &handle)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "2 of the requests failed");
+    }
+
+    fn output_fields(fields: &[&str]) -> OutputFields {
+        OutputFields {
+            fields: fields.iter().map(|field| field.to_string()).collect(),
+            separator: ',',
+        }
+    }
+
+    #[test]
+    fn filter_output_fields() {
+        let mut value = json!({
+            "uri": "http://10.0.0.1:6033/",
+            "healthy": true,
+            "domains": ["a", "b"],
+            "stats": { "reads": 3 },
+        });
+        let missing = output_fields(&["uri", "domains", "version"]).filter(&mut value);
+        assert_eq!(missing, ["version"]);
+        assert_eq!(
+            value,
+            json!({ "uri": "http://10.0.0.1:6033/", "domains": ["a", "b"] })
+        );
+
+        // The fields of each element of an array are filtered, and a field is only missing if
+        // none of the elements have it.
+        let mut value = json!([{ "host": "a", "port": 1 }, { "host": "b", "rack": 2 }]);
+        let missing = output_fields(&["host", "rack", "zone"]).filter(&mut value);
+        assert_eq!(missing, ["zone"]);
+        assert_eq!(value, json!([{ "host": "a" }, { "host": "b", "rack": 2 }]));
+
+        // Values that aren't objects have no fields to keep.
+        let mut value = json!("http://10.0.0.1:6033/");
+        assert_eq!(output_fields(&["uri"]).filter(&mut value), ["uri"]);
+        assert_eq!(value, json!("http://10.0.0.1:6033/"));
+    }
+
+    #[test]
+    fn format_output_fields() {
+        let value = json!({ "uri": "http://10.0.0.1:6033/", "domains": ["a", "b"], "n": null });
+        assert_eq!(
+            output_fields(&["uri"]).format(&value),
+            "http://10.0.0.1:6033/"
+        );
+        let mut fields = output_fields(&["domains", "uri", "n"]);
+        assert_eq!(
+            fields.format(&value),
+            "domains: a,b\nuri: http://10.0.0.1:6033/\nn: "
+        );
+        fields.separator = ' ';
+        assert_eq!(fields.format(&value).lines().next(), Some("domains: a b"));
+
+        let workers = ["http://10.0.0.1:6033", "http://10.0.0.2:6034/workers"]
+            .map(|url| url_fields(&Url::parse(url).unwrap()));
+        assert_eq!(
+            output_fields(&["host"]).format(&Value::from(workers.to_vec())),
+            "10.0.0.1\n10.0.0.2"
+        );
+        assert_eq!(
+            output_fields(&["port", "path"]).format(&workers[1]),
+            "port: 6034\npath: /workers"
+        );
+    }
+
+    #[tokio::test]
+    async fn output_fields_flags() {
+        let request = parse(&[
+            "-d",
+            "noria",
+            "controller-uri",
+            "--output-fields",
+            "host,port",
+            "--output-fields-separator",
+            ";",
+        ])
+        .unwrap();
+        assert_eq!(
+            request.output_fields(),
+            Some(OutputFields {
+                fields: vec!["host".to_owned(), "port".to_owned()],
+                separator: ';',
+            })
+        );
+        assert_eq!(
+            parse(&["-d", "noria", "list"]).unwrap().output_fields(),
+            None
+        );
+        parse(&["--output-fields-separator", ";", "list"])
+            .err()
+            .unwrap();
+
+        let handle = MockHandle::default();
+        run_with_mock(
+            &[
+                "-d",
+                "noria",
+                "healthy-workers",
+                "--output-fields",
+                "host,zone",
+            ],
+            &handle,
+        )
+        .await
+        .unwrap();
+        assert_eq!(handle.calls(), ["healthy_workers"]);
+
+        let err = run_with_mock(
+            &["-d", "noria", "--all", "--output-fields", "host"],
+            &handle,
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("--output-fields"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn requests_without_rpcs() {
+        let handle = MockHandle::default();
+        run_with_mock(&["list"], &handle).await.unwrap();
+        run_with_mock(&["-d", "noria", "--dry-run", "healthy-workers"], &handle)
+            .await
+            .unwrap();
+        assert!(handle.calls().is_empty());
+    }
+}